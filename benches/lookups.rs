@@ -0,0 +1,64 @@
+//! Benchmark for the controller's user cache. Draws the dashboard off screen
+//! with a transfer every tenth frame, on a throwaway database, and reports
+//! how many user lookups were answered from the cache instead of SQLite.
+//!
+//!     cargo bench --bench lookups -- [frames]
+
+use std::error::Error;
+use std::time::Instant;
+
+use tui::{backend::TestBackend, Terminal};
+
+use ewallet_demo::controllers::{app_controller::AppController, screens::Screen};
+use ewallet_demo::views::ui;
+
+/// Frames drawn unless a count is given.
+const DEFAULT_FRAMES: usize = 1000;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // cargo bench passes --bench, so only the first plain argument counts
+    let frames = match std::env::args().skip(1).find(|arg| !arg.starts_with('-')) {
+        Some(frames) => frames.parse().map_err(|_| format!("invalid frame count: {}", frames))?,
+        None => DEFAULT_FRAMES,
+    };
+    let dir = std::env::temp_dir().join(format!("ewallet_bench_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    std::env::set_current_dir(&dir)?;
+    let result = bench_lookups(frames);
+    std::env::set_current_dir(std::env::temp_dir())?;
+    std::fs::remove_dir_all(&dir)?;
+    result
+}
+
+fn bench_lookups(frames: usize) -> Result<(), Box<dyn Error>> {
+    let mut app_controller = AppController::new()?;
+    app_controller.create_account("bench_payee".to_string())?;
+    app_controller.create_account("bench_payer".to_string())?;
+    app_controller.deposit(frames as f64)?;
+    app_controller.current_screen = Screen::Dashboard;
+
+    let mut terminal = Terminal::new(TestBackend::new(120, 40))?;
+    let mut transfers = 0;
+    let started = Instant::now();
+    for frame in 0..frames {
+        if frame % 10 == 0 {
+            app_controller.transfer("bench_payee".to_string(), 1.0, None)?;
+            transfers += 1;
+        }
+        app_controller.tick()?;
+        terminal.draw(|f| ui::draw(f, &app_controller))?;
+    }
+    let elapsed = started.elapsed();
+
+    let stats = app_controller.get_lookup_stats();
+    let cached = stats.requested - stats.queried;
+    println!("{} frames and {} transfers in {:.2?}", frames, transfers, elapsed);
+    println!(
+        "{} user lookups, {} answered from the cache, {} queried SQLite ({:.1}% fewer queries)",
+        stats.requested,
+        cached,
+        stats.queried,
+        100.0 * cached as f64 / stats.requested.max(1) as f64
+    );
+    Ok(())
+}
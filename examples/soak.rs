@@ -0,0 +1,200 @@
+//! Soak test for the daemon. Runs a handful of users and the daemon for weeks
+//! of simulated time on a throwaway database: transfers, card top-ups, bank
+//! withdrawals, vault moves, weekly allowances, vault interest and webhooks
+//! that often fail. Once a simulated day it checks that the money held adds
+//! up to the money that came in minus the money that went out, and at the
+//! end that every webhook was delivered or is still queued.
+//!
+//!     cargo run --release --example soak -- [weeks]
+
+use std::error::Error;
+use std::rc::Rc;
+
+use chrono::Duration;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rusqlite::Connection;
+
+use ewallet_demo::controllers::{app_controller::AppController, daemon::Daemon};
+use ewallet_demo::models::{
+    clock::{Clock, SystemClock, VirtualClock},
+    database,
+    outbox::Outbox,
+    rules::RULES_PATH,
+    settings::UserSettings,
+};
+
+/// Weeks simulated unless a number is given.
+const DEFAULT_WEEKS: i64 = 4;
+
+/// Simulated time between two runs of the daemon.
+const STEP_MINUTES: i64 = 10;
+
+const USERS: [(&str, f64); 4] =
+    [("alice", 5000.0), ("bob", 2000.0), ("carol", 800.0), ("dave", 50.0)];
+
+/// Users whose transactions are sent to a webhook.
+const WEBHOOK_USERS: [&str; 2] = ["bob", "carol"];
+
+/// Fees, interest and a slow, unreliable processor, so every kind of money
+/// movement happens.
+const SOAK_RULES: &str = "
+[fees]
+card_top_up_percent = 1.5
+bank_withdrawal = 0.5
+
+[interest]
+vault_percent = 5
+
+[processor]
+latency_seconds = 30
+bank_delay_seconds = 7200
+failure_rate = 0.2
+";
+
+/// Stands in for the receiving server, refusing about a third of the payloads.
+fn flaky_webhook(_url: &str, _payload: &str) -> Result<(), String> {
+    if rand::thread_rng().gen_bool(0.3) {
+        Err("503 Service Unavailable".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let weeks = match std::env::args().nth(1) {
+        Some(weeks) => weeks.parse().map_err(|_| format!("invalid week count: {}", weeks))?,
+        None => DEFAULT_WEEKS,
+    };
+    let dir = std::env::temp_dir().join(format!("ewallet_soak_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    std::env::set_current_dir(&dir)?;
+    let result = soak(weeks);
+    std::env::set_current_dir(std::env::temp_dir())?;
+    std::fs::remove_dir_all(&dir)?;
+    result
+}
+
+fn soak(weeks: i64) -> Result<(), Box<dyn Error>> {
+    std::fs::write(RULES_PATH, SOAK_RULES)?;
+    let clock = Rc::new(VirtualClock::new(SystemClock.now()));
+    let conn = database::open(database::path())?;
+
+    // One controller per user, each logged in once, plus the daemon's own
+    let mut wallets = Vec::new();
+    for (username, deposit) in USERS {
+        let mut wallet = AppController::with_clock(clock.clone())?;
+        wallet.create_account(username.to_string())?;
+        if WEBHOOK_USERS.contains(&username) {
+            UserSettings::set(&conn, username, "webhook_url", "http://soak.invalid/hook")?;
+        }
+        wallet.deposit(deposit)?;
+        wallets.push(wallet);
+    }
+    wallets[0].add_child("kid")?;
+    wallets[0].set_child_allowance(25.0)?;
+    let mut daemon = Daemon::new(AppController::with_clock(clock.clone())?, flaky_webhook)?;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let steps_per_day = 24 * 60 / STEP_MINUTES;
+    for step in 1..=weeks * 7 * steps_per_day {
+        clock.advance(Duration::minutes(STEP_MINUTES));
+        if rng.gen_bool(0.5) {
+            let i = rng.gen_range(0..wallets.len());
+            // Only the daemon runs the periodic work, so all interest is
+            // counted; the wallets just need to see each other's payments
+            let wallet = &mut wallets[i];
+            wallet.invalidate_balance();
+            let amount = (rng.gen_range(1.0..60.0_f64) * 100.0).round() / 100.0;
+            match rng.gen_range(0..6) {
+                0 | 1 => {
+                    let recipient = USERS[(i + rng.gen_range(1..USERS.len())) % USERS.len()].0;
+                    wallet.transfer(recipient.to_string(), amount, None)?;
+                }
+                2 => wallet.top_up_by_card(amount * 2.0)?,
+                3 => {
+                    wallet.withdraw_to_bank(amount)?;
+                }
+                4 => {
+                    wallet.move_to_vault(amount)?;
+                }
+                _ => {
+                    wallet.request_vault_withdrawal(amount / 2.0)?;
+                }
+            }
+            wallet.messages.clear();
+        }
+
+        daemon.run_once()?;
+        daemon.app_controller.messages.clear();
+
+        if step % steps_per_day == 0 {
+            let (held, expected) = money_totals(&conn, daemon.interest_paid)?;
+            if (held - expected).abs() >= 0.01 {
+                return Err(format!(
+                    "day {}: wallets hold {:.2} but {:.2} came in net",
+                    step / steps_per_day,
+                    held,
+                    expected
+                )
+                .into());
+            }
+            if step % (7 * steps_per_day) == 0 {
+                println!(
+                    "week {}: {} transactions, {:.2} held, {:.2} interest paid, \
+                    {} webhook(s) delivered",
+                    step / (7 * steps_per_day),
+                    count(&conn, "SELECT COUNT(*) FROM transactions")?,
+                    held,
+                    daemon.interest_paid,
+                    daemon.webhooks_delivered
+                );
+            }
+        }
+    }
+
+    // Every transaction of a webhook user queued one message
+    let (waiting, given_up) = Outbox::counts(&conn)?;
+    let delivered = daemon.webhooks_delivered as i64;
+    let queued = count(
+        &conn,
+        "SELECT COUNT(*) FROM transactions WHERE username IN (
+            SELECT username FROM user_settings WHERE key = 'webhook_url'
+        )",
+    )?;
+    if delivered + waiting + given_up != queued {
+        return Err(format!(
+            "{} webhook(s) queued but {} delivered, {} waiting and {} given up",
+            queued, delivered, waiting, given_up
+        )
+        .into());
+    }
+    println!("No money created or destroyed in {} simulated week(s)", weeks);
+    Ok(())
+}
+
+/// The money held in balances, vaults and vault withdrawals on their way,
+/// and what should be held: deposits, less withdrawals and fees, plus
+/// vault interest.
+fn money_totals(conn: &Connection, interest: f64) -> rusqlite::Result<(f64, f64)> {
+    let held: f64 = conn.query_row(
+        "SELECT (SELECT COALESCE(SUM(balance + vault_balance), 0) FROM users)
+            + (SELECT COALESCE(SUM(amount), 0) FROM vault_withdrawals WHERE status = 'pending')",
+        [],
+        |row| row.get(0),
+    )?;
+    let net_in: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(CASE transaction_type
+            WHEN 'deposit' THEN amount
+            WHEN 'withdraw' THEN -amount
+            WHEN 'fee' THEN -amount
+            ELSE 0 END), 0)
+        FROM transactions",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok((held, net_in + interest))
+}
+
+fn count(conn: &Connection, sql: &str) -> rusqlite::Result<i64> {
+    conn.query_row(sql, [], |row| row.get(0))
+}
@@ -0,0 +1,61 @@
+#![no_main]
+
+//! Feeds random key sequences into `AppController::handle_input`, drawing a
+//! frame after each key, and fails on any panic or database error.
+
+use crossterm::event::KeyCode;
+use ewallet_demo::controllers::app_controller::AppController;
+use ewallet_demo::views::ui;
+use libfuzzer_sys::fuzz_target;
+use std::sync::Once;
+use tui::{backend::TestBackend, Terminal};
+
+static SCRATCH_DIR: Once = Once::new();
+
+/// Maps one input byte to a key. Most bytes are printable characters, so
+/// typed amounts and usernames come up often; the rest are editing and
+/// navigation keys.
+fn key(byte: u8) -> KeyCode {
+    match byte {
+        0..=15 => KeyCode::Enter,
+        16..=19 => KeyCode::Esc,
+        20..=23 => KeyCode::Backspace,
+        24..=25 => KeyCode::Up,
+        26..=27 => KeyCode::Down,
+        28 => KeyCode::Left,
+        29 => KeyCode::Right,
+        30 => KeyCode::Tab,
+        31 => KeyCode::Delete,
+        32..=126 => KeyCode::Char(byte as char),
+        _ => KeyCode::Char(char::from_u32(0xe0 + u32::from(byte - 127)).unwrap_or('?')),
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // The controller opens ewallet.db in the working directory, so the
+    // fuzzer runs in a scratch directory of its own, emptied before each
+    // input so every run starts from a fresh wallet.
+    SCRATCH_DIR.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("ewallet_fuzz_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+    });
+    for entry in std::fs::read_dir(".").unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            std::fs::remove_dir_all(path).unwrap();
+        } else {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    let mut app = AppController::new().unwrap();
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    for &byte in data {
+        if !app.handle_input(key(byte)).unwrap() {
+            break;
+        }
+        app.tick().unwrap();
+        terminal.draw(|f| ui::draw(f, &app)).unwrap();
+    }
+});
@@ -1,95 +1,553 @@
-use crate::models::{transaction::Transaction, user::User};
+use crate::controllers::command::Command;
+use crate::controllers::events::{
+    ActivityRecorder, AuditLog, BalanceAlert, Event, Limit, LimitNotice, Subscriber,
+};
+use crate::controllers::screens::{Screen, Transition};
+use crate::controllers::wallet_service::WalletService;
+use crate::models::{
+    activity::{Activity, ActivityKind, ActivityLog},
+    amount::{parse_amount, AmountError, DEFAULT_MAX_AMOUNT},
+    annotation::Annotation,
+    bill_split::BillSplit,
+    balance_snapshot::BalanceSnapshots,
+    category_rule::{CategoryRule, CategoryRules, RuleField},
+    child_account::ChildAccount,
+    clock::{Clock, SystemClock},
+    closed_account::ClosedAccounts,
+    database,
+    delegation::Delegation,
+    denomination::{self, Breakdown},
+    export_log::{ExportLog, EXPORT_LOG_DIR},
+    history_filter::HistoryFilter,
+    hold::{Hold, Holds},
+    input_history::InputHistory,
+    invoice::{Invoice, LineItem},
+    joint_account::{JointAccounts, JointMember},
+    key_provider::{Password, WalletKeySecret},
+    leaderboard::{Leaderboard, LeaderboardEntry, LeaderboardRanking},
+    net_worth::{NetWorth, NetWorthSnapshots},
+    notification::{Notification, NotificationKind, Notifications},
+    offline_token::{OfflineToken, TokenDirection},
+    password::{AccountPassword, RecoveryCodes},
+    payment_request::{PaymentRequest, RequestStatus},
+    pending_transfer::PendingTransfer,
+    processor::{PaymentKind, Processor, ProcessorConfig, ProcessorPayment},
+    retention::{Retention, Rollup},
+    round_up::{RoundUp, RoundUpTarget},
+    rules::{Rules, RulesFile, RULES_PATH},
+    session::{AccountPin, Keychain, RememberedAccount, Session, PIN_LENGTH},
+    settings::UserSettings,
+    sweep_rule::{SweepRule, SweepRules},
+    transaction::{HistorySort, Transaction, BASE_CURRENCY},
+    user::User,
+    user_data::UserData,
+    user_key::{SignatureStatus, UserKey},
+    vault::{Vault, VaultWithdrawal},
+    weekly_summary::{self, WeeklySummary, SUMMARY_WEEK_SETTING},
+};
+use crate::views::format::DisplayCurrency;
+use crate::views::{chart, format, statement};
 use crossterm::event::KeyCode;
+use ed25519_dalek::SigningKey;
 use rusqlite::{Connection, Result};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
-use rand::Rng;
-use chrono::Utc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+
+/// Fields of a transaction copied from the detail view, in column order.
+const COPIED_FIELDS: [&str; 15] = [
+    "id",
+    "timestamp",
+    "type",
+    "amount",
+    "currency",
+    "fx_rate",
+    "sender",
+    "recipient",
+    "previous_balance",
+    "new_balance",
+    "category",
+    "memo",
+    "note",
+    "transfer_id",
+    "transfer_note",
+];
+
+/// Maximum number of commands kept on the undo stack.
+const MAX_UNDO_HISTORY: usize = 50;
+
+/// How long after a transfer its sender can still undo it, taking the money
+/// back from the recipient.
+const TRANSFER_UNDO_MINUTES: i64 = 5;
+
+/// Idle lock timeouts the Settings screen cycles through, in minutes. 0 disables the lock.
+const IDLE_LOCK_CHOICES: [u64; 5] = [0, 1, 5, 15, 30];
+
+/// Idle lock timeout used until the user picks one, in minutes.
+const DEFAULT_IDLE_LOCK_MINUTES: u64 = 5;
+
+/// Payment request lifetimes the Settings screen cycles through, in hours.
+const REQUEST_TTL_CHOICES: [i64; 4] = [1, 24, 72, 168];
+
+/// Payment request lifetime used until the user picks one, in hours.
+const DEFAULT_REQUEST_TTL_HOURS: i64 = 72;
+
+/// Transfer caps the Settings screen cycles through. Larger transfers are held.
+const LARGE_TRANSFER_CAP_CHOICES: [f64; 4] = [1_000.0, 5_000.0, 10_000.0, 50_000.0];
+
+/// Overdraft limits the Settings screen cycles through. Zero turns overdraft off.
+const OVERDRAFT_CHOICES: [f64; 4] = [0.0, 100.0, 500.0, 1_000.0];
+
+/// Limits a user can choose for the largest single amount they may enter.
+const MAX_AMOUNT_CHOICES: [f64; 3] = [10_000.0, 100_000.0, DEFAULT_MAX_AMOUNT];
+
+/// Vault withdrawal delays the Settings screen cycles through, in hours.
+const VAULT_DELAY_CHOICES: [i64; 4] = [1, 24, 48, 72];
+
+/// Vault withdrawal delay used until the user picks one, in hours.
+const DEFAULT_VAULT_DELAY_HOURS: i64 = 24;
+
+/// History retention periods the Settings screen cycles through.
+const RETENTION_CHOICES: [Retention; 5] = [
+    Retention::Forever,
+    Retention::Months(6),
+    Retention::Years(1),
+    Retention::Years(3),
+    Retention::Years(7),
+];
+
+/// How long a hold stays active before it expires on its own, in days.
+const HOLD_LIFETIME_DAYS: i64 = 7;
+
+/// File that integrity mode appends unreadable or orphaned transactions to.
+const INTEGRITY_LOG_PATH: &str = "integrity.log";
+
+/// How often the tick loop looks for payment requests that have expired.
+const REQUEST_EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often the tick loop asks the simulated processor about pending payments.
+const PROCESSOR_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the tick loop checks whether `rules.toml` changed.
+const RULES_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many recent processor payments the top-up screen lists.
+const RECENT_PROCESSOR_PAYMENTS: usize = 10;
+
+/// How many lines of activity the timeline shows.
+const TIMELINE_LENGTH: usize = 500;
+
+/// How many days the net worth chart covers, today included.
+const NET_WORTH_HISTORY_DAYS: i64 = 90;
+
+/// How many user lookups the controller made and how many of them had to
+/// query SQLite because the user was not cached.
+#[derive(Clone, Copy, Default)]
+pub struct LookupStats {
+    pub requested: u64,
+    pub queried: u64,
+}
 
 /// Main controller for the e-wallet application.
 pub struct AppController {
-    pub current_state: AppState,
+    pub current_screen: Screen,
     pub input: String,
-    pub transfer_recipient: Option<String>,
+    /// Values of the completed steps of the wizard on the current screen.
+    pub wizard_values: Vec<String>,
+    pub recipient_suggestions: Vec<String>,
+    pub selected_suggestion: usize,
+    pub recent_recipients: Vec<String>,
+    pub selected_transaction: usize,
+    pub editing_field: AnnotationField,
+    pub starred_only: bool,
+    pub history_sort: HistorySort,
+    /// Filter typed on the history screen, such as `type:transfer amount>100`.
+    pub history_filter: HistoryFilter,
+    /// Whether the history shows one line per transaction instead of three.
+    pub compact_history: bool,
+    /// The day selected on the spending heatmap, whose month it shows.
+    pub heatmap_day: NaiveDate,
+    pub pending_transfer: Option<(String, f64, Option<String>)>,
+    pub hide_amounts: bool,
+    /// Code of the currency the user chose to see amounts in.
+    pub display_currency_code: String,
+    /// Whether a cash withdrawal is followed by the notes it is paid out in.
+    pub cash_breakdown: bool,
+    /// The last cash withdrawal, whose notes the cash breakdown shows.
+    pub last_cash_out: Option<f64>,
+    pub idle_lock_minutes: u64,
+    pub payment_requests: Vec<PaymentRequest>,
+    pub selected_request: usize,
+    pub category_rules: Vec<CategoryRule>,
+    pub selected_category_rule: usize,
+    pub sweep_rules: Vec<SweepRule>,
+    pub selected_sweep_rule: usize,
+    pub notifications: Vec<Notification>,
+    pub selected_notification: usize,
+    pub timeline: Vec<Activity>,
+    pub selected_activity: usize,
+    pub leaderboard: Vec<LeaderboardEntry>,
+    pub leaderboard_ranking: LeaderboardRanking,
+    /// Whether the current user is listed on the leaderboard.
+    pub on_leaderboard: bool,
+    pub request_ttl_hours: i64,
+    pub balance_alert: Option<f64>,
+    pub large_transfer_cap: f64,
+    pub max_amount: f64,
+    /// How far below zero the current user's balance may go.
+    pub overdraft_limit: f64,
+    pub pending_transfers: Vec<PendingTransfer>,
+    pub selected_pending_transfer: usize,
+    /// Co-owners of the current user's account.
+    pub joint_members: Vec<JointMember>,
+    pub selected_joint_member: usize,
+    /// Transfers from joint accounts the current user co-owns that wait
+    /// for their approval.
+    pub approvals: Vec<PendingTransfer>,
+    pub selected_approval: usize,
+    pub issued_token: Option<String>,
+    /// This wallet's public key, for other wallets to trust its tokens.
+    pub wallet_public_key: String,
+    /// Text waiting to be put on the terminal's clipboard.
+    pub clipboard: Option<String>,
+    pub sign_transfers: bool,
+    pub vault_delay_hours: i64,
+    pub round_up_to: Option<RoundUpTarget>,
+    pub integrity_mode: bool,
+    pub retention: Retention,
+    pub vault_withdrawals: Vec<VaultWithdrawal>,
+    pub selected_vault_withdrawal: usize,
+    pub children: Vec<ChildAccount>,
+    pub selected_child: usize,
+    /// Users the current user gave read-only access to their history.
+    pub delegates: Vec<Delegation>,
+    pub selected_delegate: usize,
+    /// Accounts whose history the current user was given access to.
+    pub shared_accounts: Vec<Delegation>,
+    pub selected_shared_account: usize,
+    pub checkout_items: Vec<LineItem>,
+    /// Recipients and amounts entered for a transfer to several people.
+    pub batch_transfers: Vec<(String, f64)>,
+    /// The bill split being previewed before its requests are sent.
+    pub bill_split: Option<BillSplit>,
+    pub checkout: Option<Invoice>,
+    pub remembered_accounts: Vec<RememberedAccount>,
+    pub selected_account: usize,
+    /// Whether the current user's remembered session is kept in the OS
+    /// keychain instead of the remembered accounts file.
+    pub keychain_sessions: bool,
+    /// Whether the current user has a password, which then also unlocks the
+    /// idle lock.
+    pub has_password: bool,
+    /// Recovery codes just issued, shown once and then forgotten.
+    pub recovery_codes: Vec<String>,
+    /// The user whose password is being asked for at login.
+    pending_login: Option<String>,
+    /// Whether the user logging in gave the right password but it is past
+    /// the rotation grace period, so a new one must be chosen first.
+    pub password_expired: bool,
+    /// Whether the session is read-only, as chosen at the login screen, so
+    /// the account can be looked at from a machine that is not trusted.
+    /// Everything that would change it is refused.
+    pub view_only: bool,
+    /// The current user's recent payments through the simulated processor.
+    pub processor_payments: Vec<ProcessorPayment>,
+    rules_file: RulesFile,
+    processor: ProcessorConfig,
+    clock: Rc<dyn Clock>,
+    /// Draws transaction ids, processor answers and checkout codes.
+    rng: RefCell<StdRng>,
+    last_activity: Instant,
+    last_expiry_check: Instant,
+    last_processor_check: Instant,
+    last_rules_check: Instant,
+    locked_input: String,
+    history_cursor: Option<usize>,
+    history_draft: String,
     pub messages: Vec<(String, Instant)>,
     message_timeout: Duration,
     conn: Connection,
+    report_conn: Connection,
+    user_cache: RefCell<HashMap<String, Option<f64>>>,
+    lookup_stats: Cell<LookupStats>,
+    history_window_start: Cell<usize>,
+    integrity_logged: RefCell<HashSet<String>>,
+    data_version: i64,
+    export_log: Option<ExportLog>,
+    subscribers: Vec<&'static dyn Subscriber>,
     current_user: Option<String>,
+    signing_key: Option<SigningKey>,
+    screen_stack: Vec<Screen>,
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+}
+
+/// Annotation field being edited from the transaction detail view.
+#[derive(PartialEq, Clone, Copy)]
+pub enum AnnotationField {
+    Category,
+    Memo,
+    Note,
+}
+
+impl AnnotationField {
+    /// Name of the annotation column, also used as the transaction map key.
+    pub fn column(&self) -> &'static str {
+        match self {
+            AnnotationField::Category => "category",
+            AnnotationField::Memo => "memo",
+            AnnotationField::Note => "note",
+        }
+    }
+}
+
+/// Kinds of input whose submitted values are kept for recall.
+#[derive(PartialEq, Clone, Copy)]
+pub enum InputField {
+    Amount,
+    Recipient,
 }
 
-/// Different states of the application.
-#[derive(PartialEq)]
-pub enum AppState {
-    MainMenu,
-    Login,
-    CreateAccount,
-    LoggedIn,
-    Deposit,
-    Withdraw,
-    Transfer,
-    ViewTransactions,
+impl InputField {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InputField::Amount => "amount",
+            InputField::Recipient => "recipient",
+        }
+    }
 }
 
 impl AppController {
     /// Creates a new AppController instance and initializes the database.
     pub fn new() -> Result<Self> {
-        let conn = Connection::open("ewallet.db")?;
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS users (
-                username TEXT PRIMARY KEY,
-                balance REAL NOT NULL
-            )",
-            [],
-        )?;
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS transactions (
-                id TEXT PRIMARY KEY,
-                username TEXT NOT NULL,
-                transaction_type TEXT NOT NULL,
-                amount REAL NOT NULL,
-                recipient TEXT,
-                sender TEXT,
-                previous_balance REAL NOT NULL,
-                new_balance REAL NOT NULL,
-                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+        Self::with_clock(Rc::new(SystemClock))
+    }
+
+    /// Creates an AppController that reads the date and time from `clock`.
+    pub fn with_clock(clock: Rc<dyn Clock>) -> Result<Self> {
+        Self::with_database(database::path(), clock)
+    }
+
+    /// Creates an AppController on the database at `path`, reading the date
+    /// and time from `clock`.
+    pub fn with_database(path: &str, clock: Rc<dyn Clock>) -> Result<Self> {
+        let started = clock.instant();
+        let conn = database::open(path)?;
+        let report_conn = database::open_read_only(path)?;
+        let data_version = database::data_version(&conn)?;
+        let (export_log, mut messages) = match ExportLog::open(EXPORT_LOG_DIR) {
+            Ok(log) => (Some(log), Vec::new()),
+            Err(err) => (
+                None,
+                vec![(format!("Export log disabled: {}", err), started)],
+            ),
+        };
+        let (rules_file, rules_error) = RulesFile::open(RULES_PATH);
+        if let Some(err) = rules_error {
+            messages.push((format!("{} ignored: {}", RULES_PATH, err), started));
+        }
+        let rules = rules_file.rules.clone();
         Ok(AppController {
-            current_state: AppState::MainMenu,
+            current_screen: Screen::MainMenu,
             input: String::new(),
-            transfer_recipient: None,
-            messages: Vec::new(),
+            wizard_values: Vec::new(),
+            recipient_suggestions: Vec::new(),
+            selected_suggestion: 0,
+            recent_recipients: Vec::new(),
+            selected_transaction: 0,
+            editing_field: AnnotationField::Category,
+            starred_only: false,
+            history_sort: HistorySort::NewestFirst,
+            history_filter: HistoryFilter::default(),
+            compact_history: false,
+            heatmap_day: NaiveDate::default(),
+            pending_transfer: None,
+            hide_amounts: false,
+            display_currency_code: BASE_CURRENCY.to_string(),
+            cash_breakdown: false,
+            last_cash_out: None,
+            idle_lock_minutes: DEFAULT_IDLE_LOCK_MINUTES,
+            payment_requests: Vec::new(),
+            selected_request: 0,
+            category_rules: Vec::new(),
+            selected_category_rule: 0,
+            sweep_rules: Vec::new(),
+            selected_sweep_rule: 0,
+            notifications: Vec::new(),
+            selected_notification: 0,
+            timeline: Vec::new(),
+            selected_activity: 0,
+            leaderboard: Vec::new(),
+            leaderboard_ranking: LeaderboardRanking::Balance,
+            on_leaderboard: false,
+            request_ttl_hours: DEFAULT_REQUEST_TTL_HOURS,
+            balance_alert: None,
+            large_transfer_cap: rules.large_transfer_cap,
+            max_amount: rules.max_amount,
+            overdraft_limit: 0.0,
+            pending_transfers: Vec::new(),
+            selected_pending_transfer: 0,
+            joint_members: Vec::new(),
+            selected_joint_member: 0,
+            approvals: Vec::new(),
+            selected_approval: 0,
+            issued_token: None,
+            wallet_public_key: String::new(),
+            clipboard: None,
+            sign_transfers: false,
+            vault_delay_hours: DEFAULT_VAULT_DELAY_HOURS,
+            round_up_to: None,
+            integrity_mode: false,
+            retention: Retention::Forever,
+            vault_withdrawals: Vec::new(),
+            selected_vault_withdrawal: 0,
+            children: Vec::new(),
+            selected_child: 0,
+            delegates: Vec::new(),
+            selected_delegate: 0,
+            shared_accounts: Vec::new(),
+            selected_shared_account: 0,
+            checkout_items: Vec::new(),
+            batch_transfers: Vec::new(),
+            bill_split: None,
+            checkout: None,
+            remembered_accounts: Vec::new(),
+            keychain_sessions: false,
+            has_password: false,
+            recovery_codes: Vec::new(),
+            pending_login: None,
+            password_expired: false,
+            view_only: false,
+            selected_account: 0,
+            processor_payments: Vec::new(),
+            processor: rules.processor.with_env(),
+            rules_file,
+            clock,
+            rng: RefCell::new(StdRng::from_entropy()),
+            last_activity: started,
+            last_expiry_check: started,
+            last_processor_check: started,
+            last_rules_check: started,
+            locked_input: String::new(),
+            history_cursor: None,
+            history_draft: String::new(),
+            messages,
             message_timeout: Duration::from_secs(5),
             conn,
+            report_conn,
+            user_cache: RefCell::new(HashMap::new()),
+            lookup_stats: Cell::new(LookupStats::default()),
+            history_window_start: Cell::new(0),
+            integrity_logged: RefCell::new(HashSet::new()),
+            data_version,
+            export_log,
+            subscribers: vec![&BalanceAlert, &LimitNotice, &ActivityRecorder, &AuditLog],
             current_user: None,
+            signing_key: None,
+            screen_stack: Vec::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
         })
     }
 
+    /// The current local date and time, according to the controller's clock.
+    pub fn now(&self) -> NaiveDateTime {
+        self.clock.now()
+    }
+
+    /// How long ago `at`, a reading of the controller's clock, was.
+    fn since(&self, at: Instant) -> Duration {
+        self.clock.instant().saturating_duration_since(at)
+    }
+
+    /// Draws this controller's random outcomes from `seed` from now on, so
+    /// a replay of a recorded session gets the same ones.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = RefCell::new(StdRng::seed_from_u64(seed));
+    }
+
     fn generate_transaction_id(&self) -> String {
-        let timestamp = Utc::now().timestamp_nanos_opt().unwrap_or(0);
-        let random_num: u32 = rand::thread_rng().gen();
+        let timestamp = self.now().and_utc().timestamp_nanos_opt().unwrap_or(0);
+        let random_num: u32 = self.rng.borrow_mut().gen();
         format!("{}-{}", timestamp, random_num)
-    }    
+    }
+
+    /// Adds a subscriber that is told about every event published from now on.
+    pub fn subscribe(&mut self, subscriber: &'static dyn Subscriber) {
+        self.subscribers.push(subscriber);
+    }
+
+    /// Tells every subscriber about an event, in the order they subscribed.
+    pub fn publish(&mut self, event: Event) -> Result<()> {
+        for subscriber in self.subscribers.clone() {
+            subscriber.handle_event(self, &event)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes that a transaction changing the current user's balance was
+    /// stored.
+    fn publish_committed(
+        &mut self,
+        transaction_type: &str,
+        amount: f64,
+        previous_balance: f64,
+    ) -> Result<()> {
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let new_balance = self.read_balance()?;
+        self.publish(Event::TransactionCommitted {
+            username,
+            transaction_type: transaction_type.to_string(),
+            amount,
+            previous_balance,
+            new_balance,
+        })
+    }
+
+    /// Publishes that an amount went over one of the current user's limits.
+    fn publish_limit_exceeded(&mut self, limit: Limit, amount: f64) -> Result<()> {
+        match self.current_user.clone() {
+            Some(username) => self.publish(Event::LimitExceeded {
+                username,
+                limit,
+                amount,
+            }),
+            None => Ok(()),
+        }
+    }
 
     /// Adds a new message to the message queue.
     pub fn add_message(&mut self, message: String) {
-        self.messages.push((message, Instant::now()));
+        self.messages.push((message, self.clock.instant()));
     }
 
     /// Removes expired messages from the message queue.
     pub fn clear_expired_messages(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.instant();
         self.messages
             .retain(|(_, timestamp)| now.duration_since(*timestamp) < self.message_timeout);
     }
 
     /// Attempts to log in a user.
     pub fn login(&mut self, username: String) -> Result<bool> {
-        if let Some(_user) = User::get(&self.conn, &username)? {
-            self.current_user = Some(username);
-            self.current_state = AppState::LoggedIn;
-            self.add_message("Login successful.".to_string());
+        self.pending_login = None;
+        self.password_expired = false;
+        if let Some(_user) = self.cached_user(&username)? {
+            if AccountPassword::exists(&self.conn, &username)? {
+                self.pending_login = Some(username);
+                return Ok(false);
+            }
+            self.start_session(username, None)?;
+            self.add_message(self.login_message().to_string());
             Ok(true)
         } else {
             self.add_message("User does not exist. Please try again.".to_string());
@@ -97,11 +555,269 @@ impl AppController {
         }
     }
 
+    /// Refuses a change in a view-only session. Returns `true`, after
+    /// telling the user, if the caller must not go on.
+    fn refuse_in_view_mode(&mut self) -> bool {
+        if self.view_only {
+            self.add_message(
+                "This is a view-only session. Log in normally to make changes.".to_string(),
+            );
+        }
+        self.view_only
+    }
+
+    /// What a completed login tells the user.
+    fn login_message(&self) -> &'static str {
+        if self.view_only {
+            "Login successful. This session is view-only."
+        } else {
+            "Login successful."
+        }
+    }
+
+    /// Whether the last login attempt is waiting for the user's password.
+    pub fn password_required(&self) -> bool {
+        self.pending_login.is_some()
+    }
+
+    /// Completes a login that is waiting for the user's password. A right
+    /// password past the rotation grace period sets `password_expired`
+    /// instead, and the login completes once `rotate_password` is given a
+    /// new one.
+    pub fn login_with_password(&mut self, password: &str) -> Result<bool> {
+        let username = match self.pending_login.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if !AccountPassword::verify(&self.conn, &username, password)? {
+            self.add_message("Wrong password.".to_string());
+            return Ok(false);
+        }
+        if let Some((_, days_left)) = self.password_rotation_status(&username)? {
+            if days_left <= 0 && self.view_only {
+                self.add_message(
+                    "Your password has expired. Log in normally to choose a new one.".to_string(),
+                );
+                return Ok(false);
+            }
+            if days_left <= 0 {
+                // Kept unlocked so the new password can seal it.
+                self.signing_key = self.unlock_signing_key(&username, password)?;
+                self.password_expired = true;
+                self.add_message("Your password has expired. Please choose a new one.".to_string());
+                return Ok(false);
+            }
+        }
+        self.pending_login = None;
+        self.start_session(username, Some(password))?;
+        self.add_message(self.login_message().to_string());
+        Ok(true)
+    }
+
+    /// Sets a password for the current user, who has none yet, and issues
+    /// recovery codes in `recovery_codes` to show once.
+    pub fn set_password(&mut self, password: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if AccountPassword::exists(&self.conn, &username)? {
+            self.add_message("A password is already set.".to_string());
+            return Ok(false);
+        }
+        // A key from before the account had a password is still sealed
+        // under the username; it moves under the password from now on.
+        let key = match self.signing_key.clone() {
+            Some(key) => key,
+            None => UserKey::unlock(&self.conn, &username, &Password(&username))?
+                .unwrap_or_else(UserKey::generate),
+        };
+        AccountPassword::set(&self.conn, &username, password, self.now())?;
+        UserKey::store(&self.conn, &username, &key, &Password(password))?;
+        self.recovery_codes = RecoveryCodes::generate(&self.conn, &username, Some(&key))?;
+        self.signing_key = Some(key);
+        self.has_password = true;
+        self.add_message("Password set. You will be asked for it when you log in.".to_string());
+        Ok(true)
+    }
+
+    /// Completes a login held up by an expired password with a new password.
+    pub fn rotate_password(&mut self, password: &str) -> Result<bool> {
+        let username = match self.pending_login.clone() {
+            Some(username) if self.password_expired => username,
+            _ => return Ok(false),
+        };
+        if !self.replace_password(&username, password)? {
+            return Ok(false);
+        }
+        self.pending_login = None;
+        self.password_expired = false;
+        self.start_session(username, Some(password))?;
+        self.add_message("Password changed. Login successful.".to_string());
+        Ok(true)
+    }
+
+    /// Whether `password` is the current user's password.
+    pub fn check_password(&self, password: &str) -> Result<bool> {
+        match &self.current_user {
+            Some(username) => AccountPassword::verify(&self.conn, username, password),
+            None => Ok(false),
+        }
+    }
+
+    /// Changes the current user's password from `current`, which the caller
+    /// has checked with `check_password`.
+    pub fn change_password(&mut self, current: &str, password: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        // A session resumed with a PIN has not unlocked the key yet.
+        if self.signing_key.is_none() {
+            self.signing_key = self.unlock_signing_key(&username, current)?;
+        }
+        if !self.replace_password(&username, password)? {
+            return Ok(false);
+        }
+        self.add_message(
+            "Password changed. Remembered sessions of this account were ended.".to_string(),
+        );
+        Ok(true)
+    }
+
+    /// Replaces a password with a different one, seals the unlocked signing
+    /// key under it and ends every remembered session of the account, so a
+    /// leaked session does not outlive it.
+    fn replace_password(&mut self, username: &str, password: &str) -> Result<bool> {
+        if AccountPassword::verify(&self.conn, username, password)? {
+            self.add_message(
+                "Please choose a password different from the current one.".to_string(),
+            );
+            return Ok(false);
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        AccountPassword::set(&tx, username, password, self.now())?;
+        if let Some(key) = &self.signing_key {
+            UserKey::store(&tx, username, key, &Password(password))?;
+        }
+        Session::revoke_all(&tx, username)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Unlocks the user's signing key with their password. A key still
+    /// sealed under the username is moved under the password, and a user
+    /// without a key gets one.
+    fn unlock_signing_key(&self, username: &str, password: &str) -> Result<Option<SigningKey>> {
+        let password = Password(password);
+        if !UserKey::exists(&self.conn, username)? {
+            let key = UserKey::generate();
+            return Ok(UserKey::store(&self.conn, username, &key, &password)?.then_some(key));
+        }
+        if let Some(key) = UserKey::unlock(&self.conn, username, &password)? {
+            return Ok(Some(key));
+        }
+        match UserKey::unlock(&self.conn, username, &Password(username))? {
+            Some(key) => Ok(UserKey::store(&self.conn, username, &key, &password)?.then_some(key)),
+            None => Ok(None),
+        }
+    }
+
+    /// Gets when the user's password became due to be changed under the
+    /// rotation policy in the rules, and how many days of the grace period
+    /// are left, once it is due.
+    fn password_rotation_status(&self, username: &str) -> Result<Option<(NaiveDateTime, i64)>> {
+        let max_age = self.rules().password_max_age_days;
+        if max_age == 0 {
+            return Ok(None);
+        }
+        let due = match AccountPassword::changed_at(&self.conn, username)? {
+            Some(changed_at) => changed_at + chrono::Duration::days(max_age),
+            None => return Ok(None),
+        };
+        if due > self.now() {
+            return Ok(None);
+        }
+        let elapsed = (self.now() - due).num_days();
+        Ok(Some((due, self.rules().password_grace_days - elapsed)))
+    }
+
+    /// Gets the rotation status of the current user's password, for the
+    /// reminder shown while it is overdue.
+    pub fn get_password_rotation_status(&self) -> Result<Option<(NaiveDateTime, i64)>> {
+        match &self.current_user {
+            Some(username) => self.password_rotation_status(username),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether `username` has any unused recovery codes.
+    pub fn has_recovery_codes(&self, username: &str) -> Result<bool> {
+        Ok(RecoveryCodes::remaining(&self.conn, username)? > 0)
+    }
+
+    pub fn is_recovery_code_valid(&self, username: &str, code: &str) -> Result<bool> {
+        RecoveryCodes::is_valid(&self.conn, username, code)
+    }
+
+    /// Sets a new password for `username` with one of their recovery codes
+    /// and logs them in. The code is used up, every remembered session of
+    /// the account is ended and the reset is written to the audit log.
+    pub fn recover_account(&mut self, username: &str, code: &str, password: &str) -> Result<bool> {
+        let now = self.now();
+        let tx = self.conn.unchecked_transaction()?;
+        // The code holds a copy of the signing key; a code issued without
+        // one can still reach a key sealed under the username.
+        let key = match RecoveryCodes::signing_key(&tx, username, code)? {
+            Some(key) => Some(key),
+            None => UserKey::unlock(&tx, username, &Password(username))?,
+        };
+        let redeemed = RecoveryCodes::redeem(&tx, username, code, now)?;
+        let new_key = redeemed && key.is_none() && UserKey::exists(&tx, username)?;
+        if redeemed {
+            AccountPassword::set(&tx, username, password, now)?;
+            let key = key.unwrap_or_else(UserKey::generate);
+            UserKey::store(&tx, username, &key, &Password(password))?;
+            Session::revoke_all(&tx, username)?;
+        }
+        let remaining = RecoveryCodes::remaining(&tx, username)?;
+        tx.commit()?;
+        if !redeemed {
+            self.add_message("That recovery code is not valid.".to_string());
+            return Ok(false);
+        }
+
+        self.publish(Event::RecoveryCodeUsed {
+            username: username.to_string(),
+            remaining,
+        })?;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.view_only = false;
+        self.start_session(username.to_string(), Some(password))?;
+        self.add_message(format!("Password reset. {} recovery code(s) left.", remaining));
+        if new_key {
+            self.add_message(
+                "Your signing key could not be recovered, so a new one was made. \
+                Transfers signed with the old one can no longer be verified."
+                    .to_string(),
+            );
+        }
+        Ok(true)
+    }
+
     /// Attempts to create a new user account.
     pub fn create_account(&mut self, username: String) -> Result<bool> {
         if User::create(&self.conn, &username)? {
-            self.current_user = Some(username);
-            self.current_state = AppState::LoggedIn;
+            self.view_only = false;
+            self.invalidate_balance();
+            self.start_session(username, None)?;
             self.add_message("Account created successfully.".to_string());
             Ok(true)
         } else {
@@ -112,277 +828,5179 @@ impl AppController {
         }
     }
 
+    /// Makes the given user the current user and loads their settings.
+    /// `password` is given when the user typed it to get here, and unlocks
+    /// their signing key.
+    fn start_session(&mut self, username: String, password: Option<&str>) -> Result<()> {
+        self.hide_amounts = UserSettings::get_bool(&self.conn, &username, "hide_amounts", false)?;
+        self.display_currency_code =
+            UserSettings::get(&self.conn, &username, "display_currency")?
+                .unwrap_or_else(|| BASE_CURRENCY.to_string());
+        self.compact_history =
+            UserSettings::get_bool(&self.conn, &username, "compact_history", false)?;
+        self.idle_lock_minutes = UserSettings::get(&self.conn, &username, "idle_lock_minutes")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_IDLE_LOCK_MINUTES);
+        self.request_ttl_hours = UserSettings::get(&self.conn, &username, "request_ttl_hours")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_REQUEST_TTL_HOURS);
+        self.balance_alert = UserSettings::get(&self.conn, &username, "low_balance_alert")?
+            .and_then(|value| value.parse().ok());
+        self.load_limits(&username)?;
+        self.overdraft_limit = UserSettings::get(&self.conn, &username, "overdraft_limit")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0.0);
+        self.sign_transfers =
+            UserSettings::get_bool(&self.conn, &username, "sign_transfers", false)?;
+        self.keychain_sessions =
+            UserSettings::get_bool(&self.conn, &username, "keychain_sessions", false)?;
+        self.cash_breakdown =
+            UserSettings::get_bool(&self.conn, &username, "cash_breakdown", false)?;
+        self.has_password = AccountPassword::exists(&self.conn, &username)?;
+        self.vault_delay_hours = UserSettings::get(&self.conn, &username, "vault_delay_hours")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_VAULT_DELAY_HOURS);
+        self.round_up_to = UserSettings::get(&self.conn, &username, "round_up_to")?
+            .and_then(|value| RoundUpTarget::parse(&value));
+        self.integrity_mode =
+            UserSettings::get_bool(&self.conn, &username, "integrity_mode", false)?;
+        self.on_leaderboard =
+            UserSettings::get_bool(&self.conn, &username, "leaderboard", false)?;
+        self.retention = UserSettings::get(&self.conn, &username, "retention")?
+            .and_then(|value| Retention::parse(&value))
+            .unwrap_or(Retention::Forever);
+        // The signing key is sealed under the password, so sessions that did
+        // not type it, and view-only ones, which never sign, leave it locked.
+        self.signing_key = match password {
+            Some(password) if !self.view_only => self.unlock_signing_key(&username, password)?,
+            _ => None,
+        };
+        self.current_user = Some(username.clone());
+        self.invalidate_balance();
+        let now = self.now();
+        // A view-only visit leaves the last login alone, so the next full
+        // login still reports everything since the one before.
+        if !self.view_only {
+            if let Some(since) = User::record_login(&self.conn, &username, now)? {
+                self.greet_returning_user(&username, since)?;
+            }
+        }
+        self.publish(Event::UserLoggedIn { username })?;
+        // Jobs that are due wait for a full session or the daemon, so a
+        // view-only visit leaves the database as it was.
+        if self.view_only {
+            return Ok(());
+        }
+        self.expire_payment_requests()?;
+        self.release_vault_withdrawals()?;
+        self.pay_allowances()?;
+        if let Some(username) = self.current_user.clone() {
+            if self.send_weekly_summary(&username)? {
+                self.add_message("Last week's summary is in your inbox.".to_string());
+            }
+        }
+        self.enforce_retention()
+    }
+
+    /// Tells a returning user what came in, what went out and how many
+    /// payment requests arrived since they last logged in.
+    fn greet_returning_user(&mut self, username: &str, since: NaiveDateTime) -> Result<()> {
+        let (money_in, money_out) = Transaction::get_totals_since(&self.conn, username, since)?;
+        let new_requests = PaymentRequest::count_received_since(&self.conn, username, since)?;
+        if money_in == 0.0 && money_out == 0.0 && new_requests == 0 {
+            self.add_message("Welcome back. Nothing new since your last visit.".to_string());
+            return Ok(());
+        }
+        self.add_message(format!(
+            "Since your last visit: +{} in, -{} out, {} new request{}",
+            self.format_amount(money_in),
+            self.format_amount(money_out),
+            new_requests,
+            if new_requests == 1 { "" } else { "s" }
+        ));
+        Ok(())
+    }
+
     /// Logs out the current user.
     pub fn logout(&mut self) {
         self.current_user = None;
-        self.current_state = AppState::MainMenu;
+        self.signing_key = None;
+        self.has_password = false;
+        self.view_only = false;
+        self.invalidate_balance();
+        self.hide_amounts = false;
+        self.display_currency_code = BASE_CURRENCY.to_string();
+        self.compact_history = false;
+        self.last_cash_out = None;
+        self.history_sort = HistorySort::NewestFirst;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
         self.add_message("Logged out successfully.".to_string());
     }
 
-    /// Processes a deposit for the current user.
-    pub fn deposit(&mut self, amount: f64) -> Result<()> {
-        if let Some(username) = &self.current_user {
-            let previous_balance = self.get_balance()?;
-            let new_balance = previous_balance + amount;
-            User::update_balance(&self.conn, username, new_balance)?;
-
-            let transaction = Transaction {
-                id: self.generate_transaction_id(),
-                username: username.to_string(),
-                transaction_type: "deposit".to_string(),
-                amount,
-                recipient: None,
-                sender: None,
-                previous_balance,
-                new_balance,
-                timestamp: chrono::Local::now().naive_local(),
-            };
-            Transaction::create(&self.conn, &transaction)?;
+    /// Reads the accounts remembered on this terminal for the account switcher.
+    pub fn load_remembered_accounts(&mut self) {
+        self.remembered_accounts = RememberedAccount::load();
+        self.selected_account = self
+            .remembered_accounts
+            .iter()
+            .position(|account| Some(&account.username) == self.current_user.as_ref())
+            .unwrap_or(0);
+    }
 
-            self.add_message(format!("Deposited ${:.2}", amount));
+    /// Remembers the current account on this terminal so it can be switched
+    /// to with its PIN. Returns `false` if the account needs a PIN first.
+    pub fn remember_current_account(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(true);
         }
-        Ok(())
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => {
+                self.add_message("Log in to remember an account.".to_string());
+                return Ok(true);
+            }
+        };
+        if !AccountPin::exists(&self.conn, &username)? {
+            return Ok(false);
+        }
+        if self.remembered_accounts.iter().any(|account| account.username == username) {
+            self.add_message(format!("{} is already remembered here.", username));
+            return Ok(true);
+        }
+        let token = Session::issue(&self.conn, &username, self.now())?;
+        if self.keychain_sessions {
+            if let Err(err) = Keychain::store(&username, &token) {
+                Session::revoke(&self.conn, &token)?;
+                self.add_message(format!("Could not use the OS keychain: {}", err));
+                return Ok(true);
+            }
+        }
+        self.remembered_accounts.push(RememberedAccount {
+            username: username.clone(),
+            token,
+            in_keychain: self.keychain_sessions,
+        });
+        self.remembered_accounts.sort_by(|a, b| a.username.cmp(&b.username));
+        self.save_remembered_accounts();
+        self.add_message(format!("{} will be remembered on this terminal.", username));
+        Ok(true)
     }
 
-    /// Processes a withdrawal for the current user.
-    pub fn withdraw(&mut self, amount: f64) -> Result<()> {
-        if let Some(username) = &self.current_user {
-            let previous_balance = self.get_balance()?;
-            let new_balance = previous_balance - amount;
-            User::update_balance(&self.conn, username, new_balance)?;
-
-            let transaction = Transaction {
-                id: self.generate_transaction_id(),
-                username: username.to_string(),
-                transaction_type: "withdraw".to_string(),
-                amount,
-                recipient: None,
-                sender: None,
-                previous_balance,
-                new_balance,
-                timestamp: chrono::Local::now().naive_local(),
-            };
-            Transaction::create(&self.conn, &transaction)?;
-
-            self.add_message(format!("Withdrawn ${:.2}", amount));
+    /// Sets the current user's PIN and remembers the account. Sessions
+    /// started under an earlier PIN are ended.
+    pub fn set_pin(&mut self, pin: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
         }
-        Ok(())
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if !AccountPin::is_valid(pin) {
+            self.add_message(format!(
+                "A PIN is {} to {} digits.",
+                PIN_LENGTH.start(),
+                PIN_LENGTH.end()
+            ));
+            return Ok(false);
+        }
+        AccountPin::set(&self.conn, &username, pin)?;
+        Session::revoke_all(&self.conn, &username)?;
+        self.remember_current_account()
     }
 
-    /// Checks if the current user can withdraw a specified amount.
-    pub fn can_withdraw(&self, amount: f64) -> Result<bool> {
-        if let Some(username) = &self.current_user {
-            if let Some(user) = User::get(&self.conn, username)? {
-                Ok(user.balance >= amount)
-            } else {
-                Ok(false)
-            }
-        } else {
-            Ok(false)
+    /// Forgets the selected account on this terminal and ends its session.
+    pub fn forget_selected_account(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
         }
+        if self.selected_account >= self.remembered_accounts.len() {
+            return Ok(());
+        }
+        let account = self.remembered_accounts.remove(self.selected_account);
+        Session::revoke(&self.conn, &account.token)?;
+        self.forget_keychain_token(&account);
+        self.save_remembered_accounts();
+        self.selected_account = self
+            .selected_account
+            .min(self.remembered_accounts.len().saturating_sub(1));
+        self.add_message(format!("Forgot {} on this terminal.", account.username));
+        Ok(())
     }
 
-    /// Processes a transfer between the current user and another user.
-    pub fn transfer(&mut self, recipient: String, amount: f64) -> Result<bool> {
-        if let (Some(sender_username), Some(recipient_user)) =
-            (&self.current_user, User::get(&self.conn, &recipient)?)
-        {
-            let sender_previous_balance = self.get_balance()?;
-    
-            if sender_previous_balance < amount {
+    /// Switches to the selected remembered account if `pin` is its PIN.
+    pub fn switch_to_selected_account(&mut self, pin: &str) -> Result<bool> {
+        let account = match self.remembered_accounts.get(self.selected_account) {
+            Some(account) => account.clone(),
+            None => return Ok(false),
+        };
+        let username = match Session::resume(&self.conn, &account.token, self.now())? {
+            Some(username) if username == account.username => username,
+            _ => {
+                self.remembered_accounts.remove(self.selected_account);
+                self.selected_account = 0;
+                self.forget_keychain_token(&account);
+                self.save_remembered_accounts();
                 self.add_message(format!(
-                    "Transfer failed. Insufficient funds. Your balance: ${:.2}",
-                    sender_previous_balance
+                    "The session for {} has ended. Please log in again.",
+                    account.username
                 ));
                 return Ok(false);
             }
-    
-            let sender_new_balance = sender_previous_balance - amount;
-            let recipient_previous_balance = recipient_user.balance;
-            let recipient_new_balance = recipient_previous_balance + amount;
-    
-            User::update_balance(&self.conn, sender_username, sender_new_balance)?;
-            User::update_balance(&self.conn, &recipient, recipient_new_balance)?;
-    
-            let sender_transaction = Transaction {
-                id: self.generate_transaction_id(),
-                username: sender_username.to_string(),
-                transaction_type: "transfer_out".to_string(),
-                amount,
-                recipient: Some(recipient.clone()),
-                sender: Some(sender_username.to_string()),
-                previous_balance: sender_previous_balance,
-                new_balance: sender_new_balance,
-                timestamp: chrono::Local::now().naive_local(),
-            };
-            Transaction::create(&self.conn, &sender_transaction)?;
-    
-            let recipient_transaction = Transaction {
-                id: self.generate_transaction_id(),
-                username: recipient.clone(),
-                transaction_type: "transfer_in".to_string(),
-                amount,
-                recipient: Some(recipient.clone()),
-                sender: Some(sender_username.to_string()),
-                previous_balance: recipient_previous_balance,
-                new_balance: recipient_new_balance,
-                timestamp: chrono::Local::now().naive_local(),
-            };
-            Transaction::create(&self.conn, &recipient_transaction)?;
-    
-            self.add_message(format!("Transferred ${:.2} to {}", amount, recipient));
-            Ok(true)
-        } else {
-            self.add_message(format!(
-                "Transfer failed. Recipient '{}' not found.",
-                recipient
-            ));
-            Ok(false)
+        };
+        if !AccountPin::verify(&self.conn, &username, pin)? {
+            self.add_message("Wrong PIN.".to_string());
+            return Ok(false);
         }
-    }    
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.view_only = false;
+        self.start_session(username.clone(), None)?;
+        self.add_message(format!("Switched to {}.", username));
+        Ok(true)
+    }
 
-    /// Gets the balance of the current user.
-    pub fn get_balance(&self) -> Result<f64> {
-        if let Some(username) = &self.current_user {
-            if let Some(user) = User::get(&self.conn, username)? {
-                Ok(user.balance)
+    fn save_remembered_accounts(&mut self) {
+        if let Err(err) = RememberedAccount::save(&self.remembered_accounts) {
+            self.add_message(format!("Could not save remembered accounts: {}", err));
+        }
+    }
+
+    fn forget_keychain_token(&mut self, account: &RememberedAccount) {
+        if !account.in_keychain {
+            return;
+        }
+        if let Err(err) = Keychain::delete(&account.username) {
+            self.add_message(format!("Could not remove the session from the OS keychain: {}", err));
+        }
+    }
+
+    /// Moves the current user's remembered session between the remembered
+    /// accounts file and the OS keychain, and remembers the choice. Stays
+    /// as it was if the keychain cannot be used.
+    pub fn toggle_keychain_sessions(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let keychain = !self.keychain_sessions;
+        if let Some(i) = self.remembered_accounts.iter().position(|a| a.username == username) {
+            let account = self.remembered_accounts[i].clone();
+            let moved = if keychain {
+                Keychain::store(&username, &account.token)
             } else {
-                Ok(0.0)
+                Keychain::delete(&username)
+            };
+            if let Err(err) = moved {
+                self.add_message(format!("Could not use the OS keychain: {}", err));
+                return Ok(());
             }
-        } else {
-            Ok(0.0)
+            self.remembered_accounts[i].in_keychain = keychain;
+            self.save_remembered_accounts();
         }
+        self.keychain_sessions = keychain;
+        UserSettings::set(&self.conn, &username, "keychain_sessions", &keychain.to_string())
     }
 
-    /// Gets the transactions of the current user.
-    pub fn get_transactions(&self) -> Result<Vec<HashMap<String, String>>> {
-        if let Some(username) = &self.current_user {
-            Transaction::get_user_transactions(&self.conn, username)
-        } else {
-            Ok(Vec::new())
+    /// Switches the history between compact and detailed rows and remembers
+    /// the choice for the current user, unless the session is view-only.
+    pub fn toggle_compact_history(&mut self) -> Result<()> {
+        self.compact_history = !self.compact_history;
+        if let (Some(username), false) = (&self.current_user, self.view_only) {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "compact_history",
+                &self.compact_history.to_string(),
+            )?;
         }
+        Ok(())
     }
 
-    /// Gets the username of the current user.
-    pub fn get_current_user(&self) -> Option<&str> {
-        self.current_user.as_deref()
+    /// Hides or reveals amounts on screen and remembers the choice for the
+    /// current user, unless the session is view-only.
+    pub fn toggle_hide_amounts(&mut self) -> Result<()> {
+        self.hide_amounts = !self.hide_amounts;
+        if let (Some(username), false) = (&self.current_user, self.view_only) {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "hide_amounts",
+                &self.hide_amounts.to_string(),
+            )?;
+        }
+        Ok(())
     }
 
-    /// Handles user input based on the current application state.
-    pub fn handle_input(&mut self, key: KeyCode) -> Result<bool> {
-        match self.current_state {
-            AppState::MainMenu => match key {
-                KeyCode::Char('1') => self.current_state = AppState::Login,
-                KeyCode::Char('2') => self.current_state = AppState::CreateAccount,
-                KeyCode::Char('q') => return Ok(false),
-                _ => {}
-            },
-            AppState::Login | AppState::CreateAccount => match key {
-                KeyCode::Enter => {
-                    if !self.input.is_empty() {
-                        let success = if self.current_state == AppState::Login {
-                            self.login(self.input.clone())?
-                        } else {
-                            self.create_account(self.input.clone())?
-                        };
-                        if success {
-                            self.input.clear();
-                        }
-                    }
-                }
-                KeyCode::Char(c) => self.input.push(c),
-                KeyCode::Backspace => {
-                    self.input.pop();
-                }
-                KeyCode::Esc => {
-                    self.current_state = AppState::MainMenu;
-                    self.input.clear();
-                }
-                _ => {}
-            },
-            AppState::LoggedIn => match key {
-                KeyCode::Char('1') => self.current_state = AppState::Deposit,
-                KeyCode::Char('2') => self.current_state = AppState::Withdraw,
-                KeyCode::Char('3') => self.current_state = AppState::Transfer,
-                KeyCode::Char('4') => self.current_state = AppState::ViewTransactions,
-                KeyCode::Char('5') => self.logout(),
-                _ => {}
-            },
-            AppState::Deposit | AppState::Withdraw => match key {
-                KeyCode::Enter => {
-                    if let Ok(amount) = self.input.trim().parse::<f64>() {
-                        if amount >= 0.0 {
-                            if self.current_state == AppState::Deposit {
-                                self.deposit(amount)?;
-                            } else if self.can_withdraw(amount)? {
-                                self.withdraw(amount)?;
-                            } else {
-                                self.add_message("Insufficient funds.".to_string());
-                            }
-                            self.input.clear();
-                            self.current_state = AppState::LoggedIn;
-                        } else {
-                            self.add_message(
-                                "Invalid amount. Please enter a positive number.".to_string(),
-                            );
-                        }
-                    } else {
-                        self.add_message(
-                            "Invalid amount. Please enter a valid number.".to_string(),
-                        );
-                    }
-                }
-                KeyCode::Char(c) => self.input.push(c),
-                KeyCode::Backspace => {
-                    self.input.pop();
-                }
-                KeyCode::Esc => {
-                    self.current_state = AppState::LoggedIn;
-                    self.input.clear();
-                }
-                _ => {}
-            },
-            AppState::Transfer => match key {
-                KeyCode::Enter => {
-                    if self.transfer_recipient.is_none() {
-                        self.transfer_recipient = Some(self.input.clone());
-                        self.input.clear();
-                    } else {
-                        if let Ok(amount) = self.input.trim().parse::<f64>() {
-                            if amount >= 0.0 {
-                                let recipient = self.transfer_recipient.take().unwrap();
-                                self.transfer(recipient, amount)?;
-                                self.input.clear();
-                                self.current_state = AppState::LoggedIn;
-                            } else {
-                                self.add_message(
-                                    "Invalid amount. Please enter a positive number.".to_string(),
-                                );
-                            }
-                        } else {
-                            self.add_message(
-                                "Invalid amount. Please enter a valid number.".to_string(),
-                            );
-                        }
-                    }
-                }
-                KeyCode::Char(c) => self.input.push(c),
-                KeyCode::Backspace => {
-                    self.input.pop();
-                }
-                KeyCode::Esc => {
-                    self.current_state = AppState::LoggedIn;
-                    self.input.clear();
-                    self.transfer_recipient = None;
-                }
-                _ => {}
+    /// Gets the currency amounts are shown in, at the rate in the rules. A
+    /// currency the rules no longer list falls back to the base currency.
+    pub fn display_currency(&self) -> DisplayCurrency {
+        match self.rules().display_rates.get(&self.display_currency_code) {
+            Some(&rate) => DisplayCurrency {
+                code: self.display_currency_code.clone(),
+                rate,
             },
-            AppState::ViewTransactions => {
-                if key == KeyCode::Esc || key == KeyCode::Enter {
-                    self.current_state = AppState::LoggedIn;
-                }
-            }
+            None => DisplayCurrency::base(),
         }
-        Ok(true)
+    }
+
+    /// Switches to showing amounts in the next currency the rules have a
+    /// rate for, after the base currency, and remembers the choice for the
+    /// current user, unless the session is view-only.
+    pub fn cycle_display_currency(&mut self) -> Result<()> {
+        let codes: Vec<String> = std::iter::once(BASE_CURRENCY.to_string())
+            .chain(self.rules().display_rates.keys().cloned())
+            .collect();
+        let current = self.display_currency().code;
+        let next = codes
+            .iter()
+            .position(|code| *code == current)
+            .map_or(0, |i| (i + 1) % codes.len());
+        self.display_currency_code = codes[next].clone();
+        if let (Some(username), false) = (&self.current_user, self.view_only) {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "display_currency",
+                &self.display_currency_code,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Turns signing of outgoing transfers on or off and remembers the choice.
+    pub fn toggle_sign_transfers(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if self.signing_key.is_none() {
+            self.add_message(if self.has_password {
+                "Log in with your password to unlock your signing key.".to_string()
+            } else {
+                "Set a password (p in Settings) to get a signing key.".to_string()
+            });
+            return Ok(());
+        }
+        self.sign_transfers = !self.sign_transfers;
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "sign_transfers",
+                &self.sign_transfers.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Turns the note breakdown after cash withdrawals on or off and
+    /// remembers the choice.
+    pub fn toggle_cash_breakdown(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        self.cash_breakdown = !self.cash_breakdown;
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "cash_breakdown",
+                &self.cash_breakdown.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Breaks the last cash withdrawal down into the notes in the rules.
+    pub fn get_cash_breakdown(&self) -> Option<Breakdown> {
+        self.last_cash_out
+            .map(|amount| denomination::breakdown(amount, &self.rules().cash_denominations))
+    }
+
+    /// Turns integrity mode on or off and remembers the choice. In integrity
+    /// mode the history also lists rows that cannot be read or name missing
+    /// users, and logs them to `integrity.log`.
+    pub fn toggle_integrity_mode(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        self.integrity_mode = !self.integrity_mode;
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "integrity_mode",
+                &self.integrity_mode.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Adds the current user to the leaderboard or takes them off it.
+    pub fn toggle_leaderboard(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        self.on_leaderboard = !self.on_leaderboard;
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "leaderboard",
+                &self.on_leaderboard.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Switches to the next payment request lifetime and saves it for the current user.
+    pub fn cycle_request_ttl(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = REQUEST_TTL_CHOICES
+            .iter()
+            .position(|&hours| hours == self.request_ttl_hours)
+            .map_or(0, |i| (i + 1) % REQUEST_TTL_CHOICES.len());
+        self.request_ttl_hours = REQUEST_TTL_CHOICES[next];
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "request_ttl_hours",
+                &self.request_ttl_hours.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Loads the user's large transfer cap and amount limit, falling back to
+    /// the rules for those they have not picked.
+    fn load_limits(&mut self, username: &str) -> Result<()> {
+        let rules = &self.rules_file.rules;
+        self.large_transfer_cap = UserSettings::get(&self.conn, username, "large_transfer_cap")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(rules.large_transfer_cap);
+        self.max_amount = UserSettings::get(&self.conn, username, "max_amount")?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(rules.max_amount);
+        Ok(())
+    }
+
+    /// Gets the business rules currently in force.
+    pub fn rules(&self) -> &Rules {
+        &self.rules_file.rules
+    }
+
+    /// Rereads `rules.toml` if it changed and applies it straight away.
+    /// Returns a line saying whether it was reloaded, or `None` if the file
+    /// did not change.
+    pub fn reload_rules(&mut self) -> Result<Option<String>> {
+        let message = match self.rules_file.reload_if_changed() {
+            None => return Ok(None),
+            Some(Ok(())) => format!("Reloaded {}", RULES_PATH),
+            Some(Err(err)) => format!("{} not reloaded: {}", RULES_PATH, err),
+        };
+        self.processor = self.rules_file.rules.processor.clone().with_env();
+        if let Some(username) = self.current_user.clone() {
+            self.load_limits(&username)?;
+        }
+        Ok(Some(message))
+    }
+
+    /// Switches to the next large transfer cap and saves it for the current user.
+    pub fn cycle_large_transfer_cap(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = LARGE_TRANSFER_CAP_CHOICES
+            .iter()
+            .position(|&cap| cap == self.large_transfer_cap)
+            .map_or(0, |i| (i + 1) % LARGE_TRANSFER_CAP_CHOICES.len());
+        self.large_transfer_cap = LARGE_TRANSFER_CAP_CHOICES[next];
+        if let Some(username) = self.current_user.clone() {
+            UserSettings::set(
+                &self.conn,
+                &username,
+                "large_transfer_cap",
+                &self.large_transfer_cap.to_string(),
+            )?;
+            self.publish(Event::LimitChanged {
+                username,
+                limit: Limit::LargeTransfer.as_str(),
+                value: self.large_transfer_cap,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Switches to the next overdraft limit and saves it for the current user.
+    pub fn cycle_overdraft_limit(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = OVERDRAFT_CHOICES
+            .iter()
+            .position(|&limit| limit == self.overdraft_limit)
+            .map_or(0, |i| (i + 1) % OVERDRAFT_CHOICES.len());
+        self.overdraft_limit = OVERDRAFT_CHOICES[next];
+        if let Some(username) = self.current_user.clone() {
+            UserSettings::set(
+                &self.conn,
+                &username,
+                "overdraft_limit",
+                &self.overdraft_limit.to_string(),
+            )?;
+            self.publish(Event::LimitChanged {
+                username,
+                limit: "overdraft limit",
+                value: self.overdraft_limit,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Switches to the next limit on entered amounts and saves it for the current user.
+    pub fn cycle_max_amount(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = MAX_AMOUNT_CHOICES
+            .iter()
+            .position(|&max| max == self.max_amount)
+            .map_or(0, |i| (i + 1) % MAX_AMOUNT_CHOICES.len());
+        self.max_amount = MAX_AMOUNT_CHOICES[next];
+        if let Some(username) = self.current_user.clone() {
+            UserSettings::set(&self.conn, &username, "max_amount", &self.max_amount.to_string())?;
+            self.publish(Event::LimitChanged {
+                username,
+                limit: "largest amount",
+                value: self.max_amount,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Sets or clears the low balance alert threshold for the current user.
+    pub fn set_balance_alert(&mut self, threshold: Option<f64>) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        self.balance_alert = threshold;
+        if let Some(username) = &self.current_user {
+            let value = threshold.map(|t| t.to_string()).unwrap_or_default();
+            UserSettings::set(&self.conn, username, "low_balance_alert", &value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes everything stored about the current user to `<username>-data.json`.
+    pub fn export_my_data(&mut self) -> Result<()> {
+        let username = match &self.current_user {
+            Some(username) => username.clone(),
+            None => return Ok(()),
+        };
+        let bundle = match UserData::export(&self.conn, &username, self.now())? {
+            Some(bundle) => bundle,
+            None => return Ok(()),
+        };
+        let path = format!("{}-data.json", username);
+        let contents = serde_json::to_string_pretty(&bundle).unwrap_or_default();
+        match std::fs::write(&path, contents) {
+            Ok(()) => self.add_message(format!("Your data was exported to {}", path)),
+            Err(err) => self.add_message(format!("Could not write {}: {}", path, err)),
+        }
+        Ok(())
+    }
+
+    /// Writes this month's spending by category and balance history as
+    /// `<username>-spending.svg` and `<username>-balance.svg`.
+    pub fn export_charts(&mut self) -> Result<()> {
+        let username = match &self.current_user {
+            Some(username) => username.clone(),
+            None => return Ok(()),
+        };
+        let since = self.start_of_month();
+        let month = since.format("%B %Y");
+        let history = self.get_balance_history(&username, since)?;
+        if history.is_empty() {
+            self.add_message("No transactions to chart yet.".to_string());
+            return Ok(());
+        }
+        let spending =
+            Transaction::get_spending_by_category_since(&self.report_conn, &username, since)?;
+        let charts = [
+            (
+                format!("{}-spending.svg", username),
+                chart::spending_breakdown(&format!("Spending by category, {}", month), &spending),
+            ),
+            (
+                format!("{}-balance.svg", username),
+                chart::balance_history(&format!("Balance, {}", month), &history),
+            ),
+        ];
+        let mut written = Vec::new();
+        for (path, svg) in charts {
+            match svg.map_err(|err| err.to_string()).and_then(|svg| {
+                std::fs::write(&path, svg).map_err(|err| err.to_string())
+            }) {
+                Ok(()) => written.push(path),
+                Err(err) => self.add_message(format!("Could not write {}: {}", path, err)),
+            }
+        }
+        if !written.is_empty() {
+            self.add_message(format!("Charts exported to {}", written.join(" and ")));
+        }
+        Ok(())
+    }
+
+    /// Gets a user's balance since `since`: each earlier day's closing
+    /// balance from the daily snapshots, then the balance after each of
+    /// today's transactions. Missing snapshots are backfilled first.
+    fn get_balance_history(
+        &self,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<Vec<(NaiveDateTime, f64)>> {
+        let today = self.now().date();
+        BalanceSnapshots::backfill(&self.conn, today)?;
+        let mut history: Vec<(NaiveDateTime, f64)> =
+            BalanceSnapshots::get_daily(&self.report_conn, username, since.date(), today)?
+                .into_iter()
+                .map(|(day, balance)| (day.and_hms_opt(23, 59, 59).unwrap_or_default(), balance))
+                .collect();
+        let start_of_today = today.and_hms_opt(0, 0, 0).unwrap_or_default();
+        history.extend(Transaction::get_balance_history_since(
+            &self.report_conn,
+            username,
+            start_of_today,
+        )?);
+        Ok(history)
+    }
+
+    /// Records the closing balance of every user for the days that have
+    /// none yet, for the balance charts.
+    pub fn record_balance_snapshots(&self) -> Result<usize> {
+        BalanceSnapshots::backfill(&self.conn, self.now().date())
+    }
+
+    /// Switches to the next vault withdrawal delay and saves it for the current
+    /// user. Withdrawals already requested keep the delay they were given.
+    pub fn cycle_vault_delay(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = VAULT_DELAY_CHOICES
+            .iter()
+            .position(|&hours| hours == self.vault_delay_hours)
+            .map_or(0, |i| (i + 1) % VAULT_DELAY_CHOICES.len());
+        self.vault_delay_hours = VAULT_DELAY_CHOICES[next];
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "vault_delay_hours",
+                &self.vault_delay_hours.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Switches to the next history retention period, saves it for the
+    /// current user and purges what it no longer keeps.
+    pub fn cycle_retention(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = RETENTION_CHOICES
+            .iter()
+            .position(|&retention| retention == self.retention)
+            .map_or(0, |i| (i + 1) % RETENTION_CHOICES.len());
+        self.retention = RETENTION_CHOICES[next];
+        if let Some(username) = &self.current_user {
+            UserSettings::set(&self.conn, username, "retention", &self.retention.as_setting())?;
+        }
+        self.enforce_retention()
+    }
+
+    /// Summarizes the current user's transactions older than their retention
+    /// period into monthly rollups, so the balance can still be explained.
+    fn enforce_retention(&mut self) -> Result<()> {
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let cutoff = match self.retention.cutoff(self.now()) {
+            Some(cutoff) => cutoff,
+            None => return Ok(()),
+        };
+        let purged = Rollup::purge_before(&self.conn, &username, cutoff)?;
+        if purged > 0 {
+            self.selected_transaction = 0;
+            self.add_message(format!(
+                "Summarized {} transaction(s) older than {} into monthly totals.",
+                purged,
+                self.retention.describe()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Gets the monthly totals that stand in for the current user's purged transactions.
+    pub fn get_rollups(&self) -> Result<Vec<Rollup>> {
+        match &self.current_user {
+            Some(username) => Rollup::get_user_rollups(&self.conn, username),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Sets where the current user's round-ups go: `vault`, another user's
+    /// name, or nothing to turn round-ups off.
+    pub fn set_round_up_target(&mut self, value: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let target = match value.trim() {
+            "" => None,
+            "vault" => Some(RoundUpTarget::Vault),
+            account if account == username || self.cached_user(account)?.is_none() => {
+                self.add_message(format!("User '{}' not found.", account));
+                return Ok(false);
+            }
+            account => Some(RoundUpTarget::Account(account.to_string())),
+        };
+        let setting = target.as_ref().map(RoundUpTarget::as_setting).unwrap_or_default();
+        UserSettings::set(&self.conn, &username, "round_up_to", &setting)?;
+        self.round_up_to = target;
+        Ok(true)
+    }
+
+    /// Switches to the next idle lock timeout and saves it for the current user.
+    pub fn cycle_idle_lock(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let next = IDLE_LOCK_CHOICES
+            .iter()
+            .position(|&minutes| minutes == self.idle_lock_minutes)
+            .map_or(0, |i| (i + 1) % IDLE_LOCK_CHOICES.len());
+        self.idle_lock_minutes = IDLE_LOCK_CHOICES[next];
+        if let Some(username) = &self.current_user {
+            UserSettings::set(
+                &self.conn,
+                username,
+                "idle_lock_minutes",
+                &self.idle_lock_minutes.to_string(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Runs periodic work between key presses: locks the session after the
+    /// configured time without input and, outside view-only sessions, runs
+    /// the jobs that are due.
+    pub fn tick(&mut self) -> Result<()> {
+        let idle_limit = Duration::from_secs(self.idle_lock_minutes * 60);
+        if self.current_user.is_some()
+            && self.current_screen != Screen::Locked
+            && self.idle_lock_minutes > 0
+            && self.since(self.last_activity) >= idle_limit
+        {
+            self.lock();
+        }
+        let data_version = database::data_version(&self.conn)?;
+        if data_version != self.data_version {
+            self.data_version = data_version;
+            self.invalidate_balance();
+            if self.current_screen == Screen::CheckoutCode {
+                self.refresh_checkout()?;
+            }
+        }
+        if !self.view_only {
+            self.run_due_jobs()?;
+        }
+        if self.since(self.last_rules_check) >= RULES_CHECK_INTERVAL {
+            self.last_rules_check = self.clock.instant();
+            if let Some(message) = self.reload_rules()? {
+                self.add_message(message);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the periodic jobs that write to the database: the export log,
+    /// expiries, vault releases and interest, allowances and settlements.
+    fn run_due_jobs(&mut self) -> Result<()> {
+        self.sync_export_log();
+        if self.since(self.last_expiry_check) >= REQUEST_EXPIRY_INTERVAL {
+            self.expire_payment_requests()?;
+            Holds::expire_due(&self.conn, self.now())?;
+            self.release_vault_withdrawals()?;
+            self.pay_vault_interest()?;
+            self.pay_allowances()?;
+            self.expire_joint_approvals()?;
+        }
+        if self.since(self.last_processor_check) >= PROCESSOR_CHECK_INTERVAL {
+            self.last_processor_check = self.clock.instant();
+            self.settle_processor_payments()?;
+        }
+        Ok(())
+    }
+
+    /// Appends newly committed transactions to the export log. The log is
+    /// turned off for the rest of the session if it cannot be written.
+    fn sync_export_log(&mut self) {
+        let result = match &self.export_log {
+            Some(log) => log.sync(&self.conn),
+            None => return,
+        };
+        if let Err(err) = result {
+            self.export_log = None;
+            self.add_message(format!("Export log disabled: {}", err));
+        }
+    }
+
+    /// Covers the current screen with the lock screen. Whatever was being
+    /// typed is kept and restored on unlock.
+    pub fn lock(&mut self) {
+        self.locked_input = std::mem::take(&mut self.input);
+        self.screen_stack.push(self.current_screen);
+        self.current_screen = Screen::Locked;
+        self.add_message(format!(
+            "Locked after {} minute(s) of inactivity.",
+            self.idle_lock_minutes
+        ));
+    }
+
+    /// Unlocks the session if the typed credential matches the current user:
+    /// their password, or their username if they have none.
+    pub fn unlock(&mut self, credential: &str) -> Result<bool> {
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let unlocked = if self.has_password {
+            AccountPassword::verify(&self.conn, &username, credential)?
+        } else {
+            username == credential
+        };
+        if unlocked {
+            self.input = std::mem::take(&mut self.locked_input);
+            self.add_message("Unlocked.".to_string());
+        } else if self.has_password {
+            self.add_message("Wrong password. Please try again.".to_string());
+        } else {
+            self.add_message("Incorrect username. Please try again.".to_string());
+        }
+        Ok(unlocked)
+    }
+
+    /// Formats an amount for messages in the display currency, respecting
+    /// the privacy toggle.
+    pub fn format_amount(&self, amount: f64) -> String {
+        self.display_currency().money_or_masked(amount, self.hide_amounts)
+    }
+
+    /// Parses the amount in the input box, telling the user why it was
+    /// rejected if it is not a positive number within their limit.
+    pub fn read_amount(&mut self) -> Option<f64> {
+        match parse_amount(&self.input, self.max_amount) {
+            Ok(amount) => Some(amount),
+            Err(error) => {
+                self.add_message(match error {
+                    AmountError::NotANumber => {
+                        "Invalid amount. Please enter a valid number.".to_string()
+                    }
+                    AmountError::NotPositive => {
+                        "Invalid amount. Please enter a positive number.".to_string()
+                    }
+                    AmountError::TooPrecise => {
+                        "Invalid amount. Please enter at most two decimal places.".to_string()
+                    }
+                    AmountError::TooLarge => format!(
+                        "Amounts are limited to {}. The limit can be changed in Settings.",
+                        format::money(self.max_amount)
+                    ),
+                });
+                None
+            }
+        }
+    }
+
+    /// Processes a deposit for the current user.
+    pub fn deposit(&mut self, amount: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = self.current_user.clone() {
+            let tx = self.conn.unchecked_transaction()?;
+            let (previous_balance, _) = self.record_cash(&tx, &username, "deposit", amount)?;
+            tx.commit()?;
+            self.invalidate_balance();
+
+            self.add_message(format!("Deposited {}", self.format_amount(amount)));
+            self.publish_committed("deposit", amount, previous_balance)?;
+        }
+        Ok(())
+    }
+
+    /// Processes a withdrawal for the current user.
+    pub fn withdraw(&mut self, amount: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = self.current_user.clone() {
+            let tx = self.conn.unchecked_transaction()?;
+            let (previous_balance, _) = self.record_cash(&tx, &username, "withdraw", -amount)?;
+            tx.commit()?;
+            self.invalidate_balance();
+
+            self.add_message(format!("Withdrawn {}", self.format_amount(amount)));
+            self.publish_committed("withdraw", amount, previous_balance)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `change` to `username`'s balance and records it as a
+    /// `transaction_type` transaction, both through `conn`, so inside a
+    /// transaction neither happens without the other. Returns the balance
+    /// before and after.
+    fn record_cash(
+        &self,
+        conn: &Connection,
+        username: &str,
+        transaction_type: &str,
+        change: f64,
+    ) -> Result<(f64, f64)> {
+        let (previous_balance, new_balance) =
+            User::adjust_balance(conn, username, change, self.now())?;
+        let transaction = Transaction {
+            id: self.generate_transaction_id(),
+            username: username.to_string(),
+            transaction_type: transaction_type.to_string(),
+            amount: change.abs(),
+            recipient: None,
+            sender: None,
+            previous_balance,
+            new_balance,
+            timestamp: self.now(),
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: None,
+            merchant: None,
+        };
+        Transaction::create(conn, &transaction)?;
+        Ok((previous_balance, new_balance))
+    }
+
+    /// Places a hold on part of the current user's balance, as a card
+    /// authorization would. The money stays in the balance but cannot be
+    /// spent until the hold is captured, released or expires. Returns the
+    /// hold id, or `None` if the available balance does not cover it.
+    pub fn hold(&mut self, amount: f64, description: &str) -> Result<Option<i64>> {
+        if self.refuse_in_view_mode() {
+            return Ok(None);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(None),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let balance = User::get(&tx, &username)?.map_or(0.0, |u| u.balance);
+        let available = balance - Self::committed(&tx, &username)?;
+        if amount <= 0.0 || amount > available {
+            drop(tx);
+            self.add_message(format!(
+                "Cannot hold {}. Available balance: {}",
+                self.format_amount(amount),
+                self.format_amount(available)
+            ));
+            return Ok(None);
+        }
+        let now = self.now();
+        let expires_at = now + chrono::Duration::days(HOLD_LIFETIME_DAYS);
+        let id = Holds::create(&tx, &username, amount, description, now, expires_at)?;
+        tx.commit()?;
+        self.add_message(format!("Holding {} for {}.", self.format_amount(amount), description));
+        Ok(Some(id))
+    }
+
+    /// Settles one of the current user's active holds, taking the held
+    /// amount from the balance as a withdrawal.
+    pub fn capture(&mut self, hold_id: i64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let hold = match self.get_own_hold(hold_id)? {
+            Some(hold) => hold,
+            None => return Ok(false),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let previous_balance = User::get(&tx, &hold.username)?.map_or(0.0, |u| u.balance);
+        if previous_balance < hold.amount {
+            drop(tx);
+            self.add_message(format!(
+                "Cannot capture {}. Your balance: {}",
+                self.format_amount(hold.amount),
+                self.format_amount(previous_balance)
+            ));
+            return Ok(false);
+        }
+        if !Holds::set_status(&tx, hold_id, RequestStatus::Paid)? {
+            return Ok(false);
+        }
+        let (previous_balance, new_balance) =
+            User::adjust_balance(&tx, &hold.username, -hold.amount, self.now())?;
+        Transaction::create(
+            &tx,
+            &Transaction {
+                id: self.generate_transaction_id(),
+                username: hold.username.clone(),
+                transaction_type: "withdraw".to_string(),
+                amount: hold.amount,
+                recipient: None,
+                sender: None,
+                previous_balance,
+                new_balance,
+                timestamp: self.now(),
+                currency: BASE_CURRENCY.to_string(),
+                fx_rate: 1.0,
+                transfer_id: None,
+                merchant: None,
+            },
+        )?;
+        tx.commit()?;
+        self.invalidate_balance();
+        self.add_message(format!(
+            "Captured {} for {}.",
+            self.format_amount(hold.amount),
+            hold.description
+        ));
+        self.publish_committed("withdraw", hold.amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Cancels one of the current user's active holds, making the money
+    /// available again.
+    pub fn release(&mut self, hold_id: i64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let hold = match self.get_own_hold(hold_id)? {
+            Some(hold) => hold,
+            None => return Ok(false),
+        };
+        if !Holds::set_status(&self.conn, hold_id, RequestStatus::Declined)? {
+            return Ok(false);
+        }
+        self.add_message(format!(
+            "Released the hold of {} for {}.",
+            self.format_amount(hold.amount),
+            hold.description
+        ));
+        Ok(true)
+    }
+
+    /// Gets an active hold of the current user, telling them if there is none.
+    fn get_own_hold(&mut self, hold_id: i64) -> Result<Option<Hold>> {
+        let hold = Holds::get(&self.conn, hold_id)?.filter(|hold| {
+            hold.status == RequestStatus::Pending
+                && Some(&hold.username) == self.current_user.as_ref()
+        });
+        if hold.is_none() {
+            self.add_message(format!("No active hold #{}.", hold_id));
+        }
+        Ok(hold)
+    }
+
+    /// Gets the part of the current user's balance that can be spent: the
+    /// ledger balance less active holds and held transfers still to go out.
+    pub fn get_available_balance(&self) -> Result<f64> {
+        match &self.current_user {
+            Some(username) => Ok(self.get_balance()? - Self::committed(&self.conn, username)?),
+            None => Ok(0.0),
+        }
+    }
+
+    /// Sums the money that is still in a user's balance but already spoken
+    /// for, or not cleared yet.
+    fn committed(conn: &Connection, username: &str) -> Result<f64> {
+        Ok(Holds::total_active(conn, username)?
+            + PendingTransfer::total_pending_for_sender(conn, username)?
+            + Processor::total_uncleared(conn, username)?)
+    }
+
+    /// Checks if the current user's available balance, with their overdraft,
+    /// covers a specified amount. The balance is read from the database, as
+    /// another session may have spent it since it was cached.
+    pub fn can_withdraw(&self, amount: f64) -> Result<bool> {
+        if let Some(username) = &self.current_user {
+            if let Some(user) = self.fetch_user(username)? {
+                let available = user.balance - Self::committed(&self.conn, username)?;
+                Ok(available + self.overdraft_limit >= amount)
+            } else {
+                Ok(false)
+            }
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Checks `can_withdraw` for `username` through `conn`, so a check made
+    /// inside a transaction still holds when it commits.
+    fn can_withdraw_in(&self, conn: &Connection, username: &str, amount: f64) -> Result<bool> {
+        Ok(Self::available_in(conn, username)? + self.overdraft_limit >= amount)
+    }
+
+    /// Gets a user's available balance, read through `conn`.
+    fn available_in(conn: &Connection, username: &str) -> Result<f64> {
+        let balance = User::get(conn, username)?.map_or(0.0, |user| user.balance);
+        Ok(balance - Self::committed(conn, username)?)
+    }
+
+    /// Processes a transfer between the current user and another user, with
+    /// an optional note shown to both of them. Returns the transfer id, or
+    /// `None` if the transfer did not go through.
+    pub fn transfer(
+        &mut self,
+        recipient: String,
+        amount: f64,
+        note: Option<&str>,
+    ) -> Result<Option<String>> {
+        if self.refuse_in_view_mode() {
+            return Ok(None);
+        }
+        if let (Some(sender_username), Some(_)) =
+            (self.current_user.clone(), self.cached_user(&recipient)?)
+        {
+            if !self.may_spend(amount)? {
+                return Ok(None);
+            }
+            let sender_previous_balance = self.read_balance()?;
+
+            let tx = self.conn.unchecked_transaction()?;
+            if !self.can_withdraw_in(&tx, &sender_username, amount)? {
+                let available = Self::available_in(&tx, &sender_username)?;
+                drop(tx);
+                self.add_message(format!(
+                    "Transfer failed. Insufficient funds. Available balance: {}",
+                    self.format_amount(available)
+                ));
+                return Ok(None);
+            }
+            let transfer_id =
+                self.record_transfer(&tx, &sender_username, &recipient, amount, note)?;
+            let spare = self.apply_round_up(&tx, &sender_username, &transfer_id, amount)?;
+            tx.commit()?;
+
+            self.add_message(format!(
+                "Transferred {} to {}{}",
+                self.format_amount(amount),
+                recipient,
+                self.round_up_note(spare)
+            ));
+            self.publish_committed("transfer_out", amount, sender_previous_balance)?;
+            Ok(Some(transfer_id))
+        } else {
+            self.add_message(format!(
+                "Transfer failed. Recipient '{}' not found.",
+                recipient
+            ));
+            Ok(None)
+        }
+    }
+
+    /// Transfers money from the current user to several recipients at once.
+    /// Every leg is validated up front and all of them are committed in a
+    /// single SQLite transaction, so either every transfer happens or none does.
+    /// A batch that a single transfer of its total would be held for is held
+    /// too, every transfer in it, so splitting a transfer up does not get
+    /// around the cooling period or a co-owner's approval.
+    pub fn transfer_many(&mut self, transfers: &[(String, f64)]) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let sender_username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if transfers.is_empty() {
+            self.add_message("Transfer failed. No recipients given.".to_string());
+            return Ok(false);
+        }
+
+        for (recipient, amount) in transfers {
+            if !amount.is_finite() || *amount <= 0.0 {
+                self.add_message(format!(
+                    "Transfer failed. Invalid amount for '{}'.",
+                    recipient
+                ));
+                return Ok(false);
+            }
+            if *recipient == sender_username {
+                self.add_message("Transfer failed. You cannot transfer to yourself.".to_string());
+                return Ok(false);
+            }
+            if self.cached_user(recipient)?.is_none() {
+                self.add_message(format!(
+                    "Transfer failed. Recipient '{}' not found.",
+                    recipient
+                ));
+                return Ok(false);
+            }
+        }
+
+        let total: f64 = transfers.iter().map(|(_, amount)| amount).sum();
+        if !self.may_spend(total)? {
+            return Ok(false);
+        }
+        let needs_approval = self.needs_joint_approval(total)?;
+        if needs_approval || self.is_large_transfer(total) {
+            return self.hold_batch(&sender_username, transfers, total, needs_approval);
+        }
+        let sender_previous_balance = self.read_balance()?;
+        let tx = self.conn.unchecked_transaction()?;
+        if !self.can_withdraw_in(&tx, &sender_username, total)? {
+            let available = Self::available_in(&tx, &sender_username)?;
+            drop(tx);
+            self.add_message(format!(
+                "Transfer failed. Insufficient funds for {}. Available balance: {}",
+                self.format_amount(total),
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+        let mut spare = 0.0;
+        for (recipient, amount) in transfers {
+            let payment_id = self.record_transfer(&tx, &sender_username, recipient, *amount, None)?;
+            spare += self.apply_round_up(&tx, &sender_username, &payment_id, *amount)?;
+        }
+        tx.commit()?;
+
+        self.add_message(format!(
+            "Transferred {} to {} recipients{}",
+            self.format_amount(total),
+            transfers.len(),
+            self.round_up_note(spare)
+        ));
+        self.publish_committed("transfer_out", total, sender_previous_balance)?;
+        Ok(true)
+    }
+
+    /// Adds a `username amount` line to the transfer to several people.
+    /// Returns `false` after telling the user what is wrong with it.
+    pub fn add_batch_transfer(&mut self, text: &str) -> Result<bool> {
+        let (recipient, amount) = match text.split_whitespace().collect::<Vec<_>>()[..] {
+            [recipient, amount] => (recipient.to_string(), amount),
+            _ => {
+                self.add_message("Invalid line. Use: username amount".to_string());
+                return Ok(false);
+            }
+        };
+        if self.get_current_user() == Some(recipient.as_str()) {
+            self.add_message("You cannot transfer to yourself.".to_string());
+            return Ok(false);
+        }
+        if !self.user_exists(&recipient)? {
+            self.add_message(format!("Recipient '{}' not found.", recipient));
+            return Ok(false);
+        }
+        let amount = match parse_amount(amount, self.max_amount) {
+            Ok(amount) => amount,
+            Err(_) => {
+                self.add_message(format!(
+                    "Invalid amount for '{}'. Amounts are positive and at most {}.",
+                    recipient,
+                    format::money(self.max_amount)
+                ));
+                return Ok(false);
+            }
+        };
+        self.batch_transfers.push((recipient, amount));
+        Ok(true)
+    }
+
+    /// Moves money between two users and records both legs. Balances are read
+    /// through `conn`, so legs applied inside one SQLite transaction see each other.
+    /// Transfers the signed-in user sends are signed if they turned that on.
+    /// Both legs carry the id of the sender's leg as their transfer id, which
+    /// the sender's note is stored under. Returns that id.
+    fn record_transfer(
+        &self,
+        conn: &Connection,
+        sender_username: &str,
+        recipient: &str,
+        amount: f64,
+        note: Option<&str>,
+    ) -> Result<String> {
+        let (sender_previous_balance, sender_new_balance) =
+            User::adjust_balance(conn, sender_username, -amount, self.now())?;
+        let (recipient_previous_balance, recipient_new_balance) =
+            User::adjust_balance(conn, recipient, amount, self.now())?;
+        self.invalidate_balance();
+
+        let timestamp = self.now();
+        let transfer_id = self.generate_transaction_id();
+        let sender_transaction = Transaction {
+            id: transfer_id.clone(),
+            username: sender_username.to_string(),
+            transaction_type: "transfer_out".to_string(),
+            amount,
+            recipient: Some(recipient.to_string()),
+            sender: Some(sender_username.to_string()),
+            previous_balance: sender_previous_balance,
+            new_balance: sender_new_balance,
+            timestamp,
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: Some(transfer_id.clone()),
+            merchant: None,
+        };
+        Transaction::create(conn, &sender_transaction)?;
+
+        let recipient_transaction = Transaction {
+            id: self.generate_transaction_id(),
+            username: recipient.to_string(),
+            transaction_type: "transfer_in".to_string(),
+            amount,
+            recipient: Some(recipient.to_string()),
+            sender: Some(sender_username.to_string()),
+            previous_balance: recipient_previous_balance,
+            new_balance: recipient_new_balance,
+            timestamp,
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: Some(transfer_id.clone()),
+            merchant: None,
+        };
+        Transaction::create(conn, &recipient_transaction)?;
+        let note = note.map(str::trim).filter(|note| !note.is_empty());
+        if let Some(note) = note {
+            Transaction::set_transfer_note(conn, &transfer_id, note)?;
+        }
+        if self.current_user.as_deref() != Some(recipient) {
+            let message = match note {
+                Some(note) => {
+                    format!("{} sent you {}: {}", sender_username, format::money(amount), note)
+                }
+                None => format!("{} sent you {}", sender_username, format::money(amount)),
+            };
+            Notifications::create(
+                conn,
+                recipient,
+                NotificationKind::TransferReceived,
+                &message,
+                timestamp,
+            )?;
+        }
+
+        let signs = self.sign_transfers && self.current_user.as_deref() == Some(sender_username);
+        if let Some(key) = self.signing_key.as_ref().filter(|_| signs) {
+            UserKey::sign_transfer(
+                conn,
+                key,
+                &[&sender_transaction.id, &recipient_transaction.id],
+                sender_username,
+                recipient,
+                amount,
+                &timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            )?;
+        }
+        Ok(sender_transaction.id)
+    }
+
+    /// Rounds a payment up to the next whole unit and moves the difference
+    /// wherever the payer's round-up setting says, linked to the payment.
+    /// Skipped when round-ups are off or the payer cannot cover the change.
+    /// Returns the amount moved.
+    fn apply_round_up(
+        &self,
+        conn: &Connection,
+        payer: &str,
+        payment_id: &str,
+        amount: f64,
+    ) -> Result<f64> {
+        let target = match UserSettings::get(conn, payer, "round_up_to")?
+            .and_then(|value| RoundUpTarget::parse(&value))
+        {
+            Some(target) => target,
+            None => return Ok(0.0),
+        };
+        let spare = RoundUp::spare_change(amount);
+        if spare <= 0.0 || Self::available_in(conn, payer)? < spare {
+            return Ok(0.0);
+        }
+
+        let id = match &target {
+            RoundUpTarget::Vault => {
+                let (previous_balance, _) = User::adjust_balance(conn, payer, -spare, self.now())?;
+                Vault::add(conn, payer, spare)?;
+                self.invalidate_balance();
+                self.record_vault_move(conn, payer, "vault_in", spare, previous_balance)?
+            }
+            RoundUpTarget::Account(account) => {
+                // Donations count as spending, so they respect a child's weekly cap.
+                let cap = ChildAccount::get(conn, payer)?.and_then(|c| c.spending_cap);
+                let (_, spent) = Transaction::get_totals_since(conn, payer, self.start_of_week())?;
+                if User::get(conn, account)?.is_none()
+                    || cap.is_some_and(|cap| spent + spare > cap + 0.005)
+                {
+                    return Ok(0.0);
+                }
+                self.record_transfer(conn, payer, account, spare, None)?
+            }
+        };
+        RoundUp::link(conn, &id, payment_id)?;
+        Ok(spare)
+    }
+
+    /// Describes round-up change for the message confirming a payment.
+    fn round_up_note(&self, spare: f64) -> String {
+        match &self.round_up_to {
+            _ if spare <= 0.0 => String::new(),
+            Some(RoundUpTarget::Vault) => {
+                format!(", {} rounded up into your vault", self.format_amount(spare))
+            }
+            Some(RoundUpTarget::Account(account)) => {
+                format!(", {} rounded up to {}", self.format_amount(spare), account)
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Holds every transfer of a batch, for a co-owner's approval if
+    /// `needs_approval` is set and for the cooling period otherwise. Each one
+    /// is then confirmed or approved from its screen like any held transfer.
+    fn hold_batch(
+        &mut self,
+        sender: &str,
+        transfers: &[(String, f64)],
+        total: f64,
+        needs_approval: bool,
+    ) -> Result<bool> {
+        let now = self.now();
+        let tx = self.conn.unchecked_transaction()?;
+        let mut ids = Vec::new();
+        for (recipient, amount) in transfers {
+            let id = PendingTransfer::create(
+                &tx,
+                sender,
+                recipient,
+                *amount,
+                None,
+                now,
+                needs_approval,
+            )?;
+            ids.push(format!("#{}", id));
+        }
+        if needs_approval {
+            let message = format!(
+                "{} wants to send {} to {} people. Approve or reject transfers {} in Joint \
+                Approvals.",
+                sender,
+                format::money(total),
+                transfers.len(),
+                ids.join(", ")
+            );
+            for member in JointAccounts::get_members(&tx, sender)? {
+                Notifications::create(
+                    &tx,
+                    &member.member,
+                    NotificationKind::JointApproval,
+                    &message,
+                    now,
+                )?;
+            }
+        }
+        tx.commit()?;
+
+        if needs_approval {
+            self.add_message(format!(
+                "Over {}: {} transfers waiting up to {}h for a co-owner to approve them.",
+                self.format_amount(self.rules().joint_approval_over),
+                transfers.len(),
+                self.rules().joint_approval_hours
+            ));
+        } else {
+            self.add_message(format!(
+                "Over {}: {} transfers held for {}s. Confirm them in Pending Transfers.",
+                self.format_amount(self.large_transfer_cap),
+                transfers.len(),
+                self.rules().cooling_seconds
+            ));
+            self.publish_limit_exceeded(Limit::LargeTransfer, total)?;
+        }
+        Ok(true)
+    }
+
+    /// Whether a transfer of this amount must be held for a cooling period.
+    pub fn is_large_transfer(&self, amount: f64) -> bool {
+        amount > self.large_transfer_cap
+    }
+
+    /// Holds a large transfer instead of sending it. The sender can confirm it
+    /// from the Pending Transfers screen once the cooling period has passed.
+    pub fn hold_large_transfer(
+        &mut self,
+        recipient: String,
+        amount: f64,
+        note: Option<&str>,
+    ) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let sender = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if self.cached_user(&recipient)?.is_none() {
+            self.add_message(format!(
+                "Transfer failed. Recipient '{}' not found.",
+                recipient
+            ));
+            return Ok(false);
+        }
+        let now = self.now();
+        PendingTransfer::create(&self.conn, &sender, &recipient, amount, note, now, false)?;
+        self.add_message(format!(
+            "Over {}: held for {}s. Confirm it in Pending Transfers.",
+            self.format_amount(self.large_transfer_cap),
+            self.rules().cooling_seconds
+        ));
+        self.publish_limit_exceeded(Limit::LargeTransfer, amount)?;
+        Ok(true)
+    }
+
+    /// Whether a transfer of this amount from the current user's account
+    /// needs a co-owner's approval, which it does from a joint account above
+    /// the approval threshold.
+    pub fn needs_joint_approval(&self, amount: f64) -> Result<bool> {
+        match &self.current_user {
+            Some(username) if amount > self.rules().joint_approval_over => {
+                JointAccounts::is_joint(&self.conn, username)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Holds a transfer from a joint account until one of its co-owners
+    /// approves it, and asks each of them in their inbox.
+    pub fn hold_for_approval(
+        &mut self,
+        recipient: String,
+        amount: f64,
+        note: Option<&str>,
+    ) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let sender = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if self.cached_user(&recipient)?.is_none() {
+            self.add_message(format!(
+                "Transfer failed. Recipient '{}' not found.",
+                recipient
+            ));
+            return Ok(false);
+        }
+        let now = self.now();
+        let id = PendingTransfer::create(&self.conn, &sender, &recipient, amount, note, now, true)?;
+        let message = format!(
+            "{} wants to send {} to {}. Approve or reject transfer #{} in Joint Approvals.",
+            sender,
+            format::money(amount),
+            recipient,
+            id
+        );
+        for member in JointAccounts::get_members(&self.conn, &sender)? {
+            self.notify(&member.member, NotificationKind::JointApproval, &message)?;
+        }
+        self.add_message(format!(
+            "Over {}: waiting up to {}h for a co-owner to approve it.",
+            self.format_amount(self.rules().joint_approval_over),
+            self.rules().joint_approval_hours
+        ));
+        Ok(true)
+    }
+
+    /// Reloads the current user's held transfers.
+    pub fn load_pending_transfers(&mut self) -> Result<()> {
+        self.pending_transfers = match &self.current_user {
+            Some(username) => PendingTransfer::get_pending_for_sender(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_pending_transfer >= self.pending_transfers.len() {
+            self.selected_pending_transfer = self.pending_transfers.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Seconds left before a held transfer can be confirmed.
+    pub fn cooling_seconds_left(&self, pending: &PendingTransfer) -> i64 {
+        let ready_at =
+            pending.created_at + chrono::Duration::seconds(self.rules().cooling_seconds);
+        (ready_at - self.now()).num_seconds().max(0)
+    }
+
+    /// Sends the selected held transfer if its cooling period has passed.
+    /// Returns the command to record when the transfer went through.
+    pub fn confirm_pending_transfer(&mut self) -> Result<Option<Command>> {
+        if self.refuse_in_view_mode() {
+            return Ok(None);
+        }
+        let pending = match self.pending_transfers.get(self.selected_pending_transfer) {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        if pending.needs_approval {
+            self.add_message("This transfer is waiting for a co-owner's approval.".to_string());
+            return Ok(None);
+        }
+        let seconds_left = self.cooling_seconds_left(pending);
+        if seconds_left > 0 {
+            self.add_message(format!(
+                "Please wait {} more second(s) before confirming this transfer.",
+                seconds_left
+            ));
+            return Ok(None);
+        }
+        let (id, recipient, amount, note) = (
+            pending.id,
+            pending.recipient.clone(),
+            pending.amount,
+            pending.note.clone(),
+        );
+
+        let previous_balance = self.read_balance()?;
+        let transfer_id = self.complete_pending_transfer(id, None)?;
+        if transfer_id.is_some() {
+            self.publish_committed("transfer_out", amount, previous_balance)?;
+        }
+        self.load_pending_transfers()?;
+        Ok(transfer_id.map(|transfer_id| Command::Transfer {
+            recipient,
+            amount,
+            note,
+            transfer_id,
+        }))
+    }
+
+    /// Cancels the selected held transfer.
+    pub fn cancel_pending_transfer(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(pending) = self.pending_transfers.get(self.selected_pending_transfer) {
+            if PendingTransfer::set_status(&self.conn, pending.id, RequestStatus::Declined)? {
+                self.add_message("Transfer cancelled.".to_string());
+            }
+        }
+        self.load_pending_transfers()
+    }
+
+    /// When a transfer waiting for approval expires.
+    pub fn approval_expires_at(&self, pending: &PendingTransfer) -> NaiveDateTime {
+        pending.created_at + chrono::Duration::hours(self.rules().joint_approval_hours)
+    }
+
+    /// Reloads the transfers waiting for the current user's approval.
+    pub fn load_approvals(&mut self) -> Result<()> {
+        self.approvals = match &self.current_user {
+            Some(username) => PendingTransfer::get_awaiting_approval(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_approval >= self.approvals.len() {
+            self.selected_approval = self.approvals.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Counts the transfers waiting for the current user's approval.
+    pub fn approvals_waiting(&self) -> Result<usize> {
+        match &self.current_user {
+            Some(username) => {
+                Ok(PendingTransfer::get_awaiting_approval(&self.conn, username)?.len())
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Approves and sends the selected joint account transfer.
+    pub fn approve_selected_transfer(&mut self) -> Result<()> {
+        self.decide_selected_transfer(true)
+    }
+
+    /// Rejects the selected joint account transfer; it is not sent.
+    pub fn reject_selected_transfer(&mut self) -> Result<()> {
+        self.decide_selected_transfer(false)
+    }
+
+    fn decide_selected_transfer(&mut self, approved: bool) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let member = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let (id, account, recipient, amount) = match self.approvals.get(self.selected_approval) {
+            Some(pending) => {
+                (pending.id, pending.sender.clone(), pending.recipient.clone(), pending.amount)
+            }
+            None => return Ok(()),
+        };
+        // Membership is checked again, in case it was taken away since the
+        // list was loaded.
+        if !JointAccounts::is_member(&self.conn, &account, &member)? {
+            self.add_message(format!("You are no longer a co-owner of {}.", account));
+            return self.load_approvals();
+        }
+        let decided = if approved {
+            self.complete_pending_transfer(id, Some(&member))?.is_some()
+        } else {
+            let tx = self.conn.unchecked_transaction()?;
+            let declined = PendingTransfer::set_status(&tx, id, RequestStatus::Declined)?;
+            if declined {
+                PendingTransfer::set_decided_by(&tx, id, &member)?;
+            }
+            tx.commit()?;
+            if declined {
+                self.add_message(format!(
+                    "Rejected {}'s transfer of {} to {}",
+                    account,
+                    self.format_amount(amount),
+                    recipient
+                ));
+            }
+            declined
+        };
+        if decided {
+            let outcome = if approved { "approved and sent" } else { "rejected" };
+            self.notify(
+                &account,
+                NotificationKind::JointApproval,
+                &format!(
+                    "{} {} your transfer of {} to {}",
+                    member,
+                    outcome,
+                    format::money(amount),
+                    recipient
+                ),
+            )?;
+            self.publish(Event::JointTransferDecided {
+                account,
+                member,
+                amount,
+                approved,
+            })?;
+        }
+        self.load_approvals()
+    }
+
+    /// Expires joint account transfers no co-owner approved in time and
+    /// tells the account. Returns a line describing each one.
+    pub fn expire_joint_approvals(&mut self) -> Result<Vec<String>> {
+        let cutoff = self.now() - chrono::Duration::hours(self.rules().joint_approval_hours);
+        let mut outcomes = Vec::new();
+        for pending in PendingTransfer::get_unapproved_before(&self.conn, cutoff)? {
+            if !PendingTransfer::set_status(&self.conn, pending.id, RequestStatus::Expired)? {
+                continue;
+            }
+            let message = format!(
+                "Your transfer of {} to {} expired without a co-owner's approval.",
+                self.format_amount(pending.amount),
+                pending.recipient
+            );
+            self.notify(&pending.sender, NotificationKind::RequestExpired, &message)?;
+            if self.current_user.as_deref() == Some(pending.sender.as_str()) {
+                self.add_message(message.clone());
+            }
+            outcomes.push(format!("#{} from {}: {}", pending.id, pending.sender, message));
+        }
+        if !outcomes.is_empty() {
+            self.load_pending_transfers()?;
+            self.load_approvals()?;
+        }
+        Ok(outcomes)
+    }
+
+    /// Sends a held transfer right away, skipping the cooling period. Used by
+    /// the `approve-transfer` admin command.
+    pub fn approve_pending_transfer(&mut self, id: i64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        Ok(self.complete_pending_transfer(id, None)?.is_some())
+    }
+
+    /// Closes an account: moves its whole balance to `nominee`, writes the
+    /// final statement and archives the user, all in one SQLite transaction.
+    /// Refused while the account is overdrawn or money is held, waiting in a
+    /// cooling period or in the vault. Returns the statement. Used by the
+    /// `close-account` admin command.
+    pub fn close_account(&mut self, username: &str, nominee: &str) -> Result<Option<String>> {
+        let user = match self.fetch_user(username)? {
+            Some(user) => user,
+            None => {
+                self.add_message(format!("User '{}' not found.", username));
+                return Ok(None);
+            }
+        };
+        if nominee == username || self.fetch_user(nominee)?.is_none() {
+            self.add_message(format!(
+                "Cannot close the account. '{}' is not another user's account.",
+                nominee
+            ));
+            return Ok(None);
+        }
+        if user.balance < 0.0 {
+            self.add_message("Cannot close the account while it is overdrawn.".to_string());
+            return Ok(None);
+        }
+        let committed = Self::committed(&self.conn, username)?;
+        let vault_balance = Vault::get_balance(&self.conn, username)?
+            + Vault::get_pending(&self.conn, username)?
+                .iter()
+                .map(|withdrawal| withdrawal.amount)
+                .sum::<f64>();
+        if Processor::count_pending(&self.conn, username)? > 0 {
+            self.add_message(
+                "Cannot close the account while a payment is pending with the processor."
+                    .to_string(),
+            );
+            return Ok(None);
+        }
+        if committed > 0.0 || vault_balance > 0.0 {
+            self.add_message(format!(
+                "Cannot close the account. {} is on hold or pending and {} is in the vault.",
+                self.format_amount(committed),
+                self.format_amount(vault_balance)
+            ));
+            return Ok(None);
+        }
+
+        let closed_at = self.now();
+        let tx = self.conn.unchecked_transaction()?;
+        if user.balance > 0.0 {
+            self.record_transfer(&tx, username, nominee, user.balance, Some("Account closed"))?;
+        }
+        let text = statement::final_statement(
+            username,
+            nominee,
+            closed_at,
+            &Rollup::get_user_rollups(&tx, username)?,
+            &Transaction::get_user_transactions(&tx, username, false, false)?,
+            user.balance.max(0.0),
+        );
+        ClosedAccounts::close(&tx, username, nominee, closed_at, &text)?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.add_message(format!(
+            "Closed {}. {} was transferred to {}.",
+            username,
+            self.format_amount(user.balance.max(0.0)),
+            nominee
+        ));
+        Ok(Some(text))
+    }
+
+    /// Sends a held transfer and marks it paid in one SQLite transaction,
+    /// along with the co-owner who approved it, if any. Returns the transfer
+    /// id if it went through.
+    fn complete_pending_transfer(
+        &mut self,
+        id: i64,
+        approver: Option<&str>,
+    ) -> Result<Option<String>> {
+        let pending = match PendingTransfer::get(&self.conn, id)? {
+            Some(pending) if pending.status == RequestStatus::Pending => pending,
+            _ => {
+                self.add_message("This transfer is no longer pending.".to_string());
+                return Ok(None);
+            }
+        };
+        // The transfer itself is one of the sender's commitments, so it is
+        // added back to what they have available.
+        let sender_balance = self.fetch_user(&pending.sender)?.map_or(0.0, |u| u.balance);
+        let available =
+            sender_balance - Self::committed(&self.conn, &pending.sender)? + pending.amount;
+        if available < pending.amount {
+            self.add_message(format!(
+                "Transfer failed. Insufficient funds. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(None);
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        if !PendingTransfer::set_status(&tx, id, RequestStatus::Paid)? {
+            return Ok(None);
+        }
+        if let Some(approver) = approver {
+            PendingTransfer::set_decided_by(&tx, id, approver)?;
+        }
+        let transfer_id = self.record_transfer(
+            &tx,
+            &pending.sender,
+            &pending.recipient,
+            pending.amount,
+            pending.note.as_deref(),
+        )?;
+        let spare = self.apply_round_up(&tx, &pending.sender, &transfer_id, pending.amount)?;
+        tx.commit()?;
+
+        self.add_message(match approver {
+            Some(_) => format!(
+                "Approved {}'s transfer of {} to {}",
+                pending.sender,
+                self.format_amount(pending.amount),
+                pending.recipient
+            ),
+            None => format!(
+                "Transferred {} to {}{}",
+                self.format_amount(pending.amount),
+                pending.recipient,
+                self.round_up_note(spare)
+            ),
+        });
+        Ok(Some(transfer_id))
+    }
+
+    /// Gets the current user's vault balance, not counting withdrawals on their way out.
+    pub fn get_vault_balance(&self) -> Result<f64> {
+        match &self.current_user {
+            Some(username) => Vault::get_balance(&self.conn, username),
+            None => Ok(0.0),
+        }
+    }
+
+    /// Reloads the current user's vault withdrawals that have not been released.
+    pub fn load_vault_withdrawals(&mut self) -> Result<()> {
+        self.vault_withdrawals = match &self.current_user {
+            Some(username) => Vault::get_pending(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_vault_withdrawal >= self.vault_withdrawals.len() {
+            self.selected_vault_withdrawal = self.vault_withdrawals.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Moves money from the spendable balance into the vault right away.
+    pub fn move_to_vault(&mut self, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let available = Self::available_in(&tx, &username)?;
+        if available < amount {
+            drop(tx);
+            self.add_message(format!(
+                "Not moved. Insufficient funds. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+        let (previous_balance, _) = User::adjust_balance(&tx, &username, -amount, self.now())?;
+        Vault::add(&tx, &username, amount)?;
+        self.record_vault_move(&tx, &username, "vault_in", amount, previous_balance)?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.add_message(format!("Moved {} to the vault", self.format_amount(amount)));
+        self.publish_committed("vault_in", amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Takes money out of the vault. It reaches the spendable balance once
+    /// the user's withdrawal delay has passed.
+    pub fn request_vault_withdrawal(&mut self, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let now = self.now();
+        let available_at = now + chrono::Duration::hours(self.vault_delay_hours);
+        let tx = self.conn.unchecked_transaction()?;
+        if !Vault::take(&tx, &username, amount)? {
+            let vault_balance = Vault::get_balance(&tx, &username)?;
+            drop(tx);
+            self.add_message(format!(
+                "Not withdrawn. The vault holds {}",
+                self.format_amount(vault_balance)
+            ));
+            return Ok(false);
+        }
+        Vault::create_withdrawal(&tx, &username, amount, now, available_at)?;
+        tx.commit()?;
+
+        self.add_message(format!(
+            "{} will be released from the vault at {}",
+            self.format_amount(amount),
+            available_at.format("%Y-%m-%d %H:%M")
+        ));
+        self.load_vault_withdrawals()?;
+        Ok(true)
+    }
+
+    /// Cancels the selected vault withdrawal and puts the money back in the vault.
+    pub fn cancel_vault_withdrawal(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let selected = self.vault_withdrawals.get(self.selected_vault_withdrawal);
+        let (id, username, amount) = match selected {
+            Some(w) => (w.id, w.username.clone(), w.amount),
+            None => return Ok(()),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let cancelled = Vault::set_status(&tx, id, RequestStatus::Declined)?;
+        if cancelled {
+            Vault::add(&tx, &username, amount)?;
+        }
+        tx.commit()?;
+        if cancelled {
+            self.add_message("Withdrawal cancelled. The money is back in the vault.".to_string());
+        }
+        self.load_vault_withdrawals()
+    }
+
+    /// Releases every user's vault withdrawals whose delay has passed into
+    /// their spendable balance, telling the current user about their own.
+    pub fn release_vault_withdrawals(&mut self) -> Result<()> {
+        let due = Vault::get_due(&self.conn, self.now())?;
+        for withdrawal in &due {
+            let tx = self.conn.unchecked_transaction()?;
+            if !Vault::set_status(&tx, withdrawal.id, RequestStatus::Paid)? {
+                continue;
+            }
+            let (previous_balance, _) =
+                User::adjust_balance(&tx, &withdrawal.username, withdrawal.amount, self.now())?;
+            self.record_vault_move(
+                &tx,
+                &withdrawal.username,
+                "vault_out",
+                withdrawal.amount,
+                previous_balance,
+            )?;
+            tx.commit()?;
+            self.invalidate_balance();
+
+            if self.current_user.as_deref() == Some(withdrawal.username.as_str()) {
+                self.add_message(format!(
+                    "Released {} from the vault",
+                    self.format_amount(withdrawal.amount)
+                ));
+            }
+        }
+        if !due.is_empty() {
+            self.load_vault_withdrawals()?;
+        }
+        Ok(())
+    }
+
+    /// Pays every vault the interest it earned since the last payment, at
+    /// the rate in the rules. Returns the total paid.
+    pub fn pay_vault_interest(&self) -> Result<f64> {
+        let today = self.now().date();
+        let rules = self.rules();
+        Vault::pay_interest(&self.conn, today, rules.vault_interest_percent, rules.rounding)
+    }
+
+    /// Deposits a cheque. The money is in the balance straight away but
+    /// cannot be spent until the cheque clears, after the clearing period;
+    /// if it bounces instead, it is taken back out with a fee.
+    pub fn deposit_cheque(&mut self, amount: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let now = self.now();
+        let delay = self.processor.delay_seconds(PaymentKind::Cheque);
+        let clears_at = now + chrono::Duration::seconds(delay);
+        let tx = self.conn.unchecked_transaction()?;
+        Processor::create(&tx, &username, PaymentKind::Cheque, amount, now, clears_at)?;
+        let previous_balance = self.record_processor_move(&tx, &username, "deposit", amount)?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.add_message(format!(
+            "Cheque of {} deposited. It can be spent once it clears at {}.",
+            self.format_amount(amount),
+            clears_at.format("%Y-%m-%d %H:%M")
+        ));
+        self.publish_committed("deposit", amount, previous_balance)?;
+        self.load_processor_payments()
+    }
+
+    /// Sends a card top-up to the simulated processor. The money arrives
+    /// once the processor settles it, unless the processor fails it.
+    pub fn top_up_by_card(&mut self, amount: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let now = self.now();
+        let delay = self.processor.delay_seconds(PaymentKind::CardTopUp);
+        let settles_at = now + chrono::Duration::seconds(delay);
+        Processor::create(&self.conn, &username, PaymentKind::CardTopUp, amount, now, settles_at)?;
+        self.add_message(format!(
+            "Top-up of {} sent to the card processor.",
+            self.format_amount(amount)
+        ));
+        self.load_processor_payments()
+    }
+
+    /// Reloads the current user's recent processor payments.
+    pub fn load_processor_payments(&mut self) -> Result<()> {
+        self.processor_payments = match &self.current_user {
+            Some(username) => {
+                Processor::get_recent(&self.conn, username, RECENT_PROCESSOR_PAYMENTS)?
+            }
+            None => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Sends money from the current user to their bank through the simulated
+    /// processor. It leaves the balance straight away, as a withdrawal, and
+    /// is refunded if the processor fails the payment. Returns whether it
+    /// was sent.
+    pub fn withdraw_to_bank(&mut self, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+        if !self.can_withdraw(amount)? {
+            let available = self.get_available_balance()?;
+            self.add_message(format!(
+                "Insufficient funds. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+
+        let now = self.now();
+        let delay = self.processor.delay_seconds(PaymentKind::BankWithdrawal);
+        let settles_at = now + chrono::Duration::seconds(delay);
+        let tx = self.conn.unchecked_transaction()?;
+        Processor::create(&tx, &username, PaymentKind::BankWithdrawal, amount, now, settles_at)?;
+        let previous_balance = self.record_processor_move(&tx, &username, "withdraw", amount)?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.add_message(format!(
+            "Withdrawal of {} is on its way to your bank.",
+            self.format_amount(amount)
+        ));
+        self.publish_committed("withdraw", amount, previous_balance)?;
+        self.load_processor_payments()?;
+        Ok(true)
+    }
+
+    /// Gets the simulated processor's answer for every payment that is due
+    /// and tells the users concerned. Each one fails at the configured rate.
+    /// A settled top-up is deposited, a failed bank withdrawal refunded, a
+    /// bounced cheque taken back out with the bounced cheque fee, and the
+    /// fee for a settled payment charged, in the same SQLite transaction
+    /// that records the answer. Returns a line describing each answer.
+    pub fn settle_processor_payments(&mut self) -> Result<Vec<String>> {
+        let due = Processor::get_due(&self.conn, self.now())?;
+        let mut outcomes = Vec::new();
+        for payment in &due {
+            let failure = {
+                let mut rng = self.rng.borrow_mut();
+                let reasons = payment.kind.failure_reasons();
+                (rng.gen::<f64>() < self.processor.failure_rate)
+                    .then(|| reasons[rng.gen_range(0..reasons.len())])
+            };
+            let tx = self.conn.unchecked_transaction()?;
+            let (answered, kind) = match failure {
+                Some(reason) => (
+                    Processor::fail(&tx, payment.id, reason)?,
+                    NotificationKind::PaymentFailed,
+                ),
+                None => (Processor::settle(&tx, payment.id)?, NotificationKind::PaymentSettled),
+            };
+            if !answered {
+                continue;
+            }
+            let movement = match (payment.kind, failure) {
+                (PaymentKind::CardTopUp, None) | (PaymentKind::BankWithdrawal, Some(_)) => {
+                    Some("deposit")
+                }
+                (PaymentKind::Cheque, Some(_)) => Some("withdraw"),
+                _ => None,
+            };
+            if let Some(transaction_type) = movement {
+                let username = &payment.username;
+                self.record_processor_move(&tx, username, transaction_type, payment.amount)?;
+            }
+            let fee = match (payment.kind, failure) {
+                (PaymentKind::Cheque, Some(_)) => self.rules().bounced_cheque_fee,
+                (_, Some(_)) => 0.0,
+                (_, None) => self.rules().fee(payment.kind, payment.amount),
+            };
+            if fee > 0.0 {
+                self.record_processor_move(&tx, &payment.username, "fee", fee)?;
+            }
+            tx.commit()?;
+            self.invalidate_balance();
+
+            let description = format!(
+                "{} of {}",
+                payment.kind.describe(),
+                self.format_amount(payment.amount)
+            );
+            let message = match failure {
+                Some(reason) if payment.kind == PaymentKind::BankWithdrawal => format!(
+                    "{} failed: {}. The money is back in your balance.",
+                    description, reason
+                ),
+                Some(reason) if payment.kind == PaymentKind::Cheque && fee > 0.0 => format!(
+                    "{} bounced: {}. It was taken back out, with a fee of {}.",
+                    description,
+                    reason,
+                    self.format_amount(fee)
+                ),
+                Some(reason) if payment.kind == PaymentKind::Cheque => {
+                    format!("{} bounced: {}. It was taken back out.", description, reason)
+                }
+                Some(reason) => format!("{} failed: {}", description, reason),
+                None if fee > 0.0 => {
+                    format!("{} settled, fee {}", description, self.format_amount(fee))
+                }
+                None if payment.kind == PaymentKind::Cheque => {
+                    format!("{} cleared and can be spent", description)
+                }
+                None => format!("{} settled", description),
+            };
+            self.notify(&payment.username, kind, &message)?;
+            if self.current_user.as_deref() == Some(payment.username.as_str()) {
+                self.add_message(message.clone());
+            }
+            outcomes.push(format!("#{} for {}: {}", payment.id, payment.username, message));
+        }
+        if !due.is_empty() {
+            self.load_processor_payments()?;
+        }
+        Ok(outcomes)
+    }
+
+    /// Moves a processor payment's money in or out of a user's balance as a
+    /// `deposit`, `withdraw` or `fee` transaction. Returns the balance before.
+    fn record_processor_move(
+        &self,
+        conn: &Connection,
+        username: &str,
+        transaction_type: &str,
+        amount: f64,
+    ) -> Result<f64> {
+        let change = if transaction_type == "deposit" { amount } else { -amount };
+        let (previous_balance, new_balance) =
+            User::adjust_balance(conn, username, change, self.now())?;
+        Transaction::create(
+            conn,
+            &Transaction {
+                id: self.generate_transaction_id(),
+                username: username.to_string(),
+                transaction_type: transaction_type.to_string(),
+                amount,
+                recipient: None,
+                sender: None,
+                previous_balance,
+                new_balance,
+                timestamp: self.now(),
+                currency: BASE_CURRENCY.to_string(),
+                fx_rate: 1.0,
+                transfer_id: None,
+                merchant: None,
+            },
+        )?;
+        Ok(previous_balance)
+    }
+
+    /// Records money moving into (`vault_in`) or out of (`vault_out`) the
+    /// vault as a transaction on the spendable balance.
+    fn record_vault_move(
+        &self,
+        conn: &Connection,
+        username: &str,
+        transaction_type: &str,
+        amount: f64,
+        previous_balance: f64,
+    ) -> Result<String> {
+        let new_balance = if transaction_type == "vault_in" {
+            previous_balance - amount
+        } else {
+            previous_balance + amount
+        };
+        let transaction = Transaction {
+            id: self.generate_transaction_id(),
+            username: username.to_string(),
+            transaction_type: transaction_type.to_string(),
+            amount,
+            recipient: None,
+            sender: None,
+            previous_balance,
+            new_balance,
+            timestamp: self.now(),
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: None,
+            merchant: None,
+        };
+        Transaction::create(conn, &transaction)?;
+        Ok(transaction.id)
+    }
+
+    /// Reloads the children managed by the current user.
+    pub fn load_children(&mut self) -> Result<()> {
+        self.children = match &self.current_user {
+            Some(username) => ChildAccount::get_for_parent(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_child >= self.children.len() {
+            self.selected_child = self.children.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Gets the child highlighted on the Family screen.
+    pub fn get_selected_child(&self) -> Option<&ChildAccount> {
+        self.children.get(self.selected_child)
+    }
+
+    /// Creates a new account managed by the current user.
+    pub fn add_child(&mut self, child: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let parent = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if child.is_empty() {
+            self.add_message("Please enter a username for the child.".to_string());
+            return Ok(false);
+        }
+        if !User::create(&self.conn, child)? {
+            self.add_message(
+                "Username already exists. Please choose a different username.".to_string(),
+            );
+            return Ok(false);
+        }
+        ChildAccount::create(&self.conn, child, &parent)?;
+        self.invalidate_balance();
+        self.add_message(format!("Created {}'s account.", child));
+        self.load_children()?;
+        Ok(true)
+    }
+
+    /// Sets the selected child's weekly allowance. The first one is sent on
+    /// the next check; 0 stops it.
+    pub fn set_child_allowance(&mut self, amount: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(child) = self.get_selected_child().map(|c| c.child.clone()) {
+            ChildAccount::set_allowance(&self.conn, &child, amount, self.now())?;
+            self.add_message(if amount > 0.0 {
+                format!("{} gets {} every week.", child, self.format_amount(amount))
+            } else {
+                format!("{}'s allowance stopped.", child)
+            });
+            self.load_children()?;
+        }
+        Ok(())
+    }
+
+    /// Sets or clears the selected child's weekly spending cap.
+    pub fn set_child_spending_cap(&mut self, cap: Option<f64>) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(child) = self.get_selected_child().map(|c| c.child.clone()) {
+            ChildAccount::set_spending_cap(&self.conn, &child, cap)?;
+            self.load_children()?;
+        }
+        Ok(())
+    }
+
+    /// Sums what a user has spent since the start of the week.
+    pub fn get_weekly_spending(&self, username: &str) -> Result<f64> {
+        Transaction::get_totals_since(&self.conn, username, self.start_of_week())
+            .map(|(_, money_out)| money_out)
+    }
+
+    /// Gets the selected child's balance for the parent to review.
+    pub fn get_child_balance(&self) -> Result<f64> {
+        match self.get_selected_child() {
+            Some(child) => Ok(self.cached_user(&child.child)?.map_or(0.0, |u| u.balance)),
+            None => Ok(0.0),
+        }
+    }
+
+    /// Gets the selected child's latest transactions for the parent to review.
+    pub fn get_child_activity(&self, limit: usize) -> Result<Vec<HashMap<String, String>>> {
+        match self.get_selected_child() {
+            Some(child) => Transaction::get_user_transactions_page(
+                &self.conn,
+                &child.child,
+                false,
+                false,
+                HistorySort::NewestFirst,
+                &HistoryFilter::default(),
+                0,
+                Some(limit),
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Loads who the current user gave read-only access to.
+    pub fn load_delegates(&mut self) -> Result<()> {
+        self.delegates = match &self.current_user {
+            Some(username) => Delegation::get_delegates(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_delegate >= self.delegates.len() {
+            self.selected_delegate = self.delegates.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Gives another user, such as an accountant, read-only access to the
+    /// current user's history and statements, and tells them in their inbox.
+    pub fn grant_access(&mut self, delegate: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let owner = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if delegate == owner {
+            self.add_message("You can always see your own history.".to_string());
+            return Ok(false);
+        }
+        if !self.user_exists(delegate)? {
+            self.add_message("User does not exist. Please try again.".to_string());
+            return Ok(false);
+        }
+        if !Delegation::grant(&self.conn, &owner, delegate, self.now())? {
+            self.add_message(format!("{} already has access.", delegate));
+            return Ok(false);
+        }
+        self.notify(
+            delegate,
+            NotificationKind::AccessShared,
+            &format!("{} gave you read-only access to their history", owner),
+        )?;
+        self.publish(Event::AccessGranted {
+            owner,
+            delegate: delegate.to_string(),
+        })?;
+        self.add_message(format!("{} can now view your history and statements.", delegate));
+        self.load_delegates()?;
+        Ok(true)
+    }
+
+    /// Takes back the read-only access of the delegate highlighted in Settings.
+    pub fn revoke_selected_delegate(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let (owner, delegate) = match self.delegates.get(self.selected_delegate) {
+            Some(delegation) => (delegation.owner.clone(), delegation.delegate.clone()),
+            None => return Ok(()),
+        };
+        if Delegation::revoke(&self.conn, &owner, &delegate)? {
+            self.publish(Event::AccessRevoked {
+                owner,
+                delegate: delegate.clone(),
+            })?;
+            self.add_message(format!("{} can no longer view your history.", delegate));
+        }
+        self.load_delegates()
+    }
+
+    /// Loads the co-owners of the current user's account.
+    pub fn load_joint_members(&mut self) -> Result<()> {
+        self.joint_members = match &self.current_user {
+            Some(username) => JointAccounts::get_members(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_joint_member >= self.joint_members.len() {
+            self.selected_joint_member = self.joint_members.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Makes another user a co-owner of the current user's account, which
+    /// turns it into a joint account, and tells them in their inbox.
+    pub fn add_joint_member(&mut self, member: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let account = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if member == account {
+            self.add_message("You already own this account.".to_string());
+            return Ok(false);
+        }
+        if !self.user_exists(member)? {
+            self.add_message("User does not exist. Please try again.".to_string());
+            return Ok(false);
+        }
+        if !JointAccounts::add(&self.conn, &account, member, self.now())? {
+            self.add_message(format!("{} is already a co-owner.", member));
+            return Ok(false);
+        }
+        self.notify(
+            member,
+            NotificationKind::AccessShared,
+            &format!(
+                "{} made you a co-owner. Transfers over {} from their account need your approval",
+                account,
+                format::money(self.rules().joint_approval_over)
+            ),
+        )?;
+        self.publish(Event::JointMemberAdded {
+            account,
+            member: member.to_string(),
+        })?;
+        self.add_message(format!(
+            "{} is now a co-owner and approves transfers over {}.",
+            member,
+            self.format_amount(self.rules().joint_approval_over)
+        ));
+        self.load_joint_members()?;
+        Ok(true)
+    }
+
+    /// Removes the co-owner highlighted in Settings. Transfers already
+    /// waiting for approval can still be approved by the remaining ones.
+    pub fn remove_selected_joint_member(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let (account, member) = match self.joint_members.get(self.selected_joint_member) {
+            Some(joint) => (joint.account.clone(), joint.member.clone()),
+            None => return Ok(()),
+        };
+        if JointAccounts::remove(&self.conn, &account, &member)? {
+            self.publish(Event::JointMemberRemoved {
+                account,
+                member: member.clone(),
+            })?;
+            self.add_message(format!("{} is no longer a co-owner.", member));
+        }
+        self.load_joint_members()
+    }
+
+    /// Loads the accounts whose history the current user was given access to.
+    pub fn load_shared_accounts(&mut self) -> Result<()> {
+        self.shared_accounts = match &self.current_user {
+            Some(username) => Delegation::get_shared_with(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_shared_account >= self.shared_accounts.len() {
+            self.selected_shared_account = self.shared_accounts.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Gets the account highlighted on the Shared With Me screen.
+    pub fn get_selected_shared_account(&self) -> Option<&Delegation> {
+        self.shared_accounts.get(self.selected_shared_account)
+    }
+
+    /// Whether the current user may read `owner`'s history: their own, or
+    /// one they were given access to. Checked on every read rather than
+    /// when the list is loaded, so access that is taken back ends at once.
+    fn may_view_history(&self, owner: &str) -> Result<bool> {
+        match &self.current_user {
+            Some(username) if username == owner => Ok(true),
+            Some(username) => Delegation::is_granted(&self.conn, owner, username),
+            None => Ok(false),
+        }
+    }
+
+    /// Gets the latest transactions of the selected shared account, or
+    /// `None` if its owner has taken back access.
+    pub fn get_shared_history(
+        &self,
+        limit: usize,
+    ) -> Result<Option<Vec<HashMap<String, String>>>> {
+        let owner = match self.get_selected_shared_account() {
+            Some(account) => account.owner.as_str(),
+            None => return Ok(None),
+        };
+        if !self.may_view_history(owner)? {
+            return Ok(None);
+        }
+        Transaction::get_user_transactions_page(
+            &self.conn,
+            owner,
+            false,
+            false,
+            HistorySort::NewestFirst,
+            &HistoryFilter::default(),
+            0,
+            Some(limit),
+        )
+        .map(Some)
+    }
+
+    /// Writes a statement of the selected shared account to
+    /// `<owner>-statement.txt`.
+    pub fn export_shared_statement(&mut self) -> Result<()> {
+        let owner = match self.get_selected_shared_account() {
+            Some(account) => account.owner.clone(),
+            None => return Ok(()),
+        };
+        if !self.may_view_history(&owner)? {
+            self.add_message(format!("{} no longer shares their history with you.", owner));
+            return Ok(());
+        }
+        let text = statement::statement(
+            &owner,
+            self.now(),
+            &Rollup::get_user_rollups(&self.conn, &owner)?,
+            &Transaction::get_user_transactions(&self.conn, &owner, false, false)?,
+            self.cached_user(&owner)?.map_or(0.0, |user| user.balance),
+        );
+        let path = format!("{}-statement.txt", owner);
+        match std::fs::write(&path, text) {
+            Ok(()) => self.add_message(format!("Statement written to {}", path)),
+            Err(err) => self.add_message(format!("Could not write {}: {}", path, err)),
+        }
+        Ok(())
+    }
+
+    /// Checks whether spending `amount` keeps the current user within the
+    /// weekly cap their parent set, telling them if not.
+    fn within_spending_cap(&mut self, amount: f64) -> Result<bool> {
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(true),
+        };
+        let cap = match ChildAccount::get(&self.conn, &username)?.and_then(|c| c.spending_cap) {
+            Some(cap) => cap,
+            None => return Ok(true),
+        };
+        let spent = self.get_weekly_spending(&username)?;
+        if spent + amount <= cap + 0.005 {
+            return Ok(true);
+        }
+        self.add_message(format!(
+            "Over your weekly limit of {}. You can spend {} more this week.",
+            self.format_amount(cap),
+            self.format_amount((cap - spent).max(0.0))
+        ));
+        self.publish_limit_exceeded(Limit::SpendingCap, amount)?;
+        Ok(false)
+    }
+
+    /// Checks the limits on money going out of the current user's account
+    /// other than the balance itself, telling the user when one stops it.
+    pub fn may_spend(&mut self, amount: f64) -> Result<bool> {
+        if let Some((owed, days_left)) = self.get_overdraft_status()? {
+            if days_left <= 0 {
+                self.add_message(format!(
+                    "Payments are blocked: overdrawn for over {} days. Deposit {} to restore them.",
+                    self.rules().overdraft_grace_days,
+                    self.format_amount(owed)
+                ));
+                return Ok(false);
+            }
+        }
+        self.within_spending_cap(amount)
+    }
+
+    /// Gets how much the current user owes while their balance is below
+    /// zero, and how many days of the grace period are left.
+    pub fn get_overdraft_status(&self) -> Result<Option<(f64, i64)>> {
+        let username = match &self.current_user {
+            Some(username) => username,
+            None => return Ok(None),
+        };
+        let since = match User::get_overdrawn_since(&self.conn, username)? {
+            Some(since) => since,
+            None => return Ok(None),
+        };
+        let elapsed = (self.now() - since).num_days();
+        let days_left = self.rules().overdraft_grace_days - elapsed;
+        Ok(Some((-self.read_balance()?, days_left)))
+    }
+
+    /// Sends every allowance that is due from the parent's balance. An
+    /// allowance the parent cannot cover is skipped until next week.
+    pub fn pay_allowances(&mut self) -> Result<()> {
+        let now = self.now();
+        for child in ChildAccount::get_allowances_due(&self.conn, now)? {
+            let due_at = match child.next_allowance_at {
+                Some(due_at) => due_at,
+                None => continue,
+            };
+            // Missed weeks are not paid twice; the schedule just catches up.
+            let mut next_at = due_at + chrono::Duration::days(7);
+            while next_at <= now {
+                next_at += chrono::Duration::days(7);
+            }
+
+            let tx = self.conn.unchecked_transaction()?;
+            if !ChildAccount::advance_allowance(&tx, &child.child, due_at, next_at)? {
+                continue;
+            }
+            let paid = Self::available_in(&tx, &child.parent)? >= child.weekly_allowance;
+            if paid {
+                self.record_transfer(
+                    &tx,
+                    &child.parent,
+                    &child.child,
+                    child.weekly_allowance,
+                    None,
+                )?;
+            }
+            tx.commit()?;
+
+            let amount = self.format_amount(child.weekly_allowance);
+            let current = self.current_user.as_deref();
+            if current == Some(child.parent.as_str()) {
+                self.add_message(if paid {
+                    format!("Sent {}'s allowance of {}", child.child, amount)
+                } else {
+                    format!(
+                        "Could not send {}'s allowance of {}: insufficient funds",
+                        child.child, amount
+                    )
+                });
+            } else if current == Some(child.child.as_str()) && paid {
+                self.add_message(format!("Your allowance of {} arrived", amount));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reloads the current user's sweep rules.
+    pub fn load_sweep_rules(&mut self) -> Result<()> {
+        self.sweep_rules = match &self.current_user {
+            Some(username) => SweepRules::get_for_user(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_sweep_rule >= self.sweep_rules.len() {
+            self.selected_sweep_rule = self.sweep_rules.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Adds a rule moving whatever the current user has available above
+    /// `keep` into their vault at the start of each month.
+    pub fn add_sweep_rule(&mut self, keep: f64) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = &self.current_user {
+            let now = self.now();
+            SweepRules::create(&self.conn, username, keep, now)?;
+            self.add_message(format!(
+                "Everything above {} will be moved to your vault on {}.",
+                self.format_amount(keep),
+                SweepRules::next_sweep_after(now).format("%Y-%m-%d")
+            ));
+        }
+        self.load_sweep_rules()
+    }
+
+    /// Deletes the selected sweep rule. Money it already moved stays in the vault.
+    pub fn delete_selected_sweep_rule(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let id = match self.sweep_rules.get(self.selected_sweep_rule) {
+            Some(rule) => rule.id,
+            None => return Ok(()),
+        };
+        if let Some(username) = &self.current_user {
+            if SweepRules::delete(&self.conn, username, id)? {
+                self.add_message("Sweep rule deleted.".to_string());
+            }
+        }
+        self.load_sweep_rules()
+    }
+
+    /// Runs every sweep rule that is due, moving what each user has
+    /// available above the amount to keep into their vault as a `vault_in`
+    /// linked to the rule. Missed months are not swept twice. Returns a
+    /// line describing each sweep.
+    pub fn run_sweep_rules(&mut self) -> Result<Vec<String>> {
+        let now = self.now();
+        let mut outcomes = Vec::new();
+        for rule in SweepRules::get_due(&self.conn, now)? {
+            let tx = self.conn.unchecked_transaction()?;
+            let next_at = SweepRules::next_sweep_after(now);
+            if !SweepRules::advance(&tx, rule.id, rule.next_sweep_at, next_at)? {
+                continue;
+            }
+            let balance = User::get(&tx, &rule.username)?.map_or(0.0, |u| u.balance);
+            let available = balance - Self::committed(&tx, &rule.username)?;
+            let amount = ((available - rule.keep) * 100.0).floor() / 100.0;
+            if amount > 0.0 {
+                let username = &rule.username;
+                let (previous, _) = User::adjust_balance(&tx, username, -amount, now)?;
+                Vault::add(&tx, username, amount)?;
+                let id = self.record_vault_move(&tx, username, "vault_in", amount, previous)?;
+                SweepRules::link(&tx, &id, rule.id)?;
+            }
+            tx.commit()?;
+            if amount <= 0.0 {
+                continue;
+            }
+            self.invalidate_balance();
+
+            let message = format!(
+                "Swept {} into your vault, keeping {} (sweep rule #{})",
+                self.format_amount(amount),
+                self.format_amount(rule.keep),
+                rule.id
+            );
+            self.notify(&rule.username, NotificationKind::BalanceSwept, &message)?;
+            if self.current_user.as_deref() == Some(rule.username.as_str()) {
+                self.add_message(message.clone());
+            }
+            outcomes.push(format!("Rule #{} for {}: {}", rule.id, rule.username, message));
+        }
+        Ok(outcomes)
+    }
+
+    /// Gets the sweep rule a transaction was made by, if it is a sweep.
+    pub fn get_sweep_rule(&self, transaction_id: &str) -> Result<Option<i64>> {
+        SweepRules::get_rule(&self.conn, transaction_id)
+    }
+
+    /// Sends every user who is due one a summary of last week. Returns how
+    /// many were sent.
+    pub fn send_weekly_summaries(&mut self) -> Result<usize> {
+        let mut sent = 0;
+        for username in User::get_usernames(&self.conn)? {
+            if self.send_weekly_summary(&username)? {
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Sends a user a summary of last week to their inbox the first time they
+    /// are seen in a new ISO week, whether by logging in or by the daemon.
+    /// Only the week just over is summarized, not weeks nothing moved in or
+    /// weeks before the user was first seen.
+    fn send_weekly_summary(&mut self, username: &str) -> Result<bool> {
+        let start = self.start_of_week();
+        let week = weekly_summary::week_label(start);
+        let tx = self.conn.unchecked_transaction()?;
+        let last_seen = UserSettings::get(&tx, username, SUMMARY_WEEK_SETTING)?;
+        if last_seen.as_deref() == Some(week.as_str()) {
+            return Ok(false);
+        }
+        UserSettings::set(&tx, username, SUMMARY_WEEK_SETTING, &week)?;
+        let summary = WeeklySummary::for_week(&tx, username, start - chrono::Duration::days(7))?;
+        let send = last_seen.is_some() && !summary.is_empty();
+        if send {
+            Notifications::create(
+                &tx,
+                username,
+                NotificationKind::WeeklySummary,
+                &Self::describe_weekly_summary(&summary),
+                self.now(),
+            )?;
+        }
+        tx.commit()?;
+        Ok(send)
+    }
+
+    fn describe_weekly_summary(summary: &WeeklySummary) -> String {
+        let net = summary.money_in - summary.money_out;
+        let mut message = format!(
+            "Week {}: {} in, {} out, net {}{}.",
+            summary.week,
+            format::money(summary.money_in),
+            format::money(summary.money_out),
+            if net < 0.0 { "-" } else { "+" },
+            format::money(net.abs())
+        );
+        if let Some((amount, transaction_type, recipient)) = &summary.biggest_expense {
+            let what = match (transaction_type.as_str(), recipient) {
+                ("transfer_out", Some(recipient)) => format!("to {}", recipient),
+                ("withdraw", _) => "withdrawn".to_string(),
+                ("token_out", _) => "as an offline token".to_string(),
+                ("fee", _) => "in fees".to_string(),
+                _ => "sent".to_string(),
+            };
+            message.push_str(&format!(" Biggest expense: {} {}.", format::money(*amount), what));
+        }
+        if let Some((cap, spent)) = summary.spending_cap {
+            message.push_str(&if spent > cap {
+                format!(
+                    " Went over your weekly cap of {} by {}.",
+                    format::money(cap),
+                    format::money(spent - cap)
+                )
+            } else {
+                format!(
+                    " Spent {} of your weekly cap of {}.",
+                    format::money(spent),
+                    format::money(cap)
+                )
+            });
+        }
+        message
+    }
+
+    /// Reloads the current user's sent and received payment requests.
+    pub fn load_payment_requests(&mut self) -> Result<()> {
+        self.payment_requests = match &self.current_user {
+            Some(username) => PaymentRequest::get_for_user(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_request >= self.payment_requests.len() {
+            self.selected_request = self.payment_requests.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Adds a notification to a user's inbox.
+    pub fn notify(&self, username: &str, kind: NotificationKind, message: &str) -> Result<()> {
+        Notifications::create(&self.conn, username, kind, message, self.now())
+    }
+
+    /// Adds a line to a user's activity timeline.
+    pub fn record_activity(
+        &self,
+        username: &str,
+        kind: ActivityKind,
+        description: &str,
+        amount: Option<f64>,
+    ) -> Result<()> {
+        ActivityLog::record(&self.conn, username, kind, description, amount, self.now())
+    }
+
+    /// Reloads the current user's latest activity, newest first.
+    pub fn load_timeline(&mut self) -> Result<()> {
+        self.timeline = match &self.current_user {
+            Some(username) => ActivityLog::timeline(&self.report_conn, username, TIMELINE_LENGTH)?,
+            None => Vec::new(),
+        };
+        if self.selected_activity >= self.timeline.len() {
+            self.selected_activity = self.timeline.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Reloads the current user's inbox.
+    pub fn load_notifications(&mut self) -> Result<()> {
+        self.notifications = match &self.current_user {
+            Some(username) => Notifications::get_for_user(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_notification >= self.notifications.len() {
+            self.selected_notification = self.notifications.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Recomputes the leaderboard with the current ranking.
+    pub fn load_leaderboard(&mut self) -> Result<()> {
+        self.leaderboard =
+            Leaderboard::rank(&self.report_conn, self.leaderboard_ranking, self.start_of_month())?;
+        Ok(())
+    }
+
+    /// Switches between ranking by balance and by this month's savings.
+    pub fn toggle_leaderboard_ranking(&mut self) -> Result<()> {
+        self.leaderboard_ranking = match self.leaderboard_ranking {
+            LeaderboardRanking::Balance => LeaderboardRanking::MonthlySavings,
+            LeaderboardRanking::MonthlySavings => LeaderboardRanking::Balance,
+        };
+        self.load_leaderboard()
+    }
+
+    /// Counts the current user's unread notifications.
+    pub fn get_unread_count(&self) -> Result<usize> {
+        match &self.current_user {
+            Some(username) => Notifications::count_unread(&self.conn, username),
+            None => Ok(0),
+        }
+    }
+
+    pub fn mark_selected_notification_read(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(notification) = self.notifications.get(self.selected_notification) {
+            Notifications::mark_read(&self.conn, notification.id)?;
+        }
+        self.load_notifications()
+    }
+
+    pub fn mark_all_notifications_read(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = &self.current_user {
+            Notifications::mark_all_read(&self.conn, username)?;
+        }
+        self.load_notifications()
+    }
+
+    /// Deletes every notification in the current user's inbox.
+    pub fn clear_notifications(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = &self.current_user {
+            let cleared = Notifications::clear(&self.conn, username)?;
+            self.add_message(format!("Cleared {} notification(s).", cleared));
+        }
+        self.load_notifications()
+    }
+
+    /// Asks another user to pay the current user. The request expires after
+    /// the user's configured lifetime.
+    pub fn request_payment(&mut self, payer: String, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let requester = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if payer == requester || self.cached_user(&payer)?.is_none() {
+            self.add_message(format!("Request failed. User '{}' not found.", payer));
+            return Ok(false);
+        }
+        let now = self.now();
+        let expires_at = now + chrono::Duration::hours(self.request_ttl_hours);
+        PaymentRequest::create(&self.conn, &requester, &payer, amount, now, expires_at)?;
+        self.add_message(format!(
+            "Requested {} from {}",
+            self.format_amount(amount),
+            payer
+        ));
+        self.load_payment_requests()?;
+        Ok(true)
+    }
+
+    /// Works out how a bill of `total` splits between the current user and
+    /// `people`, for the preview. Returns `false` if there is too little to
+    /// give everyone at least a cent.
+    pub fn preview_bill_split(&mut self, people: &[String], total: f64) -> bool {
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return false,
+        };
+        let mut everyone = people.to_vec();
+        everyone.push(username);
+        let split = BillSplit::new(total, everyone);
+        if split.shares.iter().any(|share| share.amount < 0.01) {
+            self.add_message(format!(
+                "{} is too little to split between {} people.",
+                self.format_amount(total),
+                split.shares.len()
+            ));
+            return false;
+        }
+        self.bill_split = Some(split);
+        true
+    }
+
+    /// Sends a payment request for every share of the previewed bill split
+    /// but the current user's own.
+    pub fn send_bill_split(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let (requester, split) = match (self.current_user.clone(), self.bill_split.take()) {
+            (Some(requester), Some(split)) => (requester, split),
+            _ => return Ok(false),
+        };
+        let now = self.now();
+        let expires_at = now + chrono::Duration::hours(self.request_ttl_hours);
+        let tx = self.conn.unchecked_transaction()?;
+        let mut requested = 0;
+        for share in split.shares.iter().filter(|share| share.username != requester) {
+            PaymentRequest::create(
+                &tx,
+                &requester,
+                &share.username,
+                share.amount,
+                now,
+                expires_at,
+            )?;
+            requested += 1;
+        }
+        tx.commit()?;
+        self.add_message(format!(
+            "Split {} between {} people and requested {} share(s).",
+            self.format_amount(split.total),
+            split.shares.len(),
+            requested
+        ));
+        self.load_payment_requests()?;
+        Ok(true)
+    }
+
+    /// Gets the payment request highlighted on the requests screen.
+    pub fn get_selected_request(&self) -> Option<&PaymentRequest> {
+        self.payment_requests.get(self.selected_request)
+    }
+
+    /// Pays the selected request if it was sent to the current user and is
+    /// still pending. The status change and the transfer commit together.
+    pub fn pay_selected_request(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let (id, requester, payer, amount) = match self.get_selected_request() {
+            Some(r) if r.status == RequestStatus::Pending => {
+                (r.id, r.requester.clone(), r.payer.clone(), r.amount)
+            }
+            _ => return Ok(false),
+        };
+        if self.current_user.as_deref() != Some(payer.as_str()) {
+            self.add_message("Only the person asked to pay can pay a request.".to_string());
+            return Ok(false);
+        }
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+        if !self.can_withdraw(amount)? {
+            self.add_message(format!(
+                "Payment failed. Insufficient funds. Your balance: {}",
+                self.format_amount(self.get_balance()?)
+            ));
+            return Ok(false);
+        }
+
+        let previous_balance = self.read_balance()?;
+        let tx = self.conn.unchecked_transaction()?;
+        if !PaymentRequest::set_status(&tx, id, RequestStatus::Paid)? {
+            drop(tx);
+            self.add_message("This request is no longer pending.".to_string());
+            self.load_payment_requests()?;
+            return Ok(false);
+        }
+        let payment_id = self.record_transfer(&tx, &payer, &requester, amount, None)?;
+        let spare = self.apply_round_up(&tx, &payer, &payment_id, amount)?;
+        tx.commit()?;
+
+        self.add_message(format!(
+            "Paid {} to {}{}",
+            self.format_amount(amount),
+            requester,
+            self.round_up_note(spare)
+        ));
+        self.publish_committed("transfer_out", amount, previous_balance)?;
+        self.load_payment_requests()?;
+        Ok(true)
+    }
+
+    /// Declines the selected request, or cancels it if the current user sent it.
+    pub fn decline_selected_request(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let id = match self.get_selected_request() {
+            Some(r) if r.status == RequestStatus::Pending => r.id,
+            _ => return Ok(false),
+        };
+        let declined = PaymentRequest::set_status(&self.conn, id, RequestStatus::Declined)?;
+        if declined {
+            self.add_message("Request declined.".to_string());
+        }
+        self.load_payment_requests()?;
+        Ok(declined)
+    }
+
+    /// Expires pending requests past their deadline and tells the current
+    /// user about any of their requests that expired since they last heard.
+    /// The other party is told the next time they sign in.
+    pub fn expire_payment_requests(&mut self) -> Result<()> {
+        self.last_expiry_check = self.clock.instant();
+        PaymentRequest::expire_due(&self.conn, self.now())?;
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(()),
+        };
+        let notices = PaymentRequest::take_expiry_notices(&self.conn, &username)?;
+        for request in &notices {
+            let message = if request.requester == username {
+                format!(
+                    "Your request to {} for {} expired.",
+                    request.payer,
+                    self.format_amount(request.amount)
+                )
+            } else {
+                format!(
+                    "{}'s request for {} expired.",
+                    request.requester,
+                    self.format_amount(request.amount)
+                )
+            };
+            self.notify(&username, NotificationKind::RequestExpired, &message)?;
+            self.add_message(message);
+        }
+        if !notices.is_empty() {
+            self.load_payment_requests()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a line item to the checkout being put together, from text like
+    /// `Coffee, 3.50, 2`.
+    pub fn add_checkout_item(&mut self, text: &str) -> bool {
+        match LineItem::parse(text) {
+            Some(item) => {
+                self.checkout_items.push(item);
+                true
+            }
+            None => {
+                self.add_message(
+                    "Invalid item. Use: description, price[, quantity]".to_string(),
+                );
+                false
+            }
+        }
+    }
+
+    /// Turns the entered line items into a checkout the current user can show
+    /// to a customer. It expires after the user's payment request lifetime.
+    pub fn create_checkout(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let merchant = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if self.checkout_items.is_empty() {
+            self.add_message("Add at least one item first.".to_string());
+            return Ok(false);
+        }
+        let now = self.now();
+        let expires_at = now + chrono::Duration::hours(self.request_ttl_hours);
+        let items = std::mem::take(&mut self.checkout_items);
+        let rng = &mut *self.rng.borrow_mut();
+        self.checkout = Some(Invoice::create(&self.conn, &merchant, &items, now, expires_at, rng)?);
+        Ok(true)
+    }
+
+    /// Reloads the checkout on display and tells the merchant once it is paid.
+    pub fn refresh_checkout(&mut self) -> Result<()> {
+        let (code, was_pending) = match &self.checkout {
+            Some(invoice) => (invoice.code.clone(), invoice.status == RequestStatus::Pending),
+            None => return Ok(()),
+        };
+        let invoice = match Invoice::get(&self.conn, &code)? {
+            Some(invoice) => invoice,
+            None => return Ok(()),
+        };
+        if was_pending && invoice.status == RequestStatus::Paid {
+            self.add_message(format!(
+                "{} paid {}",
+                invoice.paid_by.as_deref().unwrap_or("?"),
+                self.format_amount(invoice.total())
+            ));
+        }
+        self.checkout = Some(invoice);
+        Ok(())
+    }
+
+    /// Looks up a checkout code entered or scanned by a customer, keeping it
+    /// in `checkout` for review if it can still be paid.
+    pub fn open_checkout(&mut self, code: &str) -> Result<bool> {
+        let invoice = match Invoice::get(&self.conn, code)? {
+            Some(invoice) => invoice,
+            None => {
+                self.add_message(format!("No checkout with code '{}'.", code.trim()));
+                return Ok(false);
+            }
+        };
+        if self.current_user.as_deref() == Some(invoice.merchant.as_str()) {
+            self.add_message("You cannot pay your own checkout.".to_string());
+            return Ok(false);
+        }
+        if invoice.status == RequestStatus::Pending
+            && invoice.expires_at <= self.now()
+        {
+            Invoice::mark_expired(&self.conn, &invoice.code)?;
+            self.add_message("This checkout has expired.".to_string());
+            return Ok(false);
+        }
+        if invoice.status != RequestStatus::Pending {
+            self.add_message(format!("This checkout is already {}.", invoice.status.as_str()));
+            return Ok(false);
+        }
+        self.checkout = Some(invoice);
+        Ok(true)
+    }
+
+    /// Pays the checkout under review. Marking it paid and the transfer to
+    /// the merchant commit together, so it can only be paid once.
+    pub fn pay_checkout(&mut self) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let payer = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let (code, merchant, amount) = match &self.checkout {
+            Some(invoice) => (invoice.code.clone(), invoice.merchant.clone(), invoice.total()),
+            None => return Ok(false),
+        };
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+        if !self.can_withdraw(amount)? {
+            self.add_message(format!(
+                "Payment failed. Insufficient funds. Your balance: {}",
+                self.format_amount(self.get_balance()?)
+            ));
+            return Ok(false);
+        }
+
+        let previous_balance = self.read_balance()?;
+        let tx = self.conn.unchecked_transaction()?;
+        if !Invoice::mark_paid(&tx, &code, &payer)? {
+            drop(tx);
+            self.add_message("This checkout is no longer pending.".to_string());
+            return Ok(false);
+        }
+        let payment_id = self.record_transfer(&tx, &payer, &merchant, amount, None)?;
+        let spare = self.apply_round_up(&tx, &payer, &payment_id, amount)?;
+        tx.commit()?;
+
+        self.checkout = None;
+        self.add_message(format!(
+            "Paid {} to {}{}",
+            self.format_amount(amount),
+            merchant,
+            self.round_up_note(spare)
+        ));
+        self.publish_committed("transfer_out", amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Loads this wallet's public key for the offline tokens screen.
+    pub fn load_wallet_public_key(&mut self) -> Result<()> {
+        self.wallet_public_key = match self.wallet_key()? {
+            Some(key) => OfflineToken::encode_key(&key.verifying_key()),
+            None => "(locked)".to_string(),
+        };
+        Ok(())
+    }
+
+    /// Gets this wallet's signing key, telling the user if there is none to
+    /// use: it is sealed and was not unlocked when the app started, or there
+    /// was no passphrase to seal a new one with.
+    fn wallet_key(&mut self) -> Result<Option<SigningKey>> {
+        let key = OfflineToken::wallet_key(&self.conn, &WalletKeySecret)?;
+        if key.is_none() {
+            self.add_message(
+                "The wallet key is locked: its passphrase was missing or wrong when the app \
+                started. Check wallet_key in config.toml."
+                    .to_string(),
+            );
+        }
+        Ok(key)
+    }
+
+    /// Takes `amount` from the current user and turns it into a signed
+    /// offline transfer token, kept in `issued_token` for display.
+    pub fn issue_offline_token(&mut self, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+        let key = match self.wallet_key()? {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let now = self.now();
+        let token = OfflineToken::new(&key, &username, amount, now);
+        let tx = self.conn.unchecked_transaction()?;
+        if !self.can_withdraw_in(&tx, &username, amount)? {
+            let available = Self::available_in(&tx, &username)?;
+            drop(tx);
+            self.add_message(format!(
+                "Token not issued. Insufficient funds. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+        let (previous_balance, new_balance) = User::adjust_balance(&tx, &username, -amount, now)?;
+        OfflineToken::record(&tx, &token, TokenDirection::Issued, &username, now)?;
+        Transaction::create(
+            &tx,
+            &Transaction {
+                id: self.generate_transaction_id(),
+                username: username.clone(),
+                transaction_type: "token_out".to_string(),
+                amount,
+                recipient: Some(format!("token {}", token.short_nonce())),
+                sender: None,
+                previous_balance,
+                new_balance,
+                timestamp: now,
+                currency: BASE_CURRENCY.to_string(),
+                fx_rate: 1.0,
+                transfer_id: None,
+                merchant: None,
+            },
+        )?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.issued_token = Some(token.encode(&key));
+        self.add_message(format!(
+            "Issued an offline token for {}",
+            self.format_amount(amount)
+        ));
+        self.publish_committed("token_out", amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Checks a pasted offline transfer token and credits its amount to the
+    /// current user, unless this database has redeemed it before.
+    pub fn redeem_offline_token(&mut self, text: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let mut trusted = self.rules().trusted_wallet_keys.clone();
+        let own = OfflineToken::wallet_key(&self.conn, &WalletKeySecret)?;
+        trusted.extend(own.map(|key| key.verifying_key()));
+        let token = match OfflineToken::decode(text, &trusted) {
+            Ok(token) => token,
+            Err(err) => {
+                self.add_message(format!("Token rejected: {}.", err.as_str()));
+                return Ok(false);
+            }
+        };
+        if parse_amount(&token.amount.to_string(), self.max_amount).is_err() {
+            self.add_message(format!(
+                "Token rejected: {} is over your limit of {}.",
+                format::money(token.amount),
+                format::money(self.max_amount)
+            ));
+            return Ok(false);
+        }
+
+        let now = self.now();
+        let tx = self.conn.unchecked_transaction()?;
+        if !OfflineToken::record(&tx, &token, TokenDirection::Redeemed, &username, now)? {
+            drop(tx);
+            self.add_message(format!(
+                "Token rejected: {} has already been redeemed.",
+                token.short_nonce()
+            ));
+            return Ok(false);
+        }
+        let (previous_balance, new_balance) =
+            User::adjust_balance(&tx, &username, token.amount, now)?;
+        Transaction::create(
+            &tx,
+            &Transaction {
+                id: self.generate_transaction_id(),
+                username: username.clone(),
+                transaction_type: "token_in".to_string(),
+                amount: token.amount,
+                recipient: Some(format!("token {}", token.short_nonce())),
+                sender: Some(token.issuer.clone()),
+                previous_balance,
+                new_balance,
+                timestamp: now,
+                currency: BASE_CURRENCY.to_string(),
+                fx_rate: 1.0,
+                transfer_id: None,
+                merchant: None,
+            },
+        )?;
+        tx.commit()?;
+        self.invalidate_balance();
+
+        self.add_message(format!(
+            "Redeemed {} from {}",
+            self.format_amount(token.amount),
+            token.issuer
+        ));
+        self.publish_committed("token_in", token.amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Whether a user with this name exists.
+    pub fn user_exists(&self, username: &str) -> Result<bool> {
+        Ok(self.cached_user(username)?.is_some())
+    }
+
+    /// Refreshes the recipient autocomplete list from the current input.
+    pub fn update_recipient_suggestions(&mut self) -> Result<()> {
+        self.selected_suggestion = 0;
+        if self.input.is_empty() {
+            self.recipient_suggestions.clear();
+            return Ok(());
+        }
+        let current_user = self.current_user.as_deref();
+        self.recipient_suggestions = User::search_by_prefix(&self.conn, &self.input, 6)?
+            .into_iter()
+            .filter(|username| Some(username.as_str()) != current_user)
+            .take(5)
+            .collect();
+        Ok(())
+    }
+
+    /// Loads the recently-used recipients shown as quick picks on the Transfer screen.
+    pub fn load_recent_recipients(&mut self) -> Result<()> {
+        self.recent_recipients = if let Some(username) = &self.current_user {
+            Transaction::get_recent_recipients(&self.conn, username, 5)?
+        } else {
+            Vec::new()
+        };
+        Ok(())
+    }
+
+    /// Checks whether an identical transfer was made in the last two minutes.
+    pub fn is_duplicate_transfer(&self, recipient: &str, amount: f64) -> Result<bool> {
+        if let Some(username) = &self.current_user {
+            let since = self.now() - chrono::Duration::minutes(2);
+            Transaction::has_similar_transfer_since(&self.conn, username, recipient, amount, since)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Gets the balance of the current user. The balance is cached between
+    /// changes, so drawing a frame does not have to query the database.
+    pub fn get_balance(&self) -> Result<f64> {
+        match &self.current_user {
+            Some(username) => Ok(self.cached_user(username)?.map_or(0.0, |u| u.balance)),
+            None => Ok(0.0),
+        }
+    }
+
+    /// Reads the current user's balance from the database and refreshes the
+    /// cache. Used before changing a balance, so a stale cache is never used
+    /// as the starting point.
+    fn read_balance(&self) -> Result<f64> {
+        match &self.current_user {
+            Some(username) => Ok(self.fetch_user(username)?.map_or(0.0, |u| u.balance)),
+            None => Ok(0.0),
+        }
+    }
+
+    /// Looks a user up, answering from the cache when it can. Used for
+    /// existence checks and balances that are only shown.
+    fn cached_user(&self, username: &str) -> Result<Option<User>> {
+        let cached = self.user_cache.borrow().get(username).copied();
+        match cached {
+            Some(balance) => {
+                self.count_lookup(false);
+                Ok(balance.map(|balance| User::new(username.to_string(), balance)))
+            }
+            None => self.fetch_user(username),
+        }
+    }
+
+    /// Reads a user from the database and refreshes its cache entry. Used
+    /// before a balance is changed.
+    fn fetch_user(&self, username: &str) -> Result<Option<User>> {
+        self.count_lookup(true);
+        let user = User::get(&self.conn, username)?;
+        self.user_cache
+            .borrow_mut()
+            .insert(username.to_string(), user.as_ref().map(|user| user.balance));
+        Ok(user)
+    }
+
+    fn count_lookup(&self, queried: bool) {
+        let mut stats = self.lookup_stats.get();
+        stats.requested += 1;
+        stats.queried += queried as u64;
+        self.lookup_stats.set(stats);
+    }
+
+    /// Gets how many user lookups were made and how many reached SQLite.
+    pub fn get_lookup_stats(&self) -> LookupStats {
+        self.lookup_stats.get()
+    }
+
+    /// Marks cached balances and user lookups as dirty so the next read goes
+    /// to the database. Called after every balance change or new account,
+    /// and by the tick loop when another connection or process has written
+    /// to the database.
+    pub fn invalidate_balance(&self) {
+        self.user_cache.borrow_mut().clear();
+    }
+
+    /// Gets the transactions of the current user.
+    pub fn get_transactions(&self) -> Result<Vec<HashMap<String, String>>> {
+        if let Some(username) = &self.current_user {
+            let transactions = Transaction::get_user_transactions(
+                &self.conn,
+                username,
+                self.starred_only,
+                self.integrity_mode,
+            )?;
+            self.log_integrity_issues(&transactions);
+            Ok(transactions)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Gets one page of the current user's transactions, honouring the
+    /// starred and typed filters and the chosen sort. Only the rows asked for
+    /// are read from the database.
+    pub fn get_transaction_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        if let Some(username) = &self.current_user {
+            let transactions = Transaction::get_user_transactions_page(
+                &self.conn,
+                username,
+                self.starred_only,
+                self.integrity_mode,
+                self.history_sort,
+                &self.history_filter,
+                offset,
+                Some(limit),
+            )?;
+            self.log_integrity_issues(&transactions);
+            Ok(transactions)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Appends the problems integrity mode found to the integrity log, each
+    /// transaction once per session. The history is drawn every frame, so
+    /// logging every read would repeat the same lines.
+    fn log_integrity_issues(&self, transactions: &[HashMap<String, String>]) {
+        let mut logged = self.integrity_logged.borrow_mut();
+        let mut lines = String::new();
+        for transaction in transactions {
+            let issue = match transaction.get("integrity_issue") {
+                Some(issue) => issue,
+                None => continue,
+            };
+            let id = transaction.get("id").cloned().unwrap_or_default();
+            if logged.insert(id.clone()) {
+                lines.push_str(&format!(
+                    "{} transaction {} of {}: {}\n",
+                    self.now().format("%Y-%m-%d %H:%M:%S"),
+                    id,
+                    self.current_user.as_deref().unwrap_or_default(),
+                    issue
+                ));
+            }
+        }
+        if lines.is_empty() {
+            return;
+        }
+        // The log is a diagnostic aid, so failing to write it must not stop
+        // the history from showing.
+        let log = OpenOptions::new().create(true).append(true).open(INTEGRITY_LOG_PATH);
+        if let Ok(mut file) = log {
+            let _ = file.write_all(lines.as_bytes());
+        }
+    }
+
+    /// Counts the transactions shown in the history list.
+    pub fn count_transactions(&self) -> Result<usize> {
+        if let Some(username) = &self.current_user {
+            Transaction::count_user_transactions(
+                &self.conn,
+                username,
+                self.starred_only,
+                &self.history_filter,
+            )
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Filters the history by the typed expression, or stops filtering if it
+    /// is empty. Returns `false` if it could not be read, leaving the filter
+    /// as it was.
+    pub fn set_history_filter(&mut self, input: &str) -> bool {
+        match HistoryFilter::parse(input) {
+            Ok(filter) => {
+                self.history_filter = filter;
+                self.selected_transaction = 0;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Selects the first transaction in the history made on or after the date
+    /// typed as YYYY-MM-DD. Returns `false` if the date could not be read.
+    /// Sorts other than by date switch back to newest first.
+    pub fn jump_to_date(&mut self, input: &str) -> Result<bool> {
+        let date = match NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                self.add_message("Invalid date. Please use YYYY-MM-DD.".to_string());
+                return Ok(false);
+            }
+        };
+        let username = match &self.current_user {
+            Some(username) => username.clone(),
+            None => return Ok(true),
+        };
+        if !matches!(self.history_sort, HistorySort::NewestFirst | HistorySort::OldestFirst) {
+            self.history_sort = HistorySort::NewestFirst;
+            self.add_message("History sorted newest first to jump to a date.".to_string());
+        }
+        let since = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+        let on_or_after = Transaction::count_user_transactions_since(
+            &self.conn,
+            &username,
+            self.starred_only,
+            &self.history_filter,
+            since,
+        )?;
+        if on_or_after == 0 {
+            self.add_message(format!("No transactions on or after {}.", date));
+            return Ok(true);
+        }
+        // Newest first, the rows on or after the date come first and the
+        // one wanted is the last of them; oldest first, it follows the rest
+        self.selected_transaction = match self.history_sort {
+            HistorySort::OldestFirst => self.count_transactions()? - on_or_after,
+            _ => on_or_after - 1,
+        };
+        Ok(true)
+    }
+
+    /// Gets the index of the first history row to draw when `visible_rows`
+    /// rows fit on screen. The window only moves when the selection would
+    /// leave it, so scrolling behaves like a regular list.
+    pub fn history_window(&self, visible_rows: usize) -> usize {
+        let visible_rows = visible_rows.max(1);
+        let mut start = self.history_window_start.get();
+        if self.selected_transaction < start {
+            start = self.selected_transaction;
+        } else if self.selected_transaction >= start + visible_rows {
+            start = self.selected_transaction + 1 - visible_rows;
+        }
+        self.history_window_start.set(start);
+        start
+    }
+
+    /// Gets the most recent transactions of the current user.
+    pub fn get_recent_transactions(&self, limit: usize) -> Result<Vec<HashMap<String, String>>> {
+        if let Some(username) = &self.current_user {
+            let transactions = Transaction::get_user_transactions_page(
+                &self.conn,
+                username,
+                false,
+                self.integrity_mode,
+                HistorySort::NewestFirst,
+                &HistoryFilter::default(),
+                0,
+                Some(limit),
+            )?;
+            self.log_integrity_issues(&transactions);
+            Ok(transactions)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Gets the money in and money out totals of the current user for the current month.
+    pub fn get_monthly_totals(&self) -> Result<(f64, f64)> {
+        if let Some(username) = &self.current_user {
+            Transaction::get_totals_since(&self.report_conn, username, self.start_of_month())
+        } else {
+            Ok((0.0, 0.0))
+        }
+    }
+
+    /// Gets this month's totals per foreign currency, in that currency's own units.
+    pub fn get_monthly_foreign_totals(&self) -> Result<Vec<(String, f64, f64)>> {
+        if let Some(username) = &self.current_user {
+            Transaction::get_foreign_totals_since(
+                &self.report_conn,
+                username,
+                self.start_of_month(),
+            )
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn start_of_week(&self) -> NaiveDateTime {
+        let today = self.now().date();
+        (today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64))
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+    }
+
+    fn start_of_month(&self) -> NaiveDateTime {
+        let today = self.now().date();
+        today
+            .with_day(1)
+            .unwrap_or(today)
+            .and_hms_opt(0, 0, 0)
+            .unwrap_or_default()
+    }
+
+    /// Gets the current user's net spending per day in the month shown on
+    /// the spending heatmap.
+    pub fn get_heatmap(&self) -> Result<Vec<(NaiveDate, f64)>> {
+        let username = match &self.current_user {
+            Some(username) => username,
+            None => return Ok(Vec::new()),
+        };
+        let first = self.heatmap_day.with_day(1).unwrap_or(self.heatmap_day);
+        let next = first.checked_add_months(Months::new(1)).unwrap_or(first);
+        Transaction::get_daily_net_spend(&self.report_conn, username, first, next)
+    }
+
+    /// Moves the heatmap selection to the same day of the previous or next
+    /// month, or the last day of a shorter month.
+    pub fn shift_heatmap_month(&mut self, forward: bool) {
+        let months = Months::new(1);
+        let shifted = if forward {
+            self.heatmap_day.checked_add_months(months)
+        } else {
+            self.heatmap_day.checked_sub_months(months)
+        };
+        self.heatmap_day = shifted.unwrap_or(self.heatmap_day);
+    }
+
+    /// Gets the current user's transactions on the day selected on the
+    /// spending heatmap, oldest first.
+    pub fn get_heatmap_day_transactions(&self) -> Result<Vec<HashMap<String, String>>> {
+        match &self.current_user {
+            Some(username) => {
+                Transaction::get_user_transactions_on(&self.conn, username, self.heatmap_day)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Records every user's net worth for today, for the net worth chart.
+    pub fn record_net_worth_snapshots(&self) -> Result<usize> {
+        NetWorthSnapshots::record_all(&self.conn, self.now().date())
+    }
+
+    /// Works out the current user's net worth as it is now.
+    pub fn get_net_worth(&self) -> Result<Option<NetWorth>> {
+        match &self.current_user {
+            Some(username) => {
+                Ok(Some(NetWorthSnapshots::current(&self.conn, username, self.now().date())?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the current user's net worth per day over the last
+    /// `NET_WORTH_HISTORY_DAYS` days, from the daemon's snapshots, ending
+    /// with today as it is now.
+    pub fn get_net_worth_history(&self) -> Result<Vec<NetWorth>> {
+        let username = match &self.current_user {
+            Some(username) => username,
+            None => return Ok(Vec::new()),
+        };
+        let today = self.now().date();
+        let since = today - chrono::Duration::days(NET_WORTH_HISTORY_DAYS - 1);
+        let mut history = NetWorthSnapshots::get_since(&self.report_conn, username, since)?;
+        history.retain(|snapshot| snapshot.day < today);
+        history.push(NetWorthSnapshots::current(&self.conn, username, today)?);
+        Ok(history)
+    }
+
+    /// Gets the transaction currently selected in the history screen.
+    pub fn get_selected_transaction(&self) -> Result<Option<HashMap<String, String>>> {
+        Ok(self
+            .get_transaction_page(self.selected_transaction, 1)?
+            .into_iter()
+            .next())
+    }
+
+    /// Checks the sender's signature on a transfer from the history.
+    pub fn get_signature_status(
+        &self,
+        transaction: &HashMap<String, String>,
+    ) -> Result<SignatureStatus> {
+        let field = |key: &str| transaction.get(key).map(String::as_str).unwrap_or_default();
+        UserKey::verify_transfer(
+            &self.conn,
+            field("id"),
+            field("sender"),
+            field("recipient"),
+            field("amount").parse().unwrap_or(0.0),
+            field("timestamp"),
+        )
+    }
+
+    /// Gets the id of the payment a round-up transaction came from.
+    pub fn get_round_up_payment(&self, transaction_id: &str) -> Result<Option<String>> {
+        RoundUp::get_payment(&self.conn, transaction_id)
+    }
+
+    /// Reloads the current user's category rules.
+    pub fn load_category_rules(&mut self) -> Result<()> {
+        self.category_rules = match &self.current_user {
+            Some(username) => CategoryRules::get_for_user(&self.conn, username)?,
+            None => Vec::new(),
+        };
+        if self.selected_category_rule >= self.category_rules.len() {
+            self.selected_category_rule = self.category_rules.len().saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Adds a category rule for the current user. It applies to transactions
+    /// made from now on; `rerun_category_rules` applies it to older ones.
+    pub fn add_category_rule(
+        &mut self,
+        field: RuleField,
+        pattern: &str,
+        category: &str,
+    ) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = &self.current_user {
+            CategoryRules::create(&self.conn, username, field, pattern, category)?;
+            self.add_message(format!(
+                "Transactions whose {} contains '{}' will be categorized as {}.",
+                field.as_str(),
+                pattern,
+                category
+            ));
+        }
+        self.load_category_rules()
+    }
+
+    /// Deletes the selected category rule. Categories it already gave stay.
+    pub fn delete_selected_category_rule(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let id = match self.category_rules.get(self.selected_category_rule) {
+            Some(rule) => rule.id,
+            None => return Ok(()),
+        };
+        if let Some(username) = &self.current_user {
+            if CategoryRules::delete(&self.conn, username, id)? {
+                self.add_message("Rule deleted.".to_string());
+            }
+        }
+        self.load_category_rules()
+    }
+
+    /// Applies the current user's category rules to every transaction of
+    /// theirs that has no category yet.
+    pub fn rerun_category_rules(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(username) = &self.current_user {
+            let categorized = CategoryRules::apply(&self.conn, username, None)?;
+            self.add_message(format!("Categorized {} transaction(s).", categorized));
+        }
+        Ok(())
+    }
+
+    /// Saves the edited category, memo or note of the selected transaction.
+    pub fn save_annotation(&mut self, value: &str) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        let transaction = match self.get_selected_transaction()? {
+            Some(transaction) => transaction,
+            None => return Ok(()),
+        };
+        let transaction_id = transaction.get("id").cloned().unwrap_or_default();
+        let field = self.editing_field;
+        let old = transaction
+            .get(field.column())
+            .cloned()
+            .filter(|v| !v.is_empty());
+        let new = Some(value.trim().to_string()).filter(|v| !v.is_empty());
+
+        self.set_annotation(&transaction_id, field, new.as_deref())?;
+        self.add_message(match field {
+            AnnotationField::Category => "Category updated.".to_string(),
+            AnnotationField::Memo => "Memo updated.".to_string(),
+            AnnotationField::Note => "Note updated.".to_string(),
+        });
+        self.record(Command::EditAnnotation {
+            transaction_id,
+            field,
+            old,
+            new,
+        });
+        Ok(())
+    }
+
+    fn set_annotation(
+        &self,
+        transaction_id: &str,
+        field: AnnotationField,
+        value: Option<&str>,
+    ) -> Result<()> {
+        match field {
+            AnnotationField::Category => Annotation::set_category(&self.conn, transaction_id, value),
+            AnnotationField::Memo => Annotation::set_memo(&self.conn, transaction_id, value),
+            AnnotationField::Note => Annotation::set_note(&self.conn, transaction_id, value),
+        }
+    }
+
+    /// Stars or unstars the selected transaction.
+    pub fn toggle_starred(&mut self) -> Result<()> {
+        if self.refuse_in_view_mode() {
+            return Ok(());
+        }
+        if let Some(transaction) = self.get_selected_transaction()? {
+            let transaction_id = transaction.get("id").cloned().unwrap_or_default();
+            if Annotation::toggle_starred(&self.conn, &transaction_id)? {
+                self.add_message("Transaction starred.".to_string());
+            } else {
+                self.add_message("Transaction unstarred.".to_string());
+            }
+            self.record(Command::ToggleStar { transaction_id });
+        }
+        Ok(())
+    }
+
+    /// Copies the selected transaction to the clipboard as one line of
+    /// tab-separated values for spreadsheets, or as JSON for bug reports.
+    pub fn copy_selected_transaction(&mut self, as_json: bool) -> Result<()> {
+        let transaction = match self.get_selected_transaction()? {
+            Some(transaction) => transaction,
+            None => return Ok(()),
+        };
+        let field = |key: &str| transaction.get(key).cloned().unwrap_or_default();
+        let text = if as_json {
+            let record: serde_json::Map<String, serde_json::Value> = COPIED_FIELDS
+                .iter()
+                .map(|&key| {
+                    let value = field(key);
+                    let numeric =
+                        matches!(key, "amount" | "fx_rate" | "previous_balance" | "new_balance");
+                    let value = match value.parse::<f64>() {
+                        Ok(number) if numeric => serde_json::json!(number),
+                        _ => serde_json::json!(value),
+                    };
+                    (key.to_string(), value)
+                })
+                .collect();
+            serde_json::Value::Object(record).to_string()
+        } else {
+            COPIED_FIELDS
+                .iter()
+                .map(|&key| field(key).replace(['\t', '\n', '\r'], " "))
+                .collect::<Vec<_>>()
+                .join("\t")
+        };
+        self.clipboard = Some(text);
+        self.add_message(if as_json {
+            "Transaction copied as JSON.".to_string()
+        } else {
+            "Transaction copied as tab-separated values.".to_string()
+        });
+        Ok(())
+    }
+
+    /// Records a completed user action so it can be undone. Recording a new
+    /// action discards anything that was available to redo.
+    pub fn record(&mut self, command: Command) {
+        self.undo_stack.push(command);
+        if self.undo_stack.len() > MAX_UNDO_HISTORY {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    /// Opens a screen on top of the current one. Moves between browse
+    /// screens are recorded so they can be undone.
+    fn push_screen(&mut self, to: Screen) {
+        self.history_cursor = None;
+        let from = self.current_screen;
+        self.screen_stack.push(from);
+        self.current_screen = to;
+        if from.is_browse() && to.is_browse() {
+            self.record(Command::PushScreen { from, to });
+        }
+    }
+
+    /// Returns to the previous screen, if there is one.
+    fn pop_screen(&mut self) {
+        self.history_cursor = None;
+        if let Some(to) = self.screen_stack.pop() {
+            let from = self.current_screen;
+            self.current_screen = to;
+            if from.is_browse() && to.is_browse() {
+                self.record(Command::PopScreen { from, to });
+            }
+        }
+    }
+
+    /// Clears the navigation history and switches to a screen.
+    fn reset_screen(&mut self, to: Screen) {
+        self.history_cursor = None;
+        self.screen_stack.clear();
+        self.current_screen = to;
+    }
+
+    /// Undoes the most recent command.
+    pub fn undo(&mut self) -> Result<()> {
+        let command = match self.undo_stack.pop() {
+            Some(command) => command,
+            None => {
+                self.add_message("Nothing to undo.".to_string());
+                return Ok(());
+            }
+        };
+        if self.revert(&command)? {
+            self.add_message(format!("Undid {}.", command.describe(self.hide_amounts)));
+            self.redo_stack.push(command);
+        } else {
+            self.undo_stack.push(command);
+        }
+        Ok(())
+    }
+
+    /// Re-applies the most recently undone command.
+    pub fn redo(&mut self) -> Result<()> {
+        let mut command = match self.redo_stack.pop() {
+            Some(command) => command,
+            None => {
+                self.add_message("Nothing to redo.".to_string());
+                return Ok(());
+            }
+        };
+        if self.reapply(&mut command)? {
+            self.add_message(format!("Redid {}.", command.describe(self.hide_amounts)));
+            self.undo_stack.push(command);
+        } else {
+            self.redo_stack.push(command);
+        }
+        Ok(())
+    }
+
+    fn revert(&mut self, command: &Command) -> Result<bool> {
+        match command {
+            Command::PushScreen { from, .. } => {
+                self.screen_stack.pop();
+                self.current_screen = *from;
+                Ok(true)
+            }
+            Command::PopScreen { from, to } => {
+                self.screen_stack.push(*to);
+                self.current_screen = *from;
+                Ok(true)
+            }
+            Command::EditAnnotation {
+                transaction_id,
+                field,
+                old,
+                ..
+            } => {
+                self.set_annotation(transaction_id, *field, old.as_deref())?;
+                Ok(true)
+            }
+            Command::ToggleStar { transaction_id } => {
+                Annotation::toggle_starred(&self.conn, transaction_id)?;
+                Ok(true)
+            }
+            Command::Deposit { amount } => self.reverse_deposit(*amount),
+            Command::Withdraw { amount } => {
+                self.deposit(*amount)?;
+                Ok(true)
+            }
+            Command::Transfer { transfer_id, .. } => self.reverse_transfer(transfer_id),
+        }
+    }
+
+    fn reapply(&mut self, command: &mut Command) -> Result<bool> {
+        match command {
+            Command::PushScreen { from, to } => {
+                self.screen_stack.push(*from);
+                self.current_screen = *to;
+                Ok(true)
+            }
+            Command::PopScreen { to, .. } => {
+                self.screen_stack.pop();
+                self.current_screen = *to;
+                Ok(true)
+            }
+            Command::EditAnnotation {
+                transaction_id,
+                field,
+                new,
+                ..
+            } => {
+                self.set_annotation(transaction_id, *field, new.as_deref())?;
+                Ok(true)
+            }
+            Command::ToggleStar { transaction_id } => {
+                Annotation::toggle_starred(&self.conn, transaction_id)?;
+                Ok(true)
+            }
+            Command::Deposit { amount } => {
+                self.deposit(*amount)?;
+                Ok(true)
+            }
+            Command::Withdraw { amount } => WalletService::withdraw(self, *amount),
+            Command::Transfer {
+                recipient,
+                amount,
+                note,
+                transfer_id,
+            } => match self.transfer(recipient.clone(), *amount, note.as_deref())? {
+                Some(redone) => {
+                    // Undoing the redo reverses the new transfer, not the old one.
+                    *transfer_id = redone;
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+        }
+    }
+
+    /// Takes back a deposit as a withdrawal, which the spending checks
+    /// apply to. The overdraft is not counted, so undoing a deposit never
+    /// leaves the balance below zero.
+    fn reverse_deposit(&mut self, amount: f64) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let available = Self::available_in(&tx, &username)?;
+        if available < amount {
+            drop(tx);
+            self.add_message(format!(
+                "Cannot undo deposit. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+        let (previous_balance, _) = self.record_cash(&tx, &username, "withdraw", -amount)?;
+        tx.commit()?;
+        self.invalidate_balance();
+        self.publish_committed("withdraw", amount, previous_balance)?;
+        Ok(true)
+    }
+
+    /// Sends a transfer back from its recipient as a compensating transaction,
+    /// and tells the recipient. The legs are looked up by transfer id, so only
+    /// the current user's own transfer that is still in the ledger can be
+    /// reversed, and only for `TRANSFER_UNDO_MINUTES` after it was sent and
+    /// while the recipient has the money available.
+    fn reverse_transfer(&mut self, transfer_id: &str) -> Result<bool> {
+        if self.refuse_in_view_mode() {
+            return Ok(false);
+        }
+        let sender_username = match self.current_user.clone() {
+            Some(username) => username,
+            None => return Ok(false),
+        };
+        let legs = Transaction::get_transfer_legs(&self.conn, transfer_id)?;
+        let sent = legs.iter().find(|leg| {
+            leg.transaction_type == "transfer_out" && leg.username == sender_username
+        });
+        let (recipient, amount, sent_at) = match sent {
+            Some(leg) if legs.len() == 2 => {
+                (leg.recipient.clone().unwrap_or_default(), leg.amount, leg.timestamp)
+            }
+            _ => {
+                self.add_message(
+                    "Cannot undo transfer. It is no longer in the ledger.".to_string(),
+                );
+                return Ok(false);
+            }
+        };
+        if self.now() > sent_at + chrono::Duration::minutes(TRANSFER_UNDO_MINUTES) {
+            self.add_message(format!(
+                "Cannot undo transfer. Transfers can only be undone within {} minutes; \
+                ask {} to send the money back.",
+                TRANSFER_UNDO_MINUTES, recipient
+            ));
+            return Ok(false);
+        }
+        let recipient = recipient.as_str();
+
+        let tx = self.conn.unchecked_transaction()?;
+        if Self::available_in(&tx, recipient)? < amount {
+            drop(tx);
+            self.add_message(format!(
+                "Cannot undo transfer. {} no longer has {} available.",
+                recipient,
+                self.format_amount(amount)
+            ));
+            return Ok(false);
+        }
+        let sender_previous_balance = self.read_balance()?;
+        self.record_transfer(&tx, recipient, &sender_username, amount, None)?;
+        Notifications::create(
+            &tx,
+            recipient,
+            NotificationKind::TransferReversed,
+            &format!("{} undid their transfer of {}", sender_username, format::money(amount)),
+            self.now(),
+        )?;
+        tx.commit()?;
+        self.publish_committed("transfer_in", amount, sender_previous_balance)?;
+        Ok(true)
+    }
+
+    /// Saves a submitted value to the current user's input history. Nothing
+    /// is saved in a view-only session.
+    pub fn remember_input(&mut self, field: InputField, value: &str) -> Result<()> {
+        self.history_cursor = None;
+        let value = value.trim();
+        if let (Some(username), false) = (&self.current_user, value.is_empty() || self.view_only) {
+            InputHistory::add(&self.conn, username, field.as_str(), value)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the input with an older (Up) or newer (Down) value from the
+    /// current user's input history. Moving past the newest value restores
+    /// whatever was typed before recalling.
+    pub fn recall_input(&mut self, field: InputField, older: bool) -> Result<()> {
+        let entries = match &self.current_user {
+            Some(username) => InputHistory::get(&self.conn, username, field.as_str())?,
+            None => return Ok(()),
+        };
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let next = match (self.history_cursor, older) {
+            (None, true) => {
+                self.history_draft = self.input.clone();
+                Some(0)
+            }
+            (None, false) => None,
+            (Some(i), true) => Some((i + 1).min(entries.len() - 1)),
+            (Some(0), false) => None,
+            (Some(i), false) => Some(i - 1),
+        };
+        self.history_cursor = next;
+        self.input = match next {
+            Some(i) => entries[i].clone(),
+            None => std::mem::take(&mut self.history_draft),
+        };
+        Ok(())
+    }
+
+    /// Stops browsing the input history, keeping the current input as typed.
+    pub fn reset_input_history(&mut self) {
+        self.history_cursor = None;
+    }
+
+    /// Gets the username of the current user.
+    pub fn get_current_user(&self) -> Option<&str> {
+        self.current_user.as_deref()
+    }
+
+    /// Handles user input by dispatching it to the current screen's handler
+    /// and applying the transition it returns.
+    pub fn handle_input(&mut self, key: KeyCode) -> Result<bool> {
+        self.last_activity = self.clock.instant();
+        let handler = self.current_screen.handler();
+        match handler.handle_key(self, key)? {
+            Transition::Stay => {}
+            Transition::Push(screen) if self.view_only && screen.is_change_form() => {
+                self.refuse_in_view_mode();
+            }
+            Transition::Push(screen) => self.push_screen(screen),
+            Transition::Pop => self.pop_screen(),
+            Transition::Reset(screen) => self.reset_screen(screen),
+            Transition::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::clock::VirtualClock;
+    use crate::models::ndjson::TransactionRecords;
+    use std::ops::{Deref, DerefMut};
+    use std::path::PathBuf;
+
+    /// A controller on a database file of its own, removed when the test
+    /// ends, with a clock that only moves when told to.
+    struct TestApp {
+        app: AppController,
+        clock: Rc<VirtualClock>,
+        path: PathBuf,
+    }
+
+    impl TestApp {
+        fn new(name: &str) -> TestApp {
+            let path = std::env::temp_dir()
+                .join(format!("ewallet-test-{}-{}.db", std::process::id(), name));
+            remove_database(&path);
+            let start = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(9, 0, 0).unwrap();
+            let clock = Rc::new(VirtualClock::new(start));
+            let app = AppController::with_database(path.to_str().unwrap(), clock.clone()).unwrap();
+            TestApp { app, clock, path }
+        }
+
+        /// A controller logged in as `username`, a new account holding
+        /// `balance`.
+        fn with_user(name: &str, username: &str, balance: f64) -> TestApp {
+            let mut app = TestApp::new(name);
+            app.create_account(username.to_string()).unwrap();
+            app.give(username, balance);
+            app
+        }
+
+        /// Creates `username` if needed and adds `amount` to their balance.
+        fn give(&self, username: &str, amount: f64) {
+            User::create(&self.conn, username).unwrap();
+            User::adjust_balance(&self.conn, username, amount, self.now()).unwrap();
+            self.invalidate_balance();
+        }
+
+        /// Another controller on the same database, as a second copy of the
+        /// app would be, logged in as `username`.
+        fn other_session(&self, username: &str) -> AppController {
+            let path = self.path.to_str().unwrap();
+            let mut other = AppController::with_database(path, self.clock.clone()).unwrap();
+            assert!(other.login(username.to_string()).unwrap());
+            other
+        }
+
+        fn balance(&self, username: &str) -> f64 {
+            User::get(&self.conn, username).unwrap().unwrap().balance
+        }
+
+        fn count(&self, sql: &str) -> i64 {
+            self.conn.query_row(sql, [], |row| row.get(0)).unwrap()
+        }
+
+        fn last_message(&self) -> &str {
+            self.messages.last().map_or("", |(message, _)| message.as_str())
+        }
+
+        /// Transfers from the current user as the transfer screen does,
+        /// recording it for undo.
+        fn send(&mut self, recipient: &str, amount: f64) -> String {
+            let transfer_id = self.transfer(recipient.to_string(), amount, None).unwrap().unwrap();
+            self.record(Command::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+                note: None,
+                transfer_id: transfer_id.clone(),
+            });
+            transfer_id
+        }
+    }
+
+    impl Deref for TestApp {
+        type Target = AppController;
+
+        fn deref(&self) -> &AppController {
+            &self.app
+        }
+    }
+
+    impl DerefMut for TestApp {
+        fn deref_mut(&mut self) -> &mut AppController {
+            &mut self.app
+        }
+    }
+
+    impl Drop for TestApp {
+        fn drop(&mut self) {
+            remove_database(&self.path);
+        }
+    }
+
+    fn remove_database(path: &std::path::Path) {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    /// Makes every insert into `table` fail, as a full disk would.
+    fn fail_inserts(app: &TestApp, table: &str) {
+        app.conn
+            .execute_batch(&format!(
+                "CREATE TEMP TRIGGER fail_{0} BEFORE INSERT ON {0}
+                BEGIN SELECT RAISE(ABORT, 'disk full'); END",
+                table
+            ))
+            .unwrap();
+    }
+
+    #[test]
+    fn deposit_and_withdraw_record_a_transaction_each() {
+        let mut app = TestApp::with_user("cash", "alice", 0.0);
+        app.deposit(100.0).unwrap();
+        app.withdraw(30.0).unwrap();
+        assert_eq!(app.balance("alice"), 70.0);
+        assert_eq!(app.count("SELECT COUNT(*) FROM transactions WHERE username = 'alice'"), 2);
+    }
+
+    #[test]
+    fn cash_that_cannot_be_recorded_leaves_the_balance_alone() {
+        let mut app = TestApp::with_user("cash_unrecorded", "alice", 50.0);
+        fail_inserts(&app, "transactions");
+        assert!(app.deposit(100.0).is_err());
+        assert!(app.withdraw(20.0).is_err());
+        assert_eq!(app.balance("alice"), 50.0);
+    }
+
+    #[test]
+    fn navigation_can_be_undone_and_redone() {
+        let mut app = TestApp::with_user("undo_navigation", "alice", 0.0);
+        app.reset_screen(Screen::Dashboard);
+        app.push_screen(Screen::ViewTransactions);
+        app.push_screen(Screen::TransactionDetail);
+        app.pop_screen();
+        assert_eq!(app.current_screen, Screen::ViewTransactions);
+
+        app.undo().unwrap();
+        assert_eq!(app.current_screen, Screen::TransactionDetail);
+        app.undo().unwrap();
+        assert_eq!(app.current_screen, Screen::ViewTransactions);
+        app.redo().unwrap();
+        assert_eq!(app.current_screen, Screen::TransactionDetail);
+        app.redo().unwrap();
+        assert_eq!(app.current_screen, Screen::ViewTransactions);
+        assert_eq!(app.screen_stack, vec![Screen::Dashboard]);
+    }
+
+    #[test]
+    fn annotations_and_stars_can_be_undone_and_redone() {
+        let mut app = TestApp::with_user("undo_annotation", "alice", 0.0);
+        app.deposit(10.0).unwrap();
+        let id: String =
+            app.conn.query_row("SELECT id FROM transactions", [], |row| row.get(0)).unwrap();
+        let annotation = |app: &TestApp| Annotation::get(&app.conn, &id).unwrap().unwrap();
+
+        app.set_annotation(&id, AnnotationField::Category, Some("food")).unwrap();
+        app.record(Command::EditAnnotation {
+            transaction_id: id.clone(),
+            field: AnnotationField::Category,
+            old: None,
+            new: Some("food".to_string()),
+        });
+        Annotation::toggle_starred(&app.conn, &id).unwrap();
+        app.record(Command::ToggleStar { transaction_id: id.clone() });
+
+        app.undo().unwrap();
+        assert!(!annotation(&app).starred);
+        app.undo().unwrap();
+        assert_eq!(annotation(&app).category, None);
+        app.redo().unwrap();
+        assert_eq!(annotation(&app).category.as_deref(), Some("food"));
+        app.redo().unwrap();
+        assert!(annotation(&app).starred);
+    }
+
+    #[test]
+    fn deposit_can_be_undone_and_redone() {
+        let mut app = TestApp::with_user("undo_deposit", "alice", 0.0);
+        app.deposit(100.0).unwrap();
+        app.record(Command::Deposit { amount: 100.0 });
+
+        app.undo().unwrap();
+        assert_eq!(app.balance("alice"), 0.0);
+        app.redo().unwrap();
+        assert_eq!(app.balance("alice"), 100.0);
+    }
+
+    #[test]
+    fn deposit_already_spent_is_not_undone() {
+        let mut app = TestApp::with_user("undo_deposit_spent", "alice", 0.0);
+        app.overdraft_limit = 500.0;
+        app.deposit(100.0).unwrap();
+        app.record(Command::Deposit { amount: 100.0 });
+        app.withdraw(80.0).unwrap();
+
+        app.undo().unwrap();
+        assert!(app.last_message().starts_with("Cannot undo deposit."));
+        assert_eq!(app.balance("alice"), 20.0);
+        assert_eq!(app.undo_stack.len(), 1);
+    }
+
+    #[test]
+    fn deposit_undo_keeps_to_the_spending_cap() {
+        let mut app = TestApp::with_user("undo_deposit_cap", "kid", 0.0);
+        app.give("parent", 0.0);
+        ChildAccount::create(&app.conn, "kid", "parent").unwrap();
+        ChildAccount::set_spending_cap(&app.conn, "kid", Some(10.0)).unwrap();
+        app.deposit(50.0).unwrap();
+        app.record(Command::Deposit { amount: 50.0 });
+
+        app.undo().unwrap();
+        assert!(app.last_message().starts_with("Over your weekly limit"));
+        assert_eq!(app.balance("kid"), 50.0);
+    }
+
+    #[test]
+    fn withdrawal_can_be_undone_and_redone() {
+        let mut app = TestApp::with_user("undo_withdraw", "alice", 100.0);
+        app.withdraw(40.0).unwrap();
+        app.record(Command::Withdraw { amount: 40.0 });
+
+        app.undo().unwrap();
+        assert_eq!(app.balance("alice"), 100.0);
+        app.redo().unwrap();
+        assert_eq!(app.balance("alice"), 60.0);
+    }
+
+    #[test]
+    fn withdrawal_is_not_redone_without_the_funds() {
+        let mut app = TestApp::with_user("redo_withdraw", "alice", 100.0);
+        app.withdraw(40.0).unwrap();
+        app.record(Command::Withdraw { amount: 40.0 });
+        app.undo().unwrap();
+        app.withdraw(100.0).unwrap();
+
+        app.redo().unwrap();
+        assert_eq!(app.balance("alice"), 0.0);
+        assert_eq!(app.redo_stack.len(), 1);
+    }
+
+    #[test]
+    fn transfer_can_be_undone_and_redone() {
+        let mut app = TestApp::with_user("undo_transfer", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.send("bob", 30.0);
+
+        app.undo().unwrap();
+        assert_eq!((app.balance("alice"), app.balance("bob")), (100.0, 0.0));
+        let notices = Notifications::get_for_user(&app.conn, "bob").unwrap();
+        assert!(notices.iter().any(|n| n.kind == NotificationKind::TransferReversed));
+
+        app.redo().unwrap();
+        assert_eq!((app.balance("alice"), app.balance("bob")), (70.0, 30.0));
+        // Undoing the redo reverses the new transfer.
+        app.undo().unwrap();
+        assert_eq!((app.balance("alice"), app.balance("bob")), (100.0, 0.0));
+    }
+
+    #[test]
+    fn transfer_is_not_undone_after_the_window() {
+        let mut app = TestApp::with_user("undo_transfer_late", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.send("bob", 30.0);
+        app.clock.advance(chrono::Duration::minutes(TRANSFER_UNDO_MINUTES + 1));
+
+        app.undo().unwrap();
+        assert!(app.last_message().starts_with("Cannot undo transfer."));
+        assert_eq!((app.balance("alice"), app.balance("bob")), (70.0, 30.0));
+    }
+
+    #[test]
+    fn transfer_is_not_undone_once_the_recipient_spent_it() {
+        let mut app = TestApp::with_user("undo_transfer_spent", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.send("bob", 30.0);
+        User::adjust_balance(&app.conn, "bob", -20.0, app.now()).unwrap();
+
+        app.undo().unwrap();
+        assert!(app.last_message().starts_with("Cannot undo transfer."));
+        assert_eq!((app.balance("alice"), app.balance("bob")), (70.0, 10.0));
+    }
+
+    #[test]
+    fn transfer_is_not_redone_without_the_funds() {
+        let mut app = TestApp::with_user("redo_transfer", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.send("bob", 30.0);
+        app.undo().unwrap();
+        app.withdraw(100.0).unwrap();
+
+        app.redo().unwrap();
+        assert!(app.last_message().starts_with("Transfer failed."));
+        assert_eq!((app.balance("alice"), app.balance("bob")), (0.0, 0.0));
+    }
+
+    #[test]
+    fn overdrawn_since_follows_the_controller_clock() {
+        let mut app = TestApp::with_user("overdrawn_since", "alice", 0.0);
+        let start = app.now();
+        app.withdraw(50.0).unwrap();
+        assert_eq!(User::get_overdrawn_since(&app.conn, "alice").unwrap(), Some(start));
+
+        app.clock.advance(chrono::Duration::days(3));
+        app.withdraw(10.0).unwrap();
+        assert_eq!(User::get_overdrawn_since(&app.conn, "alice").unwrap(), Some(start));
+        app.deposit(100.0).unwrap();
+        assert_eq!(User::get_overdrawn_since(&app.conn, "alice").unwrap(), None);
+    }
+
+    #[test]
+    fn webhook_is_due_when_the_transaction_happened() {
+        let mut app = TestApp::with_user("webhook_due", "alice", 0.0);
+        UserSettings::set(&app.conn, "alice", "webhook_url", "http://localhost:9/hook").unwrap();
+        app.clock.advance(chrono::Duration::days(30));
+        app.deposit(5.0).unwrap();
+        let due: String = app
+            .conn
+            .query_row("SELECT next_attempt_at FROM outbox", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(due, app.now().format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+
+    /// Every row of every table, to tell whether anything was written.
+    fn dump(app: &TestApp) -> Vec<String> {
+        let mut stmt = app
+            .conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let tables: Vec<String> =
+            stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_>>().unwrap();
+        let mut rows = Vec::new();
+        for table in tables {
+            let mut stmt = app.conn.prepare(&format!("SELECT * FROM \"{}\"", table)).unwrap();
+            let columns = stmt.column_count();
+            let mut query = stmt.query([]).unwrap();
+            while let Some(row) = query.next().unwrap() {
+                let values: Vec<String> =
+                    (0..columns).map(|i| format!("{:?}", row.get_ref(i).unwrap())).collect();
+                rows.push(format!("{}: {}", table, values.join(", ")));
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn view_only_login_leaves_the_database_alone() {
+        let mut app = TestApp::with_user("view_only", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.request_payment("bob".to_string(), 10.0).unwrap();
+        app.add_child("kid").unwrap();
+        app.set_child_allowance(5.0).unwrap();
+        app.move_to_vault(20.0).unwrap();
+        app.request_vault_withdrawal(10.0).unwrap();
+        app.logout();
+        // Long enough for the request to expire, the vault withdrawal to be
+        // released, an allowance and a weekly summary to be due.
+        app.clock.advance(chrono::Duration::days(8));
+        let before = dump(&app);
+
+        app.view_only = true;
+        assert!(app.login("alice".to_string()).unwrap());
+        app.tick().unwrap();
+        app.clock.advance(chrono::Duration::minutes(1));
+        app.tick().unwrap();
+        assert_eq!(dump(&app), before);
+
+        app.logout();
+        app.login("alice".to_string()).unwrap();
+        assert_ne!(dump(&app), before);
+    }
+
+    #[test]
+    fn vault_withdrawal_takes_only_what_the_vault_holds() {
+        let mut app = TestApp::with_user("vault_withdrawal", "alice", 100.0);
+        app.move_to_vault(40.0).unwrap();
+        assert!(app.request_vault_withdrawal(25.0).unwrap());
+        assert_eq!(Vault::get_balance(&app.conn, "alice").unwrap(), 15.0);
+
+        assert!(!app.request_vault_withdrawal(20.0).unwrap());
+        assert_eq!(app.last_message(), "Not withdrawn. The vault holds $15.00");
+        assert_eq!(Vault::get_balance(&app.conn, "alice").unwrap(), 15.0);
+        assert_eq!(app.count("SELECT COUNT(*) FROM vault_withdrawals"), 1);
+    }
+
+    #[test]
+    fn vault_withdrawals_from_two_sessions_do_not_overdraw_the_vault() {
+        let mut app = TestApp::with_user("vault_two_sessions", "alice", 100.0);
+        app.move_to_vault(20.0).unwrap();
+        let mut other = app.other_session("alice");
+
+        assert!(other.request_vault_withdrawal(15.0).unwrap());
+        assert!(!app.request_vault_withdrawal(15.0).unwrap());
+        assert!(app.request_vault_withdrawal(5.0).unwrap());
+        assert_eq!(Vault::get_balance(&app.conn, "alice").unwrap(), 0.0);
+        assert_eq!(app.count("SELECT COUNT(*) FROM vault_withdrawals"), 2);
+    }
+
+    #[test]
+    fn batch_transfer_moves_every_leg_or_none() {
+        let mut app = TestApp::with_user("batch", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.give("carol", 0.0);
+        let batch = |bob, carol| vec![("bob".to_string(), bob), ("carol".to_string(), carol)];
+
+        assert!(!app.transfer_many(&batch(60.0, 60.0)).unwrap());
+        assert!(app.last_message().starts_with("Transfer failed. Insufficient funds"));
+        assert_eq!((app.balance("bob"), app.balance("carol")), (0.0, 0.0));
+
+        assert!(app.transfer_many(&batch(30.0, 20.0)).unwrap());
+        assert_eq!(app.balance("alice"), 50.0);
+        assert_eq!((app.balance("bob"), app.balance("carol")), (30.0, 20.0));
+    }
+
+    #[test]
+    fn batch_over_the_cap_is_held_for_cooling() {
+        let mut app = TestApp::with_user("batch_over_cap", "alice", 2_000.0);
+        app.give("bob", 0.0);
+        app.give("carol", 0.0);
+        app.large_transfer_cap = 1_000.0;
+        let batch = vec![("bob".to_string(), 600.0), ("carol".to_string(), 600.0)];
+
+        assert!(app.transfer_many(&batch).unwrap());
+        assert_eq!(app.balance("alice"), 2_000.0);
+        app.load_pending_transfers().unwrap();
+        assert_eq!(app.pending_transfers.len(), 2);
+        assert!(app.confirm_pending_transfer().unwrap().is_none());
+
+        let cooling = app.rules().cooling_seconds;
+        app.clock.advance(chrono::Duration::seconds(cooling));
+        assert!(app.confirm_pending_transfer().unwrap().is_some());
+        assert!(app.confirm_pending_transfer().unwrap().is_some());
+        assert_eq!(app.balance("alice"), 800.0);
+        assert_eq!((app.balance("bob"), app.balance("carol")), (600.0, 600.0));
+    }
+
+    #[test]
+    fn captured_hold_leaves_the_balance() {
+        let mut app = TestApp::with_user("hold_capture", "alice", 100.0);
+        let id = app.hold(40.0, "hotel").unwrap().unwrap();
+        assert_eq!(app.get_available_balance().unwrap(), 60.0);
+        assert_eq!(app.balance("alice"), 100.0);
+
+        assert!(app.capture(id).unwrap());
+        assert_eq!(app.balance("alice"), 60.0);
+        assert_eq!(app.get_available_balance().unwrap(), 60.0);
+    }
+
+    #[test]
+    fn held_money_cannot_be_held_or_spent_again() {
+        let mut app = TestApp::with_user("hold_funds", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.hold(70.0, "hotel").unwrap().unwrap();
+
+        assert_eq!(app.hold(40.0, "car").unwrap(), None);
+        assert_eq!(app.last_message(), "Cannot hold $40.00. Available balance: $30.00");
+        assert_eq!(app.transfer("bob".to_string(), 40.0, None).unwrap(), None);
+        assert_eq!((app.balance("alice"), app.balance("bob")), (100.0, 0.0));
+    }
+
+    #[test]
+    fn holds_from_two_sessions_do_not_overcommit_the_balance() {
+        let mut app = TestApp::with_user("hold_two_sessions", "alice", 100.0);
+        let mut other = app.other_session("alice");
+        let id = other.hold(60.0, "hotel").unwrap().unwrap();
+        assert_eq!(app.hold(60.0, "car").unwrap(), None);
+
+        assert!(other.capture(id).unwrap());
+        assert!(!app.capture(id).unwrap());
+        assert!(!app.release(id).unwrap());
+        assert_eq!(app.balance("alice"), 40.0);
+        let captures = "SELECT COUNT(*) FROM transactions WHERE transaction_type = 'withdraw'";
+        assert_eq!(app.count(captures), 1);
+    }
+
+    #[test]
+    fn vault_money_comes_back_after_the_delay() {
+        let mut app = TestApp::with_user("vault_round_trip", "alice", 100.0);
+        assert!(app.move_to_vault(40.0).unwrap());
+        assert_eq!((app.balance("alice"), app.get_vault_balance().unwrap()), (60.0, 40.0));
+        assert!(app.request_vault_withdrawal(40.0).unwrap());
+
+        app.clock.advance(chrono::Duration::hours(app.vault_delay_hours));
+        app.release_vault_withdrawals().unwrap();
+        assert_eq!((app.balance("alice"), app.get_vault_balance().unwrap()), (100.0, 0.0));
+    }
+
+    #[test]
+    fn vault_takes_only_the_available_balance() {
+        let mut app = TestApp::with_user("vault_funds", "alice", 100.0);
+        app.hold(70.0, "hotel").unwrap().unwrap();
+
+        assert!(!app.move_to_vault(40.0).unwrap());
+        assert_eq!(app.last_message(), "Not moved. Insufficient funds. Available balance: $30.00");
+        assert_eq!((app.balance("alice"), app.get_vault_balance().unwrap()), (100.0, 0.0));
+    }
+
+    #[test]
+    fn vault_withdrawal_is_released_once_across_sessions() {
+        let mut app = TestApp::with_user("vault_release_two_sessions", "alice", 100.0);
+        app.move_to_vault(40.0).unwrap();
+        app.request_vault_withdrawal(40.0).unwrap();
+        let mut other = app.other_session("alice");
+
+        app.clock.advance(chrono::Duration::hours(app.vault_delay_hours));
+        other.release_vault_withdrawals().unwrap();
+        app.release_vault_withdrawals().unwrap();
+        assert_eq!(app.balance("alice"), 100.0);
+        let releases = "SELECT COUNT(*) FROM transactions WHERE transaction_type = 'vault_out'";
+        assert_eq!(app.count(releases), 1);
+    }
+
+    #[test]
+    fn deposit_spent_in_another_session_is_not_undone() {
+        let mut app = TestApp::with_user("undo_deposit_other_session", "alice", 0.0);
+        app.give("bob", 0.0);
+        app.deposit(100.0).unwrap();
+        app.record(Command::Deposit { amount: 100.0 });
+        assert_eq!(app.get_balance().unwrap(), 100.0);
+        let mut other = app.other_session("alice");
+        other.transfer("bob".to_string(), 80.0, None).unwrap().unwrap();
+
+        app.undo().unwrap();
+        assert!(app.last_message().starts_with("Cannot undo deposit."));
+        assert_eq!(app.balance("alice"), 20.0);
+    }
+
+    #[test]
+    fn withdrawal_is_not_redone_once_another_session_spent_the_money() {
+        let mut app = TestApp::with_user("redo_withdraw_other_session", "alice", 100.0);
+        app.give("bob", 0.0);
+        app.withdraw(40.0).unwrap();
+        app.record(Command::Withdraw { amount: 40.0 });
+        app.undo().unwrap();
+        assert_eq!(app.get_balance().unwrap(), 100.0);
+        let mut other = app.other_session("alice");
+        other.transfer("bob".to_string(), 80.0, None).unwrap().unwrap();
+
+        app.redo().unwrap();
+        assert!(app.last_message().starts_with("Insufficient funds."));
+        assert_eq!(app.balance("alice"), 20.0);
+    }
+
+    /// An imported deposit or withdrawal of `amount` for `username`.
+    fn imported(id: &str, username: &str, transaction_type: &str, amount: f64) -> Transaction {
+        let change = if transaction_type == "deposit" { amount } else { -amount };
+        Transaction {
+            id: id.to_string(),
+            username: username.to_string(),
+            transaction_type: transaction_type.to_string(),
+            amount,
+            recipient: None,
+            sender: None,
+            previous_balance: 0.0,
+            new_balance: change,
+            timestamp: NaiveDate::from_ymd_opt(2026, 1, 5).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: None,
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn import_adds_to_the_balance_already_held() {
+        let app = TestApp::with_user("import", "alice", 100.0);
+        let records =
+            [imported("i1", "alice", "deposit", 50.0), imported("i2", "carol", "deposit", 20.0)];
+
+        assert_eq!(TransactionRecords::import(&app.conn, &records, app.now()).unwrap(), 2);
+        assert_eq!((app.balance("alice"), app.balance("carol")), (150.0, 20.0));
+    }
+
+    #[test]
+    fn imported_withdrawals_beyond_the_balance_leave_it_overdrawn() {
+        let app = TestApp::with_user("import_overdrawn", "alice", 30.0);
+        let records = [imported("i1", "alice", "withdraw", 50.0)];
+
+        assert_eq!(TransactionRecords::import(&app.conn, &records, app.now()).unwrap(), 1);
+        assert_eq!(app.balance("alice"), -20.0);
+        assert_eq!(User::get_overdrawn_since(&app.conn, "alice").unwrap(), Some(app.now()));
+    }
+
+    #[test]
+    fn import_from_two_sessions_is_counted_once() {
+        let mut app = TestApp::with_user("import_two_sessions", "alice", 100.0);
+        assert_eq!(app.get_balance().unwrap(), 100.0);
+        let other = app.other_session("alice");
+        let records = [imported("i1", "alice", "deposit", 50.0)];
+
+        assert_eq!(TransactionRecords::import(&other.conn, &records, app.now()).unwrap(), 1);
+        assert_eq!(TransactionRecords::import(&app.conn, &records, app.now()).unwrap(), 0);
+        app.deposit(10.0).unwrap();
+        assert_eq!(app.balance("alice"), 160.0);
+    }
+
+    #[test]
+    fn every_balance_change_bumps_the_version() {
+        let mut app = TestApp::with_user("version", "alice", 100.0);
+        app.give("bob", 0.0);
+        let version = |app: &TestApp| User::get(&app.conn, "alice").unwrap().unwrap().version;
+        let before = version(&app);
+
+        app.deposit(10.0).unwrap();
+        app.withdraw(5.0).unwrap();
+        app.send("bob", 5.0);
+        assert_eq!(version(&app), before + 3);
+    }
+
+    #[test]
+    fn transfer_checks_the_balance_another_session_left() {
+        let mut app = TestApp::with_user("version_spent", "alice", 100.0);
+        app.give("bob", 0.0);
+        assert_eq!(app.get_balance().unwrap(), 100.0);
+        let mut other = app.other_session("alice");
+        other.transfer("bob".to_string(), 80.0, None).unwrap().unwrap();
+
+        assert_eq!(app.transfer("bob".to_string(), 50.0, None).unwrap(), None);
+        assert!(app.last_message().starts_with("Transfer failed. Insufficient funds"));
+        assert_eq!((app.balance("alice"), app.balance("bob")), (20.0, 80.0));
+    }
+
+    #[test]
+    fn stale_version_does_not_overwrite_the_balance() {
+        let mut app = TestApp::with_user("version_conflict", "alice", 100.0);
+        let stale = User::get(&app.conn, "alice").unwrap().unwrap();
+        let mut other = app.other_session("alice");
+        other.deposit(50.0).unwrap();
+
+        let now = app.now();
+        assert!(!User::update_balance(&app.conn, "alice", 90.0, stale.version, now).unwrap());
+        assert_eq!(app.balance("alice"), 150.0);
+        app.withdraw(10.0).unwrap();
+        assert_eq!(app.balance("alice"), 140.0);
+    }
+
+    /// Moves the clock past the processor's delay for `kind`.
+    fn wait_for_processor(app: &TestApp, kind: PaymentKind) {
+        app.clock.advance(chrono::Duration::seconds(app.processor.delay_seconds(kind)));
+    }
+
+    #[test]
+    fn settled_top_up_is_deposited_with_its_webhook() {
+        let mut app = TestApp::with_user("top_up", "alice", 0.0);
+        UserSettings::set(&app.conn, "alice", "webhook_url", "http://localhost:9/hook").unwrap();
+        app.processor.failure_rate = 0.0;
+        app.top_up_by_card(50.0).unwrap();
+        assert_eq!(app.balance("alice"), 0.0);
+
+        wait_for_processor(&app, PaymentKind::CardTopUp);
+        assert_eq!(app.settle_processor_payments().unwrap().len(), 1);
+        let fee = app.rules().fee(PaymentKind::CardTopUp, 50.0);
+        assert_eq!(app.balance("alice"), 50.0 - fee);
+        let queued = app.count("SELECT COUNT(*) FROM outbox");
+        assert_eq!(queued, app.count("SELECT COUNT(*) FROM transactions"));
+    }
+
+    #[test]
+    fn bank_withdrawal_needs_the_available_balance() {
+        let mut app = TestApp::with_user("bank_funds", "alice", 100.0);
+        app.hold(30.0, "hotel").unwrap().unwrap();
+
+        assert!(!app.withdraw_to_bank(80.0).unwrap());
+        assert_eq!(app.last_message(), "Insufficient funds. Available balance: $70.00");
+        assert_eq!(app.balance("alice"), 100.0);
+        assert_eq!(app.count("SELECT COUNT(*) FROM processor_payments"), 0);
+    }
+
+    #[test]
+    fn bank_withdrawal_checks_the_balance_another_session_left() {
+        let mut app = TestApp::with_user("bank_two_sessions", "alice", 100.0);
+        app.give("bob", 0.0);
+        assert_eq!(app.get_balance().unwrap(), 100.0);
+        let mut other = app.other_session("alice");
+        other.transfer("bob".to_string(), 80.0, None).unwrap().unwrap();
+
+        assert!(!app.withdraw_to_bank(50.0).unwrap());
+        assert_eq!(app.balance("alice"), 20.0);
+        assert_eq!(app.count("SELECT COUNT(*) FROM processor_payments"), 0);
+    }
+
+    #[test]
+    fn processor_payment_is_answered_once_across_sessions() {
+        let mut app = TestApp::with_user("settle_two_sessions", "alice", 0.0);
+        app.processor.failure_rate = 0.0;
+        app.top_up_by_card(50.0).unwrap();
+        let mut other = app.other_session("alice");
+        other.processor.failure_rate = 0.0;
+
+        wait_for_processor(&app, PaymentKind::CardTopUp);
+        assert_eq!(other.settle_processor_payments().unwrap().len(), 1);
+        assert!(app.settle_processor_payments().unwrap().is_empty());
+        let fee = app.rules().fee(PaymentKind::CardTopUp, 50.0);
+        assert_eq!(app.balance("alice"), 50.0 - fee);
+    }
+
+    #[test]
+    fn settlement_whose_webhook_cannot_be_queued_waits_for_the_next_run() {
+        let mut app = TestApp::with_user("settle_outbox_full", "alice", 0.0);
+        UserSettings::set(&app.conn, "alice", "webhook_url", "http://localhost:9/hook").unwrap();
+        app.processor.failure_rate = 0.0;
+        app.top_up_by_card(50.0).unwrap();
+        wait_for_processor(&app, PaymentKind::CardTopUp);
+
+        fail_inserts(&app, "outbox");
+        assert!(app.settle_processor_payments().is_err());
+        assert_eq!(app.balance("alice"), 0.0);
+        let pending = "SELECT COUNT(*) FROM processor_payments WHERE status = 'pending'";
+        assert_eq!(app.count(pending), 1);
+
+        app.conn.execute_batch("DROP TRIGGER fail_outbox").unwrap();
+        assert_eq!(app.settle_processor_payments().unwrap().len(), 1);
+        assert_eq!(app.count(pending), 0);
+        assert!(app.count("SELECT COUNT(*) FROM outbox") > 0);
     }
 }
@@ -0,0 +1,59 @@
+use crate::controllers::app_controller::AnnotationField;
+use crate::controllers::screens::Screen;
+use crate::views::format;
+
+/// A completed user action recorded on the controller's undo/redo stacks.
+/// Non-financial commands are undone by restoring the previous state;
+/// financial ones are undone with a compensating transaction, so the ledger
+/// keeps both the original entry and its reversal.
+pub enum Command {
+    PushScreen {
+        from: Screen,
+        to: Screen,
+    },
+    PopScreen {
+        from: Screen,
+        to: Screen,
+    },
+    EditAnnotation {
+        transaction_id: String,
+        field: AnnotationField,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    ToggleStar {
+        transaction_id: String,
+    },
+    Deposit {
+        amount: f64,
+    },
+    Withdraw {
+        amount: f64,
+    },
+    Transfer {
+        recipient: String,
+        amount: f64,
+        note: Option<String>,
+        transfer_id: String,
+    },
+}
+
+impl Command {
+    /// Short description used in undo/redo messages. Amounts are masked when
+    /// `hide_amounts` is set.
+    pub fn describe(&self, hide_amounts: bool) -> String {
+        let money = |amount: f64| format::money_or_masked(amount, hide_amounts);
+        match self {
+            Command::PushScreen { .. } | Command::PopScreen { .. } => "navigation".to_string(),
+            Command::EditAnnotation { field, .. } => format!("{} edit", field.column()),
+            Command::ToggleStar { .. } => "star change".to_string(),
+            Command::Deposit { amount } => format!("deposit of {}", money(*amount)),
+            Command::Withdraw { amount } => format!("withdrawal of {}", money(*amount)),
+            Command::Transfer {
+                recipient, amount, ..
+            } => {
+                format!("transfer of {} to {}", money(*amount), recipient)
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{Connection, Result};
+
+use crate::controllers::app_controller::AppController;
+use crate::models::{
+    database,
+    environment::LogLevel,
+    outbox::{self, Outbox},
+};
+
+/// How often the daemon records everyone's net worth. Each day keeps the
+/// last figures recorded in it.
+const NET_WORTH_INTERVAL: chrono::Duration = chrono::Duration::hours(1);
+
+/// Sends a webhook payload to a URL, returning the error to retry on.
+pub type Deliver = fn(&str, &str) -> std::result::Result<(), String>;
+
+/// The background work `daemon` repeats: reloading the rules, releasing vault
+/// withdrawals, paying interest and allowances, sending weekly summaries,
+/// settling processor payments, running sweep rules, expiring joint account
+/// transfers nobody approved, recording balances and net worth and
+/// delivering webhooks. Time comes from the
+/// controller's clock, so the same work can be run on simulated time.
+pub struct Daemon {
+    pub app_controller: AppController,
+    /// Vault interest paid since the daemon started.
+    pub interest_paid: f64,
+    /// Webhooks delivered since the daemon started.
+    pub webhooks_delivered: usize,
+    conn: Connection,
+    deliver: Deliver,
+    net_worth_recorded_at: Option<NaiveDateTime>,
+    balances_recorded_on: Option<NaiveDate>,
+}
+
+impl Daemon {
+    /// Runs on `app_controller`, delivering webhooks with `deliver`, which is
+    /// normally `outbox::post_json`.
+    pub fn new(app_controller: AppController, deliver: Deliver) -> Result<Self> {
+        Ok(Daemon {
+            app_controller,
+            interest_paid: 0.0,
+            webhooks_delivered: 0,
+            conn: database::open(database::path())?,
+            deliver,
+            net_worth_recorded_at: None,
+            balances_recorded_on: None,
+        })
+    }
+
+    /// Does whatever work is due now and describes it, for logging.
+    pub fn run_once(&mut self) -> Result<Vec<(LogLevel, String)>> {
+        let mut log = Vec::new();
+        if let Some(message) = self.app_controller.reload_rules()? {
+            log.push((LogLevel::Info, message));
+        }
+        self.app_controller.release_vault_withdrawals()?;
+        let interest = self.app_controller.pay_vault_interest()?;
+        self.interest_paid += interest;
+        if interest > 0.0 {
+            log.push((LogLevel::Info, format!("Paid {:.2} of vault interest", interest)));
+        }
+        self.app_controller.pay_allowances()?;
+        let summaries = self.app_controller.send_weekly_summaries()?;
+        if summaries > 0 {
+            log.push((LogLevel::Info, format!("Sent weekly summaries to {} user(s)", summaries)));
+        }
+        for outcome in self.app_controller.settle_processor_payments()? {
+            log.push((LogLevel::Info, outcome));
+        }
+        for outcome in self.app_controller.run_sweep_rules()? {
+            log.push((LogLevel::Info, outcome));
+        }
+        for outcome in self.app_controller.expire_joint_approvals()? {
+            log.push((LogLevel::Info, outcome));
+        }
+        let now = self.app_controller.now();
+        if self.balances_recorded_on != Some(now.date()) {
+            let recorded = self.app_controller.record_balance_snapshots()?;
+            self.balances_recorded_on = Some(now.date());
+            let message = format!("Recorded {} day(s) of closing balances", recorded);
+            log.push((LogLevel::Debug, message));
+        }
+        let record_net_worth = self.net_worth_recorded_at.is_none_or(|at| {
+            at.date() != now.date() || now - at >= NET_WORTH_INTERVAL
+        });
+        if record_net_worth {
+            let recorded = self.app_controller.record_net_worth_snapshots()?;
+            self.net_worth_recorded_at = Some(now);
+            log.push((LogLevel::Debug, format!("Recorded net worth of {} user(s)", recorded)));
+        }
+
+        let due = Outbox::due(&self.conn, self.app_controller.now())?;
+        log.push((LogLevel::Debug, format!("{} webhook(s) due", due.len())));
+        for message in due {
+            match (self.deliver)(&message.url, &message.payload) {
+                Ok(()) => {
+                    Outbox::delivered(&self.conn, message.id)?;
+                    self.webhooks_delivered += 1;
+                    let delivered = format!("#{} delivered to {}", message.id, message.url);
+                    log.push((LogLevel::Info, delivered));
+                }
+                Err(error) => {
+                    Outbox::failed(&self.conn, &message, &error, self.app_controller.now())?;
+                    log.push((
+                        LogLevel::Warn,
+                        format!(
+                            "#{} to {} failed (attempt {}): {}",
+                            message.id,
+                            message.url,
+                            message.attempts + 1,
+                            error
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(log)
+    }
+
+    /// Describes the webhooks waiting when the daemon starts.
+    pub fn outbox_summary(&self) -> Result<String> {
+        let (waiting, given_up) = Outbox::counts(&self.conn)?;
+        Ok(format!(
+            "Delivering webhooks: {} waiting, {} given up after {} attempts",
+            waiting,
+            given_up,
+            outbox::MAX_ATTEMPTS
+        ))
+    }
+}
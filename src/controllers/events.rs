@@ -0,0 +1,258 @@
+use rusqlite::Result;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::controllers::app_controller::AppController;
+use crate::models::activity::ActivityKind;
+use crate::models::notification::NotificationKind;
+use crate::views::format;
+
+/// File the audit log subscriber appends to.
+pub const AUDIT_LOG_PATH: &str = "audit.log";
+
+/// Something that happened in the wallet. The controller publishes events
+/// and subscribers react to them, so features such as alerts and logging
+/// do not have to be called from every place money moves.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A user logged in, created an account or switched to one.
+    UserLoggedIn { username: String },
+    /// The logged-in user's balance changed and the transaction is stored.
+    TransactionCommitted {
+        username: String,
+        transaction_type: String,
+        amount: f64,
+        previous_balance: f64,
+        new_balance: f64,
+    },
+    /// A user changed one of their limits.
+    LimitChanged {
+        username: String,
+        limit: &'static str,
+        value: f64,
+    },
+    /// An amount went over one of the logged-in user's limits.
+    LimitExceeded {
+        username: String,
+        limit: Limit,
+        amount: f64,
+    },
+    /// A user reset their password with a recovery code, which is now used up.
+    RecoveryCodeUsed { username: String, remaining: usize },
+    /// A user gave another user read-only access to their history.
+    AccessGranted { owner: String, delegate: String },
+    /// A user took back the read-only access they gave another user.
+    AccessRevoked { owner: String, delegate: String },
+    /// A user made another user a co-owner of their account.
+    JointMemberAdded { account: String, member: String },
+    /// A user removed a co-owner from their account.
+    JointMemberRemoved { account: String, member: String },
+    /// A co-owner approved or rejected a transfer from a joint account.
+    JointTransferDecided {
+        account: String,
+        member: String,
+        amount: f64,
+        approved: bool,
+    },
+}
+
+/// Which limit an amount went over.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Limit {
+    /// The large transfer cap. The transfer was held for a cooling period.
+    LargeTransfer,
+    /// A child account's weekly spending cap. The payment was refused.
+    SpendingCap,
+}
+
+impl Limit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Limit::LargeTransfer => "large transfer cap",
+            Limit::SpendingCap => "weekly spending cap",
+        }
+    }
+}
+
+/// Reacts to published events. Subscribers are called in the order they
+/// were registered with `AppController::subscribe`.
+pub trait Subscriber {
+    fn handle_event(&self, app: &mut AppController, event: &Event) -> Result<()>;
+}
+
+/// Warns the user, and leaves a note in their inbox, when a transaction
+/// takes their balance from at or above their low balance alert threshold
+/// to below it.
+pub struct BalanceAlert;
+
+impl Subscriber for BalanceAlert {
+    fn handle_event(&self, app: &mut AppController, event: &Event) -> Result<()> {
+        let (username, previous_balance, new_balance) = match event {
+            Event::TransactionCommitted {
+                username,
+                previous_balance,
+                new_balance,
+                ..
+            } => (username, *previous_balance, *new_balance),
+            _ => return Ok(()),
+        };
+        if let Some(threshold) = app.balance_alert {
+            if previous_balance >= threshold && new_balance < threshold {
+                app.add_message(format!(
+                    "Warning: your balance is below {}. Current balance: {}",
+                    app.format_amount(threshold),
+                    app.format_amount(new_balance)
+                ));
+                app.notify(
+                    username,
+                    NotificationKind::BalanceAlert,
+                    &format!(
+                        "Your balance fell below {} to {}",
+                        format::money(threshold),
+                        format::money(new_balance)
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Leaves a note in the user's inbox when an amount goes over one of their limits.
+pub struct LimitNotice;
+
+impl Subscriber for LimitNotice {
+    fn handle_event(&self, app: &mut AppController, event: &Event) -> Result<()> {
+        if let Event::LimitExceeded {
+            username,
+            limit,
+            amount,
+        } = event
+        {
+            let outcome = match limit {
+                Limit::LargeTransfer => "was held for a cooling period",
+                Limit::SpendingCap => "was refused",
+            };
+            let message = format!(
+                "A payment of {} went over your {} and {}",
+                format::money(*amount),
+                limit.as_str(),
+                outcome
+            );
+            app.notify(username, NotificationKind::LimitExceeded, &message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records logins and limit changes in the user's activity timeline. A
+/// view-only session writes nothing, so its login is not recorded.
+pub struct ActivityRecorder;
+
+impl Subscriber for ActivityRecorder {
+    fn handle_event(&self, app: &mut AppController, event: &Event) -> Result<()> {
+        if app.view_only {
+            return Ok(());
+        }
+        let (username, kind, description, amount) = match event {
+            Event::UserLoggedIn { username } => {
+                (username, ActivityKind::Login, "Logged in".to_string(), None)
+            }
+            Event::LimitChanged {
+                username,
+                limit,
+                value,
+            } => (username, ActivityKind::Limit, format!("Set {} to", limit), Some(*value)),
+            Event::LimitExceeded {
+                username,
+                limit,
+                amount,
+            } => (
+                username,
+                ActivityKind::Limit,
+                format!("Went over {} with", limit.as_str()),
+                Some(*amount),
+            ),
+            _ => return Ok(()),
+        };
+        app.record_activity(username, kind, &description, amount)
+    }
+}
+
+/// Appends a line for every event to `audit.log`.
+pub struct AuditLog;
+
+impl Subscriber for AuditLog {
+    fn handle_event(&self, app: &mut AppController, event: &Event) -> Result<()> {
+        let description = match event {
+            Event::UserLoggedIn { username } => format!("{} logged in", username),
+            Event::TransactionCommitted {
+                username,
+                transaction_type,
+                amount,
+                new_balance,
+                ..
+            } => format!(
+                "{} {} {}, balance {}",
+                username,
+                transaction_type,
+                format::money(*amount),
+                format::money(*new_balance)
+            ),
+            Event::LimitChanged {
+                username,
+                limit,
+                value,
+            } => format!("{} set their {} to {}", username, limit, format::money(*value)),
+            Event::LimitExceeded {
+                username,
+                limit,
+                amount,
+            } => format!(
+                "{} went over their {} with {}",
+                username,
+                limit.as_str(),
+                format::money(*amount)
+            ),
+            Event::RecoveryCodeUsed {
+                username,
+                remaining,
+            } => format!(
+                "{} reset their password with a recovery code, {} left",
+                username, remaining
+            ),
+            Event::AccessGranted { owner, delegate } => {
+                format!("{} gave {} read-only access to their history", owner, delegate)
+            }
+            Event::AccessRevoked { owner, delegate } => {
+                format!("{} took back {}'s read-only access", owner, delegate)
+            }
+            Event::JointMemberAdded { account, member } => {
+                format!("{} made {} a co-owner", account, member)
+            }
+            Event::JointMemberRemoved { account, member } => {
+                format!("{} removed co-owner {}", account, member)
+            }
+            Event::JointTransferDecided {
+                account,
+                member,
+                amount,
+                approved,
+            } => format!(
+                "{} {} {}'s transfer of {}",
+                member,
+                if *approved { "approved" } else { "rejected" },
+                account,
+                format::money(*amount)
+            ),
+        };
+        // Like the integrity log, the audit log must not stop the action
+        // that was logged if it cannot be written.
+        let log = OpenOptions::new().create(true).append(true).open(AUDIT_LOG_PATH);
+        if let Ok(mut file) = log {
+            let line = format!("{} {}\n", app.now().format("%Y-%m-%d %H:%M:%S"), description);
+            let _ = file.write_all(line.as_bytes());
+        }
+        Ok(())
+    }
+}
@@ -1 +1,9 @@
 pub mod app_controller;
+pub mod wallet_service;
+pub mod command;
+pub mod screens;
+pub mod watch_controller;
+pub mod sql_console;
+pub mod replay;
+pub mod daemon;
+pub mod events;
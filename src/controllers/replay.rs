@@ -0,0 +1,202 @@
+use chrono::NaiveDateTime;
+use crossterm::event::KeyCode;
+use std::fs::{self, File, Permissions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// First line of every session file, so other files are not replayed.
+const SESSION_HEADER: &str = "ewallet-session 2";
+
+/// Format of the start time in a session file's header.
+const START_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// Written in place of a key typed on a password, PIN or recovery code
+/// screen.
+const MASKED_KEY: &str = "Masked";
+
+/// Where a recorded session started: the time on the app's clock and the
+/// seed its random outcomes were drawn with, so a replay can start there too.
+pub struct SessionStart {
+    pub at: NaiveDateTime,
+    pub seed: u64,
+}
+
+/// Writes every key pressed in an interactive session, with the time since
+/// the session started, so the session can be replayed. Each line is
+/// `<milliseconds>\t<key>`, for example `1520\tChar:a` or `1733\tEnter`.
+/// Keys typed on credential screens are written as `Masked`.
+pub struct SessionRecorder {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl SessionRecorder {
+    /// Starts a recording in a new file at `path`, given `permissions`
+    /// before anything is written to it.
+    pub fn create(path: &Path, start: &SessionStart, permissions: Permissions) -> io::Result<Self> {
+        let file = File::create(path)?;
+        file.set_permissions(permissions)?;
+        let mut file = BufWriter::new(file);
+        writeln!(file, "{}", SESSION_HEADER)?;
+        writeln!(file, "start\t{}", start.at.format(START_FORMAT))?;
+        writeln!(file, "seed\t{}", start.seed)?;
+        file.flush()?;
+        Ok(SessionRecorder {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    /// Appends a key press, or `Masked` in its place if `masked`. Keys that
+    /// cannot be written down, such as media keys, are skipped.
+    pub fn record(&mut self, key: KeyCode, masked: bool) -> io::Result<()> {
+        let key = match encode_key(key) {
+            Some(_) if masked => MASKED_KEY.to_string(),
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        writeln!(self.file, "{}\t{}", self.started.elapsed().as_millis(), key)?;
+        self.file.flush()
+    }
+}
+
+/// A recorded session: the database as it was when the session started,
+/// where it started and the keys pressed, each with the time since the
+/// start. Masked keys are `None`.
+pub struct Recording {
+    pub database: PathBuf,
+    pub start: SessionStart,
+    pub keys: Vec<(Duration, Option<KeyCode>)>,
+}
+
+impl Recording {
+    /// Reads the session file at `path`. Its database copy is the file next
+    /// to it with the `.db` extension.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |line: usize, problem: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path.display(), line, problem),
+            )
+        };
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        if lines.next() != Some(SESSION_HEADER) {
+            return Err(invalid(1, "not a recorded session"));
+        }
+        let at = lines
+            .next()
+            .and_then(|line| line.strip_prefix("start\t"))
+            .and_then(|at| NaiveDateTime::parse_from_str(at, START_FORMAT).ok())
+            .ok_or_else(|| invalid(2, "bad start time"))?;
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed\t"))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| invalid(3, "bad seed"))?;
+        let mut keys = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let (millis, key) = line.split_once('\t').ok_or_else(|| invalid(i + 4, line))?;
+            let millis: u64 = millis.parse().map_err(|_| invalid(i + 4, "bad time"))?;
+            let key = match key {
+                MASKED_KEY => None,
+                key => Some(decode_key(key).ok_or_else(|| invalid(i + 4, "unknown key"))?),
+            };
+            keys.push((Duration::from_millis(millis), key));
+        }
+        let database = path.with_extension("db");
+        if !database.exists() {
+            return Err(invalid(0, &format!("missing database copy {}", database.display())));
+        }
+        Ok(Recording {
+            database,
+            start: SessionStart { at, seed },
+            keys,
+        })
+    }
+}
+
+fn encode_key(key: KeyCode) -> Option<String> {
+    let name = match key {
+        KeyCode::Char(c) => return Some(format!("Char:{}", c)),
+        KeyCode::F(n) => return Some(format!("F{}", n)),
+        KeyCode::Enter => "Enter",
+        KeyCode::Esc => "Esc",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Delete => "Delete",
+        KeyCode::Tab => "Tab",
+        KeyCode::BackTab => "BackTab",
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+fn decode_key(text: &str) -> Option<KeyCode> {
+    if let Some(c) = text.strip_prefix("Char:") {
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(KeyCode::Char(c)),
+            _ => None,
+        };
+    }
+    let key = match text {
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Backspace" => KeyCode::Backspace,
+        "Delete" => KeyCode::Delete,
+        "Tab" => KeyCode::Tab,
+        "BackTab" => KeyCode::BackTab,
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        _ => KeyCode::F(text.strip_prefix('F')?.parse().ok()?),
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_keys_are_masked_and_the_start_read_back() {
+        let dir = std::env::temp_dir().join(format!("ewallet_replay_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.session");
+        fs::write(path.with_extension("db"), "").unwrap();
+        let start = SessionStart {
+            at: NaiveDateTime::parse_from_str("2026-03-01 09:30:00", "%Y-%m-%d %H:%M:%S")
+                .unwrap(),
+            seed: 42,
+        };
+        let permissions = fs::metadata(path.with_extension("db")).unwrap().permissions();
+        let mut recorder = SessionRecorder::create(&path, &start, permissions).unwrap();
+        recorder.record(KeyCode::Char('a'), false).unwrap();
+        recorder.record(KeyCode::Char('s'), true).unwrap();
+        recorder.record(KeyCode::Enter, true).unwrap();
+        recorder.record(KeyCode::Esc, false).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("Char:s"));
+        let recording = Recording::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(recording.start.at, start.at);
+        assert_eq!(recording.start.seed, 42);
+        let keys: Vec<_> = recording.keys.into_iter().map(|(_, key)| key).collect();
+        assert_eq!(keys, vec![Some(KeyCode::Char('a')), None, None, Some(KeyCode::Esc)]);
+    }
+}
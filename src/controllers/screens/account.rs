@@ -0,0 +1,44 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the Login and Create Account screens, which both ask for a username.
+/// Tab on the Login screen switches between a full and a view-only login.
+pub struct AccountHandler;
+
+impl ScreenHandler for AccountHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter if !app.input.is_empty() => {
+                let username = app.input.clone();
+                let success = if app.current_screen == Screen::Login {
+                    app.login(username)?
+                } else {
+                    app.create_account(username)?
+                };
+                if success {
+                    app.input.clear();
+                    return Ok(Transition::Reset(Screen::Dashboard));
+                }
+                if app.password_required() {
+                    app.input.clear();
+                    return Ok(Transition::Push(Screen::EnterPassword));
+                }
+            }
+            KeyCode::Tab if app.current_screen == Screen::Login => {
+                app.view_only = !app.view_only;
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                app.view_only = false;
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,95 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::{AppController, InputField};
+use crate::controllers::command::Command;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+use crate::controllers::wallet_service::WalletService;
+
+/// Handles the Deposit and Withdraw screens, which both ask for an amount.
+pub struct AmountHandler;
+
+impl ScreenHandler for AmountHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                if let Some(amount) = app.read_amount() {
+                    let input = std::mem::take(&mut app.input);
+                    app.remember_input(InputField::Amount, &input)?;
+                    if app.current_screen == Screen::Deposit {
+                        app.deposit(amount)?;
+                        app.record(Command::Deposit { amount });
+                        return Ok(Transition::Reset(Screen::Dashboard));
+                    }
+                    return withdraw(app, amount);
+                }
+            }
+            KeyCode::Up => app.recall_input(InputField::Amount, true)?,
+            KeyCode::Down => app.recall_input(InputField::Amount, false)?,
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Amounts the quick withdrawal screen takes out on keys 1 to 3.
+pub const QUICK_WITHDRAW_AMOUNTS: [f64; 3] = [20.0, 50.0, 100.0];
+
+/// Handles the quick withdrawal screen, which takes out one of
+/// `QUICK_WITHDRAW_AMOUNTS` in one key press, or opens the Withdraw screen
+/// to type any other amount.
+pub struct QuickWithdrawHandler;
+
+impl ScreenHandler for QuickWithdrawHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char(c @ '1'..='3') => {
+                let amount = QUICK_WITHDRAW_AMOUNTS[c as usize - '1' as usize];
+                withdraw(app, amount)
+            }
+            KeyCode::Char('4') => {
+                app.input.clear();
+                Ok(Transition::Push(Screen::Withdraw))
+            }
+            KeyCode::Esc => Ok(Transition::Pop),
+            _ => Ok(Transition::Stay),
+        }
+    }
+}
+
+/// Withdraws `amount` through the `WalletService`, which tells the user why
+/// when they may not spend it or do not have it, then goes back to the
+/// dashboard, or to the notes it is paid out in if they asked for them.
+fn withdraw(app: &mut AppController, amount: f64) -> Result<Transition> {
+    if WalletService::withdraw(app, amount)? {
+        app.record(Command::Withdraw { amount });
+        if app.cash_breakdown {
+            app.last_cash_out = Some(amount);
+            return Ok(Transition::Reset(Screen::CashBreakdown));
+        }
+    }
+    Ok(Transition::Reset(Screen::Dashboard))
+}
+
+/// Handles the notes a cash withdrawal was paid out in.
+pub struct CashBreakdownHandler;
+
+impl ScreenHandler for CashBreakdownHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc | KeyCode::Enter => {
+                app.last_cash_out = None;
+                return Ok(Transition::Reset(Screen::Dashboard));
+            }
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
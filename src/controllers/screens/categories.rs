@@ -0,0 +1,72 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard::{self, Wizard, WizardStep};
+use crate::controllers::screens::{Screen, ScreenHandler, Transition, MAX_TEXT_INPUT};
+use crate::models::category_rule::RuleField;
+
+/// Handles the list of the user's category rules.
+pub struct CategoryRulesHandler;
+
+impl ScreenHandler for CategoryRulesHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => {
+                app.selected_category_rule = app.selected_category_rule.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_category_rule + 1 < app.category_rules.len() => {
+                app.selected_category_rule += 1;
+            }
+            KeyCode::Char('n') => return Ok(wizard::start(app, Screen::NewCategoryRule)),
+            KeyCode::Char('d') => app.delete_selected_category_rule()?,
+            KeyCode::Char('r') => app.rerun_category_rules()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// The three-step new rule screen: which field to look at, the text it
+/// must contain, and the category to give.
+pub struct NewCategoryRuleWizard;
+
+static NEW_CATEGORY_RULE_STEPS: [WizardStep; 3] = [
+    WizardStep::new(MAX_TEXT_INPUT, validate_field),
+    WizardStep::new(MAX_TEXT_INPUT, |app, input| required(app, input, "text to match")),
+    WizardStep::new(MAX_TEXT_INPUT, |app, input| required(app, input, "category")),
+];
+
+fn validate_field(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let field = input.trim().to_lowercase();
+    if RuleField::parse(&field).is_none() {
+        app.add_message("Please enter recipient, sender or merchant.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(field))
+}
+
+fn required(app: &mut AppController, input: &str, what: &str) -> Result<Option<String>> {
+    let value = input.trim();
+    if value.is_empty() {
+        app.add_message(format!("Please enter the {}.", what));
+        return Ok(None);
+    }
+    Ok(Some(value.to_string()))
+}
+
+impl Wizard for NewCategoryRuleWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &NEW_CATEGORY_RULE_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [field, pattern, category] = values.as_slice() {
+            if let Some(field) = RuleField::parse(field) {
+                app.add_category_rule(field, pattern, category)?;
+            }
+        }
+        Ok(Transition::Pop)
+    }
+}
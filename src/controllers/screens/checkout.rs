@@ -0,0 +1,101 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles a merchant entering line items. Each Enter adds the typed item;
+/// Enter on an empty line creates the checkout and shows its code.
+pub struct NewCheckoutHandler;
+
+impl ScreenHandler for NewCheckoutHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter if app.input.trim().is_empty() => {
+                if app.create_checkout()? {
+                    return Ok(Transition::Push(Screen::CheckoutCode));
+                }
+            }
+            KeyCode::Enter => {
+                let item = std::mem::take(&mut app.input);
+                if !app.add_checkout_item(&item) {
+                    app.input = item;
+                }
+            }
+            KeyCode::Delete => {
+                app.checkout_items.pop();
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                app.checkout_items.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the merchant's view of a checkout code, which updates once a
+/// customer pays it.
+pub struct CheckoutCodeHandler;
+
+impl ScreenHandler for CheckoutCodeHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        if matches!(key, KeyCode::Esc | KeyCode::Enter) {
+            app.checkout = None;
+            app.load_payment_requests()?;
+            return Ok(Transition::Reset(Screen::Dashboard));
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles a customer entering or scanning a checkout code.
+pub struct PayCheckoutHandler;
+
+impl ScreenHandler for PayCheckoutHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let code = std::mem::take(&mut app.input);
+                if app.open_checkout(&code)? {
+                    return Ok(Transition::Push(Screen::ReviewCheckout));
+                }
+                app.input = code;
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the customer reviewing a checkout's line items before paying.
+pub struct ReviewCheckoutHandler;
+
+impl ScreenHandler for ReviewCheckoutHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        Ok(match key {
+            KeyCode::Char('y') | KeyCode::Enter => {
+                if app.pay_checkout()? {
+                    Transition::Reset(Screen::Dashboard)
+                } else {
+                    Transition::Stay
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Esc => {
+                app.checkout = None;
+                Transition::Pop
+            }
+            _ => Transition::Stay,
+        })
+    }
+}
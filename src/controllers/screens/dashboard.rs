@@ -0,0 +1,119 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{wizard, Screen, ScreenHandler, Transition};
+use crate::models::history_filter::HistoryFilter;
+
+pub struct DashboardHandler;
+
+impl ScreenHandler for DashboardHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        Ok(match key {
+            KeyCode::Char('1') => Transition::Push(Screen::Deposit),
+            KeyCode::Char('2') => Transition::Push(Screen::QuickWithdraw),
+            KeyCode::Char('3') => {
+                app.load_recent_recipients()?;
+                app.recipient_suggestions.clear();
+                wizard::start(app, Screen::Transfer)
+            }
+            KeyCode::Char('m') => {
+                app.input.clear();
+                app.batch_transfers.clear();
+                Transition::Push(Screen::BatchTransfer)
+            }
+            KeyCode::Char('4') => {
+                app.selected_transaction = 0;
+                app.starred_only = false;
+                app.history_filter = HistoryFilter::default();
+                Transition::Push(Screen::ViewTransactions)
+            }
+            KeyCode::Char('6') => Transition::Push(Screen::Settings),
+            KeyCode::Char('7') => {
+                app.selected_request = 0;
+                app.load_payment_requests()?;
+                Transition::Push(Screen::PaymentRequests)
+            }
+            KeyCode::Char('8') => {
+                app.selected_pending_transfer = 0;
+                app.load_pending_transfers()?;
+                Transition::Push(Screen::PendingTransfers)
+            }
+            KeyCode::Char('9') => {
+                app.load_wallet_public_key()?;
+                Transition::Push(Screen::OfflineTokens)
+            }
+            KeyCode::Char('0') => {
+                app.selected_vault_withdrawal = 0;
+                app.load_vault_withdrawals()?;
+                Transition::Push(Screen::Vault)
+            }
+            KeyCode::Char('5') => {
+                app.logout();
+                Transition::Reset(Screen::MainMenu)
+            }
+            KeyCode::Char('s') => {
+                app.load_remembered_accounts();
+                Transition::Push(Screen::AccountSwitcher)
+            }
+            KeyCode::Char('f') => {
+                app.selected_child = 0;
+                app.load_children()?;
+                Transition::Push(Screen::Family)
+            }
+            KeyCode::Char('i') => {
+                app.selected_notification = 0;
+                app.load_notifications()?;
+                Transition::Push(Screen::Inbox)
+            }
+            KeyCode::Char('e') => {
+                app.selected_activity = 0;
+                app.load_timeline()?;
+                Transition::Push(Screen::Timeline)
+            }
+            KeyCode::Char('t') => {
+                app.input.clear();
+                app.load_processor_payments()?;
+                Transition::Push(Screen::CardTopUp)
+            }
+            KeyCode::Char('b') => {
+                app.input.clear();
+                app.load_processor_payments()?;
+                Transition::Push(Screen::BankWithdrawal)
+            }
+            KeyCode::Char('c') => {
+                app.input.clear();
+                app.load_processor_payments()?;
+                Transition::Push(Screen::ChequeDeposit)
+            }
+            KeyCode::Char('a') => {
+                app.selected_shared_account = 0;
+                app.load_shared_accounts()?;
+                Transition::Push(Screen::SharedAccounts)
+            }
+            KeyCode::Char('j') => {
+                app.selected_approval = 0;
+                app.load_approvals()?;
+                Transition::Push(Screen::Approvals)
+            }
+            KeyCode::Char('w') => Transition::Push(Screen::NetWorth),
+            KeyCode::Char('l') => {
+                app.load_leaderboard()?;
+                Transition::Push(Screen::Leaderboard)
+            }
+            KeyCode::Char('h') => {
+                app.toggle_hide_amounts()?;
+                Transition::Stay
+            }
+            KeyCode::Char('u') => {
+                app.undo()?;
+                Transition::Stay
+            }
+            KeyCode::Char('r') => {
+                app.redo()?;
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        })
+    }
+}
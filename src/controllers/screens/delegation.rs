@@ -0,0 +1,91 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the list in Settings of users given read-only access to the
+/// current user's history.
+pub struct DelegatesHandler;
+
+impl ScreenHandler for DelegatesHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_delegate = app.selected_delegate.saturating_sub(1),
+            KeyCode::Down if app.selected_delegate + 1 < app.delegates.len() => {
+                app.selected_delegate += 1;
+            }
+            KeyCode::Char('n') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::NewDelegate));
+            }
+            KeyCode::Char('d') | KeyCode::Delete => app.revoke_selected_delegate()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the username of a user to give read-only access to.
+pub struct NewDelegateHandler;
+
+impl ScreenHandler for NewDelegateHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let delegate = app.input.trim().to_string();
+                if app.grant_access(&delegate)? {
+                    app.input.clear();
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the list of accounts whose history the current user was given
+/// access to.
+pub struct SharedAccountsHandler;
+
+impl ScreenHandler for SharedAccountsHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        let selected = app.get_selected_shared_account().is_some();
+        match key {
+            KeyCode::Up => {
+                app.selected_shared_account = app.selected_shared_account.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_shared_account + 1 < app.shared_accounts.len() => {
+                app.selected_shared_account += 1;
+            }
+            KeyCode::Enter if selected => return Ok(Transition::Push(Screen::SharedHistory)),
+            KeyCode::Char('e') if selected => app.export_shared_statement()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the read-only view of a shared account's history.
+pub struct SharedHistoryHandler;
+
+impl ScreenHandler for SharedHistoryHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('e') => app.export_shared_statement()?,
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,126 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the list of child accounts the current user manages.
+pub struct FamilyHandler;
+
+impl ScreenHandler for FamilyHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        let selected = app.get_selected_child().is_some();
+        match key {
+            KeyCode::Up => app.selected_child = app.selected_child.saturating_sub(1),
+            KeyCode::Down if app.selected_child + 1 < app.children.len() => {
+                app.selected_child += 1;
+            }
+            KeyCode::Char('n') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::NewChild));
+            }
+            KeyCode::Char('a') if selected => {
+                app.input = app
+                    .get_selected_child()
+                    .map(|c| c.weekly_allowance)
+                    .filter(|amount| *amount > 0.0)
+                    .map(|amount| amount.to_string())
+                    .unwrap_or_default();
+                return Ok(Transition::Push(Screen::EditAllowance));
+            }
+            KeyCode::Char('c') if selected => {
+                app.input = app
+                    .get_selected_child()
+                    .and_then(|c| c.spending_cap)
+                    .map(|cap| cap.to_string())
+                    .unwrap_or_default();
+                return Ok(Transition::Push(Screen::EditSpendingCap));
+            }
+            KeyCode::Enter if selected => return Ok(Transition::Push(Screen::ChildActivity)),
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the username of a new child account.
+pub struct NewChildHandler;
+
+impl ScreenHandler for NewChildHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let child = app.input.trim().to_string();
+                if app.add_child(&child)? {
+                    app.input.clear();
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles editing the selected child's weekly allowance or spending cap.
+/// An empty value stops the allowance or removes the cap.
+pub struct ChildLimitHandler;
+
+impl ScreenHandler for ChildLimitHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let value = app.input.trim();
+                let amount = if value.is_empty() {
+                    None
+                } else {
+                    match value.parse::<f64>() {
+                        Ok(amount) if amount >= 0.0 && amount.is_finite() => Some(amount),
+                        _ => {
+                            app.add_message(
+                                "Invalid amount. Please enter a positive number.".to_string(),
+                            );
+                            return Ok(Transition::Stay);
+                        }
+                    }
+                };
+                if app.current_screen == Screen::EditAllowance {
+                    app.set_child_allowance(amount.unwrap_or(0.0))?;
+                } else {
+                    app.set_child_spending_cap(amount)?;
+                }
+                app.input.clear();
+                Ok(Transition::Pop)
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                Ok(Transition::Pop)
+            }
+            _ => {
+                edit_input(app, key);
+                Ok(Transition::Stay)
+            }
+        }
+    }
+}
+
+/// Handles the parent's review of a child's recent activity.
+pub struct ChildActivityHandler;
+
+impl ScreenHandler for ChildActivityHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
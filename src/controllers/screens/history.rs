@@ -0,0 +1,224 @@
+use chrono::Duration;
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::{AnnotationField, AppController};
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the selectable transaction history list.
+pub struct HistoryHandler;
+
+impl ScreenHandler for HistoryHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        let count = app.count_transactions()?;
+        match key {
+            KeyCode::Up => {
+                app.selected_transaction = app.selected_transaction.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_transaction + 1 < count => {
+                app.selected_transaction += 1;
+            }
+            KeyCode::Enter if app.selected_transaction < count => {
+                return Ok(Transition::Push(Screen::TransactionDetail));
+            }
+            KeyCode::Char('s') => {
+                app.toggle_starred()?;
+                let count = app.count_transactions()?;
+                app.selected_transaction = app.selected_transaction.min(count.saturating_sub(1));
+            }
+            KeyCode::Char('f') => {
+                app.starred_only = !app.starred_only;
+                app.selected_transaction = 0;
+            }
+            KeyCode::Char('t') => {
+                app.history_sort = app.history_sort.next();
+                app.selected_transaction = 0;
+            }
+            KeyCode::Char('g') => return Ok(Transition::Push(Screen::JumpToDate)),
+            KeyCode::Char('/') => {
+                app.input = app.history_filter.text.clone();
+                return Ok(Transition::Push(Screen::FilterHistory));
+            }
+            KeyCode::Char('o') => return Ok(Transition::Push(Screen::Rollups)),
+            KeyCode::Char('c') => {
+                app.heatmap_day = app.now().date();
+                return Ok(Transition::Push(Screen::Heatmap));
+            }
+            KeyCode::Char('v') => app.toggle_compact_history()?,
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Char('u') => app.undo()?,
+            KeyCode::Char('r') => app.redo()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the monthly totals of transactions purged by the retention policy.
+pub struct RollupsHandler;
+
+impl ScreenHandler for RollupsHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the spending heatmap: arrows move between days, `[` and `]`
+/// between months, and Enter lists the selected day's transactions.
+pub struct HeatmapHandler;
+
+impl ScreenHandler for HeatmapHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        let days = match key {
+            KeyCode::Left => -1,
+            KeyCode::Right => 1,
+            KeyCode::Up => -7,
+            KeyCode::Down => 7,
+            _ => 0,
+        };
+        if days != 0 {
+            app.heatmap_day = app
+                .heatmap_day
+                .checked_add_signed(Duration::days(days))
+                .unwrap_or(app.heatmap_day);
+            return Ok(Transition::Stay);
+        }
+        match key {
+            KeyCode::Char('[') => app.shift_heatmap_month(false),
+            KeyCode::Char(']') => app.shift_heatmap_month(true),
+            KeyCode::Enter => return Ok(Transition::Push(Screen::DayActivity)),
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the list of one day's transactions opened from the heatmap.
+pub struct DayActivityHandler;
+
+impl ScreenHandler for DayActivityHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the detail view of the selected transaction.
+pub struct TransactionDetailHandler;
+
+impl ScreenHandler for TransactionDetailHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('c') | KeyCode::Char('m') | KeyCode::Char('n') => {
+                let field = match key {
+                    KeyCode::Char('c') => AnnotationField::Category,
+                    KeyCode::Char('m') => AnnotationField::Memo,
+                    _ => AnnotationField::Note,
+                };
+                app.editing_field = field;
+                app.input = app
+                    .get_selected_transaction()?
+                    .and_then(|t| t.get(field.column()).cloned())
+                    .unwrap_or_default();
+                return Ok(Transition::Push(Screen::EditAnnotation));
+            }
+            KeyCode::Char('s') => {
+                app.toggle_starred()?;
+                if app.get_selected_transaction()?.is_none() {
+                    app.selected_transaction = 0;
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Char('y') => app.copy_selected_transaction(false)?,
+            KeyCode::Char('j') => app.copy_selected_transaction(true)?,
+            KeyCode::Char('u') => app.undo()?,
+            KeyCode::Char('r') => app.redo()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles typing the date to jump to in the history.
+pub struct JumpToDateHandler;
+
+impl ScreenHandler for JumpToDateHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let date = app.input.clone();
+                if !app.jump_to_date(&date)? {
+                    return Ok(Transition::Stay);
+                }
+            }
+            KeyCode::Esc => {}
+            _ => {
+                edit_input(app, key);
+                return Ok(Transition::Stay);
+            }
+        }
+        app.input.clear();
+        Ok(Transition::Pop)
+    }
+}
+
+/// Handles typing the filter expression for the history. One that cannot be
+/// read is not applied; the screen shows what is wrong with it as it is typed.
+pub struct FilterHistoryHandler;
+
+impl ScreenHandler for FilterHistoryHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let filter = app.input.clone();
+                if !app.set_history_filter(&filter) {
+                    return Ok(Transition::Stay);
+                }
+            }
+            KeyCode::Esc => {}
+            _ => {
+                edit_input(app, key);
+                return Ok(Transition::Stay);
+            }
+        }
+        app.input.clear();
+        Ok(Transition::Pop)
+    }
+}
+
+/// Handles editing a category, memo or note of the selected transaction.
+pub struct EditAnnotationHandler;
+
+impl ScreenHandler for EditAnnotationHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let value = std::mem::take(&mut app.input);
+                app.save_annotation(&value)?;
+                Ok(Transition::Pop)
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                Ok(Transition::Pop)
+            }
+            _ => {
+                edit_input(app, key);
+                Ok(Transition::Stay)
+            }
+        }
+    }
+}
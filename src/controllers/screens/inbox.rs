@@ -0,0 +1,27 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{ScreenHandler, Transition};
+
+/// Handles the inbox of notifications.
+pub struct InboxHandler;
+
+impl ScreenHandler for InboxHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => {
+                app.selected_notification = app.selected_notification.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_notification + 1 < app.notifications.len() => {
+                app.selected_notification += 1;
+            }
+            KeyCode::Enter => app.mark_selected_notification_read()?,
+            KeyCode::Char('a') => app.mark_all_notifications_read()?,
+            KeyCode::Char('c') => app.clear_notifications()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
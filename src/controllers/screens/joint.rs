@@ -0,0 +1,73 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the list in Settings of the co-owners of the current user's
+/// account.
+pub struct JointMembersHandler;
+
+impl ScreenHandler for JointMembersHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_joint_member = app.selected_joint_member.saturating_sub(1),
+            KeyCode::Down if app.selected_joint_member + 1 < app.joint_members.len() => {
+                app.selected_joint_member += 1;
+            }
+            KeyCode::Char('n') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::NewJointMember));
+            }
+            KeyCode::Char('d') | KeyCode::Delete => app.remove_selected_joint_member()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the username of a user to make a co-owner.
+pub struct NewJointMemberHandler;
+
+impl ScreenHandler for NewJointMemberHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let member = app.input.trim().to_string();
+                if app.add_joint_member(&member)? {
+                    app.input.clear();
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the transfers from joint accounts the user co-owns that wait for
+/// their approval.
+pub struct ApprovalsHandler;
+
+impl ScreenHandler for ApprovalsHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_approval = app.selected_approval.saturating_sub(1),
+            KeyCode::Down if app.selected_approval + 1 < app.approvals.len() => {
+                app.selected_approval += 1;
+            }
+            KeyCode::Char('a') => app.approve_selected_transfer()?,
+            KeyCode::Char('x') => app.reject_selected_transfer()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
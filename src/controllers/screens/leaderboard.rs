@@ -0,0 +1,23 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{ScreenHandler, Transition};
+
+/// Handles the demo leaderboard of users who opted in.
+pub struct LeaderboardHandler;
+
+impl ScreenHandler for LeaderboardHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Tab => app.toggle_leaderboard_ranking()?,
+            KeyCode::Char('j') => {
+                app.toggle_leaderboard()?;
+                app.load_leaderboard()?;
+            }
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,27 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, ScreenHandler, Transition};
+
+/// Handles the idle lock screen. Only the correct credential gets past it;
+/// Esc clears the input instead of going back.
+pub struct LockHandler;
+
+impl ScreenHandler for LockHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let credential = std::mem::take(&mut app.input);
+                if app.unlock(&credential)? {
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => app.input.clear(),
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
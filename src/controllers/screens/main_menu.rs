@@ -0,0 +1,24 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard;
+use crate::controllers::screens::{Screen, ScreenHandler, Transition};
+
+pub struct MainMenuHandler;
+
+impl ScreenHandler for MainMenuHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        Ok(match key {
+            KeyCode::Char('1') => Transition::Push(Screen::Login),
+            KeyCode::Char('2') => Transition::Push(Screen::CreateAccount),
+            KeyCode::Char('3') => {
+                app.load_remembered_accounts();
+                Transition::Push(Screen::AccountSwitcher)
+            }
+            KeyCode::Char('4') => wizard::start(app, Screen::RecoverAccount),
+            KeyCode::Char('q') => Transition::Quit,
+            _ => Transition::Stay,
+        })
+    }
+}
@@ -0,0 +1,349 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard::Wizard;
+use crate::models::session::PIN_LENGTH;
+
+pub mod account;
+pub mod amount;
+pub mod categories;
+pub mod checkout;
+pub mod dashboard;
+pub mod delegation;
+pub mod family;
+pub mod history;
+pub mod inbox;
+pub mod joint;
+pub mod leaderboard;
+pub mod lock;
+pub mod main_menu;
+pub mod net_worth;
+pub mod password;
+pub mod processor;
+pub mod requests;
+pub mod settings;
+pub mod switcher;
+pub mod timeline;
+pub mod tokens;
+pub mod transfer;
+pub mod vault;
+pub mod wizard;
+
+/// Longest amount that can be typed.
+const MAX_AMOUNT_INPUT: usize = 20;
+
+/// Longest username that can be typed.
+const MAX_USERNAME_INPUT: usize = 32;
+
+/// Longest password that can be typed.
+const MAX_PASSWORD_INPUT: usize = 64;
+
+/// Longest free text, such as a transfer note or a checkout item.
+const MAX_TEXT_INPUT: usize = 200;
+
+/// Longest date that can be typed, as YYYY-MM-DD.
+const MAX_DATE_INPUT: usize = 10;
+
+/// Longest offline token that can be pasted.
+const MAX_TOKEN_INPUT: usize = 1024;
+
+/// Screens of the application. Each screen has a `ScreenHandler` that turns
+/// key presses into actions and an explicit `Transition` to the next screen.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Screen {
+    MainMenu,
+    Login,
+    EnterPassword,
+    RotatePassword,
+    RecoverAccount,
+    CreateAccount,
+    Dashboard,
+    Deposit,
+    QuickWithdraw,
+    Withdraw,
+    CashBreakdown,
+    Transfer,
+    ConfirmDuplicateTransfer,
+    BatchTransfer,
+    PendingTransfers,
+    ViewTransactions,
+    Rollups,
+    Heatmap,
+    DayActivity,
+    JumpToDate,
+    FilterHistory,
+    TransactionDetail,
+    EditAnnotation,
+    Settings,
+    EditBalanceAlert,
+    EditRoundUp,
+    CategoryRules,
+    NewCategoryRule,
+    Locked,
+    SetPassword,
+    ChangePassword,
+    RecoveryCodes,
+    AccountSwitcher,
+    SetPin,
+    EnterPin,
+    PaymentRequests,
+    NewPaymentRequest,
+    SplitBill,
+    NewCheckout,
+    CheckoutCode,
+    PayCheckout,
+    ReviewCheckout,
+    OfflineTokens,
+    IssueOfflineToken,
+    RedeemOfflineToken,
+    Vault,
+    VaultDeposit,
+    VaultWithdraw,
+    SweepRules,
+    NewSweepRule,
+    Family,
+    NewChild,
+    EditAllowance,
+    EditSpendingCap,
+    ChildActivity,
+    Delegates,
+    NewDelegate,
+    JointMembers,
+    NewJointMember,
+    Approvals,
+    SharedAccounts,
+    SharedHistory,
+    Inbox,
+    Timeline,
+    Leaderboard,
+    NetWorth,
+    CardTopUp,
+    BankWithdrawal,
+    ChequeDeposit,
+}
+
+impl Screen {
+    /// Gets the input handler responsible for this screen.
+    pub fn handler(&self) -> &'static dyn ScreenHandler {
+        match self {
+            Screen::MainMenu => &main_menu::MainMenuHandler,
+            Screen::Login | Screen::CreateAccount => &account::AccountHandler,
+            Screen::EnterPassword => &password::EnterPasswordHandler,
+            Screen::RotatePassword => &password::RotatePasswordWizard,
+            Screen::RecoverAccount => &password::RecoverAccountWizard,
+            Screen::Dashboard => &dashboard::DashboardHandler,
+            Screen::Deposit | Screen::Withdraw => &amount::AmountHandler,
+            Screen::QuickWithdraw => &amount::QuickWithdrawHandler,
+            Screen::CashBreakdown => &amount::CashBreakdownHandler,
+            Screen::Transfer => &transfer::TransferWizard,
+            Screen::ConfirmDuplicateTransfer => &transfer::ConfirmDuplicateTransferHandler,
+            Screen::BatchTransfer => &transfer::BatchTransferHandler,
+            Screen::PendingTransfers => &transfer::PendingTransfersHandler,
+            Screen::ViewTransactions => &history::HistoryHandler,
+            Screen::Rollups => &history::RollupsHandler,
+            Screen::Heatmap => &history::HeatmapHandler,
+            Screen::DayActivity => &history::DayActivityHandler,
+            Screen::JumpToDate => &history::JumpToDateHandler,
+            Screen::FilterHistory => &history::FilterHistoryHandler,
+            Screen::TransactionDetail => &history::TransactionDetailHandler,
+            Screen::EditAnnotation => &history::EditAnnotationHandler,
+            Screen::Settings => &settings::SettingsHandler,
+            Screen::EditBalanceAlert => &settings::BalanceAlertHandler,
+            Screen::EditRoundUp => &settings::RoundUpHandler,
+            Screen::CategoryRules => &categories::CategoryRulesHandler,
+            Screen::NewCategoryRule => &categories::NewCategoryRuleWizard,
+            Screen::Locked => &lock::LockHandler,
+            Screen::SetPassword => &password::SetPasswordWizard,
+            Screen::ChangePassword => &password::ChangePasswordWizard,
+            Screen::RecoveryCodes => &password::RecoveryCodesHandler,
+            Screen::AccountSwitcher => &switcher::AccountSwitcherHandler,
+            Screen::SetPin => &switcher::SetPinHandler,
+            Screen::EnterPin => &switcher::EnterPinHandler,
+            Screen::PaymentRequests => &requests::PaymentRequestsHandler,
+            Screen::NewPaymentRequest => &requests::NewPaymentRequestWizard,
+            Screen::SplitBill => &requests::SplitBillWizard,
+            Screen::NewCheckout => &checkout::NewCheckoutHandler,
+            Screen::CheckoutCode => &checkout::CheckoutCodeHandler,
+            Screen::PayCheckout => &checkout::PayCheckoutHandler,
+            Screen::ReviewCheckout => &checkout::ReviewCheckoutHandler,
+            Screen::OfflineTokens => &tokens::OfflineTokensHandler,
+            Screen::IssueOfflineToken => &tokens::IssueOfflineTokenHandler,
+            Screen::RedeemOfflineToken => &tokens::RedeemOfflineTokenHandler,
+            Screen::Vault => &vault::VaultHandler,
+            Screen::VaultDeposit | Screen::VaultWithdraw => &vault::VaultAmountHandler,
+            Screen::SweepRules => &vault::SweepRulesHandler,
+            Screen::NewSweepRule => &vault::NewSweepRuleHandler,
+            Screen::Family => &family::FamilyHandler,
+            Screen::NewChild => &family::NewChildHandler,
+            Screen::EditAllowance | Screen::EditSpendingCap => &family::ChildLimitHandler,
+            Screen::ChildActivity => &family::ChildActivityHandler,
+            Screen::Delegates => &delegation::DelegatesHandler,
+            Screen::NewDelegate => &delegation::NewDelegateHandler,
+            Screen::JointMembers => &joint::JointMembersHandler,
+            Screen::NewJointMember => &joint::NewJointMemberHandler,
+            Screen::Approvals => &joint::ApprovalsHandler,
+            Screen::SharedAccounts => &delegation::SharedAccountsHandler,
+            Screen::SharedHistory => &delegation::SharedHistoryHandler,
+            Screen::Inbox => &inbox::InboxHandler,
+            Screen::Timeline => &timeline::TimelineHandler,
+            Screen::Leaderboard => &leaderboard::LeaderboardHandler,
+            Screen::NetWorth => &net_worth::NetWorthHandler,
+            Screen::CardTopUp | Screen::BankWithdrawal | Screen::ChequeDeposit => {
+                &processor::ProcessorHandler
+            }
+        }
+    }
+}
+
+impl Screen {
+    /// Gets the wizard driving this screen, for screens that are multi-step forms.
+    pub fn wizard(&self) -> Option<&'static dyn Wizard> {
+        match self {
+            Screen::Transfer => Some(&transfer::TransferWizard),
+            Screen::NewPaymentRequest => Some(&requests::NewPaymentRequestWizard),
+            Screen::SplitBill => Some(&requests::SplitBillWizard),
+            Screen::NewCategoryRule => Some(&categories::NewCategoryRuleWizard),
+            Screen::SetPassword => Some(&password::SetPasswordWizard),
+            Screen::ChangePassword => Some(&password::ChangePasswordWizard),
+            Screen::RotatePassword => Some(&password::RotatePasswordWizard),
+            Screen::RecoverAccount => Some(&password::RecoverAccountWizard),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a screen for browsing rather than entering input.
+    /// Moves between browse screens are recorded on the undo stack.
+    pub fn is_browse(&self) -> bool {
+        matches!(
+            self,
+            Screen::Dashboard | Screen::ViewTransactions | Screen::TransactionDetail
+        )
+    }
+
+    /// Whether this is a form for changing the account, which a view-only
+    /// session does not open.
+    pub fn is_change_form(&self) -> bool {
+        matches!(
+            self,
+            Screen::Deposit
+                | Screen::QuickWithdraw
+                | Screen::Withdraw
+                | Screen::Transfer
+                | Screen::BatchTransfer
+                | Screen::EditAnnotation
+                | Screen::EditBalanceAlert
+                | Screen::EditRoundUp
+                | Screen::NewCategoryRule
+                | Screen::SetPassword
+                | Screen::ChangePassword
+                | Screen::SetPin
+                | Screen::NewPaymentRequest
+                | Screen::SplitBill
+                | Screen::NewCheckout
+                | Screen::CheckoutCode
+                | Screen::IssueOfflineToken
+                | Screen::RedeemOfflineToken
+                | Screen::VaultDeposit
+                | Screen::VaultWithdraw
+                | Screen::NewSweepRule
+                | Screen::NewChild
+                | Screen::NewDelegate
+                | Screen::NewJointMember
+                | Screen::EditAllowance
+                | Screen::EditSpendingCap
+                | Screen::CardTopUp
+                | Screen::BankWithdrawal
+                | Screen::ChequeDeposit
+        )
+    }
+
+    /// Whether keys typed on this screen are a password, PIN or recovery
+    /// code, which a session recording leaves out.
+    pub fn is_credential_input(&self) -> bool {
+        matches!(
+            self,
+            Screen::EnterPassword
+                | Screen::RotatePassword
+                | Screen::RecoverAccount
+                | Screen::SetPassword
+                | Screen::ChangePassword
+                | Screen::SetPin
+                | Screen::EnterPin
+        )
+    }
+}
+
+/// What should happen to the current screen after a key press.
+pub enum Transition {
+    /// Stay on the current screen.
+    Stay,
+    /// Open another screen on top of the current one; going back returns here.
+    Push(Screen),
+    /// Go back to the previous screen.
+    Pop,
+    /// Clear the navigation history and switch to a screen, e.g. after a
+    /// flow completes or the user logs in or out.
+    Reset(Screen),
+    /// Exit the application.
+    Quit,
+}
+
+/// Handles key presses for one or more screens.
+pub trait ScreenHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition>;
+}
+
+/// Gets how many characters the field being typed on the current screen
+/// may hold.
+pub fn input_limit(app: &AppController) -> usize {
+    if let Some(wizard) = app.current_screen.wizard() {
+        return wizard.current_step(app).limit;
+    }
+    match app.current_screen {
+        Screen::Login
+        | Screen::CreateAccount
+        | Screen::Locked
+        | Screen::NewChild
+        | Screen::NewDelegate
+        | Screen::NewJointMember
+        | Screen::EditRoundUp => MAX_USERNAME_INPUT,
+        Screen::Deposit
+        | Screen::Withdraw
+        | Screen::VaultDeposit
+        | Screen::VaultWithdraw
+        | Screen::NewSweepRule
+        | Screen::IssueOfflineToken
+        | Screen::EditBalanceAlert
+        | Screen::EditAllowance
+        | Screen::EditSpendingCap
+        | Screen::CardTopUp
+        | Screen::BankWithdrawal
+        | Screen::ChequeDeposit => MAX_AMOUNT_INPUT,
+        Screen::SetPin | Screen::EnterPin => *PIN_LENGTH.end(),
+        Screen::EnterPassword => MAX_PASSWORD_INPUT,
+        Screen::JumpToDate => MAX_DATE_INPUT,
+        Screen::RedeemOfflineToken => MAX_TOKEN_INPUT,
+        _ => MAX_TEXT_INPUT,
+    }
+}
+
+/// Applies a key press to the shared text input. Returns `true` if the key
+/// was an editing key. Characters past the field's limit are dropped.
+pub fn edit_input(app: &mut AppController, key: KeyCode) -> bool {
+    match key {
+        KeyCode::Char(c) => {
+            if app.input.chars().count() < input_limit(app) {
+                app.input.push(c);
+            }
+            app.reset_input_history();
+            true
+        }
+        KeyCode::Backspace => {
+            app.input.pop();
+            app.reset_input_history();
+            true
+        }
+        _ => false,
+    }
+}
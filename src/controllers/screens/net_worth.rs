@@ -0,0 +1,19 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{ScreenHandler, Transition};
+
+/// Handles the net worth breakdown and chart.
+pub struct NetWorthHandler;
+
+impl ScreenHandler for NetWorthHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,185 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard::{self, Wizard, WizardStep};
+use crate::controllers::screens::{
+    edit_input, Screen, ScreenHandler, Transition, MAX_PASSWORD_INPUT, MAX_USERNAME_INPUT,
+};
+use crate::models::password::{AccountPassword, MIN_PASSWORD_LENGTH};
+
+/// Handles the password step of logging in to an account that has one.
+pub struct EnterPasswordHandler;
+
+impl ScreenHandler for EnterPasswordHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let password = std::mem::take(&mut app.input);
+                if app.login_with_password(&password)? {
+                    return Ok(Transition::Reset(Screen::Dashboard));
+                }
+                if app.password_expired {
+                    return Ok(wizard::start(app, Screen::RotatePassword));
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// The two-step screen for choosing a password: the password, then the
+/// same again to confirm it.
+pub struct SetPasswordWizard;
+
+static SET_PASSWORD_STEPS: [WizardStep; 2] = [
+    WizardStep::new(MAX_PASSWORD_INPUT, validate_new_password),
+    WizardStep::new(MAX_PASSWORD_INPUT, confirm_password),
+];
+
+fn validate_new_password(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    if !AccountPassword::is_valid(input) {
+        app.add_message(format!("A password is at least {} characters.", MIN_PASSWORD_LENGTH));
+        return Ok(None);
+    }
+    Ok(Some(input.to_string()))
+}
+
+/// Checks the password typed again against the one in the step before.
+fn confirm_password(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    if app.wizard_values.last().map(String::as_str) != Some(input) {
+        app.add_message("The passwords do not match.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(input.to_string()))
+}
+
+impl Wizard for SetPasswordWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &SET_PASSWORD_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [password, _] = values.as_slice() {
+            if app.set_password(password)? {
+                return Ok(Transition::Push(Screen::RecoveryCodes));
+            }
+        }
+        Ok(Transition::Pop)
+    }
+}
+
+/// The change password screen in Settings: the current password, then the
+/// new one twice.
+pub struct ChangePasswordWizard;
+
+static CHANGE_PASSWORD_STEPS: [WizardStep; 3] = [
+    WizardStep::new(MAX_PASSWORD_INPUT, validate_current_password),
+    WizardStep::new(MAX_PASSWORD_INPUT, validate_new_password),
+    WizardStep::new(MAX_PASSWORD_INPUT, confirm_password),
+];
+
+fn validate_current_password(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    if !app.check_password(input)? {
+        app.add_message("Wrong password.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(input.to_string()))
+}
+
+impl Wizard for ChangePasswordWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &CHANGE_PASSWORD_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [current, password, _] = values.as_slice() {
+            app.change_password(current, password)?;
+        }
+        Ok(Transition::Pop)
+    }
+}
+
+/// The screen a login goes through when the password is past the rotation
+/// grace period: the new password, twice.
+pub struct RotatePasswordWizard;
+
+impl Wizard for RotatePasswordWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &SET_PASSWORD_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [password, _] = values.as_slice() {
+            if app.rotate_password(password)? {
+                return Ok(Transition::Reset(Screen::Dashboard));
+            }
+        }
+        // Starts over, as the login cannot go on without a new password
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the one-time display of newly issued recovery codes.
+pub struct RecoveryCodesHandler;
+
+impl ScreenHandler for RecoveryCodesHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        if matches!(key, KeyCode::Esc | KeyCode::Enter) {
+            app.recovery_codes.clear();
+            return Ok(Transition::Reset(Screen::Dashboard));
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// The account recovery screen: the username, one of its recovery codes,
+/// and a new password, typed twice.
+pub struct RecoverAccountWizard;
+
+static RECOVER_ACCOUNT_STEPS: [WizardStep; 4] = [
+    WizardStep::new(MAX_USERNAME_INPUT, validate_recovery_username),
+    WizardStep::new(MAX_USERNAME_INPUT, validate_recovery_code),
+    WizardStep::new(MAX_PASSWORD_INPUT, validate_new_password),
+    WizardStep::new(MAX_PASSWORD_INPUT, confirm_password),
+];
+
+fn validate_recovery_username(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let username = input.trim();
+    if !app.has_recovery_codes(username)? {
+        app.add_message("That account has no recovery codes.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(username.to_string()))
+}
+
+fn validate_recovery_code(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let username = app.wizard_values.first().cloned().unwrap_or_default();
+    if !app.is_recovery_code_valid(&username, input)? {
+        app.add_message("That recovery code is not valid.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(input.trim().to_string()))
+}
+
+impl Wizard for RecoverAccountWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &RECOVER_ACCOUNT_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [username, code, password, _] = values.as_slice() {
+            if app.recover_account(username, code, password)? {
+                return Ok(Transition::Reset(Screen::Dashboard));
+            }
+        }
+        Ok(Transition::Pop)
+    }
+}
@@ -0,0 +1,41 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::{AppController, InputField};
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the card top-up, bank withdrawal and cheque deposit screens: the
+/// amount to send through the simulated processor, above the recent payments
+/// and their status.
+pub struct ProcessorHandler;
+
+impl ScreenHandler for ProcessorHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                if let Some(amount) = app.read_amount() {
+                    let input = app.input.clone();
+                    app.remember_input(InputField::Amount, &input)?;
+                    match app.current_screen {
+                        Screen::BankWithdrawal => {
+                            app.withdraw_to_bank(amount)?;
+                        }
+                        Screen::ChequeDeposit => app.deposit_cheque(amount)?,
+                        _ => app.top_up_by_card(amount)?,
+                    }
+                    app.input.clear();
+                }
+            }
+            KeyCode::Up => app.recall_input(InputField::Amount, true)?,
+            KeyCode::Down => app.recall_input(InputField::Amount, false)?,
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,140 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard::{self, Wizard, WizardStep};
+use crate::controllers::screens::{
+    Screen, ScreenHandler, Transition, MAX_AMOUNT_INPUT, MAX_TEXT_INPUT, MAX_USERNAME_INPUT,
+};
+
+/// Handles the list of sent and received payment requests.
+pub struct PaymentRequestsHandler;
+
+impl ScreenHandler for PaymentRequestsHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_request = app.selected_request.saturating_sub(1),
+            KeyCode::Down if app.selected_request + 1 < app.payment_requests.len() => {
+                app.selected_request += 1;
+            }
+            KeyCode::Char('n') => return Ok(wizard::start(app, Screen::NewPaymentRequest)),
+            KeyCode::Char('s') => return Ok(wizard::start(app, Screen::SplitBill)),
+            KeyCode::Char('m') => {
+                app.input.clear();
+                app.checkout_items.clear();
+                return Ok(Transition::Push(Screen::NewCheckout));
+            }
+            KeyCode::Char('c') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::PayCheckout));
+            }
+            KeyCode::Char('p') => {
+                app.pay_selected_request()?;
+            }
+            KeyCode::Char('d') => {
+                app.decline_selected_request()?;
+            }
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// The two-step new request screen: first who should pay, then the amount.
+pub struct NewPaymentRequestWizard;
+
+static NEW_PAYMENT_REQUEST_STEPS: [WizardStep; 2] = [
+    WizardStep::new(MAX_USERNAME_INPUT, validate_payer),
+    WizardStep::new(MAX_AMOUNT_INPUT, |app, _| {
+        Ok(app.read_amount().map(|amount| amount.to_string()))
+    }),
+];
+
+fn validate_payer(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let payer = input.trim();
+    if app.get_current_user() == Some(payer) {
+        app.add_message("You cannot request money from yourself.".to_string());
+        return Ok(None);
+    }
+    if !app.user_exists(payer)? {
+        app.add_message(format!("User '{}' not found.", payer));
+        return Ok(None);
+    }
+    Ok(Some(payer.to_string()))
+}
+
+impl Wizard for NewPaymentRequestWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &NEW_PAYMENT_REQUEST_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        if let [payer, amount] = values.as_slice() {
+            app.request_payment(payer.clone(), amount.parse().unwrap_or_default())?;
+        }
+        Ok(Transition::Pop)
+    }
+}
+
+/// The split bill screen: who to split with, the amount, then a preview of
+/// everyone's share before the requests are sent.
+pub struct SplitBillWizard;
+
+static SPLIT_BILL_STEPS: [WizardStep; 3] = [
+    WizardStep::new(MAX_TEXT_INPUT, validate_people),
+    WizardStep::new(MAX_AMOUNT_INPUT, preview_split),
+    WizardStep::new(0, |_, _| Ok(Some(String::new()))),
+];
+
+/// Splits the names typed, by commas or spaces.
+fn split_names(input: &str) -> Vec<String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn validate_people(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let username = app.get_current_user().unwrap_or_default().to_string();
+    let mut people = Vec::new();
+    for name in split_names(input) {
+        if name == username || people.contains(&name) {
+            continue;
+        }
+        if !app.user_exists(&name)? {
+            app.add_message(format!("User '{}' not found.", name));
+            return Ok(None);
+        }
+        people.push(name);
+    }
+    if people.is_empty() {
+        app.add_message("Name at least one other person to split the bill with.".to_string());
+        return Ok(None);
+    }
+    Ok(Some(people.join(", ")))
+}
+
+fn preview_split(app: &mut AppController, _: &str) -> Result<Option<String>> {
+    let amount = match app.read_amount() {
+        Some(amount) => amount,
+        None => return Ok(None),
+    };
+    let people = split_names(app.wizard_values.first().map(String::as_str).unwrap_or(""));
+    if !app.preview_bill_split(&people, amount) {
+        return Ok(None);
+    }
+    Ok(Some(amount.to_string()))
+}
+
+impl Wizard for SplitBillWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &SPLIT_BILL_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, _values: Vec<String>) -> Result<Transition> {
+        app.send_bill_split()?;
+        Ok(Transition::Pop)
+    }
+}
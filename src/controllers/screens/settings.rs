@@ -0,0 +1,132 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::wizard;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+use crate::models::round_up::RoundUpTarget;
+
+/// Handles the per-user Settings screen.
+pub struct SettingsHandler;
+
+impl ScreenHandler for SettingsHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Char('0') => app.toggle_integrity_mode()?,
+            KeyCode::Char('1') => app.cycle_idle_lock()?,
+            KeyCode::Char('2') => app.toggle_hide_amounts()?,
+            KeyCode::Char('3') => app.export_my_data()?,
+            KeyCode::Char('4') => app.cycle_request_ttl()?,
+            KeyCode::Char('5') => {
+                app.input = app.balance_alert.map(|t| t.to_string()).unwrap_or_default();
+                return Ok(Transition::Push(Screen::EditBalanceAlert));
+            }
+            KeyCode::Char('6') => app.cycle_large_transfer_cap()?,
+            KeyCode::Char('7') => app.toggle_sign_transfers()?,
+            KeyCode::Char('8') => app.cycle_vault_delay()?,
+            KeyCode::Char('r') => app.cycle_retention()?,
+            KeyCode::Char('m') => app.cycle_max_amount()?,
+            KeyCode::Char('l') => app.toggle_leaderboard()?,
+            KeyCode::Char('k') => app.toggle_keychain_sessions()?,
+            KeyCode::Char('p') if app.has_password => {
+                return Ok(wizard::start(app, Screen::ChangePassword));
+            }
+            KeyCode::Char('p') => return Ok(wizard::start(app, Screen::SetPassword)),
+            KeyCode::Char('c') => app.export_charts()?,
+            KeyCode::Char('a') => {
+                app.load_category_rules()?;
+                return Ok(Transition::Push(Screen::CategoryRules));
+            }
+            KeyCode::Char('o') => app.cycle_overdraft_limit()?,
+            KeyCode::Char('u') => app.cycle_display_currency()?,
+            KeyCode::Char('n') => app.toggle_cash_breakdown()?,
+            KeyCode::Char('d') => {
+                app.selected_delegate = 0;
+                app.load_delegates()?;
+                return Ok(Transition::Push(Screen::Delegates));
+            }
+            KeyCode::Char('j') => {
+                app.selected_joint_member = 0;
+                app.load_joint_members()?;
+                return Ok(Transition::Push(Screen::JointMembers));
+            }
+            KeyCode::Char('9') => {
+                app.input = match &app.round_up_to {
+                    Some(RoundUpTarget::Vault) => "vault".to_string(),
+                    Some(RoundUpTarget::Account(account)) => account.clone(),
+                    None => String::new(),
+                };
+                return Ok(Transition::Push(Screen::EditRoundUp));
+            }
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles editing the low balance alert threshold. An empty value turns the alert off.
+pub struct BalanceAlertHandler;
+
+impl ScreenHandler for BalanceAlertHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter if app.input.trim().is_empty() => {
+                app.set_balance_alert(None)?;
+                app.add_message("Low balance alert turned off.".to_string());
+            }
+            KeyCode::Enter => match app.input.trim().parse::<f64>() {
+                Ok(threshold) if threshold >= 0.0 && threshold.is_finite() => {
+                    app.set_balance_alert(Some(threshold))?;
+                    app.add_message(format!(
+                        "You will be warned when your balance drops below {}",
+                        app.format_amount(threshold)
+                    ));
+                }
+                _ => {
+                    app.add_message("Invalid amount. Please enter a positive number.".to_string());
+                    return Ok(Transition::Stay);
+                }
+            },
+            KeyCode::Esc => {}
+            _ => {
+                edit_input(app, key);
+                return Ok(Transition::Stay);
+            }
+        }
+        app.input.clear();
+        Ok(Transition::Pop)
+    }
+}
+
+/// Handles choosing where payment round-ups go. An empty value turns them off.
+pub struct RoundUpHandler;
+
+impl ScreenHandler for RoundUpHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let target = app.input.trim().to_string();
+                if !app.set_round_up_target(&target)? {
+                    return Ok(Transition::Stay);
+                }
+                app.add_message(match &app.round_up_to {
+                    Some(RoundUpTarget::Vault) => {
+                        "Payments will be rounded up into your vault.".to_string()
+                    }
+                    Some(RoundUpTarget::Account(account)) => {
+                        format!("Payments will be rounded up and the change sent to {}.", account)
+                    }
+                    None => "Round-ups turned off.".to_string(),
+                });
+            }
+            KeyCode::Esc => {}
+            _ => {
+                edit_input(app, key);
+                return Ok(Transition::Stay);
+            }
+        }
+        app.input.clear();
+        Ok(Transition::Pop)
+    }
+}
@@ -0,0 +1,85 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the list of accounts remembered on this terminal.
+pub struct AccountSwitcherHandler;
+
+impl ScreenHandler for AccountSwitcherHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_account = app.selected_account.saturating_sub(1),
+            KeyCode::Down if app.selected_account + 1 < app.remembered_accounts.len() => {
+                app.selected_account += 1;
+            }
+            KeyCode::Enter if app.selected_account < app.remembered_accounts.len() => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::EnterPin));
+            }
+            KeyCode::Char('a') => {
+                let remembered = app.remember_current_account()?;
+                if !remembered {
+                    app.input.clear();
+                    return Ok(Transition::Push(Screen::SetPin));
+                }
+            }
+            KeyCode::Char('d') => app.forget_selected_account()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles choosing a PIN for the current account before it is remembered.
+pub struct SetPinHandler;
+
+impl ScreenHandler for SetPinHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let pin = std::mem::take(&mut app.input);
+                if app.set_pin(&pin)? {
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles entering the PIN of the remembered account being switched to.
+pub struct EnterPinHandler;
+
+impl ScreenHandler for EnterPinHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let pin = std::mem::take(&mut app.input);
+                if app.switch_to_selected_account(&pin)? {
+                    return Ok(Transition::Reset(Screen::Dashboard));
+                }
+                if app.selected_account >= app.remembered_accounts.len() {
+                    return Ok(Transition::Pop);
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
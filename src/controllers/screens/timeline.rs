@@ -0,0 +1,23 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{ScreenHandler, Transition};
+
+/// Handles the activity timeline.
+pub struct TimelineHandler;
+
+impl ScreenHandler for TimelineHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_activity = app.selected_activity.saturating_sub(1),
+            KeyCode::Down if app.selected_activity + 1 < app.timeline.len() => {
+                app.selected_activity += 1;
+            }
+            KeyCode::Char('h') => app.toggle_hide_amounts()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
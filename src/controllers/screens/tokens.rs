@@ -0,0 +1,83 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the offline token menu.
+pub struct OfflineTokensHandler;
+
+impl ScreenHandler for OfflineTokensHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        Ok(match key {
+            KeyCode::Char('1') => {
+                app.input.clear();
+                app.issued_token = None;
+                Transition::Push(Screen::IssueOfflineToken)
+            }
+            KeyCode::Char('2') => {
+                app.input.clear();
+                Transition::Push(Screen::RedeemOfflineToken)
+            }
+            KeyCode::Esc => Transition::Pop,
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Handles issuing a token: first the amount, then the token is shown until
+/// the user leaves the screen.
+pub struct IssueOfflineTokenHandler;
+
+impl ScreenHandler for IssueOfflineTokenHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        if app.issued_token.is_some() {
+            if matches!(key, KeyCode::Esc | KeyCode::Enter) {
+                app.issued_token = None;
+                return Ok(Transition::Reset(Screen::Dashboard));
+            }
+            return Ok(Transition::Stay);
+        }
+        match key {
+            KeyCode::Enter => match app.read_amount() {
+                Some(amount) => {
+                    app.input.clear();
+                    app.issue_offline_token(amount)?;
+                }
+                None => app.input.clear(),
+            },
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles pasting a token to redeem.
+pub struct RedeemOfflineTokenHandler;
+
+impl ScreenHandler for RedeemOfflineTokenHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => {
+                let token = std::mem::take(&mut app.input);
+                if app.redeem_offline_token(&token)? {
+                    return Ok(Transition::Reset(Screen::Dashboard));
+                }
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,241 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::{AppController, InputField};
+use crate::controllers::command::Command;
+use crate::controllers::screens::wizard::{StepKey, Wizard, WizardStep};
+use crate::controllers::screens::{
+    edit_input, Screen, ScreenHandler, Transition, MAX_AMOUNT_INPUT, MAX_TEXT_INPUT,
+    MAX_USERNAME_INPUT,
+};
+
+/// Sends a transfer, holds it for a co-owner's approval if it is a large one
+/// from a joint account, or holds it for a cooling period if it is above the
+/// user's large transfer cap.
+fn send_transfer(
+    app: &mut AppController,
+    recipient: String,
+    amount: f64,
+    note: Option<String>,
+) -> Result<()> {
+    if app.needs_joint_approval(amount)? {
+        app.hold_for_approval(recipient, amount, note.as_deref())?;
+    } else if app.is_large_transfer(amount) {
+        app.hold_large_transfer(recipient, amount, note.as_deref())?;
+    } else if let Some(transfer_id) = app.transfer(recipient.clone(), amount, note.as_deref())? {
+        app.record(Command::Transfer {
+            recipient,
+            amount,
+            note,
+            transfer_id,
+        });
+    }
+    Ok(())
+}
+
+/// The three-step Transfer screen: the recipient, the amount, then an
+/// optional note for the recipient.
+pub struct TransferWizard;
+
+static TRANSFER_STEPS: [WizardStep; 3] = [
+    WizardStep {
+        limit: MAX_USERNAME_INPUT,
+        validate: validate_recipient,
+        on_key: Some(recipient_key),
+        on_return: Some(AppController::update_recipient_suggestions),
+    },
+    WizardStep {
+        limit: MAX_AMOUNT_INPUT,
+        validate: validate_amount,
+        on_key: Some(amount_key),
+        on_return: None,
+    },
+    WizardStep::new(MAX_TEXT_INPUT, |_, note| Ok(Some(note.trim().to_string()))),
+];
+
+/// Takes the highlighted suggestion if there is one, otherwise the typed name.
+fn validate_recipient(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    let recipient = app
+        .recipient_suggestions
+        .get(app.selected_suggestion)
+        .cloned()
+        .unwrap_or_else(|| input.trim().to_string());
+    if app.get_current_user() == Some(recipient.as_str()) {
+        app.add_message("You cannot transfer to yourself.".to_string());
+        return Ok(None);
+    }
+    if !app.user_exists(&recipient)? {
+        app.add_message(format!("Recipient '{}' not found.", recipient));
+        return Ok(None);
+    }
+    app.remember_input(InputField::Recipient, &recipient)?;
+    app.recipient_suggestions.clear();
+    Ok(Some(recipient))
+}
+
+/// Moves through suggestions or history, picks a recent recipient by number
+/// and refreshes the suggestions as the name is typed.
+fn recipient_key(app: &mut AppController, key: KeyCode) -> Result<StepKey> {
+    match key {
+        KeyCode::Up if !app.recipient_suggestions.is_empty() => {
+            app.selected_suggestion = app.selected_suggestion.saturating_sub(1);
+        }
+        KeyCode::Down if !app.recipient_suggestions.is_empty() => {
+            if app.selected_suggestion + 1 < app.recipient_suggestions.len() {
+                app.selected_suggestion += 1;
+            }
+        }
+        KeyCode::Up => app.recall_input(InputField::Recipient, true)?,
+        KeyCode::Down => app.recall_input(InputField::Recipient, false)?,
+        KeyCode::Char(c) if app.input.is_empty() && c.is_ascii_digit() => {
+            let pick = c.to_digit(10).unwrap_or(0) as usize;
+            if let Some(recipient) = pick
+                .checked_sub(1)
+                .and_then(|i| app.recent_recipients.get(i))
+            {
+                app.input = recipient.clone();
+                return Ok(StepKey::Submit);
+            }
+            app.input.push(c);
+            app.update_recipient_suggestions()?;
+        }
+        _ => {
+            if !edit_input(app, key) {
+                return Ok(StepKey::Unused);
+            }
+            app.update_recipient_suggestions()?;
+        }
+    }
+    Ok(StepKey::Used)
+}
+
+fn validate_amount(app: &mut AppController, input: &str) -> Result<Option<String>> {
+    match app.read_amount() {
+        Some(amount) => {
+            app.remember_input(InputField::Amount, input)?;
+            Ok(Some(amount.to_string()))
+        }
+        None => Ok(None),
+    }
+}
+
+fn amount_key(app: &mut AppController, key: KeyCode) -> Result<StepKey> {
+    match key {
+        KeyCode::Up => app.recall_input(InputField::Amount, true)?,
+        KeyCode::Down => app.recall_input(InputField::Amount, false)?,
+        _ => return Ok(StepKey::Unused),
+    }
+    Ok(StepKey::Used)
+}
+
+impl Wizard for TransferWizard {
+    fn steps(&self) -> &'static [WizardStep] {
+        &TRANSFER_STEPS
+    }
+
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition> {
+        let [recipient, amount, note]: [String; 3] = match values.try_into() {
+            Ok(values) => values,
+            Err(_) => return Ok(Transition::Pop),
+        };
+        let amount = amount.parse().unwrap_or_default();
+        let note = Some(note).filter(|note| !note.is_empty());
+        if app.is_duplicate_transfer(&recipient, amount)? {
+            app.pending_transfer = Some((recipient, amount, note));
+            return Ok(Transition::Push(Screen::ConfirmDuplicateTransfer));
+        }
+        send_transfer(app, recipient, amount, note)?;
+        Ok(Transition::Reset(Screen::Dashboard))
+    }
+}
+
+/// Asks for confirmation before repeating a transfer made moments ago.
+pub struct ConfirmDuplicateTransferHandler;
+
+impl ScreenHandler for ConfirmDuplicateTransferHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        Ok(match key {
+            KeyCode::Char('y') => {
+                if let Some((recipient, amount, note)) = app.pending_transfer.take() {
+                    send_transfer(app, recipient, amount, note)?;
+                }
+                Transition::Reset(Screen::Dashboard)
+            }
+            KeyCode::Char('n') => {
+                app.pending_transfer = None;
+                app.add_message("Transfer cancelled.".to_string());
+                Transition::Reset(Screen::Dashboard)
+            }
+            KeyCode::Esc => {
+                // Go back to the note entry with the transfer details restored.
+                if let Some((recipient, amount, note)) = app.pending_transfer.take() {
+                    app.wizard_values = vec![recipient, amount.to_string()];
+                    app.input = note.unwrap_or_default();
+                }
+                Transition::Pop
+            }
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Handles a transfer to several people. Each Enter adds the typed
+/// recipient and amount; Enter on an empty line sends them all at once.
+pub struct BatchTransferHandler;
+
+impl ScreenHandler for BatchTransferHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter if app.input.trim().is_empty() => {
+                let transfers = app.batch_transfers.clone();
+                if app.transfer_many(&transfers)? {
+                    app.batch_transfers.clear();
+                    return Ok(Transition::Reset(Screen::Dashboard));
+                }
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut app.input);
+                if !app.add_batch_transfer(&line)? {
+                    app.input = line;
+                }
+            }
+            KeyCode::Delete => {
+                app.batch_transfers.pop();
+            }
+            KeyCode::Esc => {
+                app.input.clear();
+                app.batch_transfers.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the list of held large transfers waiting for confirmation.
+pub struct PendingTransfersHandler;
+
+impl ScreenHandler for PendingTransfersHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => {
+                app.selected_pending_transfer = app.selected_pending_transfer.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_pending_transfer + 1 < app.pending_transfers.len() => {
+                app.selected_pending_transfer += 1;
+            }
+            KeyCode::Enter => {
+                if let Some(command) = app.confirm_pending_transfer()? {
+                    app.record(command);
+                }
+            }
+            KeyCode::Char('x') => app.cancel_pending_transfer()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,120 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// Handles the vault overview and its list of withdrawals on their way out.
+pub struct VaultHandler;
+
+impl ScreenHandler for VaultHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => {
+                app.selected_vault_withdrawal = app.selected_vault_withdrawal.saturating_sub(1);
+            }
+            KeyCode::Down if app.selected_vault_withdrawal + 1 < app.vault_withdrawals.len() => {
+                app.selected_vault_withdrawal += 1;
+            }
+            KeyCode::Char('d') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::VaultDeposit));
+            }
+            KeyCode::Char('w') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::VaultWithdraw));
+            }
+            KeyCode::Char('x') => app.cancel_vault_withdrawal()?,
+            KeyCode::Char('s') => {
+                app.load_sweep_rules()?;
+                return Ok(Transition::Push(Screen::SweepRules));
+            }
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the amount for moving money into or out of the vault.
+pub struct VaultAmountHandler;
+
+impl ScreenHandler for VaultAmountHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => match app.read_amount() {
+                Some(amount) => {
+                    app.input.clear();
+                    let done = if app.current_screen == Screen::VaultDeposit {
+                        app.move_to_vault(amount)?
+                    } else {
+                        app.request_vault_withdrawal(amount)?
+                    };
+                    if done {
+                        return Ok(Transition::Pop);
+                    }
+                }
+                None => app.input.clear(),
+            },
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the list of rules sweeping the balance into the vault each month.
+pub struct SweepRulesHandler;
+
+impl ScreenHandler for SweepRulesHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Up => app.selected_sweep_rule = app.selected_sweep_rule.saturating_sub(1),
+            KeyCode::Down if app.selected_sweep_rule + 1 < app.sweep_rules.len() => {
+                app.selected_sweep_rule += 1;
+            }
+            KeyCode::Char('n') => {
+                app.input.clear();
+                return Ok(Transition::Push(Screen::NewSweepRule));
+            }
+            KeyCode::Char('d') | KeyCode::Delete => app.delete_selected_sweep_rule()?,
+            KeyCode::Esc => return Ok(Transition::Pop),
+            _ => {}
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Handles the amount a new sweep rule keeps in the balance.
+pub struct NewSweepRuleHandler;
+
+impl ScreenHandler for NewSweepRuleHandler {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        match key {
+            KeyCode::Enter => match app.input.trim().parse::<f64>() {
+                Ok(keep) if keep >= 0.0 && keep.is_finite() => {
+                    app.input.clear();
+                    app.add_sweep_rule(keep)?;
+                    return Ok(Transition::Pop);
+                }
+                _ => {
+                    app.add_message("Invalid amount. Please enter a positive number.".to_string());
+                    app.input.clear();
+                }
+            },
+            KeyCode::Esc => {
+                app.input.clear();
+                return Ok(Transition::Pop);
+            }
+            _ => {
+                edit_input(app, key);
+            }
+        }
+        Ok(Transition::Stay)
+    }
+}
@@ -0,0 +1,112 @@
+use crossterm::event::KeyCode;
+use rusqlite::Result;
+
+use crate::controllers::app_controller::AppController;
+use crate::controllers::screens::{edit_input, Screen, ScreenHandler, Transition};
+
+/// What a step's own key handler did with a key press.
+pub enum StepKey {
+    /// The key was not for this step; the wizard handles it as usual.
+    Unused,
+    /// The key was handled.
+    Used,
+    /// The key picked a value, now in the input, so the step is submitted.
+    Submit,
+}
+
+/// One step of a wizard, asking for a single value.
+pub struct WizardStep {
+    /// Longest value that can be typed.
+    pub limit: usize,
+    /// Checks the typed value when Enter is pressed. Returns the value to
+    /// keep, or `None` after telling the user what is wrong, which keeps the
+    /// wizard on this step.
+    pub validate: fn(&mut AppController, &str) -> Result<Option<String>>,
+    /// Handles keys before the wizard does, e.g. to pick a suggestion.
+    pub on_key: Option<fn(&mut AppController, KeyCode) -> Result<StepKey>>,
+    /// Runs whenever the step is shown again after going back to it.
+    pub on_return: Option<fn(&mut AppController) -> Result<()>>,
+}
+
+impl WizardStep {
+    /// A step that only needs its value checked.
+    pub const fn new(
+        limit: usize,
+        validate: fn(&mut AppController, &str) -> Result<Option<String>>,
+    ) -> Self {
+        WizardStep {
+            limit,
+            validate,
+            on_key: None,
+            on_return: None,
+        }
+    }
+}
+
+/// A multi-step form. Steps are filled in in order, each value is checked
+/// before moving on, and Esc goes back a step with its value restored for
+/// editing, or leaves the screen from the first step. Values of completed
+/// steps are kept in `AppController::wizard_values`.
+///
+/// Every wizard is also a `ScreenHandler`, so a screen only has to name it.
+pub trait Wizard {
+    fn steps(&self) -> &'static [WizardStep];
+
+    /// Completes the flow with the value of every step, in order.
+    fn finish(&self, app: &mut AppController, values: Vec<String>) -> Result<Transition>;
+
+    /// Gets the step being filled in.
+    fn current_step(&self, app: &AppController) -> &'static WizardStep {
+        let steps = self.steps();
+        &steps[app.wizard_values.len().min(steps.len() - 1)]
+    }
+}
+
+impl<W: Wizard> ScreenHandler for W {
+    fn handle_key(&self, app: &mut AppController, key: KeyCode) -> Result<Transition> {
+        let step = self.current_step(app);
+        let submit = match step.on_key {
+            Some(on_key) if key != KeyCode::Esc => match on_key(app, key)? {
+                StepKey::Unused => key == KeyCode::Enter,
+                StepKey::Used => return Ok(Transition::Stay),
+                StepKey::Submit => true,
+            },
+            _ => key == KeyCode::Enter,
+        };
+
+        if submit {
+            let input = app.input.clone();
+            if let Some(value) = (step.validate)(app, &input)? {
+                app.wizard_values.push(value);
+                app.input.clear();
+                if app.wizard_values.len() == self.steps().len() {
+                    let values = std::mem::take(&mut app.wizard_values);
+                    return self.finish(app, values);
+                }
+            }
+        } else if key == KeyCode::Esc {
+            match app.wizard_values.pop() {
+                Some(value) => {
+                    app.input = value;
+                    if let Some(on_return) = self.current_step(app).on_return {
+                        on_return(app)?;
+                    }
+                }
+                None => {
+                    app.input.clear();
+                    return Ok(Transition::Pop);
+                }
+            }
+        } else {
+            edit_input(app, key);
+        }
+        Ok(Transition::Stay)
+    }
+}
+
+/// Opens a wizard screen at its first step.
+pub fn start(app: &mut AppController, screen: Screen) -> Transition {
+    app.wizard_values.clear();
+    app.input.clear();
+    Transition::Push(screen)
+}
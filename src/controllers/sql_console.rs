@@ -0,0 +1,199 @@
+use crossterm::event::KeyCode;
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, Result};
+
+use crate::models::database;
+
+/// Most rows a query shows; the rest are counted but not kept.
+pub const SQL_CONSOLE_MAX_ROWS: usize = 500;
+
+/// Statements the console runs, by their first keyword. Anything else,
+/// such as `VACUUM INTO` or `ATTACH`, could write files even through a
+/// read-only connection.
+const ALLOWED_KEYWORDS: [&str; 5] = ["SELECT", "WITH", "EXPLAIN", "VALUES", "PRAGMA"];
+
+/// Pragmas the console runs. Those that also set a value, such as
+/// `journal_mode`, are only run without one; others, such as `optimize`,
+/// write even without one.
+const READ_ONLY_PRAGMAS: &[&str] = &[
+    "application_id",
+    "collation_list",
+    "compile_options",
+    "data_version",
+    "database_list",
+    "encoding",
+    "foreign_key_check",
+    "foreign_key_list",
+    "foreign_keys",
+    "freelist_count",
+    "function_list",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "journal_mode",
+    "module_list",
+    "page_count",
+    "page_size",
+    "pragma_list",
+    "quick_check",
+    "schema_version",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+    "user_version",
+];
+
+/// Of those, the pragmas whose argument names a table or index, or limits
+/// a check, rather than setting a value.
+const PRAGMAS_WITH_ARGUMENT: &[&str] = &[
+    "foreign_key_check",
+    "foreign_key_list",
+    "index_info",
+    "index_list",
+    "index_xinfo",
+    "integrity_check",
+    "quick_check",
+    "table_info",
+    "table_list",
+    "table_xinfo",
+];
+
+/// Read-only SQL prompt against the wallet database, for debugging demo
+/// data. Queries go through a read-only connection, and only statements that
+/// read are accepted, so nothing typed here can change the wallet.
+pub struct SqlConsole {
+    pub input: String,
+    /// The query the results below are for.
+    pub query: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Rows the query returned beyond `SQL_CONSOLE_MAX_ROWS`.
+    pub rows_hidden: usize,
+    pub error: Option<String>,
+    /// First row shown, for scrolling through long results.
+    pub scroll: usize,
+    conn: Connection,
+}
+
+impl SqlConsole {
+    pub fn new() -> Result<Self> {
+        Ok(SqlConsole {
+            input: String::new(),
+            query: String::new(),
+            columns: Vec::new(),
+            rows: Vec::new(),
+            rows_hidden: 0,
+            error: None,
+            scroll: 0,
+            conn: database::open_read_only(database::path())?,
+        })
+    }
+
+    /// Applies a key press. Returns `false` when the console should close.
+    pub fn handle_key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Esc => return false,
+            KeyCode::Enter => self.run(),
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down if self.scroll + 1 < self.rows.len() => self.scroll += 1,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => {
+                self.scroll = (self.scroll + 10).min(self.rows.len().saturating_sub(1))
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Runs the typed query, replacing the results shown. A blocked or
+    /// failing query leaves an error instead.
+    pub fn run(&mut self) {
+        let query = self.input.trim().trim_end_matches(';').to_string();
+        if query.is_empty() {
+            return;
+        }
+        self.columns.clear();
+        self.rows.clear();
+        self.rows_hidden = 0;
+        self.scroll = 0;
+        self.error = Self::check(&query).err().or_else(|| self.query_rows(&query).err());
+        self.query = query;
+        self.input.clear();
+    }
+
+    /// Refuses statements that are not on the allowed list, and pragmas
+    /// that are not known to only read.
+    fn check(query: &str) -> std::result::Result<(), String> {
+        let keyword = query
+            .split(|c: char| !c.is_ascii_alphabetic())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        if !ALLOWED_KEYWORDS.contains(&keyword.as_str()) {
+            return Err(format!("Blocked: only {} are allowed", ALLOWED_KEYWORDS.join(", ")));
+        }
+        if keyword == "PRAGMA" {
+            return Self::check_pragma(&query[keyword.len()..]);
+        }
+        Ok(())
+    }
+
+    /// Accepts `name`, `schema.name` and, for pragmas that take one,
+    /// `name(argument)`, for read-only pragmas only.
+    fn check_pragma(pragma: &str) -> std::result::Result<(), String> {
+        let pragma = pragma.trim_start();
+        let pragma = match pragma.split_once('.') {
+            Some((schema, rest)) if schema.chars().all(|c| c.is_alphanumeric() || c == '_') => rest,
+            _ => pragma,
+        };
+        let name_end = pragma
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(pragma.len());
+        let name = pragma[..name_end].to_ascii_lowercase();
+        let argument = pragma[name_end..].trim();
+        if !READ_ONLY_PRAGMAS.contains(&name.as_str()) {
+            return Err(format!("Blocked: {} is not a pragma that only reads", name));
+        }
+        let takes_argument = PRAGMAS_WITH_ARGUMENT.contains(&name.as_str());
+        let in_parentheses = argument.starts_with('(') && argument.ends_with(')');
+        let reads = argument.is_empty() || (takes_argument && in_parentheses);
+        if !reads {
+            return Err(format!("Blocked: {} can only be read", name));
+        }
+        Ok(())
+    }
+
+    fn query_rows(&mut self, query: &str) -> std::result::Result<(), String> {
+        let mut stmt = self.conn.prepare(query).map_err(|err| err.to_string())?;
+        self.columns = stmt.column_names().iter().map(|name| name.to_string()).collect();
+        let column_count = self.columns.len();
+        let mut rows = stmt.query([]).map_err(|err| err.to_string())?;
+        while let Some(row) = rows.next().map_err(|err| err.to_string())? {
+            if self.rows.len() == SQL_CONSOLE_MAX_ROWS {
+                self.rows_hidden += 1;
+                continue;
+            }
+            let values = (0..column_count)
+                .map(|i| row.get_ref(i).map(Self::display_value))
+                .collect::<Result<Vec<_>>>()
+                .map_err(|err| err.to_string())?;
+            self.rows.push(values);
+        }
+        Ok(())
+    }
+
+    fn display_value(value: ValueRef) -> String {
+        match value {
+            ValueRef::Null => "NULL".to_string(),
+            ValueRef::Integer(number) => number.to_string(),
+            ValueRef::Real(number) => number.to_string(),
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+            ValueRef::Blob(bytes) => format!("<{} bytes>", bytes.len()),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use rusqlite::Result;
+use std::collections::HashMap;
+
+use crate::controllers::app_controller::AppController;
+
+/// Wallet operations the UI depends on, independent of how they are stored.
+/// `AppController` implements it on top of SQLite; tests or alternative
+/// backends can provide their own implementation. Money going out through
+/// it is subject to the same limits as everywhere else in the app.
+pub trait WalletService {
+    /// Logs in an existing user. Returns `false` if the user does not exist.
+    fn login(&mut self, username: String) -> Result<bool>;
+
+    /// Logs out the current user.
+    fn logout(&mut self);
+
+    /// Deposits money into the current user's account.
+    fn deposit(&mut self, amount: f64) -> Result<()>;
+
+    /// Withdraws money from the current user's account. Returns `false` if
+    /// a spending limit stops it or the balance does not cover the amount.
+    fn withdraw(&mut self, amount: f64) -> Result<bool>;
+
+    /// Transfers money from the current user to another user.
+    fn transfer(&mut self, recipient: String, amount: f64) -> Result<bool>;
+
+    /// Gets the transaction history of the current user, newest first.
+    fn history(&self) -> Result<Vec<HashMap<String, String>>>;
+
+    /// Gets the balance of the current user.
+    fn balance(&self) -> Result<f64>;
+
+    /// Gets the balance of the current user less their active holds.
+    fn available_balance(&self) -> Result<f64>;
+
+    /// Reserves part of the current user's balance for a payment that is
+    /// settled later. Returns the hold id, or `None` if a spending limit
+    /// stops it or the available balance does not cover it.
+    fn hold(&mut self, amount: f64, description: &str) -> Result<Option<i64>>;
+
+    /// Takes the money of an active hold. Returns `false` if the hold is not
+    /// active or not the current user's.
+    fn capture(&mut self, hold_id: i64) -> Result<bool>;
+
+    /// Cancels an active hold, making its money available again.
+    fn release(&mut self, hold_id: i64) -> Result<bool>;
+
+    /// Gets the username of the logged in user, if any.
+    fn current_user(&self) -> Option<&str>;
+}
+
+impl WalletService for AppController {
+    fn login(&mut self, username: String) -> Result<bool> {
+        AppController::login(self, username)
+    }
+
+    fn logout(&mut self) {
+        AppController::logout(self)
+    }
+
+    fn deposit(&mut self, amount: f64) -> Result<()> {
+        AppController::deposit(self, amount)
+    }
+
+    fn withdraw(&mut self, amount: f64) -> Result<bool> {
+        if !self.may_spend(amount)? {
+            return Ok(false);
+        }
+        if !self.can_withdraw(amount)? {
+            let available = self.get_available_balance()?;
+            self.add_message(format!(
+                "Insufficient funds. Available balance: {}",
+                self.format_amount(available)
+            ));
+            return Ok(false);
+        }
+        AppController::withdraw(self, amount)?;
+        Ok(true)
+    }
+
+    fn transfer(&mut self, recipient: String, amount: f64) -> Result<bool> {
+        Ok(AppController::transfer(self, recipient, amount, None)?.is_some())
+    }
+
+    fn history(&self) -> Result<Vec<HashMap<String, String>>> {
+        self.get_transactions()
+    }
+
+    fn balance(&self) -> Result<f64> {
+        self.get_balance()
+    }
+
+    fn available_balance(&self) -> Result<f64> {
+        self.get_available_balance()
+    }
+
+    fn hold(&mut self, amount: f64, description: &str) -> Result<Option<i64>> {
+        if !self.may_spend(amount)? {
+            return Ok(None);
+        }
+        AppController::hold(self, amount, description)
+    }
+
+    fn capture(&mut self, hold_id: i64) -> Result<bool> {
+        AppController::capture(self, hold_id)
+    }
+
+    fn release(&mut self, hold_id: i64) -> Result<bool> {
+        AppController::release(self, hold_id)
+    }
+
+    fn current_user(&self) -> Option<&str> {
+        self.get_current_user()
+    }
+}
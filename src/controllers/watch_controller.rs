@@ -0,0 +1,79 @@
+use chrono::{Local, NaiveDateTime};
+use rusqlite::{Connection, Result};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::models::{database, transaction::Transaction, user::User};
+
+/// How often the watch screen reloads the wallet.
+pub const WATCH_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of incoming transactions the watch screen lists.
+const WATCH_INCOMING_LIMIT: usize = 15;
+
+/// Live, read-only view of one user's wallet for a second monitor or kiosk.
+/// It reads through a read-only connection, so nothing on this screen can
+/// change the wallet.
+pub struct WatchController {
+    pub username: String,
+    pub balance: f64,
+    pub incoming: Vec<HashMap<String, String>>,
+    /// Incoming transactions that arrived after watching started.
+    pub new_ids: HashSet<String>,
+    pub refreshed_at: NaiveDateTime,
+    seen_ids: Option<HashSet<String>>,
+    last_refresh: Instant,
+    conn: Connection,
+}
+
+impl WatchController {
+    /// Starts watching `username`. Returns `None` if there is no such user.
+    pub fn new(username: &str) -> Result<Option<Self>> {
+        let conn = database::open_read_only(database::path())?;
+        if User::get(&conn, username)?.is_none() {
+            return Ok(None);
+        }
+        let mut watch = WatchController {
+            username: username.to_string(),
+            balance: 0.0,
+            incoming: Vec::new(),
+            new_ids: HashSet::new(),
+            refreshed_at: Local::now().naive_local(),
+            seen_ids: None,
+            last_refresh: Instant::now(),
+            conn,
+        };
+        watch.refresh()?;
+        Ok(Some(watch))
+    }
+
+    /// Reloads the wallet once the refresh interval has passed.
+    pub fn tick(&mut self) -> Result<()> {
+        if self.last_refresh.elapsed() >= WATCH_REFRESH_INTERVAL {
+            self.refresh()?;
+        }
+        Ok(())
+    }
+
+    /// Reloads the balance and incoming transactions.
+    pub fn refresh(&mut self) -> Result<()> {
+        self.last_refresh = Instant::now();
+        self.refreshed_at = Local::now().naive_local();
+        self.balance = User::get(&self.conn, &self.username)?.map_or(0.0, |u| u.balance);
+        self.incoming =
+            Transaction::get_incoming(&self.conn, &self.username, WATCH_INCOMING_LIMIT)?;
+
+        let ids = self.incoming.iter().filter_map(|t| t.get("id").cloned());
+        match &mut self.seen_ids {
+            Some(seen) => {
+                for id in ids {
+                    if seen.insert(id.clone()) {
+                        self.new_ids.insert(id);
+                    }
+                }
+            }
+            None => self.seen_ids = Some(ids.collect()),
+        }
+        Ok(())
+    }
+}
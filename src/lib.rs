@@ -0,0 +1,6 @@
+//! The e-wallet's models, views and controllers. The `ewallet_demo` binary
+//! runs them as a terminal app; the fuzz targets drive them directly.
+
+pub mod models;
+pub mod views;
+pub mod controllers;
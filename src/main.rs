@@ -1,47 +1,333 @@
-mod models;
-mod views;
-mod controllers;
-
+use std::collections::VecDeque;
 use std::error::Error;
-use std::io;
+use std::io::{self, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use tui::{backend::CrosstermBackend, Terminal};
+use tui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    Terminal,
+};
+
+use ewallet_demo::controllers::{
+    app_controller::AppController,
+    daemon::Daemon,
+    events::AUDIT_LOG_PATH,
+    replay::{Recording, SessionRecorder, SessionStart},
+    sql_console::SqlConsole,
+    watch_controller::WatchController,
+};
+use ewallet_demo::models::{
+    archive::Archive,
+    clock::VirtualClock,
+    audit_export::AuditExport,
+    backup_diff::BackupDiff,
+    database,
+    doctor::{Doctor, Severity},
+    environment::{Environment, LogLevel, ENVIRONMENTS_PATH, ENV_VAR},
+    export_log::{ExportLog, EXPORT_LOG_DIR},
+    key_provider::{self, WalletKeySecret},
+    ndjson::TransactionRecords,
+    offline_token::OfflineToken,
+    outbox,
+    profile::Profile,
+    retention::{Retention, Rollup},
+    rules::RULES_PATH,
+    settings::UserSettings,
+    user::User,
+    user_data::UserData,
+};
+use ewallet_demo::views::{format, picker, sql_console, ui, watch};
 
-use controllers::app_controller::AppController;
-use views::ui;
+type CrosstermTerminal = Terminal<CrosstermBackend<io::Stdout>>;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // Set up terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let started_in = std::env::current_dir()?;
+    let mut profile = None;
+    let mut environment_name = std::env::var(ENV_VAR).ok();
+    let mut record = None;
+    loop {
+        match args.first().map(String::as_str) {
+            Some("--profile") => profile = Some(args.get(1).ok_or(USAGE)?.clone()),
+            Some("--env") => environment_name = Some(args.get(1).ok_or(USAGE)?.clone()),
+            Some("--record") => record = Some(started_in.join(args.get(1).ok_or(USAGE)?)),
+            _ => break,
+        }
+        args.drain(..2);
+    }
+
+    // The environment is read from where the app was started, before
+    // moving into a profile
+    let environment = match environment_name {
+        Some(name) => Environment::load(ENVIRONMENTS_PATH, &name)?,
+        None => Environment::default(),
+    };
+    database::use_path(&environment.database);
+
+    // Everything below runs inside the chosen profile. Without --profile the
+    // interactive app asks when there is more than one.
+    if let Some(name) = profile {
+        Profile::activate(&name).map_err(|err| format!("cannot open profile {}: {}", name, err))?;
+    } else if args.is_empty() {
+        let profiles = Profile::list()?;
+        if profiles.len() > 1 {
+            match pick("Choose a Profile (Enter: open, Esc: quit)", &profiles)? {
+                Some(i) => Profile::activate(&profiles[i])?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    // The doctor reports on a damaged database instead of offering a restore
+    if args.first().map(String::as_str) == Some("doctor") {
+        return run_doctor();
+    }
+
+    // A replay runs on its own copy of the database, never the wallet's own
+    if args.first().map(String::as_str) == Some("--replay") {
+        return run_replay(&started_in.join(args.get(1).ok_or(USAGE)?));
+    }
+
+    // Check the database before taking over the terminal, so a damaged file
+    // can be reported and restored with a plain prompt
+    check_database()?;
+    check_schema()?;
+
+    // A passphrase prompt for the wallet key needs the terminal to itself too
+    key_provider::use_wallet_key_provider(environment.wallet_key.provider().as_ref());
+
+    // Fill an empty database with demo users if the environment asks for it
+    if environment.seed {
+        seed_demo_data(&environment)?;
+    }
+
+    // A watch display needs no login and only ever reads the database
+    if args.first().map(String::as_str) == Some("--watch") {
+        return match args.get(1) {
+            Some(username) => run_watch(username),
+            None => Err(USAGE.into()),
+        };
+    }
+
+    // Admin commands run against the database and exit without the UI
+    if !args.is_empty() {
+        return run_admin_command(&args, &environment);
+    }
+
+    // Take the periodic backup, if one is due, before the wallet is used
+    let now = chrono::Local::now().naive_local();
+    if database::backup_due(now) {
+        take_backup(&database::open(database::path())?, now)?;
+    }
+
+    // With --record, record the session next to a copy of the database as
+    // it is now, so it can be replayed with --replay
+    let start = SessionStart {
+        at: now,
+        seed: rand::random(),
+    };
+    let recorder = match record {
+        Some(path) => Some(
+            start_recording(&path, &start)
+                .map_err(|err| format!("cannot record to {}: {}", path.display(), err))?,
+        ),
+        None => None,
+    };
+
+    let mut terminal = setup_terminal()?;
 
     // Create app controller
     let mut app_controller = AppController::new()?;
+    app_controller.seed_rng(start.seed);
+    run_app(&mut terminal, &mut app_controller, recorder, None)?;
 
-    // Main loop
+    restore_terminal(&mut terminal)
+}
+
+/// Keys being replayed, and the clock the replayed session runs on. The
+/// clock is moved to each key's recorded time as the key is pressed, and
+/// follows real time once the keys run out.
+struct Replay {
+    keys: VecDeque<(Duration, Option<KeyCode>)>,
+    clock: Rc<VirtualClock>,
+    played: Duration,
+}
+
+impl Replay {
+    fn advance_to(&mut self, at: Duration) {
+        if let Some(by) = at.checked_sub(self.played) {
+            self.clock.advance(chrono::Duration::from_std(by).unwrap_or_default());
+            self.played = at;
+        }
+    }
+}
+
+/// Runs the main loop until the user quits. Key presses are written to
+/// `recorder`, if any, masked on credential screens. Keys in `replay` are
+/// fed in first, each once its recorded time since the start has passed,
+/// and keys typed meanwhile are ignored so they cannot change what is
+/// replayed. At masked keys the replay waits for the credential to be
+/// typed in, until the app leaves the credential screen.
+fn run_app(
+    terminal: &mut CrosstermTerminal,
+    app_controller: &mut AppController,
+    mut recorder: Option<SessionRecorder>,
+    mut replay: Option<Replay>,
+) -> Result<(), Box<dyn Error>> {
+    let mut started = Instant::now();
+    let mut typing_since: Option<Instant> = None;
     loop {
-        // Clear expired messages
+        // Clear expired messages and run periodic work
         app_controller.clear_expired_messages();
+        app_controller.tick()?;
 
         // Draw UI
-        terminal.draw(|f| ui::draw(f, &app_controller))?;
+        terminal.draw(|f| ui::draw(f, app_controller))?;
 
-        // Handle input
-        if let Event::Key(key) = event::read()? {
-            if !app_controller.handle_input(key.code)? {
-                break;
+        // Handle input, waking up regularly so timers run without key presses.
+        // A resize is redrawn straight away rather than on the next wake-up.
+        let mut wait = Duration::from_millis(250);
+        if let Some(since) = typing_since {
+            if !app_controller.current_screen.is_credential_input() {
+                started += since.elapsed();
+                typing_since = None;
             }
         }
+        if let (Some(replay), None) = (replay.as_mut(), typing_since) {
+            match replay.keys.front() {
+                Some(&(at, key)) => match at.checked_sub(started.elapsed()) {
+                    Some(left) if !left.is_zero() => wait = wait.min(left),
+                    _ => {
+                        replay.keys.pop_front();
+                        replay.advance_to(at);
+                        app_controller.clear_expired_messages();
+                        app_controller.tick()?;
+                        match key {
+                            Some(key) => {
+                                if !app_controller.handle_input(key)? {
+                                    break;
+                                }
+                            }
+                            None => {
+                                while replay.keys.front().is_some_and(|(_, key)| key.is_none()) {
+                                    replay.keys.pop_front();
+                                }
+                                if app_controller.current_screen.is_credential_input() {
+                                    typing_since = Some(Instant::now());
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                },
+                None => replay.advance_to(started.elapsed()),
+            }
+        }
+        let replaying = replay.as_ref().is_some_and(|replay| !replay.keys.is_empty());
+        if event::poll(wait)? {
+            match event::read()? {
+                Event::Key(_) if replaying && typing_since.is_none() => {}
+                Event::Key(key) => {
+                    let masked = app_controller.current_screen.is_credential_input();
+                    if let Some(Err(err)) = recorder.as_mut().map(|r| r.record(key.code, masked)) {
+                        app_controller.add_message(format!("Session recording stopped: {}", err));
+                        recorder = None;
+                    }
+                    if !app_controller.handle_input(key.code)? {
+                        break;
+                    }
+                }
+                Event::Resize(width, height) => resize(terminal, width, height)?,
+                _ => {}
+            }
+        }
+        if let Some(text) = app_controller.clipboard.take() {
+            copy_to_clipboard(terminal, &text)?;
+        }
     }
+    Ok(())
+}
+
+/// Copies the database next to `path`, with the `.db` extension, and
+/// starts recording keys into `path`. Both files get the database's
+/// permissions, as they hold as much as it does.
+fn start_recording(path: &Path, start: &SessionStart) -> Result<SessionRecorder, Box<dyn Error>> {
+    let permissions = std::fs::metadata(database::path())?.permissions();
+    let copy = path.with_extension("db");
+    database::backup(&database::open(database::path())?, &copy)?;
+    std::fs::set_permissions(&copy, permissions.clone())?;
+    Ok(SessionRecorder::create(path, start, permissions)?)
+}
+
+/// Replays a recorded session on screen against a copy of the database it
+/// started from, in a temporary directory, with the keys pressed at the
+/// times they were recorded. Once the keys run out the app carries on as
+/// normal, still on the copy, until the user quits.
+fn run_replay(session: &Path) -> Result<(), Box<dyn Error>> {
+    let recording = Recording::load(session)
+        .map_err(|err| format!("cannot replay {}: {}", session.display(), err))?;
+    let rules = std::fs::read_to_string(RULES_PATH).ok();
+    let dir = std::env::temp_dir().join(format!("ewallet_replay_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let copy = dir.join(database::path());
+    if let Some(parent) = copy.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(&recording.database, &copy)?;
+    if let Some(rules) = rules {
+        std::fs::write(dir.join(RULES_PATH), rules)?;
+    }
+    std::env::set_current_dir(&dir)?;
 
-    // Restore terminal
+    let clock = Rc::new(VirtualClock::new(recording.start.at));
+    let mut terminal = setup_terminal()?;
+    let result = AppController::with_clock(clock.clone()).map_err(Box::from).and_then(|mut app| {
+        app.seed_rng(recording.start.seed);
+        let replay = Replay {
+            keys: recording.keys.into_iter().collect(),
+            clock,
+            played: Duration::ZERO,
+        };
+        run_app(&mut terminal, &mut app, None, Some(replay))
+    });
+    restore_terminal(&mut terminal)?;
+
+    std::env::set_current_dir(std::env::temp_dir())?;
+    std::fs::remove_dir_all(&dir)?;
+    result
+}
+
+fn setup_terminal() -> Result<CrosstermTerminal, Box<dyn Error>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    Ok(Terminal::new(CrosstermBackend::new(stdout))?)
+}
+
+/// Clears the screen and lays it out for the terminal's new size, so the
+/// next frame is drawn in full rather than diffed against the old layout.
+fn resize(terminal: &mut CrosstermTerminal, width: u16, height: u16) -> io::Result<()> {
+    terminal.resize(Rect::new(0, 0, width, height))
+}
+
+/// Asks the terminal to put `text` on the system clipboard with an OSC 52
+/// escape sequence, which also works over SSH. Terminals without OSC 52
+/// support ignore it.
+fn copy_to_clipboard(terminal: &mut CrosstermTerminal, text: &str) -> io::Result<()> {
+    let backend = terminal.backend_mut();
+    write!(backend, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    backend.flush()
+}
+
+fn restore_terminal(terminal: &mut CrosstermTerminal) -> Result<(), Box<dyn Error>> {
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
@@ -49,6 +335,625 @@ fn main() -> Result<(), Box<dyn Error>> {
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
+    Ok(())
+}
+
+/// Lets the user choose one of several items, such as profiles. Returns
+/// the index of the chosen one, or `None` if they quit.
+fn pick(title: &str, choices: &[String]) -> Result<Option<usize>, Box<dyn Error>> {
+    let mut terminal = setup_terminal()?;
+    let mut selected = 0;
+    let choice = loop {
+        terminal.draw(|f| picker::draw(f, title, choices, selected))?;
+        match event::read()? {
+            Event::Key(key) => match key.code {
+                KeyCode::Up => selected = selected.saturating_sub(1),
+                KeyCode::Down if selected + 1 < choices.len() => selected += 1,
+                KeyCode::Enter => break Some(selected),
+                KeyCode::Esc | KeyCode::Char('q') => break None,
+                _ => {}
+            },
+            Event::Resize(width, height) => resize(&mut terminal, width, height)?,
+            _ => {}
+        }
+    };
+    restore_terminal(&mut terminal)?;
+    Ok(choice)
+}
+
+/// Shows a live, read-only dashboard of one user's wallet until q or Esc is pressed.
+fn run_watch(username: &str) -> Result<(), Box<dyn Error>> {
+    let mut watch = WatchController::new(username)?
+        .ok_or_else(|| format!("no such user: {}", username))?;
+
+    let mut terminal = setup_terminal()?;
+    loop {
+        watch.tick()?;
+        terminal.draw(|f| watch::draw(f, &watch))?;
+
+        if event::poll(Duration::from_millis(250))? {
+            match event::read()? {
+                Event::Key(key) if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) => break,
+                Event::Resize(width, height) => resize(&mut terminal, width, height)?,
+                _ => {}
+            }
+        }
+    }
+    restore_terminal(&mut terminal)
+}
+
+/// Runs read-only SQL against the database until Esc is pressed.
+fn run_sql_console() -> Result<(), Box<dyn Error>> {
+    let mut console = SqlConsole::new()?;
+    let mut terminal = setup_terminal()?;
+    loop {
+        terminal.draw(|f| sql_console::draw(f, &console))?;
+        match event::read()? {
+            Event::Key(key) if !console.handle_key(key.code) => break,
+            Event::Resize(width, height) => resize(&mut terminal, width, height)?,
+            _ => {}
+        }
+    }
+    restore_terminal(&mut terminal)
+}
+
+/// Walks the user through upgrading a database written by an older version
+/// of the app: says what will happen, and only after they agree backs it up,
+/// migrates it and checks the result. Databases from a newer version are
+/// refused, since this version could misread them.
+fn check_schema() -> Result<(), Box<dyn Error>> {
+    let path = database::path();
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+    let (version, written_by) = {
+        let conn = database::open_read_only(path)?;
+        (database::schema_version(&conn)?, database::written_by(&conn)?)
+    };
+    // A version of 0 is a database that was never set up
+    if version == 0 || version == database::SCHEMA_VERSION {
+        return Ok(());
+    }
+    if version > database::SCHEMA_VERSION {
+        let written_by = written_by
+            .map_or("an unknown version".to_string(), |v| format!("version {}", v));
+        return Err(format!(
+            "{} has schema version {}, written by {}; this is version {} with schema {}. \
+            Upgrade the app to open it",
+            path,
+            version,
+            written_by,
+            database::APP_VERSION,
+            database::SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    let written_by = written_by
+        .map_or("an older version".to_string(), |v| format!("version {}", v));
+    eprintln!(
+        "{} has schema version {}, written by {}. This is version {}, which uses schema {}.",
+        path,
+        version,
+        written_by,
+        database::APP_VERSION,
+        database::SCHEMA_VERSION
+    );
+    eprintln!("Upgrading will:");
+    eprintln!("  1. back it up to {}/", database::backup_dir().display());
+    eprintln!("  2. apply {} migration(s)", database::SCHEMA_VERSION - version);
+    eprintln!("  3. check the upgraded database's integrity");
+    eprintln!("Older versions may misread it afterwards; restore the backup to go back.");
+    eprint!("Upgrade now? [y/N] ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return Err("database left unchanged".into());
+    }
+
+    let now = chrono::Local::now().naive_local();
+    let backup = take_backup(&database::open_read_only(path)?, now)?;
+    eprintln!("Backed up to {}", backup.display());
+    database::open(path)?;
+    if let Some(problem) = database::integrity_problem(path) {
+        let error = format!(
+            "the upgraded database failed its integrity check: {}. Run restore to go back",
+            problem
+        );
+        return Err(error.into());
+    }
+    eprintln!("Upgraded {} to schema version {}", path, database::SCHEMA_VERSION);
+    Ok(())
+}
+
+fn check_database() -> Result<(), Box<dyn Error>> {
+    let problem = match database::integrity_problem(database::path()) {
+        Some(problem) => problem,
+        None => return Ok(()),
+    };
+    eprintln!("{} failed its integrity check: {}", database::path(), problem);
+
+    let backup = match database::latest_backup() {
+        Some(backup) => backup,
+        None => {
+            let dir = database::backup_dir();
+            return Err(format!("no backups found in {}/", dir.display()).into());
+        }
+    };
+    eprint!("Restore from {}? [y/N] ", backup.display());
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return Err("database left unchanged".into());
+    }
+
+    database::restore_backup(&backup, database::path(), ".corrupt")?;
+    eprintln!(
+        "Restored. The damaged file was kept as {}.corrupt",
+        database::path()
+    );
+    Ok(())
+}
+
+/// Prints a health report on the database, most urgent findings first.
+/// Fails if any finding is critical, so scripts can alert on it.
+fn run_doctor() -> Result<(), Box<dyn Error>> {
+    let findings = Doctor::examine(database::path());
+    for finding in &findings {
+        println!(
+            "[{:<8}] {:<9} {}",
+            finding.severity.as_str(),
+            finding.check,
+            finding.detail
+        );
+    }
+    let critical = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Critical)
+        .count();
+    if critical > 0 {
+        return Err(format!("{} critical problem(s) found", critical).into());
+    }
+    Ok(())
+}
+
+/// Backs the database up into `BACKUP_DIR` and deletes the oldest backups
+/// beyond `BACKUPS_KEPT`.
+fn take_backup(
+    conn: &rusqlite::Connection,
+    now: chrono::NaiveDateTime,
+) -> Result<std::path::PathBuf, Box<dyn Error>> {
+    let path = database::new_backup_path(now)?;
+    database::backup(conn, &path)?;
+    database::prune_backups(database::BACKUPS_KEPT)?;
+    Ok(path)
+}
+
+/// Lets the user pick a backup, newest first, and puts it in place of the
+/// database. The current database is kept with a `.before-restore` suffix.
+fn run_restore(conn: rusqlite::Connection) -> Result<(), Box<dyn Error>> {
+    let mut backups = database::list_backups();
+    backups.reverse();
+    if backups.is_empty() {
+        return Err(format!("no backups found in {}/", database::backup_dir().display()).into());
+    }
+    let choices: Vec<String> = backups
+        .iter()
+        .map(|backup| match database::backup_taken_at(backup) {
+            Some(taken_at) => format!("{}  ({})", taken_at, backup.display()),
+            None => backup.display().to_string(),
+        })
+        .collect();
+    let backup = match pick("Restore a Backup (Enter: restore, Esc: cancel)", &choices)? {
+        Some(i) => &backups[i],
+        None => return Ok(()),
+    };
+    // Closing the connection checkpoints the write-ahead log into the file
+    // being replaced, so nothing is left behind for the restored copy.
+    drop(conn);
+    database::restore_backup(backup, database::path(), ".before-restore")?;
+    println!(
+        "Restored {}. The previous database was kept as {}.before-restore",
+        backup.display(),
+        database::path()
+    );
+    Ok(())
+}
+
+/// Runs database maintenance and describes how much smaller the file got.
+fn run_maintenance(conn: &rusqlite::Connection) -> Result<String, Box<dyn Error>> {
+    let (before, after) = database::maintain(conn)?;
+    Ok(format!(
+        "Maintenance done: {} KiB before, {} KiB after, {} KiB freed",
+        before / 1024,
+        after / 1024,
+        (before - after).max(0) / 1024
+    ))
+}
+
+/// Prints what differs between two copies of the database, e.g. two
+/// backups. Fails if anything does, so scripts can check that a restore or
+/// sync left the data as expected.
+fn run_diff(before: &str, after: &str) -> Result<(), Box<dyn Error>> {
+    for path in [before, after] {
+        if !std::path::Path::new(path).exists() {
+            return Err(format!("no such file: {}", path).into());
+        }
+    }
+    let diff = BackupDiff::compare(before, after)?;
+    if diff.is_empty() {
+        println!("No differences between {} and {}", before, after);
+        return Ok(());
+    }
+    if !diff.users_added.is_empty() {
+        println!("Users added ({}): {}", diff.users_added.len(), diff.users_added.join(", "));
+    }
+    if !diff.users_removed.is_empty() {
+        println!(
+            "Users removed ({}): {}",
+            diff.users_removed.len(),
+            diff.users_removed.join(", ")
+        );
+    }
+    if !diff.balance_changes.is_empty() {
+        println!("Balance changes ({}):", diff.balance_changes.len());
+        for change in &diff.balance_changes {
+            println!(
+                "  {:<20} {:>14} -> {:>14}",
+                change.username,
+                format::money(change.before),
+                format::money(change.after)
+            );
+        }
+    }
+    for (heading, entries) in [
+        (format!("Only in {}", before), &diff.transactions_removed),
+        (format!("Only in {}", after), &diff.transactions_added),
+    ] {
+        if entries.is_empty() {
+            continue;
+        }
+        println!("{} ({} transaction(s)):", heading, entries.len());
+        for entry in entries {
+            println!(
+                "  {}  {:<20} {:<12} {:>14}  {}",
+                entry.timestamp,
+                entry.username,
+                entry.transaction_type,
+                format::money(entry.amount),
+                entry.id
+            );
+        }
+    }
+    Err("the databases differ".into())
+}
+
+/// How often `tail` looks for new transactions.
+const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
+/// Prints every transaction committed from now on as an NDJSON record,
+/// optionally only those of one user, until the process is stopped or
+/// stdout is closed, e.g. by `head` at the end of a pipe.
+fn run_tail(conn: &rusqlite::Connection, username: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let mut position = TransactionRecords::latest_position(conn)?;
+    let mut stdout = io::stdout();
+    loop {
+        let mut batch = String::new();
+        for (next, record) in TransactionRecords::committed_after(conn, position, username)? {
+            batch.push_str(&record);
+            batch.push('\n');
+            position = next;
+        }
+        if !batch.is_empty() {
+            match stdout.write_all(batch.as_bytes()).and_then(|()| stdout.flush()) {
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+                result => result?,
+            }
+        }
+        std::thread::sleep(TAIL_POLL_INTERVAL);
+    }
+}
+
+/// How often the daemon runs database maintenance.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often the daemon looks for webhooks to deliver.
+const DAEMON_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delivers queued webhooks until the process is stopped, retrying failed
+/// ones with backoff. Messages are only removed once the server accepts
+/// them, so a crash at any point means a retry rather than a lost message.
+/// Also does the rest of the `Daemon`'s work, such as settling payments
+/// waiting on the simulated processor, takes the periodic database backup
+/// whenever one is due, and runs maintenance when it starts and every
+/// `MAINTENANCE_INTERVAL` after that. What it does is printed at the
+/// environment's log level.
+fn run_daemon(
+    conn: &rusqlite::Connection,
+    environment: &Environment,
+) -> Result<(), Box<dyn Error>> {
+    let log = |level: LogLevel, message: &str| log(environment, level, message);
+    log(
+        LogLevel::Debug,
+        &format!(
+            "Environment {}: database {}, log level {}, wallet key {}",
+            environment.name,
+            database::path(),
+            environment.log_level.as_str(),
+            environment.wallet_key.as_str()
+        ),
+    );
+    let mut daemon = Daemon::new(AppController::new()?, outbox::post_json)?;
+    for (message, _) in daemon.app_controller.messages.drain(..) {
+        log(LogLevel::Warn, &message);
+    }
+    log(LogLevel::Info, &daemon.outbox_summary()?);
+    let mut last_maintenance: Option<Instant> = None;
+    loop {
+        let now = daemon.app_controller.now();
+        if database::backup_due(now) {
+            let path = take_backup(conn, now)?;
+            log(LogLevel::Info, &format!("Backed up to {}", path.display()));
+        }
+        if last_maintenance.is_none_or(|at| at.elapsed() >= MAINTENANCE_INTERVAL) {
+            log(LogLevel::Info, &run_maintenance(conn)?);
+            last_maintenance = Some(Instant::now());
+        }
+        for (level, message) in daemon.run_once()? {
+            log(level, &message);
+        }
+        std::thread::sleep(DAEMON_POLL_INTERVAL);
+    }
+}
+
+/// Prints a message if the environment's log level includes it. Warnings
+/// and errors go to stderr.
+fn log(environment: &Environment, level: LogLevel, message: &str) {
+    if level > environment.log_level {
+        return;
+    }
+    match level {
+        LogLevel::Error | LogLevel::Warn => eprintln!("[{}] {}", level.as_str(), message),
+        LogLevel::Info | LogLevel::Debug => println!("[{}] {}", level.as_str(), message),
+    }
+}
+
+/// Demo users created by `seed_demo_data`, with what they deposit.
+const SEED_USERS: [(&str, f64); 3] = [("alice", 1_000.0), ("bob", 250.0), ("carol", 50.0)];
+
+/// Creates a few demo users with some money and transfers between them, if
+/// the database has no users yet. Everything goes through the controller,
+/// so the seeded history looks like one made in the app.
+fn seed_demo_data(environment: &Environment) -> Result<(), Box<dyn Error>> {
+    if User::count(&database::open(database::path())?)? > 0 {
+        return Ok(());
+    }
+    let mut app_controller = AppController::new()?;
+    for (username, deposit) in SEED_USERS {
+        app_controller.create_account(username.to_string())?;
+        app_controller.deposit(deposit)?;
+        app_controller.logout();
+    }
+    app_controller.login("alice".to_string())?;
+    app_controller.transfer("bob".to_string(), 120.0, Some("Concert tickets"))?;
+    app_controller.move_to_vault(300.0)?;
+    app_controller.logout();
+    app_controller.login("bob".to_string())?;
+    app_controller.transfer("carol".to_string(), 35.0, Some("Lunch"))?;
+    app_controller.logout();
+    let names: Vec<&str> = SEED_USERS.iter().map(|(username, _)| *username).collect();
+    log(
+        environment,
+        LogLevel::Info,
+        &format!("Seeded {} with {}", database::path(), names.join(", ")),
+    );
+    Ok(())
+}
+
+/// Admin commands that delete or overwrite data, refused in environments
+/// that turn destructive commands off.
+const DESTRUCTIVE_COMMANDS: [&str; 5] =
+    ["anonymize", "archive", "purge", "restore", "close-account"];
+
+const USAGE: &str = "usage: ewallet_demo [--profile <name>] [--env <name>] [--record <file>] \
+[--watch <username> | --replay <session> | doctor | export <username> [file] | \
+export --ndjson <username> [file] | import --ndjson <file> | anonymize <username> | \
+approve-transfer <id> | snapshot [file] | archive <months> | purge | \
+webhook <username> [url] | daemon | export-audit [file] | verify-audit <file> [key] | \
+verify-export-log | close-account <username> <nominee> [file] | backup | restore | maintenance | \
+tail [username] | sql | diff <backup_a> <backup_b>]";
+
+fn run_admin_command(args: &[String], environment: &Environment) -> Result<(), Box<dyn Error>> {
+    if !environment.destructive_commands && DESTRUCTIVE_COMMANDS.contains(&args[0].as_str()) {
+        let error = format!("{} is disabled in the {} environment", args[0], environment.name);
+        return Err(error.into());
+    }
+    let conn = database::open(database::path())?;
+    match (args[0].as_str(), args.get(1)) {
+        ("export", Some(flag)) if flag == "--ndjson" => {
+            let username = args.get(2).ok_or(USAGE)?;
+            if User::get(&conn, username)?.is_none() {
+                return Err(format!("no such user: {}", username).into());
+            }
+            let mut contents = TransactionRecords::export(&conn, username)?.join("\n");
+            contents.push('\n');
+            match args.get(3) {
+                Some(path) => std::fs::write(path, contents)?,
+                None => print!("{}", contents),
+            }
+        }
+        ("import", Some(flag)) if flag == "--ndjson" => {
+            let path = args.get(2).ok_or(USAGE)?;
+            let text = std::fs::read_to_string(path)?;
+            let transactions = match TransactionRecords::parse(&text) {
+                Ok(transactions) => transactions,
+                Err(errors) => {
+                    for error in &errors {
+                        eprintln!("{}: {}", path, error);
+                    }
+                    let summary = format!("{} invalid record(s), nothing imported", errors.len());
+                    return Err(summary.into());
+                }
+            };
+            let now = chrono::Local::now().naive_local();
+            let added = TransactionRecords::import(&conn, &transactions, now)?;
+            println!(
+                "Imported {} transaction(s); {} already present",
+                added,
+                transactions.len() - added
+            );
+        }
+        ("export", Some(username)) => {
+            let bundle = UserData::export(&conn, username, chrono::Local::now().naive_local())?
+                .ok_or_else(|| format!("no such user: {}", username))?;
+            let contents = serde_json::to_string_pretty(&bundle)?;
+            match args.get(2) {
+                Some(path) => std::fs::write(path, contents)?,
+                None => println!("{}", contents),
+            }
+        }
+        ("snapshot", path) => {
+            let default_path = format!(
+                "snapshot-{}.db",
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            );
+            let path = path.cloned().unwrap_or(default_path);
+            database::snapshot(&conn, &path)?;
+            println!("Anonymized snapshot written to {}", path);
+        }
+        ("archive", Some(months)) => {
+            let months: u32 = months.parse().map_err(|_| format!("invalid months: {}", months))?;
+            let cutoff = chrono::Local::now()
+                .naive_local()
+                .checked_sub_months(chrono::Months::new(months))
+                .ok_or("cutoff is out of range")?;
+            let moved = Archive::archive_before(&conn, cutoff)?;
+            println!(
+                "Archived {} transaction(s) older than {} month(s); {} in the archive",
+                moved,
+                months,
+                Archive::count(&conn)?
+            );
+        }
+        ("purge", None) => {
+            let now = chrono::Local::now().naive_local();
+            let mut total = 0;
+            for (username, value) in UserSettings::get_all(&conn, "retention")? {
+                let cutoff = match Retention::parse(&value).and_then(|r| r.cutoff(now)) {
+                    Some(cutoff) => cutoff,
+                    None => continue,
+                };
+                let purged = Rollup::purge_before(&conn, &username, cutoff)?;
+                if purged > 0 {
+                    println!(
+                        "{}: summarized {} transaction(s) before {}",
+                        username,
+                        purged,
+                        cutoff.format("%Y-%m")
+                    );
+                }
+                total += purged;
+            }
+            println!("Purged {} transaction(s) past their retention period", total);
+        }
+        ("approve-transfer", Some(id)) => {
+            let id: i64 = id.parse().map_err(|_| format!("invalid transfer id: {}", id))?;
+            let mut app_controller = AppController::new()?;
+            let approved = app_controller.approve_pending_transfer(id)?;
+            for (message, _) in &app_controller.messages {
+                println!("{}", message);
+            }
+            if !approved {
+                return Err("transfer was not approved".into());
+            }
+        }
+        ("webhook", Some(username)) => {
+            if User::get(&conn, username)?.is_none() {
+                return Err(format!("no such user: {}", username).into());
+            }
+            match args.get(2) {
+                Some(url) => {
+                    if outbox::parse_url(url).is_none() {
+                        return Err(format!("not an http:// URL: {}", url).into());
+                    }
+                    UserSettings::set(&conn, username, "webhook_url", url)?;
+                    println!("{}'s transactions will be posted to {}", username, url);
+                }
+                None => {
+                    UserSettings::remove(&conn, username, "webhook_url")?;
+                    println!("Webhook removed for {}", username);
+                }
+            }
+        }
+        ("daemon", None) => run_daemon(&conn, environment)?,
+        ("backup", None) => {
+            let path = take_backup(&conn, chrono::Local::now().naive_local())?;
+            println!("Backed up to {}", path.display());
+        }
+        ("restore", None) => run_restore(conn)?,
+        ("maintenance", None) => println!("{}", run_maintenance(&conn)?),
+        ("tail", username) => run_tail(&conn, username.map(String::as_str))?,
+        ("sql", None) => run_sql_console()?,
+        ("diff", Some(before)) => run_diff(before, args.get(2).ok_or(USAGE)?)?,
+        ("export-audit", path) => {
+            let log = match std::fs::read_to_string(AUDIT_LOG_PATH) {
+                Ok(log) => log,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(err) => return Err(err.into()),
+            };
+            let key = OfflineToken::wallet_key(&conn, &WalletKeySecret)?
+                .ok_or("no wallet key to sign with; check wallet_key in config.toml")?;
+            let signed = AuditExport::sign(&log, &key);
+            match path {
+                Some(path) => std::fs::write(path, signed)?,
+                None => print!("{}", signed),
+            }
+            eprintln!(
+                "Signed {} audit line(s) with key {}",
+                log.lines().count(),
+                AuditExport::encode_key(&key.verifying_key())
+            );
+        }
+        ("verify-audit", Some(path)) => {
+            let text = std::fs::read_to_string(path)?;
+            let (lines, key) = AuditExport::verify(&text, args.get(2).map(String::as_str))
+                .map_err(|err| format!("{}: {}", path, err.as_str()))?;
+            println!("{}: {} line(s), signature valid for key {}", path, lines.len(), key);
+        }
+        ("close-account", Some(username)) => {
+            let nominee = args.get(2).ok_or(USAGE)?;
+            let mut app_controller = AppController::new()?;
+            let statement = app_controller.close_account(username, nominee)?;
+            for (message, _) in &app_controller.messages {
+                println!("{}", message);
+            }
+            let statement = statement.ok_or("account was not closed")?;
+            let default_path = format!("final-statement-{}.txt", username);
+            let path = args.get(3).unwrap_or(&default_path);
+            std::fs::write(path, statement)?;
+            println!("Final statement written to {}", path);
+        }
+        ("anonymize", Some(username)) => {
+            let tombstone = UserData::anonymize(&conn, username)?
+                .ok_or_else(|| format!("no such user: {}", username))?;
+            let now = chrono::Local::now().naive_local();
+            let audit_lines =
+                UserData::record_redaction(AUDIT_LOG_PATH, username, &tombstone, now)?;
+            let export_log = ExportLog::open(EXPORT_LOG_DIR)?;
+            let export_lines = export_log.record_redaction(&conn, username, &tombstone, now)?;
+            println!("{} is now {}", username, tombstone);
+            println!(
+                "Marked {} line(s) of {} and {} line(s) of the export log for redaction",
+                audit_lines, AUDIT_LOG_PATH, export_lines
+            );
+        }
+        ("verify-export-log", None) => {
+            let lines = ExportLog::open(EXPORT_LOG_DIR)?.verify(&conn)?;
+            println!("{}: {} line(s), checksum chain intact", EXPORT_LOG_DIR, lines);
+        }
+        _ => return Err(USAGE.into()),
+    }
     Ok(())
 }
@@ -0,0 +1,130 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+use crate::models::payment_request::RequestStatus;
+use crate::models::transaction::ARCHIVE_TABLE;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// What a line of the activity timeline is about.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ActivityKind {
+    Transaction,
+    Login,
+    /// A limit was changed or an amount went over one.
+    Limit,
+    /// A payment request the user made or was sent.
+    Request,
+}
+
+impl ActivityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityKind::Transaction => "transaction",
+            ActivityKind::Login => "login",
+            ActivityKind::Limit => "limit",
+            ActivityKind::Request => "request",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "login" => ActivityKind::Login,
+            "limit" => ActivityKind::Limit,
+            "request" => ActivityKind::Request,
+            _ => ActivityKind::Transaction,
+        }
+    }
+}
+
+/// One line of a user's activity timeline.
+pub struct Activity {
+    pub at: NaiveDateTime,
+    pub kind: ActivityKind,
+    /// The transaction type, whether a request was `sent` or `received`,
+    /// or a recorded description.
+    pub detail: String,
+    /// The other user in a transfer or request.
+    pub counterparty: Option<String>,
+    pub amount: Option<f64>,
+    /// Where a payment request stands now.
+    pub status: Option<RequestStatus>,
+}
+
+/// Everything that happened to a user, for the activity timeline:
+/// transactions and payment requests from their own tables, and logins and
+/// limit changes, which are recorded in `activity_log` as they are published.
+pub struct ActivityLog;
+
+impl ActivityLog {
+    pub fn record(
+        conn: &Connection,
+        username: &str,
+        kind: ActivityKind,
+        description: &str,
+        amount: Option<f64>,
+        at: NaiveDateTime,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO activity_log (username, kind, description, amount, at)
+            VALUES (?, ?, ?, ?, ?)",
+            params![
+                username,
+                kind.as_str(),
+                description,
+                amount,
+                at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the user's latest `limit` lines of activity, newest first.
+    pub fn timeline(conn: &Connection, username: &str, limit: usize) -> Result<Vec<Activity>> {
+        let transactions = |table: &str| {
+            format!(
+                "SELECT timestamp AS at, 'transaction' AS kind, transaction_type AS detail,
+                    CASE transaction_type
+                        WHEN 'transfer_out' THEN recipient
+                        WHEN 'transfer_in' THEN sender
+                    END AS counterparty,
+                    amount, NULL AS status
+                FROM {} WHERE username = ?1",
+                table
+            )
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT at, kind, detail, counterparty, amount, status FROM (
+                {}
+                UNION ALL
+                {}
+                UNION ALL
+                SELECT at, kind, description, NULL, amount, NULL
+                FROM activity_log WHERE username = ?1
+                UNION ALL
+                SELECT created_at, 'request', 'sent', payer, amount, status
+                FROM payment_requests WHERE requester = ?1
+                UNION ALL
+                SELECT created_at, 'request', 'received', requester, amount, status
+                FROM payment_requests WHERE payer = ?1
+            )
+            ORDER BY at DESC
+            LIMIT ?2",
+            transactions("transactions"),
+            transactions(ARCHIVE_TABLE)
+        ))?;
+        let activity = stmt.query_map(params![username, limit as i64], |row| {
+            let at: String = row.get(0)?;
+            let status: Option<String> = row.get(5)?;
+            Ok(Activity {
+                at: NaiveDateTime::parse_from_str(&at, TIMESTAMP_FORMAT).unwrap_or_default(),
+                kind: ActivityKind::parse(&row.get::<_, String>(1)?),
+                detail: row.get(2)?,
+                counterparty: row.get(3)?,
+                amount: row.get(4)?,
+                status: status.map(|status| RequestStatus::parse(&status)),
+            })
+        })?;
+        activity.collect()
+    }
+}
@@ -0,0 +1,94 @@
+/// Largest amount a single deposit, withdrawal or transfer may be for,
+/// unless the user picks another limit in Settings.
+pub const DEFAULT_MAX_AMOUNT: f64 = 1_000_000.0;
+
+/// Why a typed amount was rejected.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AmountError {
+    NotANumber,
+    NotPositive,
+    TooPrecise,
+    TooLarge,
+}
+
+/// Parses an amount typed by the user, such as `12` or `12.50`, that must be
+/// above zero, in whole cents and at most `max`.
+///
+/// Only plain decimals are accepted. `str::parse::<f64>` also takes `inf`,
+/// `NaN`, `1e6` and a leading `+`, none of which anyone types on purpose.
+pub fn parse_amount(input: &str, max: f64) -> Result<f64, AmountError> {
+    let input = input.trim();
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, input),
+    };
+    let is_decimal = digits.chars().any(|c| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1;
+    if !is_decimal {
+        return Err(AmountError::NotANumber);
+    }
+    if digits.split_once('.').is_some_and(|(_, cents)| cents.len() > 2) {
+        return Err(AmountError::TooPrecise);
+    }
+
+    let amount: f64 = digits.parse().map_err(|_| AmountError::NotANumber)?;
+    if negative || amount <= 0.0 {
+        Err(AmountError::NotPositive)
+    } else if !amount.is_finite() || amount > max {
+        Err(AmountError::TooLarge)
+    } else {
+        Ok(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_decimals() {
+        assert_eq!(parse_amount("12", 100.0), Ok(12.0));
+        assert_eq!(parse_amount(" 12.50 ", 100.0), Ok(12.5));
+        assert_eq!(parse_amount(".5", 100.0), Ok(0.5));
+        assert_eq!(parse_amount("100", 100.0), Ok(100.0));
+    }
+
+    #[test]
+    fn rejects_what_f64_parsing_would_take() {
+        for input in ["NaN", "nan", "inf", "-inf", "infinity", "1e6", "+5", "0x10", ""] {
+            assert_eq!(parse_amount(input, 100.0), Err(AmountError::NotANumber), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        for input in ["-", ".", "1.2.3", "1,50", "12 50", "--5"] {
+            assert_eq!(parse_amount(input, 100.0), Err(AmountError::NotANumber), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_amounts_that_are_not_positive() {
+        assert_eq!(parse_amount("0", 100.0), Err(AmountError::NotPositive));
+        assert_eq!(parse_amount("0.00", 100.0), Err(AmountError::NotPositive));
+        assert_eq!(parse_amount("-5", 100.0), Err(AmountError::NotPositive));
+    }
+
+    #[test]
+    fn rejects_fractions_of_a_cent() {
+        assert_eq!(parse_amount("1.005", 100.0), Err(AmountError::TooPrecise));
+        assert_eq!(parse_amount("0.001", 100.0), Err(AmountError::TooPrecise));
+    }
+
+    #[test]
+    fn rejects_amounts_over_the_maximum() {
+        assert_eq!(parse_amount("100.01", 100.0), Err(AmountError::TooLarge));
+        assert_eq!(
+            parse_amount("1000000.01", DEFAULT_MAX_AMOUNT),
+            Err(AmountError::TooLarge)
+        );
+        let huge = "9".repeat(400);
+        assert_eq!(parse_amount(&huge, DEFAULT_MAX_AMOUNT), Err(AmountError::TooLarge));
+    }
+}
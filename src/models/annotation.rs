@@ -0,0 +1,75 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// User-editable metadata attached to a transaction. Kept in its own table so
+/// the ledger rows themselves are never modified after they are written.
+#[allow(dead_code)]
+pub struct Annotation {
+    pub transaction_id: String,
+    pub category: Option<String>,
+    pub memo: Option<String>,
+    pub note: Option<String>,
+    pub starred: bool,
+}
+
+impl Annotation {
+    #[allow(dead_code)]
+    pub fn get(conn: &Connection, transaction_id: &str) -> Result<Option<Annotation>> {
+        conn.query_row(
+            "SELECT transaction_id, category, memo, note, starred
+            FROM transaction_annotations
+            WHERE transaction_id = ?",
+            params![transaction_id],
+            |row| {
+                Ok(Annotation {
+                    transaction_id: row.get(0)?,
+                    category: row.get(1)?,
+                    memo: row.get(2)?,
+                    note: row.get(3)?,
+                    starred: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    pub fn set_category(conn: &Connection, transaction_id: &str, category: Option<&str>) -> Result<()> {
+        conn.execute(
+            "INSERT INTO transaction_annotations (transaction_id, category) VALUES (?1, ?2)
+            ON CONFLICT(transaction_id) DO UPDATE SET category = ?2",
+            params![transaction_id, category],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_memo(conn: &Connection, transaction_id: &str, memo: Option<&str>) -> Result<()> {
+        conn.execute(
+            "INSERT INTO transaction_annotations (transaction_id, memo) VALUES (?1, ?2)
+            ON CONFLICT(transaction_id) DO UPDATE SET memo = ?2",
+            params![transaction_id, memo],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_note(conn: &Connection, transaction_id: &str, note: Option<&str>) -> Result<()> {
+        conn.execute(
+            "INSERT INTO transaction_annotations (transaction_id, note) VALUES (?1, ?2)
+            ON CONFLICT(transaction_id) DO UPDATE SET note = ?2",
+            params![transaction_id, note],
+        )?;
+        Ok(())
+    }
+
+    /// Flips the starred flag of a transaction and returns the new value.
+    pub fn toggle_starred(conn: &Connection, transaction_id: &str) -> Result<bool> {
+        conn.execute(
+            "INSERT INTO transaction_annotations (transaction_id, starred) VALUES (?1, 1)
+            ON CONFLICT(transaction_id) DO UPDATE SET starred = NOT starred",
+            params![transaction_id],
+        )?;
+        conn.query_row(
+            "SELECT starred FROM transaction_annotations WHERE transaction_id = ?",
+            params![transaction_id],
+            |row| row.get(0),
+        )
+    }
+}
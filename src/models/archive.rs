@@ -0,0 +1,37 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+/// Moves old transactions out of the live `transactions` table into
+/// `transactions_archive`, keeping the table the app writes to small.
+/// History queries read both tables, so archived rows stay visible.
+pub struct Archive;
+
+const COLUMNS: &str = "id, username, transaction_type, amount, recipient, sender, \
+    previous_balance, new_balance, timestamp, currency, fx_rate, transfer_id, merchant";
+
+impl Archive {
+    /// Moves every transaction recorded before `cutoff` into the archive.
+    /// Returns how many transactions were moved.
+    pub fn archive_before(conn: &Connection, cutoff: NaiveDateTime) -> Result<usize> {
+        let cutoff = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            &format!(
+                "INSERT INTO transactions_archive ({0})
+                SELECT {0} FROM transactions WHERE timestamp < ?",
+                COLUMNS
+            ),
+            params![cutoff],
+        )?;
+        let moved = tx.execute("DELETE FROM transactions WHERE timestamp < ?", params![cutoff])?;
+        tx.commit()?;
+        Ok(moved)
+    }
+
+    /// Counts the transactions in the archive.
+    pub fn count(conn: &Connection) -> Result<usize> {
+        conn.query_row("SELECT COUNT(*) FROM transactions_archive", [], |row| {
+            row.get::<_, i64>(0).map(|count| count as usize)
+        })
+    }
+}
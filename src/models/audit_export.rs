@@ -0,0 +1,89 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+/// Marks the signature line, so a future format can be told apart.
+const SIGNATURE_PREFIX: &str = "EWA1";
+
+/// Why a signed audit export failed verification.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AuditError {
+    Unsigned,
+    Malformed,
+    BadSignature,
+    WrongKey,
+}
+
+impl AuditError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditError::Unsigned => "no signature line found",
+            AuditError::Malformed => "signature line is not valid",
+            AuditError::BadSignature => "signature does not match; the log was changed",
+            AuditError::WrongKey => "signed by a different key than the one given",
+        }
+    }
+}
+
+/// The audit log as handed to an auditor: the log's lines unchanged,
+/// followed by one line `EWA1.<public key>.<signature>`, both base64url.
+/// The signature is made with the instance's wallet key over every byte
+/// before that line, so adding, removing or editing any line is detected.
+pub struct AuditExport;
+
+impl AuditExport {
+    /// Appends the signature line to `log`.
+    pub fn sign(log: &str, key: &SigningKey) -> String {
+        let mut signed = log.to_string();
+        if !signed.is_empty() && !signed.ends_with('\n') {
+            signed.push('\n');
+        }
+        let signature = key.sign(signed.as_bytes());
+        signed.push_str(&format!(
+            "{}.{}.{}\n",
+            SIGNATURE_PREFIX,
+            Self::encode_key(&key.verifying_key()),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ));
+        signed
+    }
+
+    /// Checks a signed export. If `expected_key` is given the export must
+    /// also have been signed with it; without it, the check only shows the
+    /// file is as its signer wrote it. Returns the log lines and the key.
+    pub fn verify(
+        text: &str,
+        expected_key: Option<&str>,
+    ) -> Result<(Vec<String>, String), AuditError> {
+        let body_end = text.trim_end_matches('\n').rfind('\n').map_or(0, |index| index + 1);
+        let (body, signature_line) = text.split_at(body_end);
+        let mut parts = signature_line.trim_end().split('.');
+        let (key, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(SIGNATURE_PREFIX), Some(key), Some(signature), None) => (key, signature),
+            _ => return Err(AuditError::Unsigned),
+        };
+
+        let public_key = URL_SAFE_NO_PAD
+            .decode(key)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+            .ok_or(AuditError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or(AuditError::Malformed)?;
+        public_key
+            .verify(body.as_bytes(), &signature)
+            .map_err(|_| AuditError::BadSignature)?;
+        if expected_key.is_some_and(|expected| expected.trim() != key) {
+            return Err(AuditError::WrongKey);
+        }
+        Ok((body.lines().map(str::to_string).collect(), key.to_string()))
+    }
+
+    /// Formats a public key the way it appears in the signature line.
+    pub fn encode_key(key: &VerifyingKey) -> String {
+        URL_SAFE_NO_PAD.encode(key.as_bytes())
+    }
+}
@@ -0,0 +1,108 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::models::database;
+use crate::models::transaction::ARCHIVE_TABLE;
+
+/// A user whose balance differs between two databases.
+pub struct BalanceChange {
+    pub username: String,
+    pub before: f64,
+    pub after: f64,
+}
+
+/// A ledger row found in only one of two databases.
+pub struct LedgerEntry {
+    pub id: String,
+    pub username: String,
+    pub transaction_type: String,
+    pub amount: f64,
+    pub timestamp: String,
+}
+
+/// What changed between two copies of the wallet database, such as two
+/// backups, or a backup and the database it was restored into. Archived
+/// transactions count as present, so archiving does not show up as a
+/// difference.
+pub struct BackupDiff {
+    pub users_added: Vec<String>,
+    pub users_removed: Vec<String>,
+    pub balance_changes: Vec<BalanceChange>,
+    /// Transactions only in the second database.
+    pub transactions_added: Vec<LedgerEntry>,
+    /// Transactions only in the first database.
+    pub transactions_removed: Vec<LedgerEntry>,
+}
+
+impl BackupDiff {
+    /// Compares the databases at `before` and `after`, opening both read-only.
+    pub fn compare(before: &str, after: &str) -> Result<Self> {
+        let conn = database::open_read_only(before)?;
+        conn.execute("ATTACH DATABASE ? AS other", params![after])?;
+        Ok(BackupDiff {
+            users_added: Self::users_only_in(&conn, "other", "main")?,
+            users_removed: Self::users_only_in(&conn, "main", "other")?,
+            balance_changes: Self::balance_changes(&conn)?,
+            transactions_added: Self::ledger_only_in(&conn, "other", "main")?,
+            transactions_removed: Self::ledger_only_in(&conn, "main", "other")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users_added.is_empty()
+            && self.users_removed.is_empty()
+            && self.balance_changes.is_empty()
+            && self.transactions_added.is_empty()
+            && self.transactions_removed.is_empty()
+    }
+
+    fn users_only_in(conn: &Connection, schema: &str, other: &str) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT username FROM {0}.users
+            WHERE username NOT IN (SELECT username FROM {1}.users)
+            ORDER BY username",
+            schema, other
+        ))?;
+        let usernames = stmt.query_map([], |row| row.get(0))?;
+        usernames.collect()
+    }
+
+    fn balance_changes(conn: &Connection) -> Result<Vec<BalanceChange>> {
+        let mut stmt = conn.prepare(
+            "SELECT a.username, a.balance, b.balance
+            FROM main.users a JOIN other.users b ON b.username = a.username
+            WHERE abs(a.balance - b.balance) >= 0.005
+            ORDER BY a.username",
+        )?;
+        let changes = stmt.query_map([], |row| {
+            Ok(BalanceChange {
+                username: row.get(0)?,
+                before: row.get(1)?,
+                after: row.get(2)?,
+            })
+        })?;
+        changes.collect()
+    }
+
+    fn ledger_only_in(conn: &Connection, schema: &str, other: &str) -> Result<Vec<LedgerEntry>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM (
+                SELECT {3} FROM {0}.transactions UNION ALL SELECT {3} FROM {0}.{2}
+            )
+            WHERE id NOT IN (
+                SELECT id FROM {1}.transactions UNION ALL SELECT id FROM {1}.{2}
+            )
+            ORDER BY timestamp, id",
+            schema, other, ARCHIVE_TABLE, "id, username, transaction_type, amount, timestamp"
+        ))?;
+        let entries = stmt.query_map([], |row| {
+            Ok(LedgerEntry {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                transaction_type: row.get(2)?,
+                amount: row.get(3)?,
+                timestamp: row.get(4)?,
+            })
+        })?;
+        entries.collect()
+    }
+}
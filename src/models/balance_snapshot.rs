@@ -0,0 +1,99 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, Result};
+
+use crate::models::transaction::ARCHIVE_TABLE;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Each user's closing balance on every day their balance changed, so
+/// charts can read one row a day instead of going through the whole
+/// transaction log. Days without a row closed with the balance of the last
+/// day before them that has one.
+pub struct BalanceSnapshots;
+
+impl BalanceSnapshots {
+    /// Records the closing balance of every day before `today` that has
+    /// transactions but no snapshot yet, for every user, from the live and
+    /// archived transactions. Only days after a user's latest snapshot are
+    /// looked at, so running it again is cheap. Returns the number of days
+    /// recorded.
+    pub fn backfill(conn: &Connection, today: NaiveDate) -> Result<usize> {
+        conn.execute(
+            &format!(
+                "INSERT OR IGNORE INTO balance_snapshots (username, day, balance)
+                SELECT username, day, new_balance FROM (
+                    SELECT username, day, new_balance, ROW_NUMBER() OVER (
+                        PARTITION BY username, day ORDER BY timestamp DESC, seq DESC
+                    ) AS latest
+                    FROM (
+                        SELECT username, substr(timestamp, 1, 10) AS day, timestamp,
+                            new_balance, rowid AS seq
+                        FROM transactions
+                        UNION ALL
+                        SELECT username, substr(timestamp, 1, 10), timestamp,
+                            new_balance, rowid
+                        FROM {}
+                    ) t
+                    WHERE day < ?1 AND day > COALESCE(
+                        (SELECT MAX(day) FROM balance_snapshots s
+                            WHERE s.username = t.username),
+                        ''
+                    )
+                )
+                WHERE latest = 1",
+                ARCHIVE_TABLE
+            ),
+            params![today.format(DATE_FORMAT).to_string()],
+        )
+    }
+
+    /// Gets the user's closing balance on each day from `from` up to but not
+    /// including `to`, oldest first, carrying balances over days without
+    /// transactions. Empty if the user has no snapshots up to then. Days
+    /// that have not been backfilled yet are taken to have no transactions.
+    pub fn get_daily(
+        conn: &Connection,
+        username: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, f64)>> {
+        let mut stmt = conn.prepare(
+            "SELECT day, balance FROM balance_snapshots
+            WHERE username = ?1 AND day < ?3 AND day >= COALESCE(
+                (SELECT MAX(day) FROM balance_snapshots WHERE username = ?1 AND day < ?2),
+                ?2
+            )
+            ORDER BY day",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                username,
+                from.format(DATE_FORMAT).to_string(),
+                to.format(DATE_FORMAT).to_string()
+            ],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+        )?;
+
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let (day, balance) = row?;
+            if let Ok(day) = NaiveDate::parse_from_str(&day, DATE_FORMAT) {
+                snapshots.push((day, balance));
+            }
+        }
+
+        if snapshots.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut daily = Vec::new();
+        let mut balance = 0.0;
+        let mut next = snapshots.iter().peekable();
+        for day in from.iter_days().take_while(|day| *day < to) {
+            while let Some((_, closing)) = next.next_if(|(at, _)| *at <= day) {
+                balance = *closing;
+            }
+            daily.push((day, balance));
+        }
+        Ok(daily)
+    }
+}
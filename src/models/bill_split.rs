@@ -0,0 +1,98 @@
+use crate::models::money::Money;
+
+/// One person's part of a split bill.
+pub struct SplitShare {
+    pub username: String,
+    pub amount: f64,
+    /// Whether this share pays one of the cents left over when the bill
+    /// does not divide evenly.
+    pub extra_cent: bool,
+}
+
+/// A bill divided evenly between the user who paid it and the people they
+/// split it with, before payment requests for the shares are sent.
+pub struct BillSplit {
+    pub total: f64,
+    /// Shares in alphabetical order, which is also the order the left over
+    /// cents are handed out in, so the same bill always splits the same way.
+    pub shares: Vec<SplitShare>,
+}
+
+impl BillSplit {
+    pub fn new(total: f64, mut people: Vec<String>) -> Self {
+        people.sort();
+        people.dedup();
+        let even_cents = (total * 100.0).round() as i64 / people.len().max(1) as i64;
+        let amounts = Money::allocate(total, &vec![1.0; people.len()]);
+        let shares = people
+            .into_iter()
+            .zip(amounts)
+            .map(|(username, amount)| SplitShare {
+                username,
+                amount,
+                extra_cent: (amount * 100.0).round() as i64 > even_cents,
+            })
+            .collect();
+        BillSplit { total, shares }
+    }
+
+    /// Gets how many of the shares pay a cent more than the rest.
+    pub fn extra_cents(&self) -> usize {
+        self.shares.iter().filter(|share| share.extra_cent).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn shares(split: &BillSplit) -> Vec<(&str, f64, bool)> {
+        split
+            .shares
+            .iter()
+            .map(|share| (share.username.as_str(), share.amount, share.extra_cent))
+            .collect()
+    }
+
+    #[test]
+    fn extra_cents_go_in_alphabetical_order() {
+        let split = BillSplit::new(10.0, people(&["carol", "alice", "bob"]));
+        assert_eq!(
+            shares(&split),
+            vec![("alice", 3.34, true), ("bob", 3.33, false), ("carol", 3.33, false)]
+        );
+        assert_eq!(split.extra_cents(), 1);
+
+        let split = BillSplit::new(0.01, people(&["bob", "alice"]));
+        assert_eq!(shares(&split), vec![("alice", 0.01, true), ("bob", 0.0, false)]);
+        assert_eq!(split.extra_cents(), 1);
+    }
+
+    #[test]
+    fn an_even_bill_has_no_extra_cents() {
+        let split = BillSplit::new(9.0, people(&["alice", "bob", "carol"]));
+        assert!(split.shares.iter().all(|share| share.amount == 3.0));
+        assert_eq!(split.extra_cents(), 0);
+    }
+
+    #[test]
+    fn shares_add_up_to_the_total() {
+        for total in [0.01, 0.05, 10.0, 99.99, 1000.01] {
+            let split = BillSplit::new(total, people(&["a", "b", "c", "d", "e", "f"]));
+            let cents: i64 =
+                split.shares.iter().map(|share| (share.amount * 100.0).round() as i64).sum();
+            assert_eq!(cents, (total * 100.0).round() as i64, "{}", total);
+            assert_eq!(split.total, total);
+        }
+    }
+
+    #[test]
+    fn the_same_person_gets_one_share() {
+        let split = BillSplit::new(10.0, people(&["bob", "alice", "bob"]));
+        assert_eq!(shares(&split), vec![("alice", 5.0, false), ("bob", 5.0, false)]);
+    }
+}
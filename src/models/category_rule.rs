@@ -0,0 +1,119 @@
+use rusqlite::{params, Connection, Result};
+
+use crate::models::transaction::ARCHIVE_TABLE;
+
+/// Which part of a transaction a category rule looks at.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RuleField {
+    Recipient,
+    Sender,
+    /// The canonical merchant name given to imported external payments.
+    Merchant,
+}
+
+impl RuleField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleField::Recipient => "recipient",
+            RuleField::Sender => "sender",
+            RuleField::Merchant => "merchant",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "recipient" => Some(RuleField::Recipient),
+            "sender" => Some(RuleField::Sender),
+            "merchant" => Some(RuleField::Merchant),
+            _ => None,
+        }
+    }
+}
+
+/// A user's rule giving a category to their transactions whose recipient,
+/// sender or merchant contains some text, ignoring case, e.g. recipient contains "pizza"
+/// → Food. Rules only fill in a missing category and the oldest matching
+/// rule wins, so categories set by hand are never replaced.
+pub struct CategoryRule {
+    pub id: i64,
+    pub field: RuleField,
+    pub pattern: String,
+    pub category: String,
+}
+
+pub struct CategoryRules;
+
+impl CategoryRules {
+    pub fn create(
+        conn: &Connection,
+        username: &str,
+        field: RuleField,
+        pattern: &str,
+        category: &str,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO category_rules (username, field, pattern, category) VALUES (?, ?, ?, ?)",
+            params![username, field.as_str(), pattern, category],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, username: &str, id: i64) -> Result<bool> {
+        let deleted = conn.execute(
+            "DELETE FROM category_rules WHERE id = ? AND username = ?",
+            params![id, username],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Gets the user's rules in the order they are tried.
+    pub fn get_for_user(conn: &Connection, username: &str) -> Result<Vec<CategoryRule>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, field, pattern, category FROM category_rules
+            WHERE username = ? ORDER BY id",
+        )?;
+        let rules = stmt.query_map(params![username], |row| {
+            Ok((row.get(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get(3)?))
+        })?;
+
+        let mut parsed = Vec::new();
+        for rule in rules {
+            let (id, field, pattern, category) = rule?;
+            if let Some(field) = RuleField::parse(&field) {
+                parsed.push(CategoryRule {
+                    id,
+                    field,
+                    pattern,
+                    category,
+                });
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Applies the user's rules to their transactions without a category,
+    /// live and archived, or only to `transaction_id` if given. Returns how
+    /// many transactions were categorized.
+    pub fn apply(conn: &Connection, username: &str, transaction_id: Option<&str>) -> Result<usize> {
+        let mut categorized = 0;
+        for rule in Self::get_for_user(conn, username)? {
+            for table in ["transactions", ARCHIVE_TABLE] {
+                categorized += conn.execute(
+                    &format!(
+                        "INSERT INTO transaction_annotations (transaction_id, category)
+                        SELECT t.id, ?3 FROM {0} t
+                        LEFT JOIN transaction_annotations a ON a.transaction_id = t.id
+                        WHERE t.username = ?1 AND (?2 IS NULL OR t.id = ?2)
+                            AND COALESCE(a.category, '') = ''
+                            AND instr(lower(t.{1}), lower(?4)) > 0
+                        ON CONFLICT(transaction_id) DO UPDATE SET category = excluded.category",
+                        table,
+                        rule.field.as_str()
+                    ),
+                    params![username, transaction_id, rule.category, rule.pattern],
+                )?;
+            }
+        }
+        Ok(categorized)
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A child's account managed by a parent. The child signs in like any other
+/// user; the parent sets a weekly allowance sent automatically from their
+/// own balance and an optional cap on what the child can spend per week.
+pub struct ChildAccount {
+    pub child: String,
+    pub parent: String,
+    pub weekly_allowance: f64,
+    pub spending_cap: Option<f64>,
+    pub next_allowance_at: Option<NaiveDateTime>,
+}
+
+impl ChildAccount {
+    pub fn create(conn: &Connection, child: &str, parent: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO child_accounts (child, parent, weekly_allowance) VALUES (?, ?, 0)",
+            params![child, parent],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the account if `child` is managed by a parent.
+    pub fn get(conn: &Connection, child: &str) -> Result<Option<ChildAccount>> {
+        conn.query_row(
+            "SELECT child, parent, weekly_allowance, spending_cap, next_allowance_at
+            FROM child_accounts WHERE child = ?",
+            params![child],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    /// Gets the children managed by `parent`, by name.
+    pub fn get_for_parent(conn: &Connection, parent: &str) -> Result<Vec<ChildAccount>> {
+        let mut stmt = conn.prepare(
+            "SELECT child, parent, weekly_allowance, spending_cap, next_allowance_at
+            FROM child_accounts WHERE parent = ? ORDER BY child",
+        )?;
+        let children = stmt.query_map(params![parent], Self::from_row)?;
+        children.collect()
+    }
+
+    /// Sets the weekly allowance. A new allowance is first paid at `first_at`;
+    /// an allowance of 0 stops the payments.
+    pub fn set_allowance(
+        conn: &Connection,
+        child: &str,
+        amount: f64,
+        first_at: NaiveDateTime,
+    ) -> Result<()> {
+        conn.execute(
+            "UPDATE child_accounts
+            SET weekly_allowance = ?1,
+                next_allowance_at = CASE WHEN ?1 > 0 THEN COALESCE(next_allowance_at, ?2) END
+            WHERE child = ?3",
+            params![amount, first_at.format(TIMESTAMP_FORMAT).to_string(), child],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_spending_cap(conn: &Connection, child: &str, cap: Option<f64>) -> Result<()> {
+        conn.execute(
+            "UPDATE child_accounts SET spending_cap = ? WHERE child = ?",
+            params![cap, child],
+        )?;
+        Ok(())
+    }
+
+    /// Gets every child whose allowance is due.
+    pub fn get_allowances_due(conn: &Connection, now: NaiveDateTime) -> Result<Vec<ChildAccount>> {
+        let mut stmt = conn.prepare(
+            "SELECT child, parent, weekly_allowance, spending_cap, next_allowance_at
+            FROM child_accounts
+            WHERE weekly_allowance > 0 AND next_allowance_at <= ?
+            ORDER BY next_allowance_at",
+        )?;
+        let children = stmt.query_map(
+            params![now.format(TIMESTAMP_FORMAT).to_string()],
+            Self::from_row,
+        )?;
+        children.collect()
+    }
+
+    /// Schedules the next allowance, if it is still due at `due_at`.
+    /// Returns `false` if another instance already paid it.
+    pub fn advance_allowance(
+        conn: &Connection,
+        child: &str,
+        due_at: NaiveDateTime,
+        next_at: NaiveDateTime,
+    ) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE child_accounts SET next_allowance_at = ? WHERE child = ? AND next_allowance_at = ?",
+            params![
+                next_at.format(TIMESTAMP_FORMAT).to_string(),
+                child,
+                due_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<ChildAccount> {
+        let next_allowance_at: Option<String> = row.get(4)?;
+        Ok(ChildAccount {
+            child: row.get(0)?,
+            parent: row.get(1)?,
+            weekly_allowance: row.get(2)?,
+            spending_cap: row.get(3)?,
+            next_allowance_at: next_allowance_at
+                .and_then(|value| NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT).ok()),
+        })
+    }
+}
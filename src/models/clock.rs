@@ -0,0 +1,63 @@
+use chrono::{Duration, Local, NaiveDateTime};
+use std::cell::Cell;
+use std::time::Instant;
+
+/// Where the app gets the time. Everything time-based, such as settling
+/// payments, paying interest and allowances, expiring messages and locking
+/// idle sessions, asks the clock, so it can run on simulated time.
+pub trait Clock {
+    /// The local date and time, for anything stored or shown.
+    fn now(&self) -> NaiveDateTime;
+
+    /// A monotonic reading, for measuring how long something has waited.
+    fn instant(&self) -> Instant;
+}
+
+/// The computer's clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that stands still until it is moved forward, for running weeks
+/// of the wallet in seconds.
+pub struct VirtualClock {
+    start: NaiveDateTime,
+    origin: Instant,
+    elapsed: Cell<std::time::Duration>,
+}
+
+impl VirtualClock {
+    pub fn new(start: NaiveDateTime) -> Self {
+        VirtualClock {
+            start,
+            origin: Instant::now(),
+            elapsed: Cell::new(std::time::Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward. Going back is ignored, as a monotonic clock
+    /// never does.
+    pub fn advance(&self, by: Duration) {
+        if let Ok(by) = by.to_std() {
+            self.elapsed.set(self.elapsed.get() + by);
+        }
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> NaiveDateTime {
+        self.start + Duration::from_std(self.elapsed.get()).unwrap_or_else(|_| Duration::zero())
+    }
+
+    fn instant(&self) -> Instant {
+        self.origin + self.elapsed.get()
+    }
+}
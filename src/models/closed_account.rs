@@ -0,0 +1,65 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Accounts that have been closed. The user row is removed, so the name can
+/// no longer log in, receive money or be registered again, while the ledger
+/// rows stay for counterparties' histories. The final statement is kept
+/// with the closure record.
+pub struct ClosedAccounts;
+
+impl ClosedAccounts {
+    /// Records the closure and removes the user and their per-user state.
+    /// Open payment requests to or from them are declined and any child
+    /// account link or delegated access is dropped. Call inside the transaction that moved the
+    /// remaining balance out.
+    pub fn close(
+        conn: &Connection,
+        username: &str,
+        nominee: &str,
+        closed_at: NaiveDateTime,
+        statement: &str,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO closed_accounts (username, nominee, closed_at, statement)
+            VALUES (?, ?, ?, ?)",
+            params![
+                username,
+                nominee,
+                closed_at.format(TIMESTAMP_FORMAT).to_string(),
+                statement
+            ],
+        )?;
+        conn.execute(
+            "UPDATE payment_requests SET status = 'declined'
+            WHERE status = 'pending' AND (requester = ?1 OR payer = ?1)",
+            params![username],
+        )?;
+        conn.execute(
+            "DELETE FROM child_accounts WHERE child = ?1 OR parent = ?1",
+            params![username],
+        )?;
+        conn.execute("DELETE FROM user_settings WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM category_rules WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM input_history WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM account_pins WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM account_passwords WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM recovery_codes WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM net_worth_snapshots WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM sweep_rules WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM activity_log WHERE username = ?", params![username])?;
+        conn.execute(
+            "DELETE FROM delegations WHERE owner = ?1 OR delegate = ?1",
+            params![username],
+        )?;
+        conn.execute(
+            "DELETE FROM joint_members WHERE account = ?1 OR member = ?1",
+            params![username],
+        )?;
+        conn.execute("DELETE FROM sessions WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM notifications WHERE username = ?", params![username])?;
+        conn.execute("DELETE FROM users WHERE username = ?", params![username])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,987 @@
+use chrono::NaiveDateTime;
+use rand::Rng;
+use rusqlite::{ffi, params, Connection, OpenFlags, OptionalExtension, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Location of the wallet database unless the environment names another.
+pub const DB_PATH: &str = "ewallet.db";
+
+/// Directory holding database backups, newest picked for restores.
+pub const BACKUP_DIR: &str = "backups";
+
+/// Database file chosen for this run, if not `DB_PATH`.
+static CHOSEN_PATH: OnceLock<String> = OnceLock::new();
+
+/// Backups kept in the backup directory; older ones are deleted as new ones
+/// are taken.
+pub const BACKUPS_KEPT: usize = 10;
+
+/// How old the newest backup may get before another is taken, in hours.
+pub const BACKUP_INTERVAL_HOURS: i64 = 24;
+
+/// Names backup files, which start with this sortable timestamp.
+const BACKUP_NAME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Schema version written by this build, stored in `PRAGMA user_version`
+/// and in the `meta` table.
+pub const SCHEMA_VERSION: i64 = 40;
+
+/// Version of the app, stored in the `meta` table of every database it opens
+/// so a database shows which release last wrote it.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Schema migrations, applied in order to bring older databases up to
+/// `SCHEMA_VERSION`. Each entry is the version it produces and its SQL.
+const MIGRATIONS: &[(i64, &str)] = &[
+    (
+        1,
+        "CREATE TABLE IF NOT EXISTS users (
+            username TEXT PRIMARY KEY,
+            balance REAL NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS transactions (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            transaction_type TEXT NOT NULL,
+            amount REAL NOT NULL,
+            recipient TEXT,
+            sender TEXT,
+            previous_balance REAL NOT NULL,
+            new_balance REAL NOT NULL,
+            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
+        );",
+    ),
+    (
+        2,
+        "CREATE TABLE IF NOT EXISTS user_settings (
+            username TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (username, key)
+        );
+        CREATE TABLE IF NOT EXISTS input_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            field TEXT NOT NULL,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS transaction_annotations (
+            transaction_id TEXT PRIMARY KEY,
+            category TEXT,
+            memo TEXT,
+            note TEXT,
+            starred INTEGER NOT NULL DEFAULT 0
+        );",
+    ),
+    (
+        3,
+        "CREATE TABLE IF NOT EXISTS payment_requests (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            requester TEXT NOT NULL,
+            payer TEXT NOT NULL,
+            amount REAL NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            requester_notified INTEGER NOT NULL DEFAULT 0,
+            payer_notified INTEGER NOT NULL DEFAULT 0
+        );",
+    ),
+    (
+        4,
+        "ALTER TABLE transactions ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+        ALTER TABLE transactions ADD COLUMN fx_rate REAL NOT NULL DEFAULT 1.0;",
+    ),
+    (
+        5,
+        "CREATE TABLE IF NOT EXISTS pending_transfers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            sender TEXT NOT NULL,
+            recipient TEXT NOT NULL,
+            amount REAL NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );",
+    ),
+    (
+        6,
+        "CREATE INDEX IF NOT EXISTS idx_transactions_username_timestamp
+            ON transactions (username, timestamp);",
+    ),
+    (
+        7,
+        "CREATE TABLE IF NOT EXISTS transactions_archive (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            transaction_type TEXT NOT NULL,
+            amount REAL NOT NULL,
+            recipient TEXT,
+            sender TEXT,
+            previous_balance REAL NOT NULL,
+            new_balance REAL NOT NULL,
+            timestamp DATETIME,
+            currency TEXT NOT NULL DEFAULT 'USD',
+            fx_rate REAL NOT NULL DEFAULT 1.0
+        );
+        CREATE INDEX IF NOT EXISTS idx_transactions_archive_username_timestamp
+            ON transactions_archive (username, timestamp);",
+    ),
+    (
+        8,
+        "CREATE TABLE IF NOT EXISTS transaction_log (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id TEXT NOT NULL
+        );
+        INSERT INTO transaction_log (transaction_id)
+            SELECT id FROM transactions ORDER BY timestamp, rowid;
+        CREATE TABLE IF NOT EXISTS export_log_state (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            last_seq INTEGER NOT NULL,
+            last_checksum TEXT NOT NULL
+        );
+        CREATE TRIGGER IF NOT EXISTS transactions_log_insert
+            AFTER INSERT ON transactions
+            BEGIN
+                INSERT INTO transaction_log (transaction_id) VALUES (NEW.id);
+            END;",
+    ),
+    (
+        9,
+        "CREATE TABLE IF NOT EXISTS wallet_key (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            secret_key BLOB NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS offline_tokens (
+            nonce TEXT NOT NULL,
+            direction TEXT NOT NULL,
+            username TEXT NOT NULL,
+            amount REAL NOT NULL,
+            counterparty TEXT,
+            created_at TEXT NOT NULL,
+            PRIMARY KEY (nonce, direction)
+        );",
+    ),
+    (
+        10,
+        "CREATE TABLE IF NOT EXISTS user_keys (
+            username TEXT PRIMARY KEY,
+            public_key BLOB NOT NULL,
+            salt BLOB NOT NULL,
+            nonce BLOB NOT NULL,
+            encrypted_secret BLOB NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS transfer_signatures (
+            transaction_id TEXT PRIMARY KEY,
+            signature BLOB NOT NULL
+        );",
+    ),
+    (
+        11,
+        "ALTER TABLE users ADD COLUMN vault_balance REAL NOT NULL DEFAULT 0;
+        CREATE TABLE IF NOT EXISTS vault_withdrawals (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            amount REAL NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            available_at TEXT NOT NULL
+        );",
+    ),
+    (
+        12,
+        "CREATE TABLE IF NOT EXISTS child_accounts (
+            child TEXT PRIMARY KEY,
+            parent TEXT NOT NULL,
+            weekly_allowance REAL NOT NULL DEFAULT 0,
+            spending_cap REAL,
+            next_allowance_at TEXT
+        );",
+    ),
+    (
+        13,
+        "CREATE TABLE IF NOT EXISTS invoices (
+            code TEXT PRIMARY KEY,
+            merchant TEXT NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            paid_by TEXT
+        );
+        CREATE TABLE IF NOT EXISTS invoice_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            code TEXT NOT NULL,
+            description TEXT NOT NULL,
+            quantity INTEGER NOT NULL,
+            unit_price REAL NOT NULL
+        );",
+    ),
+    (
+        14,
+        "CREATE TABLE IF NOT EXISTS round_ups (
+            transaction_id TEXT PRIMARY KEY,
+            payment_id TEXT NOT NULL
+        );",
+    ),
+    (
+        15,
+        "CREATE TABLE IF NOT EXISTS transaction_rollups (
+            username TEXT NOT NULL,
+            month TEXT NOT NULL,
+            transactions INTEGER NOT NULL,
+            money_in REAL NOT NULL,
+            money_out REAL NOT NULL,
+            opening_balance REAL NOT NULL,
+            closing_balance REAL NOT NULL,
+            PRIMARY KEY (username, month)
+        );",
+    ),
+    (
+        16,
+        "CREATE TABLE IF NOT EXISTS account_pins (
+            username TEXT PRIMARY KEY,
+            salt BLOB NOT NULL,
+            pin_hash BLOB NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS sessions (
+            token_hash TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            last_used_at TEXT NOT NULL
+        );",
+    ),
+    (
+        17,
+        "CREATE TABLE IF NOT EXISTS holds (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            amount REAL NOT NULL,
+            description TEXT NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        );",
+    ),
+    (
+        18,
+        "ALTER TABLE transactions ADD COLUMN transfer_id TEXT;
+        ALTER TABLE transactions_archive ADD COLUMN transfer_id TEXT;
+        ALTER TABLE pending_transfers ADD COLUMN note TEXT;
+        CREATE TABLE IF NOT EXISTS transfer_notes (
+            transfer_id TEXT PRIMARY KEY,
+            note TEXT NOT NULL
+        );",
+    ),
+    // Links the legs of older transfers, pairing each received leg with the
+    // closest sent leg recorded before it with the same details.
+    (
+        19,
+        "UPDATE transactions SET transfer_id = id
+            WHERE transaction_type = 'transfer_out' AND transfer_id IS NULL;
+        UPDATE transactions AS t SET transfer_id = (
+            SELECT o.id FROM transactions o
+            WHERE o.transaction_type = 'transfer_out' AND o.rowid < t.rowid
+                AND o.sender IS t.sender AND o.recipient IS t.recipient
+                AND o.amount = t.amount AND o.timestamp = t.timestamp
+            ORDER BY o.rowid DESC
+            LIMIT 1
+        )
+        WHERE t.transaction_type = 'transfer_in' AND t.transfer_id IS NULL;
+        UPDATE transactions_archive SET transfer_id = id
+            WHERE transaction_type = 'transfer_out' AND transfer_id IS NULL;
+        UPDATE transactions_archive AS t SET transfer_id = (
+            SELECT o.id FROM transactions_archive o
+            WHERE o.transaction_type = 'transfer_out' AND o.rowid < t.rowid
+                AND o.sender IS t.sender AND o.recipient IS t.recipient
+                AND o.amount = t.amount AND o.timestamp = t.timestamp
+            ORDER BY o.rowid DESC
+            LIMIT 1
+        )
+        WHERE t.transaction_type = 'transfer_in' AND t.transfer_id IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_transactions_transfer_id
+            ON transactions (transfer_id);
+        CREATE INDEX IF NOT EXISTS idx_transactions_archive_transfer_id
+            ON transactions_archive (transfer_id);",
+    ),
+    (
+        20,
+        "ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 0;",
+    ),
+    (
+        21,
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            url TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL,
+            last_error TEXT
+        );
+        CREATE TRIGGER IF NOT EXISTS transactions_outbox_insert
+            AFTER INSERT ON transactions
+            BEGIN
+                INSERT INTO outbox (username, url, payload, next_attempt_at)
+                SELECT NEW.username, value, json_object(
+                    'v', 1,
+                    'id', NEW.id,
+                    'username', NEW.username,
+                    'type', NEW.transaction_type,
+                    'amount', NEW.amount,
+                    'currency', NEW.currency,
+                    'fx_rate', NEW.fx_rate,
+                    'sender', NEW.sender,
+                    'recipient', NEW.recipient,
+                    'previous_balance', NEW.previous_balance,
+                    'new_balance', NEW.new_balance,
+                    'timestamp', NEW.timestamp
+                ), strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime')
+                FROM user_settings
+                WHERE username = NEW.username AND key = 'webhook_url';
+            END;",
+    ),
+    (
+        22,
+        "CREATE TABLE IF NOT EXISTS closed_accounts (
+            username TEXT PRIMARY KEY,
+            nominee TEXT NOT NULL,
+            closed_at TEXT NOT NULL,
+            statement TEXT NOT NULL
+        );",
+    ),
+    (
+        23,
+        "CREATE TABLE IF NOT EXISTS notifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            message TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            read INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_notifications_username
+            ON notifications (username, read);",
+    ),
+    (
+        24,
+        "ALTER TABLE users ADD COLUMN last_login_at TEXT;",
+    ),
+    (
+        25,
+        "ALTER TABLE users ADD COLUMN overdrawn_since TEXT;
+        CREATE TRIGGER IF NOT EXISTS users_overdrawn_since
+            AFTER UPDATE OF balance ON users
+            WHEN (NEW.balance < -0.005) != (OLD.balance < -0.005)
+            BEGIN
+                UPDATE users SET overdrawn_since = CASE
+                    WHEN NEW.balance < -0.005
+                    THEN strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime')
+                END
+                WHERE username = NEW.username;
+            END;",
+    ),
+    (
+        26,
+        "CREATE TABLE IF NOT EXISTS processor_payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            amount REAL NOT NULL,
+            status TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            settles_at TEXT NOT NULL,
+            failure TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_processor_payments_status
+            ON processor_payments (status, settles_at);",
+    ),
+    (
+        27,
+        "ALTER TABLE users ADD COLUMN vault_interest_paid_on TEXT;",
+    ),
+    (
+        28,
+        "CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    ),
+    (
+        29,
+        "CREATE TABLE IF NOT EXISTS category_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            field TEXT NOT NULL,
+            pattern TEXT NOT NULL,
+            category TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_category_rules_username
+            ON category_rules (username);",
+    ),
+    (
+        30,
+        "ALTER TABLE transactions ADD COLUMN merchant TEXT;
+        ALTER TABLE transactions_archive ADD COLUMN merchant TEXT;",
+    ),
+    (
+        31,
+        "ALTER TABLE wallet_key ADD COLUMN salt BLOB;
+        ALTER TABLE wallet_key ADD COLUMN nonce BLOB;",
+    ),
+    (
+        32,
+        "CREATE TABLE IF NOT EXISTS account_passwords (
+            username TEXT PRIMARY KEY,
+            salt BLOB NOT NULL,
+            password_hash BLOB NOT NULL,
+            changed_at TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS recovery_codes (
+            username TEXT NOT NULL,
+            code_hash TEXT NOT NULL,
+            used_at TEXT,
+            PRIMARY KEY (username, code_hash)
+        );",
+    ),
+    (
+        33,
+        "CREATE TABLE IF NOT EXISTS delegations (
+            owner TEXT NOT NULL,
+            delegate TEXT NOT NULL,
+            granted_at TEXT NOT NULL,
+            PRIMARY KEY (owner, delegate)
+        );
+        CREATE INDEX IF NOT EXISTS idx_delegations_delegate ON delegations (delegate);",
+    ),
+    (
+        34,
+        "CREATE TABLE IF NOT EXISTS net_worth_snapshots (
+            username TEXT NOT NULL,
+            day TEXT NOT NULL,
+            balance REAL NOT NULL,
+            committed REAL NOT NULL,
+            vault REAL NOT NULL,
+            PRIMARY KEY (username, day)
+        );",
+    ),
+    (
+        35,
+        "CREATE TABLE IF NOT EXISTS balance_snapshots (
+            username TEXT NOT NULL,
+            day TEXT NOT NULL,
+            balance REAL NOT NULL,
+            PRIMARY KEY (username, day)
+        );",
+    ),
+    (
+        36,
+        "CREATE TABLE IF NOT EXISTS sweep_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            keep REAL NOT NULL,
+            created_at TEXT NOT NULL,
+            next_sweep_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sweep_rules_next ON sweep_rules (next_sweep_at);
+        CREATE TABLE IF NOT EXISTS sweep_transfers (
+            transaction_id TEXT PRIMARY KEY,
+            rule_id INTEGER NOT NULL
+        );",
+    ),
+    (
+        37,
+        "CREATE TABLE IF NOT EXISTS joint_members (
+            account TEXT NOT NULL,
+            member TEXT NOT NULL,
+            added_at TEXT NOT NULL,
+            PRIMARY KEY (account, member)
+        );
+        CREATE INDEX IF NOT EXISTS idx_joint_members_member ON joint_members (member);
+        ALTER TABLE pending_transfers ADD COLUMN needs_approval INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE pending_transfers ADD COLUMN decided_by TEXT;",
+    ),
+    (
+        38,
+        "CREATE TABLE IF NOT EXISTS activity_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            description TEXT NOT NULL,
+            amount REAL,
+            at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_activity_log_user ON activity_log (username, at);",
+    ),
+    (
+        39,
+        "ALTER TABLE recovery_codes ADD COLUMN key_salt BLOB;
+        ALTER TABLE recovery_codes ADD COLUMN key_nonce BLOB;
+        ALTER TABLE recovery_codes ADD COLUMN encrypted_key BLOB;",
+    ),
+    (
+        40,
+        "DROP TRIGGER IF EXISTS users_overdrawn_since;
+        DROP TRIGGER IF EXISTS transactions_outbox_insert;
+        CREATE TRIGGER transactions_outbox_insert
+            AFTER INSERT ON transactions
+            BEGIN
+                INSERT INTO outbox (username, url, payload, next_attempt_at)
+                SELECT NEW.username, value, json_object(
+                    'v', 1,
+                    'id', NEW.id,
+                    'username', NEW.username,
+                    'type', NEW.transaction_type,
+                    'amount', NEW.amount,
+                    'currency', NEW.currency,
+                    'fx_rate', NEW.fx_rate,
+                    'sender', NEW.sender,
+                    'recipient', NEW.recipient,
+                    'previous_balance', NEW.previous_balance,
+                    'new_balance', NEW.new_balance,
+                    'timestamp', NEW.timestamp
+                ), NEW.timestamp
+                FROM user_settings
+                WHERE username = NEW.username AND key = 'webhook_url';
+            END;",
+    ),
+];
+
+/// Columns the application relies on that may be missing from databases
+/// written by development builds. Missing ones are added on open.
+const REQUIRED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("transaction_annotations", "note", "TEXT"),
+    ("transaction_annotations", "starred", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+/// Columns holding usernames, rewritten to pseudonyms in analytics snapshots.
+const USERNAME_COLUMNS: &[(&str, &str)] = &[
+    ("users", "username"),
+    ("transactions", "username"),
+    ("transactions", "sender"),
+    ("transactions", "recipient"),
+    ("transactions_archive", "username"),
+    ("transactions_archive", "sender"),
+    ("transactions_archive", "recipient"),
+    ("payment_requests", "requester"),
+    ("payment_requests", "payer"),
+    ("pending_transfers", "sender"),
+    ("pending_transfers", "recipient"),
+    ("pending_transfers", "decided_by"),
+    ("offline_tokens", "username"),
+    ("offline_tokens", "counterparty"),
+    ("vault_withdrawals", "username"),
+    ("processor_payments", "username"),
+    ("child_accounts", "child"),
+    ("child_accounts", "parent"),
+    ("invoices", "merchant"),
+    ("invoices", "paid_by"),
+    ("transaction_rollups", "username"),
+    ("holds", "username"),
+    ("closed_accounts", "username"),
+    ("closed_accounts", "nominee"),
+    ("category_rules", "username"),
+    ("net_worth_snapshots", "username"),
+    ("balance_snapshots", "username"),
+    ("sweep_rules", "username"),
+    ("joint_members", "account"),
+    ("joint_members", "member"),
+    ("activity_log", "username"),
+];
+
+/// Opens the database at `path`, brings its schema up to date and stamps
+/// it with this version of the app. A database written by a newer version
+/// is refused rather than risk writing data it would misread. The database
+/// uses write-ahead logging so readers never block the writer.
+pub fn open(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    let version = schema_version(&conn)?;
+    if version > SCHEMA_VERSION {
+        let written_by = written_by(&conn)?
+            .map_or("a newer version".to_string(), |v| format!("version {}", v));
+        return Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_CANTOPEN),
+            Some(format!(
+                "{} has schema version {}, written by {}; this version only \
+                understands up to {}. Upgrade the app to open it",
+                path, version, written_by, SCHEMA_VERSION
+            )),
+        ));
+    }
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    migrate(&conn)?;
+    repair_columns(&conn)?;
+    stamp_versions(&conn)?;
+    Ok(conn)
+}
+
+/// Opens a read-only connection for reports, so long-running aggregation
+/// queries run beside writes on the main connection instead of blocking them.
+pub fn open_read_only(path: &str) -> Result<Connection> {
+    Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+}
+
+/// Gets SQLite's data version for the connection. It changes whenever
+/// another connection commits a write, so callers can tell when cached
+/// values may be stale.
+pub fn data_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA data_version", [], |row| row.get(0))
+}
+
+/// Gets the schema version recorded in the database.
+pub fn schema_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Gets the app version that last opened the database, as stored in the
+/// `meta` table. Databases from before the table existed have none.
+pub fn written_by(conn: &Connection) -> Result<Option<String>> {
+    let has_meta: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'meta')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_meta {
+        return Ok(None);
+    }
+    conn.query_row("SELECT value FROM meta WHERE key = 'app_version'", [], |row| row.get(0))
+        .optional()
+}
+
+/// Records this app and schema version in the `meta` table, writing only
+/// when they changed.
+fn stamp_versions(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "INSERT INTO meta (key, value) VALUES ('app_version', ?1), ('schema_version', ?2)
+        ON CONFLICT (key) DO UPDATE SET value = excluded.value WHERE value <> excluded.value",
+        params![APP_VERSION, SCHEMA_VERSION.to_string()],
+    )?;
+    Ok(())
+}
+
+fn migrate(conn: &Connection) -> Result<()> {
+    let current = schema_version(conn)?;
+    if current >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    for (version, sql) in MIGRATIONS.iter().filter(|(version, _)| *version > current) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn repair_columns(conn: &Connection) -> Result<()> {
+    for (table, column, definition) in REQUIRED_COLUMNS {
+        if !has_column(conn, table, column)? {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether a table has a column.
+pub fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?) WHERE name = ?)",
+        params![table, column],
+        |row| row.get(0),
+    )
+}
+
+/// Runs SQLite's integrity check on the database at `path`. Returns a
+/// description of the problem, or `None` if the database is healthy or does
+/// not exist yet.
+pub fn integrity_problem(path: &str) -> Option<String> {
+    if !Path::new(path).exists() {
+        return None;
+    }
+    let check = Connection::open(path).and_then(|conn| {
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<Result<Vec<String>>>()
+    });
+    match check {
+        Ok(rows) if rows.len() == 1 && rows[0] == "ok" => None,
+        Ok(rows) => Some(rows.join("; ")),
+        Err(err) => Some(err.to_string()),
+    }
+}
+
+/// Uses the database at `path` instead of `DB_PATH` for the rest of the
+/// run. Only the first call has an effect.
+pub fn use_path(path: &str) {
+    let _ = CHOSEN_PATH.set(path.to_string());
+}
+
+/// Gets the location of the database in use.
+pub fn path() -> &'static str {
+    CHOSEN_PATH.get().map_or(DB_PATH, String::as_str)
+}
+
+/// Gets the directory holding backups of the database in use. Databases
+/// other than `DB_PATH` keep theirs in a subdirectory of `BACKUP_DIR`
+/// named after the file, so a restore never picks another database's copy.
+pub fn backup_dir() -> PathBuf {
+    let dir = Path::new(BACKUP_DIR);
+    match Path::new(path()).file_stem() {
+        Some(stem) if path() != DB_PATH => dir.join(stem),
+        _ => dir.to_path_buf(),
+    }
+}
+
+/// Lists the backup files in the backup directory, oldest first. Backup
+/// file names start with a sortable timestamp, so name order is age order.
+pub fn list_backups() -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = match fs::read_dir(backup_dir()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "db"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    backups.sort();
+    backups
+}
+
+/// Finds the most recent backup file in the backup directory.
+pub fn latest_backup() -> Option<PathBuf> {
+    list_backups().pop()
+}
+
+/// Gets when a backup was taken, from its file name.
+pub fn backup_taken_at(backup: &Path) -> Option<NaiveDateTime> {
+    let stem = backup.file_stem()?.to_str()?;
+    NaiveDateTime::parse_from_str(stem, BACKUP_NAME_FORMAT).ok()
+}
+
+/// Whether the newest backup is older than `BACKUP_INTERVAL_HOURS`, or
+/// there is none.
+pub fn backup_due(now: NaiveDateTime) -> bool {
+    match latest_backup().and_then(|backup| backup_taken_at(&backup)) {
+        Some(taken_at) => now - taken_at >= chrono::Duration::hours(BACKUP_INTERVAL_HOURS),
+        None => true,
+    }
+}
+
+/// Creates the backup directory if needed and names the backup taken at
+/// `now`. Fails if a backup was already taken that second.
+pub fn new_backup_path(now: NaiveDateTime) -> io::Result<PathBuf> {
+    let dir = backup_dir();
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.db", now.format(BACKUP_NAME_FORMAT)));
+    if path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        ));
+    }
+    Ok(path)
+}
+
+/// Copies the database to `path`. `VACUUM INTO` writes a consistent copy
+/// even while other connections are writing.
+pub fn backup(conn: &Connection, path: &Path) -> Result<()> {
+    conn.execute("VACUUM INTO ?", params![path.to_string_lossy()])?;
+    Ok(())
+}
+
+/// Deletes all but the newest `keep` backups. Returns how many were deleted.
+pub fn prune_backups(keep: usize) -> io::Result<usize> {
+    let backups = list_backups();
+    let excess = backups.len().saturating_sub(keep);
+    for backup in &backups[..excess] {
+        fs::remove_file(backup)?;
+    }
+    Ok(excess)
+}
+
+/// Replaces the database at `path` with a backup. The replaced file, and
+/// any write-ahead log files that belong to it, are kept next to it with
+/// `suffix` appended, e.g. `.corrupt`, for inspection.
+pub fn restore_backup(backup: &Path, path: &str, suffix: &str) -> io::Result<()> {
+    for file in [path.to_string(), format!("{}-wal", path), format!("{}-shm", path)] {
+        if Path::new(&file).exists() {
+            fs::rename(&file, format!("{}{}", file, suffix))?;
+        }
+    }
+    fs::copy(backup, path)?;
+    Ok(())
+}
+
+/// Gets the size of the database in bytes, not counting the write-ahead log.
+pub fn size(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Rebuilds every index, refreshes the statistics the query planner uses
+/// and rewrites the file without free pages. Returns the size in bytes
+/// before and after.
+pub fn maintain(conn: &Connection) -> Result<(i64, i64)> {
+    let before = size(conn)?;
+    conn.execute_batch("REINDEX; ANALYZE; VACUUM;")?;
+    Ok((before, size(conn)?))
+}
+
+/// Writes an anonymized copy of the database to `path` for analytics.
+/// Each username is replaced by a random pseudonym, so rows from the same
+/// user still group together. The pseudonyms are made up for this snapshot
+/// and never kept, so they cannot be guessed from a username or matched
+/// across snapshots. Free text that may identify people (memos, notes,
+/// activity descriptions, invoice items, rule patterns, input history and
+/// settings) is dropped.
+pub fn snapshot(conn: &Connection, path: &str) -> Result<()> {
+    conn.execute("VACUUM INTO ?", params![path])?;
+    let snapshot = Connection::open(path)?;
+    let tx = snapshot.unchecked_transaction()?;
+
+    let usernames: Vec<String> = {
+        let query = USERNAME_COLUMNS
+            .iter()
+            .map(|(table, column)| {
+                format!("SELECT {1} FROM {0} WHERE {1} IS NOT NULL", table, column)
+            })
+            .collect::<Vec<_>>()
+            .join(" UNION ");
+        let mut stmt = tx.prepare(&query)?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<Result<_>>()?
+    };
+    let mut rng = rand::thread_rng();
+    // Usernames count as taken, so no pseudonym can be mistaken for one.
+    let mut taken: HashSet<String> = usernames.iter().cloned().collect();
+    for username in &usernames {
+        let pseudonym = loop {
+            let pseudonym = format!("user-{:016x}", rng.gen::<u64>());
+            if taken.insert(pseudonym.clone()) {
+                break pseudonym;
+            }
+        };
+        for (table, column) in USERNAME_COLUMNS {
+            tx.execute(
+                &format!("UPDATE {0} SET {1} = ?1 WHERE {1} = ?2", table, column),
+                params![pseudonym, username],
+            )?;
+        }
+    }
+    tx.execute_batch(
+        "UPDATE transaction_annotations SET memo = NULL, note = NULL;
+        UPDATE pending_transfers SET note = NULL;
+        UPDATE activity_log SET description = kind;
+        UPDATE invoice_items SET description = '';
+        UPDATE category_rules SET pattern = '';
+        DELETE FROM transfer_notes;
+        DELETE FROM input_history;
+        DELETE FROM user_settings;
+        DELETE FROM wallet_key;
+        DELETE FROM user_keys;
+        DELETE FROM transfer_signatures;
+        DELETE FROM account_pins;
+        DELETE FROM account_passwords;
+        DELETE FROM recovery_codes;
+        DELETE FROM delegations;
+        DELETE FROM sessions;
+        DELETE FROM outbox;
+        DELETE FROM notifications;
+        UPDATE closed_accounts SET statement = '';",
+    )?;
+    tx.commit()?;
+    snapshot.execute_batch("VACUUM")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::activity::{ActivityKind, ActivityLog};
+    use crate::models::annotation::Annotation;
+    use crate::models::invoice::{Invoice, LineItem};
+    use crate::models::transaction::Transaction;
+    use crate::models::user::User;
+    use chrono::NaiveDate;
+
+    const ALICE: &str = "alice_smith";
+    const BOB: &str = "bob_jones";
+
+    /// Takes a snapshot of `conn` and returns the file's bytes and the
+    /// usernames in its `users` table.
+    fn take_snapshot(conn: &Connection, name: &str) -> (Vec<u8>, Vec<String>) {
+        let path = std::env::temp_dir()
+            .join(format!("ewallet-snapshot-{}-{}.db", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        snapshot(conn, path).unwrap();
+        let bytes = fs::read(path).unwrap();
+        let usernames = User::get_usernames(&Connection::open(path).unwrap()).unwrap();
+        fs::remove_file(path).unwrap();
+        (bytes, usernames)
+    }
+
+    fn contains(bytes: &[u8], text: &str) -> bool {
+        bytes.windows(text.len()).any(|window| window == text.as_bytes())
+    }
+
+    #[test]
+    fn snapshot_leaves_no_username_behind() {
+        let conn = open(":memory:").unwrap();
+        let now = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        User::create(&conn, ALICE).unwrap();
+        User::create(&conn, BOB).unwrap();
+        let transfer = Transaction {
+            id: "t1".to_string(),
+            username: ALICE.to_string(),
+            transaction_type: "transfer_out".to_string(),
+            amount: 10.0,
+            recipient: Some(BOB.to_string()),
+            sender: Some(ALICE.to_string()),
+            previous_balance: 10.0,
+            new_balance: 0.0,
+            timestamp: now,
+            currency: "USD".to_string(),
+            fx_rate: 1.0,
+            transfer_id: Some("t1".to_string()),
+            merchant: None,
+        };
+        Transaction::create(&conn, &transfer).unwrap();
+        Transaction::set_transfer_note(&conn, "t1", &format!("rent for {}", BOB)).unwrap();
+        Annotation::set_note(&conn, "t1", Some(&format!("{} paid me back", BOB))).unwrap();
+        let description = format!("Shared the account with {}", BOB);
+        ActivityLog::record(&conn, ALICE, ActivityKind::Limit, &description, None, now).unwrap();
+        let item = LineItem::parse(&format!("Lunch with {}, 5", BOB)).unwrap();
+        Invoice::create(&conn, ALICE, &[item], now, now, &mut rand::thread_rng()).unwrap();
+        conn.execute(
+            "INSERT INTO category_rules (username, field, pattern, category)
+            VALUES (?1, 'recipient', ?2, 'rent')",
+            params![ALICE, BOB],
+        )
+        .unwrap();
+
+        let (bytes, usernames) = take_snapshot(&conn, "names");
+        assert_eq!(usernames.len(), 2);
+        assert!(!contains(&bytes, ALICE));
+        assert!(!contains(&bytes, BOB));
+    }
+
+    #[test]
+    fn each_snapshot_has_its_own_pseudonyms() {
+        let conn = open(":memory:").unwrap();
+        User::create(&conn, ALICE).unwrap();
+        let (_, first) = take_snapshot(&conn, "first");
+        let (_, second) = take_snapshot(&conn, "second");
+        assert_ne!(first, second);
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result, Row};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Read-only access to a user's transaction history and statements that
+/// they gave another user, such as their accountant. The delegate logs in
+/// as themselves and can look, but never move money or change anything.
+pub struct Delegation {
+    pub owner: String,
+    pub delegate: String,
+    pub granted_at: NaiveDateTime,
+}
+
+impl Delegation {
+    /// Gives `delegate` access to `owner`'s history. Returns `false` if they
+    /// already have it.
+    pub fn grant(
+        conn: &Connection,
+        owner: &str,
+        delegate: &str,
+        now: NaiveDateTime,
+    ) -> Result<bool> {
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO delegations (owner, delegate, granted_at) VALUES (?, ?, ?)",
+            params![owner, delegate, now.format(TIMESTAMP_FORMAT).to_string()],
+        )?;
+        Ok(added > 0)
+    }
+
+    pub fn revoke(conn: &Connection, owner: &str, delegate: &str) -> Result<bool> {
+        let removed = conn.execute(
+            "DELETE FROM delegations WHERE owner = ? AND delegate = ?",
+            params![owner, delegate],
+        )?;
+        Ok(removed > 0)
+    }
+
+    pub fn is_granted(conn: &Connection, owner: &str, delegate: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM delegations WHERE owner = ? AND delegate = ?)",
+            params![owner, delegate],
+            |row| row.get(0),
+        )
+    }
+
+    /// Gets who `owner` gave access to, by name.
+    pub fn get_delegates(conn: &Connection, owner: &str) -> Result<Vec<Delegation>> {
+        let mut stmt = conn.prepare(
+            "SELECT owner, delegate, granted_at FROM delegations
+            WHERE owner = ? ORDER BY delegate",
+        )?;
+        let delegations = stmt.query_map(params![owner], Self::from_row)?;
+        delegations.collect()
+    }
+
+    /// Gets the accounts `delegate` was given access to, by owner.
+    pub fn get_shared_with(conn: &Connection, delegate: &str) -> Result<Vec<Delegation>> {
+        let mut stmt = conn.prepare(
+            "SELECT owner, delegate, granted_at FROM delegations
+            WHERE delegate = ? ORDER BY owner",
+        )?;
+        let delegations = stmt.query_map(params![delegate], Self::from_row)?;
+        delegations.collect()
+    }
+
+    fn from_row(row: &Row) -> Result<Delegation> {
+        let granted_at: String = row.get(2)?;
+        Ok(Delegation {
+            owner: row.get(0)?,
+            delegate: row.get(1)?,
+            granted_at: NaiveDateTime::parse_from_str(&granted_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+        })
+    }
+}
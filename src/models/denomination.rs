@@ -0,0 +1,41 @@
+/// How a cash withdrawal is paid out: the number of each note, largest
+/// first, and whatever is too small for the smallest note.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Breakdown {
+    pub notes: Vec<(f64, u64)>,
+    pub remainder: f64,
+}
+
+impl Breakdown {
+    pub fn note_count(&self) -> u64 {
+        self.notes.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// Breaks `amount` down into `denominations` the way an ATM does: as many
+/// of the largest note as fit, then as many of the next, and so on. Works
+/// in whole cents, so amounts such as 0.10 are not lost to rounding.
+///
+/// Greedy is what cash machines do and gives the fewest notes for usual
+/// sets such as 100/50/20/10, but not for every set: with only 50s and 20s,
+/// 60 comes out as one 50 with 10 left over rather than three 20s.
+pub fn breakdown(amount: f64, denominations: &[f64]) -> Breakdown {
+    let cents = |value: f64| (value * 100.0).round().max(0.0) as u64;
+    let mut notes: Vec<f64> =
+        denominations.iter().copied().filter(|&note| cents(note) > 0).collect();
+    notes.sort_by(|a, b| b.total_cmp(a));
+
+    let mut left = cents(amount);
+    let mut counts = Vec::new();
+    for note in notes {
+        let count = left / cents(note);
+        if count > 0 {
+            counts.push((note, count));
+            left -= count * cents(note);
+        }
+    }
+    Breakdown {
+        notes: counts,
+        remainder: left as f64 / 100.0,
+    }
+}
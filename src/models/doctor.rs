@@ -0,0 +1,297 @@
+use rusqlite::{params, Connection, Result};
+use std::path::Path;
+
+use crate::models::database::{self, SCHEMA_VERSION};
+use crate::models::transaction::ARCHIVE_TABLE;
+
+/// Indexes the application relies on for fast history queries.
+const EXPECTED_INDEXES: &[&str] = &[
+    "idx_transactions_username_timestamp",
+    "idx_transactions_archive_username_timestamp",
+];
+
+/// Most drifting or orphaned rows named in a single finding.
+const MAX_EXAMPLES: usize = 5;
+
+/// A check run against an open database.
+type Check = fn(&Connection) -> Result<Finding>;
+
+/// How urgent a finding is. Reports list the most urgent first.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Severity {
+    Critical,
+    Warning,
+    Ok,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Critical => "CRITICAL",
+            Severity::Warning => "WARNING",
+            Severity::Ok => "OK",
+        }
+    }
+}
+
+/// The outcome of one check.
+pub struct Finding {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub detail: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, check: &'static str, detail: impl Into<String>) -> Self {
+        Finding {
+            severity,
+            check,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Self-diagnostics for the wallet database, behind the `doctor` command.
+pub struct Doctor;
+
+impl Doctor {
+    /// Runs every check against the database at `path` through a read-only
+    /// connection and returns the findings, most urgent first.
+    pub fn examine(path: &str) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if !Path::new(path).exists() {
+            findings.push(Finding::new(
+                Severity::Critical,
+                "database",
+                format!("{} does not exist", path),
+            ));
+            return findings;
+        }
+        let conn = match database::open_read_only(path).and_then(|conn| {
+            conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))?;
+            Ok(conn)
+        }) {
+            Ok(conn) => conn,
+            Err(err) => {
+                findings.push(Finding::new(
+                    Severity::Critical,
+                    "database",
+                    format!("cannot read {}: {}", path, err),
+                ));
+                return findings;
+            }
+        };
+        findings.push(Finding::new(Severity::Ok, "database", format!("{} is readable", path)));
+
+        findings.push(match database::integrity_problem(path) {
+            Some(problem) => Finding::new(Severity::Critical, "integrity", problem),
+            None => Finding::new(Severity::Ok, "integrity", "integrity check passed"),
+        });
+
+        let checks: [(&'static str, Check); 5] = [
+            ("schema", Self::check_schema),
+            ("indexes", Self::check_indexes),
+            ("wal", Self::check_wal),
+            ("orphans", Self::check_orphans),
+            ("balances", Self::check_drift),
+        ];
+        for (name, check) in checks {
+            findings.push(check(&conn).unwrap_or_else(|err| {
+                Finding::new(Severity::Critical, name, format!("check failed: {}", err))
+            }));
+        }
+
+        findings.sort_by_key(|finding| finding.severity);
+        findings
+    }
+
+    fn check_schema(conn: &Connection) -> Result<Finding> {
+        let version = database::schema_version(conn)?;
+        let written_by = database::written_by(conn)?
+            .map_or("unknown".to_string(), |v| format!("version {}", v));
+        Ok(if version == SCHEMA_VERSION {
+            Finding::new(
+                Severity::Ok,
+                "schema",
+                format!("version {}, written by app {}", version, written_by),
+            )
+        } else if version < SCHEMA_VERSION {
+            Finding::new(
+                Severity::Warning,
+                "schema",
+                format!(
+                    "version {}, expected {}; the app offers to upgrade it when it starts",
+                    version, SCHEMA_VERSION
+                ),
+            )
+        } else {
+            Finding::new(
+                Severity::Critical,
+                "schema",
+                format!(
+                    "version {}, written by app {}, is newer than this build understands ({})",
+                    version, written_by, SCHEMA_VERSION
+                ),
+            )
+        })
+    }
+
+    fn check_indexes(conn: &Connection) -> Result<Finding> {
+        let mut missing = Vec::new();
+        for index in EXPECTED_INDEXES {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'index' AND name = ?)",
+                params![index],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                missing.push(*index);
+            }
+        }
+        Ok(if missing.is_empty() {
+            Finding::new(Severity::Ok, "indexes", "all present")
+        } else {
+            let detail = format!("missing {}", missing.join(", "));
+            Finding::new(Severity::Warning, "indexes", detail)
+        })
+    }
+
+    fn check_wal(conn: &Connection) -> Result<Finding> {
+        let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        Ok(if mode.eq_ignore_ascii_case("wal") {
+            Finding::new(Severity::Ok, "wal", "write-ahead logging is on")
+        } else {
+            Finding::new(
+                Severity::Warning,
+                "wal",
+                format!("journal mode is {}; readers may block writes", mode),
+            )
+        })
+    }
+
+    /// Looks for ledger rows naming users that do not exist. Only transfers
+    /// are checked for counterparties, since token senders may come from
+    /// other instances.
+    fn check_orphans(conn: &Connection) -> Result<Finding> {
+        let ledger = Self::ledger_tables(conn)?
+            .iter()
+            .map(|table| {
+                format!(
+                    "SELECT id, username, transaction_type, sender, recipient, timestamp FROM {}",
+                    table
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" UNION ALL ");
+        // Closed accounts keep their ledger rows but no longer have a user row
+        let known_users = if Self::table_exists(conn, "closed_accounts")? {
+            "SELECT username FROM users UNION ALL SELECT username FROM closed_accounts"
+        } else {
+            "SELECT username FROM users"
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id FROM ({0}) t
+            WHERE t.username NOT IN ({1})
+                OR (t.transaction_type IN ('transfer_in', 'transfer_out')
+                    AND (t.sender NOT IN ({1}) OR t.recipient NOT IN ({1})))
+            ORDER BY t.timestamp",
+            ledger, known_users
+        ))?;
+        let orphans = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>>>()?;
+        Ok(if orphans.is_empty() {
+            Finding::new(Severity::Ok, "orphans", "every transaction belongs to known users")
+        } else {
+            Finding::new(
+                Severity::Warning,
+                "orphans",
+                format!(
+                    "{} transaction(s) name users that do not exist, e.g. {}",
+                    orphans.len(),
+                    Self::examples(&orphans)
+                ),
+            )
+        })
+    }
+
+    /// Compares each balance with the new balance of the user's latest
+    /// transaction, or with the closing balance of their latest monthly
+    /// rollup once every transaction has been purged.
+    fn check_drift(conn: &Connection) -> Result<Finding> {
+        let latest = |table: &str| {
+            format!(
+                "(SELECT new_balance FROM {} WHERE username = u.username
+                ORDER BY timestamp DESC, rowid DESC LIMIT 1)",
+                table
+            )
+        };
+        let mut ledger: Vec<String> =
+            Self::ledger_tables(conn)?.into_iter().map(latest).collect();
+        if Self::table_exists(conn, "transaction_rollups")? {
+            ledger.push(
+                "(SELECT closing_balance FROM transaction_rollups WHERE username = u.username
+                ORDER BY month DESC LIMIT 1)"
+                    .to_string(),
+            );
+        }
+        ledger.push("0".to_string());
+        let mut stmt = conn.prepare(&format!(
+            "SELECT username, balance, ledger FROM (
+                SELECT u.username, u.balance, COALESCE({}) AS ledger FROM users u
+            )
+            WHERE ABS(balance - ledger) >= 0.005
+            ORDER BY username",
+            ledger.join(", ")
+        ))?;
+        let drifted = stmt
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} ({:.2} vs ledger {:.2})",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?
+                ))
+            })?
+            .collect::<Result<Vec<String>>>()?;
+        Ok(if drifted.is_empty() {
+            Finding::new(Severity::Ok, "balances", "every balance matches its ledger")
+        } else {
+            Finding::new(
+                Severity::Critical,
+                "balances",
+                format!(
+                    "{} balance(s) differ from the ledger: {}",
+                    drifted.len(),
+                    Self::examples(&drifted)
+                ),
+            )
+        })
+    }
+
+    /// Gets the tables holding transactions, live ones first. Databases
+    /// that have not been migrated yet may have no archive.
+    fn ledger_tables(conn: &Connection) -> Result<Vec<&'static str>> {
+        let mut tables = vec!["transactions"];
+        if Self::table_exists(conn, ARCHIVE_TABLE)? {
+            tables.push(ARCHIVE_TABLE);
+        }
+        Ok(tables)
+    }
+
+    fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+            params![table],
+            |row| row.get(0),
+        )
+    }
+
+    fn examples(items: &[String]) -> String {
+        let mut shown = items[..items.len().min(MAX_EXAMPLES)].join(", ");
+        if items.len() > MAX_EXAMPLES {
+            shown.push_str(", ...");
+        }
+        shown
+    }
+}
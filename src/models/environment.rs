@@ -0,0 +1,113 @@
+use std::fs;
+
+use crate::models::database::DB_PATH;
+use crate::models::key_provider::KeySource;
+
+/// File the environments are defined in, in the directory the app is
+/// started from.
+pub const ENVIRONMENTS_PATH: &str = "config.toml";
+
+/// Environment variable naming the environment when `--env` is not given.
+pub const ENV_VAR: &str = "EWALLET_ENV";
+
+/// How much the daemon and other long-running commands print, least first.
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// Settings that differ between running the wallet for development, for a
+/// demo and for real, such as `dev`, `demo` and `prod`. Each environment is
+/// a section of `config.toml`; settings it leaves out keep the defaults,
+/// which are also used when no environment is chosen.
+pub struct Environment {
+    pub name: String,
+    /// Database file, relative to the profile directory.
+    pub database: String,
+    pub log_level: LogLevel,
+    /// Whether an empty database is filled with demo users on start.
+    pub seed: bool,
+    /// Whether admin commands that delete or overwrite data may run.
+    pub destructive_commands: bool,
+    /// Where the passphrase sealing the wallet's own signing key comes from.
+    pub wallet_key: KeySource,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            name: "default".to_string(),
+            database: DB_PATH.to_string(),
+            log_level: LogLevel::Info,
+            seed: false,
+            destructive_commands: true,
+            wallet_key: KeySource::default(),
+        }
+    }
+}
+
+impl Environment {
+    /// Reads the environment called `name` from the file at `path`.
+    pub fn load(path: &str, name: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let table: toml::Table = text
+            .parse()
+            .map_err(|err: toml::de::Error| format!("{}: {}", path, err.message()))?;
+        let settings = match table.get(name) {
+            Some(toml::Value::Table(settings)) => settings,
+            Some(_) => return Err(format!("{}: '{}' is not a section", path, name)),
+            None => return Err(format!("no environment '{}' in {}", name, path)),
+        };
+        let mut environment = Environment {
+            name: name.to_string(),
+            ..Environment::default()
+        };
+        for (key, value) in settings {
+            let invalid = || format!("{}: invalid {}.{}", path, name, key);
+            match (key.as_str(), value) {
+                ("database", toml::Value::String(database)) if !database.is_empty() => {
+                    environment.database = database.clone()
+                }
+                ("log_level", toml::Value::String(level)) => {
+                    environment.log_level = LogLevel::parse(level).ok_or_else(invalid)?
+                }
+                ("seed", toml::Value::Boolean(seed)) => environment.seed = *seed,
+                ("destructive_commands", toml::Value::Boolean(allowed)) => {
+                    environment.destructive_commands = *allowed
+                }
+                ("wallet_key", toml::Value::String(source)) => {
+                    environment.wallet_key = KeySource::parse(source).ok_or_else(invalid)?
+                }
+                ("database" | "log_level" | "seed" | "destructive_commands" | "wallet_key", _) => {
+                    return Err(invalid())
+                }
+                _ => return Err(format!("{}: unknown setting {}.{}", path, name, key)),
+            }
+        }
+        Ok(environment)
+    }
+}
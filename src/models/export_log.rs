@@ -0,0 +1,323 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Directory the export log is written to.
+pub const EXPORT_LOG_DIR: &str = "export-log";
+
+/// Name of the log file currently being appended to.
+const CURRENT_FILE: &str = "transactions.ndjson";
+
+/// Size after which the current file is rotated, in bytes.
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Append-only log of every committed transaction, one JSON object per line,
+/// for external systems to tail.
+///
+/// Each line carries `seq`, the transaction's position in the
+/// `transaction_log` table, and `checksum`, the hex SHA-256 of the previous
+/// line's checksum followed by the line serialized without its checksum.
+/// The chain continues across rotated files, so a reader can check that no
+/// line was lost or changed. Lines are never rewritten: anonymizing a user
+/// appends a `redaction` line listing the seqs that name them. The current
+/// file is rotated to `transactions-<timestamp>.ndjson` when it passes 10 MB
+/// or the day changes.
+///
+/// The last exported seq and checksum are kept in `export_log_state`, and
+/// each append happens inside a write transaction, so several running
+/// instances take turns instead of interleaving lines.
+pub struct ExportLog {
+    dir: PathBuf,
+}
+
+impl ExportLog {
+    /// Opens the log in `dir`, creating the directory if needed.
+    pub fn open(dir: &str) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        Ok(ExportLog {
+            dir: PathBuf::from(dir),
+        })
+    }
+
+    /// Appends every transaction committed since the last call. Returns the
+    /// number of lines written.
+    pub fn sync(&self, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+        let newest: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(seq), 0) FROM transaction_log",
+            [],
+            |row| row.get(0),
+        )?;
+        if newest <= Self::last_exported(conn)?.0 {
+            return Ok(0);
+        }
+
+        let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        let (mut last_seq, mut last_checksum) = Self::last_exported(&tx)?;
+
+        // Rows may have been archived since they were committed, so both
+        // tables are read.
+        let mut stmt = tx.prepare(
+            "SELECT l.seq, t.id, t.username, t.transaction_type, t.amount, t.recipient, t.sender,
+                t.previous_balance, t.new_balance, t.timestamp, t.currency, t.fx_rate
+            FROM transaction_log l
+            JOIN transactions t ON t.id = l.transaction_id
+            WHERE l.seq > ?1
+            UNION ALL
+            SELECT l.seq, t.id, t.username, t.transaction_type, t.amount, t.recipient, t.sender,
+                t.previous_balance, t.new_balance, t.timestamp, t.currency, t.fx_rate
+            FROM transaction_log l
+            JOIN transactions_archive t ON t.id = l.transaction_id
+            WHERE l.seq > ?1
+            ORDER BY 1",
+        )?;
+        let entries = stmt
+            .query_map(params![last_seq], |row| {
+                Ok(json!({
+                    "seq": row.get::<_, i64>(0)?,
+                    "id": row.get::<_, String>(1)?,
+                    "username": row.get::<_, String>(2)?,
+                    "type": row.get::<_, String>(3)?,
+                    "amount": row.get::<_, f64>(4)?,
+                    "recipient": row.get::<_, Option<String>>(5)?,
+                    "sender": row.get::<_, Option<String>>(6)?,
+                    "previous_balance": row.get::<_, f64>(7)?,
+                    "new_balance": row.get::<_, f64>(8)?,
+                    "timestamp": row.get::<_, String>(9)?,
+                    "currency": row.get::<_, String>(10)?,
+                    "fx_rate": row.get::<_, f64>(11)?,
+                }))
+            })?
+            .collect::<rusqlite::Result<Vec<Value>>>()?;
+        drop(stmt);
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        self.rotate_if_needed()?;
+        let path = self.dir.join(CURRENT_FILE);
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut lines = String::new();
+        for mut entry in entries {
+            let body = serde_json::to_string(&entry)?;
+            let checksum = chain_checksum(&last_checksum, &body);
+            entry["checksum"] = Value::String(checksum.clone());
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+            last_seq = entry["seq"].as_i64().unwrap_or(last_seq);
+            last_checksum = checksum;
+        }
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+
+        tx.execute(
+            "INSERT INTO export_log_state (id, last_seq, last_checksum) VALUES (1, ?1, ?2)
+            ON CONFLICT(id) DO UPDATE SET last_seq = ?1, last_checksum = ?2",
+            params![last_seq, last_checksum],
+        )?;
+        tx.commit()?;
+        Ok(lines.lines().count())
+    }
+
+    /// Appends a `redaction` line naming `tombstone` and the seqs of every
+    /// line whose `username`, `sender` or `recipient` is `username`, so
+    /// readers know which lines to scrub. Lines already written are left
+    /// alone and the checksum chain carries on through the new line.
+    /// Returns the number of lines named.
+    pub fn record_redaction(
+        &self,
+        conn: &Connection,
+        username: &str,
+        tombstone: &str,
+        now: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        // Holding the write lock keeps other instances from appending while
+        // the files are read.
+        let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        let mut seqs = Vec::new();
+        for path in self.files()? {
+            for line in fs::read_to_string(&path)?.lines() {
+                let entry: Value = serde_json::from_str(line)?;
+                let named = ["username", "sender", "recipient"]
+                    .iter()
+                    .any(|field| entry[*field].as_str() == Some(username));
+                if let (true, Some(seq)) = (named, entry["seq"].as_i64()) {
+                    seqs.push(seq);
+                }
+            }
+        }
+        if seqs.is_empty() {
+            return Ok(0);
+        }
+
+        let (_, last_checksum) = Self::last_exported(&tx)?;
+        let mut entry = json!({
+            "type": "redaction",
+            "tombstone": tombstone,
+            "seqs": seqs,
+            "timestamp": now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        });
+        let checksum = chain_checksum(&last_checksum, &serde_json::to_string(&entry)?);
+        entry["checksum"] = Value::String(checksum.clone());
+        self.rotate_if_needed()?;
+        let mut file =
+            OpenOptions::new().create(true).append(true).open(self.dir.join(CURRENT_FILE))?;
+        file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        file.sync_data()?;
+
+        tx.execute(
+            "UPDATE export_log_state SET last_checksum = ? WHERE id = 1",
+            params![checksum],
+        )?;
+        tx.commit()?;
+        Ok(seqs.len())
+    }
+
+    /// Checks the checksum chain through every file, oldest first, and that
+    /// it ends at the checksum kept in `export_log_state`. Returns the
+    /// number of lines checked.
+    pub fn verify(&self, conn: &Connection) -> Result<usize, Box<dyn Error>> {
+        let mut checked = 0;
+        let mut last_checksum = String::new();
+        for path in self.files()? {
+            for (number, line) in fs::read_to_string(&path)?.lines().enumerate() {
+                let mut entry: Value = serde_json::from_str(line)?;
+                let checksum = entry
+                    .as_object_mut()
+                    .and_then(|fields| fields.remove("checksum"))
+                    .unwrap_or_default();
+                last_checksum = chain_checksum(&last_checksum, &serde_json::to_string(&entry)?);
+                if checksum.as_str() != Some(last_checksum.as_str()) {
+                    let error =
+                        format!("{} line {}: checksum does not match", path.display(), number + 1);
+                    return Err(error.into());
+                }
+                checked += 1;
+            }
+        }
+        if last_checksum != Self::last_exported(conn)?.1 {
+            return Err("the last line does not match export_log_state".into());
+        }
+        Ok(checked)
+    }
+
+    /// Lists the rotated files, oldest first, then the current file if it
+    /// exists.
+    fn files(&self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                name.starts_with("transactions-") && name.ends_with(".ndjson")
+            })
+            .collect();
+        files.sort();
+        let current = self.dir.join(CURRENT_FILE);
+        if current.exists() {
+            files.push(current);
+        }
+        Ok(files)
+    }
+
+    /// Gets the seq and checksum of the last line written.
+    fn last_exported(conn: &Connection) -> rusqlite::Result<(i64, String)> {
+        conn.query_row(
+            "SELECT last_seq, last_checksum FROM export_log_state WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map(Option::unwrap_or_default)
+    }
+
+    /// Rotates the current file if it is too big or was last written on an earlier day.
+    fn rotate_if_needed(&self) -> Result<(), Box<dyn Error>> {
+        let path = self.dir.join(CURRENT_FILE);
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(()),
+        };
+        let modified: chrono::DateTime<chrono::Local> = metadata.modified()?.into();
+        let new_day = modified.date_naive() != chrono::Local::now().date_naive();
+        if metadata.len() >= MAX_FILE_BYTES || new_day {
+            let rotated = format!(
+                "transactions-{}.ndjson",
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            );
+            fs::rename(&path, self.dir.join(rotated))?;
+        }
+        Ok(())
+    }
+}
+
+fn chain_checksum(previous: &str, body: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(body.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::database;
+    use crate::models::transaction::Transaction;
+    use crate::models::user::User;
+    use crate::models::user_data::UserData;
+    use chrono::NaiveDate;
+
+    fn transfer(id: &str, from: &str, to: &str, at: NaiveDateTime) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            username: from.to_string(),
+            transaction_type: "transfer_out".to_string(),
+            amount: 5.0,
+            recipient: Some(to.to_string()),
+            sender: Some(from.to_string()),
+            previous_balance: 10.0,
+            new_balance: 5.0,
+            timestamp: at,
+            currency: "USD".to_string(),
+            fx_rate: 1.0,
+            transfer_id: Some(id.to_string()),
+            merchant: None,
+        }
+    }
+
+    #[test]
+    fn anonymizing_keeps_the_chain_intact() {
+        let dir = std::env::temp_dir().join(format!("ewallet-export-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let log = ExportLog::open(dir.to_str().unwrap()).unwrap();
+        let conn = database::open(":memory:").unwrap();
+        let now = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        User::create(&conn, "alice").unwrap();
+        User::create(&conn, "bob").unwrap();
+        User::create(&conn, "carol").unwrap();
+        Transaction::create(&conn, &transfer("t1", "alice", "bob", now)).unwrap();
+        Transaction::create(&conn, &transfer("t2", "bob", "carol", now)).unwrap();
+        Transaction::create(&conn, &transfer("t3", "carol", "alice", now)).unwrap();
+        assert_eq!(log.sync(&conn).unwrap(), 3);
+        let before = fs::read_to_string(dir.join(CURRENT_FILE)).unwrap();
+
+        let tombstone = UserData::anonymize(&conn, "alice").unwrap().unwrap();
+        assert_eq!(log.record_redaction(&conn, "alice", &tombstone, now).unwrap(), 2);
+        Transaction::create(&conn, &transfer("t4", "bob", "carol", now)).unwrap();
+        assert_eq!(log.sync(&conn).unwrap(), 1);
+
+        let after = fs::read_to_string(dir.join(CURRENT_FILE)).unwrap();
+        assert!(after.starts_with(&before));
+        let redaction: Value = serde_json::from_str(after.lines().nth(3).unwrap()).unwrap();
+        assert_eq!(redaction["tombstone"], tombstone.as_str());
+        assert_eq!(redaction["seqs"], json!([1, 3]));
+        assert_eq!(log.verify(&conn).unwrap(), 5);
+
+        fs::write(dir.join(CURRENT_FILE), after.replacen("bob", "mallory", 1)).unwrap();
+        assert!(log.verify(&conn).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
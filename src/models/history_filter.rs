@@ -0,0 +1,203 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+use rusqlite::types::Value;
+use std::fmt;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Names `type:` accepts, and the transaction types each stands for.
+const TYPE_NAMES: &[(&str, &[&str])] = &[
+    ("transfer", &["transfer_in", "transfer_out"]),
+    ("transfer_in", &["transfer_in"]),
+    ("transfer_out", &["transfer_out"]),
+    ("deposit", &["deposit"]),
+    ("withdraw", &["withdraw"]),
+    ("withdrawal", &["withdraw"]),
+    ("token", &["token_in", "token_out"]),
+    ("token_in", &["token_in"]),
+    ("token_out", &["token_out"]),
+    ("vault", &["vault_in", "vault_out"]),
+    ("vault_in", &["vault_in"]),
+    ("vault_out", &["vault_out"]),
+    ("fee", &["fee"]),
+];
+
+/// Comparisons `amount` accepts, longest first so `>=` is not read as `>`.
+const AMOUNT_OPERATORS: &[&str] = &[">=", "<=", ">", "<", "="];
+
+/// A term of a history filter that could not be read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterError {
+    pub term: String,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "'{}': {}", self.term, self.message)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Condition {
+    Types(&'static [&'static str]),
+    Amount(&'static str, f64),
+    To(String),
+    From(String),
+    Category(String),
+    Since(NaiveDateTime),
+    Until(NaiveDateTime),
+}
+
+/// A filter typed on the history screen, such as
+/// `type:transfer amount>100 to:bob since:2024-01`. Every term must match.
+///
+/// | Term | Matches |
+/// |---|---|
+/// | `type:NAME` | `transfer`, `deposit`, `withdraw`, `token`, `vault`, `fee`, or an exact type |
+/// | `amount>N` | also `>=`, `<`, `<=` and `=` |
+/// | `to:NAME` | the recipient or merchant |
+/// | `from:NAME` | the sender |
+/// | `category:NAME` | the category annotation |
+/// | `since:DATE` | on or after `YYYY`, `YYYY-MM` or `YYYY-MM-DD` |
+/// | `until:DATE` | up to the end of that year, month or day |
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HistoryFilter {
+    /// The filter as typed.
+    pub text: String,
+    conditions: Vec<Condition>,
+}
+
+impl HistoryFilter {
+    pub fn parse(text: &str) -> Result<Self, FilterError> {
+        let conditions = text.split_whitespace().map(Self::parse_term).collect::<Result<_, _>>()?;
+        Ok(HistoryFilter {
+            text: text.trim().to_string(),
+            conditions,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    fn parse_term(term: &str) -> Result<Condition, FilterError> {
+        let error = |message: String| FilterError {
+            term: term.to_string(),
+            message,
+        };
+        if let Some(rest) = term.strip_prefix("amount") {
+            let operator = AMOUNT_OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+                .ok_or_else(|| error("expected amount>, >=, <, <= or = and a number".to_string()))?;
+            let value = &rest[operator.len()..];
+            return match value.parse::<f64>() {
+                Ok(amount) if amount.is_finite() => Ok(Condition::Amount(operator, amount)),
+                _ => Err(error(format!("'{}' is not a number", value))),
+            };
+        }
+
+        let (key, value) = term.split_once(':').ok_or_else(|| {
+            error("expected type:, amount>, to:, from:, category:, since: or until:".to_string())
+        })?;
+        if value.is_empty() {
+            return Err(error("nothing after the colon".to_string()));
+        }
+        match key {
+            "type" => TYPE_NAMES
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(value))
+                .map(|(_, types)| Condition::Types(types))
+                .ok_or_else(|| {
+                    error(format!(
+                        "unknown type '{}'; try transfer, deposit, withdraw, token, vault or fee",
+                        value
+                    ))
+                }),
+            "to" => Ok(Condition::To(value.to_string())),
+            "from" => Ok(Condition::From(value.to_string())),
+            "category" => Ok(Condition::Category(value.to_string())),
+            "since" | "until" => {
+                let (start, end) = Self::parse_period(value).ok_or_else(|| {
+                    error(format!("'{}' is not a date; use YYYY, YYYY-MM or YYYY-MM-DD", value))
+                })?;
+                Ok(if key == "since" { Condition::Since(start) } else { Condition::Until(end) })
+            }
+            _ => Err(error(format!("unknown filter '{}:'", key))),
+        }
+    }
+
+    /// Reads a year, month or day as the start of it and the start of the
+    /// one after.
+    fn parse_period(value: &str) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let parts: Vec<&str> = value.split('-').collect();
+        let number = |part: &str| part.parse::<u32>().ok().filter(|_| !part.starts_with('+'));
+        let year = number(parts[0]).filter(|_| parts[0].len() == 4)? as i32;
+        let (start, end) = match parts.as_slice() {
+            [_] => {
+                let start = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                (start, start.with_year(year + 1)?)
+            }
+            [_, month] => {
+                let start = NaiveDate::from_ymd_opt(year, number(month)?, 1)?;
+                (start, start.checked_add_months(Months::new(1))?)
+            }
+            [_, month, day] => {
+                let start = NaiveDate::from_ymd_opt(year, number(month)?, number(day)?)?;
+                (start, start.succ_opt()?)
+            }
+            _ => return None,
+        };
+        Some((start.and_hms_opt(0, 0, 0)?, end.and_hms_opt(0, 0, 0)?))
+    }
+
+    /// Gets the filter as SQL to add to a WHERE clause over transactions
+    /// aliased `t` and their annotations aliased `a`, and the values to bind
+    /// to it. Parameters are numbered from `first` on. Empty if there is
+    /// nothing to filter.
+    pub fn to_sql(&self, first: usize) -> (String, Vec<Value>) {
+        let mut sql = String::new();
+        let mut values = Vec::new();
+        for condition in &self.conditions {
+            let next = first + values.len();
+            let predicate = match condition {
+                Condition::Types(types) => {
+                    let placeholders: Vec<String> =
+                        (0..types.len()).map(|i| format!("?{}", next + i)).collect();
+                    values.extend(types.iter().map(|name| Value::Text(name.to_string())));
+                    format!("t.transaction_type IN ({})", placeholders.join(", "))
+                }
+                Condition::Amount(operator, amount) => {
+                    values.push(Value::Real(*amount));
+                    format!("t.amount {} ?{}", operator, next)
+                }
+                Condition::To(name) => {
+                    values.push(Value::Text(name.clone()));
+                    format!(
+                        "(t.recipient = ?{0} COLLATE NOCASE OR t.merchant = ?{0} COLLATE NOCASE)",
+                        next
+                    )
+                }
+                Condition::From(name) => {
+                    values.push(Value::Text(name.clone()));
+                    format!("t.sender = ?{} COLLATE NOCASE", next)
+                }
+                Condition::Category(name) => {
+                    values.push(Value::Text(name.clone()));
+                    format!("a.category = ?{} COLLATE NOCASE", next)
+                }
+                Condition::Since(at) => {
+                    values.push(Value::Text(at.format(TIMESTAMP_FORMAT).to_string()));
+                    format!("t.timestamp >= ?{}", next)
+                }
+                Condition::Until(at) => {
+                    values.push(Value::Text(at.format(TIMESTAMP_FORMAT).to_string()));
+                    format!("t.timestamp < ?{}", next)
+                }
+            };
+            sql.push_str(" AND ");
+            sql.push_str(&predicate);
+        }
+        (sql, values)
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::models::payment_request::RequestStatus;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Money set aside for a card-style payment that has been authorized but
+/// not yet settled. Uses the payment request lifecycle: pending while the
+/// hold is active, paid once captured, declined once released, and expired
+/// when it was neither before `expires_at`.
+#[allow(dead_code)]
+pub struct Hold {
+    pub id: i64,
+    pub username: String,
+    pub amount: f64,
+    pub description: String,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Two-phase payments: a hold reserves part of the balance, and is later
+/// captured, taking the money, or released, giving it back.
+pub struct Holds;
+
+impl Holds {
+    pub fn create(
+        conn: &Connection,
+        username: &str,
+        amount: f64,
+        description: &str,
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO holds (username, amount, description, status, created_at, expires_at)
+            VALUES (?, ?, ?, 'pending', ?, ?)",
+            params![
+                username,
+                amount,
+                description,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                expires_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get(conn: &Connection, id: i64) -> Result<Option<Hold>> {
+        conn.query_row(
+            "SELECT id, username, amount, description, status, created_at, expires_at
+            FROM holds WHERE id = ?",
+            params![id],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    /// Sums the user's active holds, the part of the balance that cannot be spent.
+    pub fn total_active(conn: &Connection, username: &str) -> Result<f64> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM holds
+            WHERE username = ? AND status = 'pending'",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Moves a hold to a new status if it is still active. Returns `false`
+    /// if it had already been settled.
+    pub fn set_status(conn: &Connection, id: i64, status: RequestStatus) -> Result<bool> {
+        if !RequestStatus::Pending.can_become(status) {
+            return Ok(false);
+        }
+        let updated = conn.execute(
+            "UPDATE holds SET status = ? WHERE id = ? AND status = 'pending'",
+            params![status.as_str(), id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Expires every active hold whose lifetime has passed, returning the money to its owner.
+    pub fn expire_due(conn: &Connection, now: NaiveDateTime) -> Result<usize> {
+        conn.execute(
+            "UPDATE holds SET status = 'expired' WHERE status = 'pending' AND expires_at <= ?",
+            params![now.format(TIMESTAMP_FORMAT).to_string()],
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<Hold> {
+        let created_at: String = row.get(5)?;
+        let expires_at: String = row.get(6)?;
+        Ok(Hold {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            amount: row.get(2)?,
+            description: row.get(3)?,
+            status: RequestStatus::parse(&row.get::<_, String>(4)?),
+            created_at: NaiveDateTime::parse_from_str(&created_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+            expires_at: NaiveDateTime::parse_from_str(&expires_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,46 @@
+use rusqlite::{params, Connection, Result};
+
+/// Number of entries kept per user and field.
+const MAX_ENTRIES: i64 = 20;
+
+/// Recently submitted values of an input field, kept per user so they can be
+/// recalled with Up/Down like shell history.
+pub struct InputHistory;
+
+impl InputHistory {
+    /// Adds a value to the user's history for a field, moving it to the front
+    /// if it was already there.
+    pub fn add(conn: &Connection, username: &str, field: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM input_history WHERE username = ? AND field = ? AND value = ?",
+            params![username, field, value],
+        )?;
+        conn.execute(
+            "INSERT INTO input_history (username, field, value) VALUES (?, ?, ?)",
+            params![username, field, value],
+        )?;
+        conn.execute(
+            "DELETE FROM input_history
+            WHERE username = ?1 AND field = ?2 AND id NOT IN (
+                SELECT id FROM input_history
+                WHERE username = ?1 AND field = ?2
+                ORDER BY id DESC
+                LIMIT ?3
+            )",
+            params![username, field, MAX_ENTRIES],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the user's history for a field, newest first.
+    pub fn get(conn: &Connection, username: &str, field: &str) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(
+            "SELECT value FROM input_history
+            WHERE username = ? AND field = ?
+            ORDER BY id DESC",
+        )?;
+        let values = stmt.query_map(params![username, field], |row| row.get(0))?;
+
+        values.collect()
+    }
+}
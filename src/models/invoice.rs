@@ -0,0 +1,181 @@
+use chrono::NaiveDateTime;
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::models::payment_request::RequestStatus;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Prefix of the text encoded in a checkout QR code, before the code itself.
+pub const PAYMENT_URI_PREFIX: &str = "ewallet:pay:";
+
+/// Characters used in checkout codes. Leaves out 0/O and 1/I so codes can
+/// be read off a screen and typed without mistakes.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// One line of a merchant checkout.
+#[derive(Clone, Debug)]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: u32,
+    pub unit_price: f64,
+}
+
+impl LineItem {
+    /// Parses `description, price` or `description, price, quantity`.
+    pub fn parse(text: &str) -> Option<LineItem> {
+        let mut parts = text.split(',').map(str::trim);
+        let description = parts.next().filter(|d| !d.is_empty())?.to_string();
+        let unit_price: f64 = parts.next()?.parse().ok()?;
+        let quantity: u32 = match parts.next() {
+            Some(quantity) => quantity.parse().ok()?,
+            None => 1,
+        };
+        if parts.next().is_some() || !unit_price.is_finite() || unit_price <= 0.0 || quantity == 0 {
+            return None;
+        }
+        Some(LineItem {
+            description,
+            quantity,
+            unit_price,
+        })
+    }
+
+    pub fn total(&self) -> f64 {
+        self.unit_price * self.quantity as f64
+    }
+}
+
+/// A merchant's itemized checkout that anyone holding its code can pay once.
+/// Uses the payment request lifecycle: pending until paid or expired.
+#[allow(dead_code)]
+pub struct Invoice {
+    pub code: String,
+    pub merchant: String,
+    pub items: Vec<LineItem>,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub paid_by: Option<String>,
+}
+
+impl Invoice {
+    /// Stores a new checkout under a fresh code drawn from `rng` and
+    /// returns it.
+    pub fn create(
+        conn: &Connection,
+        merchant: &str,
+        items: &[LineItem],
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+        rng: &mut impl Rng,
+    ) -> Result<Invoice> {
+        let code: String = (0..8)
+            .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+            .collect();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO invoices (code, merchant, status, created_at, expires_at)
+            VALUES (?, ?, 'pending', ?, ?)",
+            params![
+                code,
+                merchant,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                expires_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        for item in items {
+            tx.execute(
+                "INSERT INTO invoice_items (code, description, quantity, unit_price)
+                VALUES (?, ?, ?, ?)",
+                params![code, item.description, item.quantity, item.unit_price],
+            )?;
+        }
+        tx.commit()?;
+        Ok(Invoice {
+            code,
+            merchant: merchant.to_string(),
+            items: items.to_vec(),
+            status: RequestStatus::Pending,
+            created_at,
+            expires_at,
+            paid_by: None,
+        })
+    }
+
+    /// Looks up a checkout by its code, or by a scanned `ewallet:pay:` URI.
+    /// Case and surrounding spaces are ignored.
+    pub fn get(conn: &Connection, code: &str) -> Result<Option<Invoice>> {
+        let code = code.trim();
+        let code = code.strip_prefix(PAYMENT_URI_PREFIX).unwrap_or(code).to_uppercase();
+        let invoice = conn
+            .query_row(
+                "SELECT code, merchant, status, created_at, expires_at, paid_by
+                FROM invoices WHERE code = ?",
+                params![code],
+                |row| {
+                    let created_at: String = row.get(3)?;
+                    let expires_at: String = row.get(4)?;
+                    Ok(Invoice {
+                        code: row.get(0)?,
+                        merchant: row.get(1)?,
+                        items: Vec::new(),
+                        status: RequestStatus::parse(&row.get::<_, String>(2)?),
+                        created_at: NaiveDateTime::parse_from_str(&created_at, TIMESTAMP_FORMAT)
+                            .unwrap_or_default(),
+                        expires_at: NaiveDateTime::parse_from_str(&expires_at, TIMESTAMP_FORMAT)
+                            .unwrap_or_default(),
+                        paid_by: row.get(5)?,
+                    })
+                },
+            )
+            .optional()?;
+        let mut invoice = match invoice {
+            Some(invoice) => invoice,
+            None => return Ok(None),
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT description, quantity, unit_price FROM invoice_items WHERE code = ? ORDER BY id",
+        )?;
+        invoice.items = stmt
+            .query_map(params![invoice.code], |row| {
+                Ok(LineItem {
+                    description: row.get(0)?,
+                    quantity: row.get(1)?,
+                    unit_price: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_>>()?;
+        Ok(Some(invoice))
+    }
+
+    /// Marks a pending checkout paid by `payer`. Returns `false` if it had
+    /// already been settled.
+    pub fn mark_paid(conn: &Connection, code: &str, payer: &str) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE invoices SET status = 'paid', paid_by = ? WHERE code = ? AND status = 'pending'",
+            params![payer, code],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Marks a pending checkout expired. Returns `false` if it had already
+    /// been settled.
+    pub fn mark_expired(conn: &Connection, code: &str) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE invoices SET status = 'expired' WHERE code = ? AND status = 'pending'",
+            params![code],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.items.iter().map(LineItem::total).sum()
+    }
+
+    /// Text for the QR code shown to customers.
+    pub fn payment_uri(&self) -> String {
+        format!("{}{}", PAYMENT_URI_PREFIX, self.code)
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result, Row};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A co-owner of a joint account. Anyone logged in to the account can
+/// move money as usual, but a transfer over the approval threshold waits
+/// until one of the account's members approves it from their own login.
+pub struct JointMember {
+    pub account: String,
+    pub member: String,
+    pub added_at: NaiveDateTime,
+}
+
+pub struct JointAccounts;
+
+impl JointAccounts {
+    /// Makes `member` a co-owner of `account`. Returns `false` if they
+    /// already are one.
+    pub fn add(conn: &Connection, account: &str, member: &str, now: NaiveDateTime) -> Result<bool> {
+        let added = conn.execute(
+            "INSERT OR IGNORE INTO joint_members (account, member, added_at) VALUES (?, ?, ?)",
+            params![account, member, now.format(TIMESTAMP_FORMAT).to_string()],
+        )?;
+        Ok(added > 0)
+    }
+
+    pub fn remove(conn: &Connection, account: &str, member: &str) -> Result<bool> {
+        let removed = conn.execute(
+            "DELETE FROM joint_members WHERE account = ? AND member = ?",
+            params![account, member],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Whether `account` has any co-owners, which makes it a joint account.
+    pub fn is_joint(conn: &Connection, account: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM joint_members WHERE account = ?)",
+            params![account],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn is_member(conn: &Connection, account: &str, member: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM joint_members WHERE account = ? AND member = ?)",
+            params![account, member],
+            |row| row.get(0),
+        )
+    }
+
+    /// Gets the co-owners of `account`, by name.
+    pub fn get_members(conn: &Connection, account: &str) -> Result<Vec<JointMember>> {
+        let mut stmt = conn.prepare(
+            "SELECT account, member, added_at FROM joint_members
+            WHERE account = ? ORDER BY member",
+        )?;
+        let members = stmt.query_map(params![account], Self::from_row)?;
+        members.collect()
+    }
+
+    fn from_row(row: &Row) -> Result<JointMember> {
+        let added_at: String = row.get(2)?;
+        Ok(JointMember {
+            account: row.get(0)?,
+            member: row.get(1)?,
+            added_at: NaiveDateTime::parse_from_str(&added_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+        })
+    }
+}
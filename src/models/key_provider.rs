@@ -0,0 +1,175 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal;
+use rand::Rng;
+use std::env;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+/// Service name secrets are filed under in the OS keychain.
+pub const KEYCHAIN_SERVICE: &str = "ewallet_demo";
+
+/// Environment variable the wallet key passphrase is read from with the
+/// `env` source.
+pub const WALLET_KEY_VAR: &str = "EWALLET_WALLET_KEY";
+
+static WALLET_KEY_SECRET: OnceLock<(Option<String>, bool)> = OnceLock::new();
+
+/// Where the secret that seals a stored signing key comes from.
+pub trait KeyProvider {
+    /// Gets the secret, or `None` if this source has none to give.
+    fn secret(&self) -> Option<String>;
+
+    /// Whether a key may be stored unsealed when there is no secret. Only a
+    /// source chosen to mean "no passphrase" allows it.
+    fn allows_unsealed(&self) -> bool {
+        false
+    }
+}
+
+/// A credential the user typed, which seals their own signing key.
+pub struct Password<'a>(pub &'a str);
+
+impl KeyProvider for Password<'_> {
+    fn secret(&self) -> Option<String> {
+        Some(self.0.to_string())
+    }
+}
+
+/// A passphrase in an environment variable, for a daemon started without
+/// anyone at a terminal.
+pub struct EnvPassphrase(pub &'static str);
+
+impl KeyProvider for EnvPassphrase {
+    fn secret(&self) -> Option<String> {
+        env::var(self.0).ok().filter(|secret| !secret.is_empty())
+    }
+}
+
+/// A passphrase kept in the OS keychain, made up and stored there the first
+/// time, so the machine's login is all that unlocks the key.
+pub struct KeychainPassphrase;
+
+impl KeychainPassphrase {
+    fn entry() -> keyring::Result<keyring::Entry> {
+        let dir = env::current_dir().unwrap_or_default();
+        keyring::Entry::new(KEYCHAIN_SERVICE, &format!("{}#wallet-key", dir.display()))
+    }
+}
+
+impl KeyProvider for KeychainPassphrase {
+    fn secret(&self) -> Option<String> {
+        let entry = Self::entry().ok()?;
+        if let Ok(secret) = entry.get_password() {
+            return Some(secret);
+        }
+        let secret = format!("{:032x}", rand::thread_rng().gen::<u128>());
+        entry.set_password(&secret).ok()?;
+        Some(secret)
+    }
+}
+
+/// A passphrase typed at a prompt, without echoing it.
+pub struct PromptPassphrase(pub &'static str);
+
+impl KeyProvider for PromptPassphrase {
+    fn secret(&self) -> Option<String> {
+        eprint!("{}: ", self.0);
+        io::stderr().flush().ok()?;
+        terminal::enable_raw_mode().ok()?;
+        let mut secret = String::new();
+        let typed = loop {
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => match key.code {
+                    KeyCode::Enter => break true,
+                    KeyCode::Esc => break false,
+                    KeyCode::Backspace => {
+                        secret.pop();
+                    }
+                    KeyCode::Char(c) => secret.push(c),
+                    _ => {}
+                },
+                Ok(_) => {}
+                Err(_) => break false,
+            }
+        };
+        let _ = terminal::disable_raw_mode();
+        eprintln!();
+        (typed && !secret.is_empty()).then_some(secret)
+    }
+}
+
+/// No passphrase: stored keys are kept as they are, unsealed. Only used
+/// when `wallet_key = "none"` is set on purpose.
+pub struct NoPassphrase;
+
+impl KeyProvider for NoPassphrase {
+    fn secret(&self) -> Option<String> {
+        None
+    }
+
+    fn allows_unsealed(&self) -> bool {
+        true
+    }
+}
+
+/// Which provider seals the wallet's own key, as named in `config.toml`.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum KeySource {
+    None,
+    Prompt,
+    #[default]
+    Env,
+    Keychain,
+}
+
+impl KeySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeySource::None => "none",
+            KeySource::Prompt => "prompt",
+            KeySource::Env => "env",
+            KeySource::Keychain => "keychain",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(KeySource::None),
+            "prompt" => Some(KeySource::Prompt),
+            "env" => Some(KeySource::Env),
+            "keychain" => Some(KeySource::Keychain),
+            _ => None,
+        }
+    }
+
+    pub fn provider(&self) -> Box<dyn KeyProvider> {
+        match self {
+            KeySource::None => Box::new(NoPassphrase),
+            KeySource::Prompt => Box::new(PromptPassphrase("Wallet key passphrase")),
+            KeySource::Env => Box::new(EnvPassphrase(WALLET_KEY_VAR)),
+            KeySource::Keychain => Box::new(KeychainPassphrase),
+        }
+    }
+}
+
+/// Gets the wallet key's secret from `provider` for the rest of the run.
+/// It is asked for now rather than when the key is first needed, which may
+/// be while the app is drawn. Only the first call has an effect.
+pub fn use_wallet_key_provider(provider: &dyn KeyProvider) {
+    if WALLET_KEY_SECRET.get().is_none() {
+        let _ = WALLET_KEY_SECRET.set((provider.secret(), provider.allows_unsealed()));
+    }
+}
+
+/// The secret chosen with `use_wallet_key_provider`, if any.
+pub struct WalletKeySecret;
+
+impl KeyProvider for WalletKeySecret {
+    fn secret(&self) -> Option<String> {
+        WALLET_KEY_SECRET.get().and_then(|(secret, _)| secret.clone())
+    }
+
+    fn allows_unsealed(&self) -> bool {
+        WALLET_KEY_SECRET.get().is_some_and(|(_, allowed)| *allowed)
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+/// Lower bounds of the balance ranges shown on the leaderboard, so that
+/// other users only learn roughly how much someone holds.
+pub const BALANCE_BUCKETS: [f64; 5] = [0.0, 100.0, 1_000.0, 10_000.0, 100_000.0];
+
+/// How many users the leaderboard lists.
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// What the leaderboard ranks users by.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LeaderboardRanking {
+    Balance,
+    MonthlySavings,
+}
+
+impl LeaderboardRanking {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaderboardRanking::Balance => "Balance",
+            LeaderboardRanking::MonthlySavings => "Saved this month",
+        }
+    }
+}
+
+/// One place on the leaderboard. For `Balance` the value is the lower bound
+/// of the user's balance range rather than the balance itself; for
+/// `MonthlySavings` it is money in minus money out since the month began.
+pub struct LeaderboardEntry {
+    pub username: String,
+    pub value: f64,
+}
+
+/// The opt-in demo leaderboard. Only users who turned on the `leaderboard`
+/// setting are listed.
+pub struct Leaderboard;
+
+impl Leaderboard {
+    /// Ranks opted-in users, best first.
+    pub fn rank(
+        conn: &Connection,
+        ranking: LeaderboardRanking,
+        month_start: NaiveDateTime,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        match ranking {
+            LeaderboardRanking::Balance => Self::by_balance(conn),
+            LeaderboardRanking::MonthlySavings => Self::by_monthly_savings(conn, month_start),
+        }
+    }
+
+    /// Users within the same balance range are listed by name, so the order
+    /// does not give away who holds more.
+    fn by_balance(conn: &Connection) -> Result<Vec<LeaderboardEntry>> {
+        let mut stmt = conn.prepare(
+            "SELECT u.username, u.balance FROM users u
+            JOIN user_settings s ON s.username = u.username
+            WHERE s.key = 'leaderboard' AND s.value = 'true'",
+        )?;
+        let mut entries = stmt
+            .query_map([], |row| {
+                Ok(LeaderboardEntry {
+                    username: row.get(0)?,
+                    value: Self::bucket(row.get(1)?),
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| {
+            b.value.total_cmp(&a.value).then_with(|| a.username.cmp(&b.username))
+        });
+        entries.truncate(LEADERBOARD_SIZE);
+        Ok(entries)
+    }
+
+    fn by_monthly_savings(
+        conn: &Connection,
+        month_start: NaiveDateTime,
+    ) -> Result<Vec<LeaderboardEntry>> {
+        let mut stmt = conn.prepare(
+            "SELECT u.username,
+                COALESCE(SUM(CASE
+                    WHEN t.transaction_type IN ('deposit', 'transfer_in', 'token_in') THEN t.amount
+                    WHEN t.transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee') THEN -t.amount
+                    ELSE 0 END), 0) AS saved
+            FROM users u
+            JOIN user_settings s ON s.username = u.username
+            LEFT JOIN transactions t ON t.username = u.username AND t.timestamp >= ?
+            WHERE s.key = 'leaderboard' AND s.value = 'true'
+            GROUP BY u.username
+            ORDER BY saved DESC, u.username
+            LIMIT ?",
+        )?;
+        let entries = stmt
+            .query_map(
+                params![
+                    month_start.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    LEADERBOARD_SIZE as i64
+                ],
+                |row| {
+                    Ok(LeaderboardEntry {
+                        username: row.get(0)?,
+                        value: row.get(1)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Gets the lower bound of the balance range a balance falls in.
+    /// Overdrawn balances share the lowest range.
+    pub fn bucket(balance: f64) -> f64 {
+        BALANCE_BUCKETS
+            .iter()
+            .rev()
+            .copied()
+            .find(|&floor| balance >= floor)
+            .unwrap_or(BALANCE_BUCKETS[0])
+    }
+}
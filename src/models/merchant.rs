@@ -0,0 +1,105 @@
+use rusqlite::{Connection, Result};
+
+use crate::models::transaction::ARCHIVE_TABLE;
+
+/// Payment processors that put their own name before a `*` and the
+/// merchant's after it, as in `SQ *BLUE BOTTLE`.
+const PROCESSOR_PREFIXES: &[&str] = &["SQ", "TST", "PAYPAL", "PP", "SP", "GOOGLE", "IZ"];
+
+/// Short forms statements use for some merchants, and the name they stand for.
+const ALIASES: &[(&str, &str)] = &[
+    ("AMZN", "AMAZON"),
+    ("AMZ", "AMAZON"),
+    ("SBUX", "STARBUCKS"),
+    ("WM", "WALMART"),
+    ("WMT", "WALMART"),
+    ("WAL MART", "WALMART"),
+    ("MCD", "MCDONALDS"),
+    ("MCDONALD S", "MCDONALDS"),
+    ("UBER TRIP", "UBER"),
+    ("NFLX", "NETFLIX"),
+];
+
+/// Web address endings dropped from merchant names, longest first.
+const DOMAIN_SUFFIXES: &[&str] = &[".CO.UK", ".COM", ".NET", ".ORG", ".CO", ".IO"];
+
+/// Turns a counterparty name from an external statement into the canonical
+/// merchant name used for analytics and category rules, so the variants
+/// a bank prints for one merchant are counted as one. `AMZN*1A2B3C`,
+/// `AMAZON.CO` and `Amazon Marketplace` all become `AMAZON` once `AMAZON`
+/// is among the `known` merchants.
+///
+/// Reference numbers and web address endings are stripped, known short
+/// forms are expanded, and a name that starts with a known merchant's
+/// name is taken to be that merchant.
+pub fn normalize(name: &str, known: &[String]) -> String {
+    let cleaned = clean(name);
+    if cleaned.is_empty() {
+        return name.trim().to_string();
+    }
+    let expanded = expand_alias(&cleaned);
+    known
+        .iter()
+        .filter(|merchant| {
+            let rest = expanded.strip_prefix(merchant.as_str());
+            rest.is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
+        .max_by_key(|merchant| merchant.len())
+        .cloned()
+        .unwrap_or(expanded)
+}
+
+/// Uppercases a name and strips what varies between payments to the same
+/// merchant: processor references after `*` or `#`, web address endings,
+/// punctuation and any word containing a digit.
+fn clean(name: &str) -> String {
+    let upper = name.trim().to_uppercase();
+    let name = match upper.split_once('*') {
+        Some((prefix, rest)) if PROCESSOR_PREFIXES.contains(&prefix.trim()) => rest,
+        Some((name, _)) => name,
+        None => upper.as_str(),
+    };
+    let mut name = name.split('#').next().unwrap_or_default().to_string();
+    for suffix in DOMAIN_SUFFIXES {
+        if let Some(at) = name.find(suffix) {
+            let end = at + suffix.len();
+            if name[end..].chars().next().is_none_or(|c| !c.is_alphanumeric()) {
+                name.replace_range(at..end, " ");
+            }
+        }
+    }
+    name.split(|c: char| !c.is_alphanumeric() && c != '&')
+        .filter(|word| !word.is_empty() && !word.chars().any(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Replaces a short form at the start of a cleaned name with the name it
+/// stands for.
+fn expand_alias(cleaned: &str) -> String {
+    for (alias, merchant) in ALIASES {
+        if cleaned == *alias {
+            return merchant.to_string();
+        }
+        if let Some(rest) = cleaned.strip_prefix(alias).filter(|rest| rest.starts_with(' ')) {
+            return format!("{}{}", merchant, rest);
+        }
+    }
+    cleaned.to_string()
+}
+
+pub struct Merchants;
+
+impl Merchants {
+    /// Gets every canonical merchant name already recorded, live or archived.
+    pub fn known(conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT merchant FROM transactions WHERE merchant IS NOT NULL
+            UNION
+            SELECT merchant FROM {} WHERE merchant IS NOT NULL",
+            ARCHIVE_TABLE
+        ))?;
+        let merchants = stmt.query_map([], |row| row.get(0))?;
+        merchants.collect()
+    }
+}
@@ -1,2 +1,49 @@
 pub mod user;
 pub mod transaction;
+pub mod annotation;
+pub mod input_history;
+pub mod settings;
+pub mod database;
+pub mod user_data;
+pub mod payment_request;
+pub mod pending_transfer;
+pub mod archive;
+pub mod export_log;
+pub mod offline_token;
+pub mod user_key;
+pub mod vault;
+pub mod child_account;
+pub mod invoice;
+pub mod round_up;
+pub mod ndjson;
+pub mod doctor;
+pub mod retention;
+pub mod profile;
+pub mod session;
+pub mod hold;
+pub mod amount;
+pub mod outbox;
+pub mod audit_export;
+pub mod closed_account;
+pub mod notification;
+pub mod leaderboard;
+pub mod processor;
+pub mod rules;
+pub mod environment;
+pub mod backup_diff;
+pub mod clock;
+pub mod weekly_summary;
+pub mod category_rule;
+pub mod merchant;
+pub mod key_provider;
+pub mod password;
+pub mod delegation;
+pub mod net_worth;
+pub mod balance_snapshot;
+pub mod denomination;
+pub mod sweep_rule;
+pub mod joint_account;
+pub mod activity;
+pub mod history_filter;
+pub mod money;
+pub mod bill_split;
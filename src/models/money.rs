@@ -0,0 +1,206 @@
+/// How an amount that falls exactly between two cents is rounded.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum RoundingPolicy {
+    /// Halves go away from zero: 0.125 becomes 0.13.
+    #[default]
+    HalfUp,
+    /// Halves go to the even cent, so over many amounts they do not all
+    /// lean the same way: 0.125 becomes 0.12 and 0.135 becomes 0.14. Also
+    /// called banker's rounding.
+    HalfEven,
+}
+
+impl RoundingPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundingPolicy::HalfUp => "half_up",
+            RoundingPolicy::HalfEven => "half_even",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "half_up" => Some(RoundingPolicy::HalfUp),
+            "half_even" | "bankers" => Some(RoundingPolicy::HalfEven),
+            _ => None,
+        }
+    }
+}
+
+/// How far from a half cent, in cents, an amount may be and still count as
+/// one. An amount such as 1.005 is stored as 1.00499999…, and is meant as a
+/// half all the same.
+const HALF_CENT_TOLERANCE: f64 = 1e-6;
+
+/// Amounts of money, which are `f64`s in the base currency throughout the
+/// wallet. Fees, interest and anything else worked out rather than typed
+/// are rounded to the cent here, with the policy in the rules, so they all
+/// round the same way, and amounts divided between people are allocated
+/// here so no cent goes missing.
+pub struct Money;
+
+impl Money {
+    /// Divides `total` into shares in proportion to `weights`, each to the
+    /// cent and together exactly `total`. Every share is first rounded down
+    /// to the cent; the cents that leaves over go one each to the shares
+    /// that lost the most in rounding down (the largest remainder method),
+    /// and between equal remainders to the earlier share.
+    pub fn allocate(total: f64, weights: &[f64]) -> Vec<f64> {
+        let total_cents = (total * 100.0).round() as i64;
+        let total_weight: f64 = weights.iter().sum();
+        if total_weight <= 0.0 {
+            return vec![0.0; weights.len()];
+        }
+        let exact: Vec<f64> =
+            weights.iter().map(|weight| total_cents as f64 * weight / total_weight).collect();
+        let mut cents: Vec<i64> = exact.iter().map(|share| share.floor() as i64).collect();
+        let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+        by_remainder.sort_by(|&a, &b| {
+            let remainder = |i: usize| exact[i] - exact[i].floor();
+            remainder(b).total_cmp(&remainder(a)).then(a.cmp(&b))
+        });
+        let left_over = total_cents - cents.iter().sum::<i64>();
+        for &i in by_remainder.iter().take(left_over.max(0) as usize) {
+            cents[i] += 1;
+        }
+        cents.into_iter().map(|cents| cents as f64 / 100.0).collect()
+    }
+
+    /// Rounds an amount to the cent, settling halves by `policy`.
+    pub fn round(amount: f64, policy: RoundingPolicy) -> f64 {
+        let cents = amount.abs() * 100.0;
+        let whole = cents.floor();
+        let fraction = cents - whole;
+        let up = if (fraction - 0.5).abs() < HALF_CENT_TOLERANCE {
+            match policy {
+                RoundingPolicy::HalfUp => true,
+                RoundingPolicy::HalfEven => whole % 2.0 == 1.0,
+            }
+        } else {
+            fraction > 0.5
+        };
+        let rounded = if up { whole + 1.0 } else { whole };
+        if amount < 0.0 && rounded > 0.0 {
+            -rounded / 100.0
+        } else {
+            rounded / 100.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::models::database;
+    use crate::models::processor::PaymentKind;
+    use crate::models::rules::Rules;
+    use crate::models::user::User;
+    use crate::models::vault::Vault;
+
+    const HALF_UP: RoundingPolicy = RoundingPolicy::HalfUp;
+    const HALF_EVEN: RoundingPolicy = RoundingPolicy::HalfEven;
+
+    fn cents(amounts: &[f64]) -> i64 {
+        amounts.iter().map(|amount| (amount * 100.0).round() as i64).sum()
+    }
+
+    #[test]
+    fn allocate_hands_left_over_cents_to_the_earliest_shares() {
+        let shares = Money::allocate(10.0, &[1.0, 1.0, 1.0]);
+        assert_eq!(shares, vec![3.34, 3.33, 3.33]);
+        assert_eq!(cents(&shares), 1000);
+        assert_eq!(Money::allocate(0.01, &[1.0, 1.0]), vec![0.01, 0.0]);
+        assert_eq!(Money::allocate(0.02, &[1.0, 1.0, 1.0]), vec![0.01, 0.01, 0.0]);
+    }
+
+    #[test]
+    fn allocate_hands_left_over_cents_to_the_largest_remainders() {
+        // Exact shares 0.666…, 3.333… and 6.0: the first lost the most.
+        let shares = Money::allocate(10.0, &[2.0, 10.0, 18.0]);
+        assert_eq!(shares, vec![0.67, 3.33, 6.0]);
+        assert_eq!(cents(&shares), 1000);
+    }
+
+    #[test]
+    fn allocate_always_adds_up_to_the_total() {
+        for total in [0.01, 0.99, 1.0, 10.0, 100.01, 1234.57] {
+            for people in 1..=7 {
+                let shares = Money::allocate(total, &vec![1.0; people]);
+                assert_eq!(shares.len(), people);
+                assert_eq!(cents(&shares), (total * 100.0).round() as i64, "{}", total);
+                let (low, high) = (cents(&shares[people - 1..]), cents(&shares[..1]));
+                assert!(high - low <= 1, "{} split {} ways", total, people);
+            }
+        }
+    }
+
+    #[test]
+    fn allocate_without_weight_gives_nothing() {
+        assert_eq!(Money::allocate(10.0, &[0.0, 0.0]), vec![0.0, 0.0]);
+        assert!(Money::allocate(10.0, &[]).is_empty());
+    }
+
+    #[test]
+    fn halves_round_by_policy() {
+        // 1.005 is stored as 1.00499999… and still counts as a half.
+        assert_eq!(Money::round(1.005, HALF_UP), 1.01);
+        assert_eq!(Money::round(1.005, HALF_EVEN), 1.0);
+        assert_eq!(Money::round(1.015, HALF_EVEN), 1.02);
+        assert_eq!(Money::round(0.025, HALF_UP), 0.03);
+        assert_eq!(Money::round(0.025, HALF_EVEN), 0.02);
+        assert_eq!(Money::round(2.5, HALF_UP), 2.5);
+        assert_eq!(Money::round(2.5, HALF_EVEN), 2.5);
+    }
+
+    #[test]
+    fn amounts_off_a_half_round_to_the_nearest_cent() {
+        for policy in [HALF_UP, HALF_EVEN] {
+            assert_eq!(Money::round(1.004, policy), 1.0);
+            assert_eq!(Money::round(1.006, policy), 1.01);
+            assert_eq!(Money::round(12.34, policy), 12.34);
+        }
+    }
+
+    #[test]
+    fn negative_amounts_round_like_positive_ones() {
+        assert_eq!(Money::round(-1.005, HALF_UP), -1.01);
+        assert_eq!(Money::round(-1.005, HALF_EVEN), -1.0);
+        assert_eq!(Money::round(-0.125, HALF_EVEN), -0.12);
+        assert_eq!(Money::round(-1.006, HALF_UP), -1.01);
+        let zero = Money::round(-0.001, HALF_UP);
+        assert_eq!(zero, 0.0);
+        assert!(zero.is_sign_positive());
+    }
+
+    #[test]
+    fn fees_are_rounded_by_the_rules() {
+        let mut rules = Rules {
+            card_top_up_fee_percent: 1.0,
+            bank_withdrawal_fee: 1.005,
+            ..Rules::default()
+        };
+        assert_eq!(rules.fee(PaymentKind::CardTopUp, 2.5), 0.03);
+        assert_eq!(rules.fee(PaymentKind::BankWithdrawal, 50.0), 1.01);
+        assert_eq!(rules.fee(PaymentKind::Cheque, 50.0), 0.0);
+        rules.rounding = HALF_EVEN;
+        assert_eq!(rules.fee(PaymentKind::CardTopUp, 2.5), 0.02);
+        assert_eq!(rules.fee(PaymentKind::BankWithdrawal, 50.0), 1.0);
+    }
+
+    #[test]
+    fn vault_interest_is_rounded_by_the_policy() {
+        // 45.625% a year on 100.00 is 0.125 a day.
+        let interest = |rounding| {
+            let conn = database::open(":memory:").unwrap();
+            User::create(&conn, "saver").unwrap();
+            Vault::update_balance(&conn, "saver", 100.0).unwrap();
+            let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+            Vault::pay_interest(&conn, day(1), 45.625, rounding).unwrap();
+            Vault::pay_interest(&conn, day(2), 45.625, rounding).unwrap()
+        };
+        assert_eq!(interest(HALF_UP), 0.13);
+        assert_eq!(interest(HALF_EVEN), 0.12);
+    }
+}
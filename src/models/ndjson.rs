@@ -0,0 +1,308 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result, TransactionBehavior};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::models::merchant::{self, Merchants};
+use crate::models::transaction::{Transaction, ARCHIVE_TABLE};
+use crate::models::user::User;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Version of the transaction record format written by `export --ndjson`.
+/// Bump it for any change other tools would have to know about.
+pub const RECORD_VERSION: i64 = 1;
+
+/// Transaction types that add to the balance. Every other known type takes away.
+const MONEY_IN_TYPES: &[&str] = &["deposit", "transfer_in", "token_in", "vault_out"];
+
+const KNOWN_TYPES: &[&str] = &[
+    "deposit",
+    "withdraw",
+    "transfer_in",
+    "transfer_out",
+    "token_in",
+    "token_out",
+    "vault_in",
+    "vault_out",
+    "fee",
+];
+
+/// A line of an NDJSON file that failed validation.
+#[derive(Debug)]
+pub struct RecordError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Reads and writes transactions as versioned JSON Lines records, one
+/// transaction per line. See the README for the format.
+pub struct TransactionRecords;
+
+impl TransactionRecords {
+    /// Gets every transaction of `username`, archived ones included, as
+    /// records in the order they happened.
+    pub fn export(conn: &Connection, username: &str) -> Result<Vec<String>> {
+        let mut transactions = Transaction::get_user_transactions(conn, username, false, false)?;
+        transactions.reverse();
+        Ok(transactions
+            .iter()
+            .map(|t| {
+                let field = |key: &str| t.get(key).cloned().unwrap_or_default();
+                let number = |key: &str| field(key).parse::<f64>().unwrap_or(0.0);
+                let optional = |key: &str| Some(field(key)).filter(|value| !value.is_empty());
+                json!({
+                    "v": RECORD_VERSION,
+                    "id": field("id"),
+                    "username": username,
+                    "type": field("type"),
+                    "amount": number("amount"),
+                    "currency": field("currency"),
+                    "fx_rate": number("fx_rate"),
+                    "sender": optional("sender"),
+                    "recipient": optional("recipient"),
+                    "previous_balance": number("previous_balance"),
+                    "new_balance": number("new_balance"),
+                    "timestamp": field("timestamp"),
+                })
+                .to_string()
+            })
+            .collect())
+    }
+
+    /// Gets the position of the newest transaction, for `committed_after`.
+    pub fn latest_position(conn: &Connection) -> Result<i64> {
+        conn.query_row("SELECT COALESCE(MAX(rowid), 0) FROM transactions", [], |row| {
+            row.get(0)
+        })
+    }
+
+    /// Gets the transactions committed after `position`, optionally only
+    /// those of one user, as records in commit order, each with its position.
+    pub fn committed_after(
+        conn: &Connection,
+        position: i64,
+        username: Option<&str>,
+    ) -> Result<Vec<(i64, String)>> {
+        let mut stmt = conn.prepare(
+            "SELECT rowid, json_object(
+                'v', ?,
+                'id', id,
+                'username', username,
+                'type', transaction_type,
+                'amount', amount,
+                'currency', currency,
+                'fx_rate', fx_rate,
+                'sender', sender,
+                'recipient', recipient,
+                'previous_balance', previous_balance,
+                'new_balance', new_balance,
+                'timestamp', timestamp
+            )
+            FROM transactions
+            WHERE rowid > ? AND (?3 IS NULL OR username = ?3)
+            ORDER BY rowid",
+        )?;
+        let records = stmt
+            .query_map(params![RECORD_VERSION, position, username], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(records)
+    }
+
+    /// Parses and validates every line of `text`. Blank lines are skipped.
+    /// Returns all problems found, each with its line number, rather than
+    /// stopping at the first.
+    pub fn parse(text: &str) -> std::result::Result<Vec<Transaction>, Vec<RecordError>> {
+        let mut transactions = Vec::new();
+        let mut errors = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Self::parse_record(line) {
+                Ok(transaction) => transactions.push(transaction),
+                Err(message) => errors.push(RecordError {
+                    line: index + 1,
+                    message,
+                }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(transactions)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Adds parsed transactions in one SQLite transaction, taken for writing
+    /// from the start so an import running at the same time waits for this
+    /// one. Records whose id is already stored are skipped, so importing a
+    /// file twice is harmless.
+    /// Users that do not exist are created, and what the added transactions
+    /// moved in or out is added to each user's balance at `now`, so money
+    /// already in the wallet is kept.
+    /// Counterparties that are not wallet users are taken to be merchants
+    /// and get a canonical merchant name. Returns how many transactions were
+    /// added.
+    pub fn import(
+        conn: &Connection,
+        transactions: &[Transaction],
+        now: NaiveDateTime,
+    ) -> Result<usize> {
+        let tx = rusqlite::Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        let mut added = 0;
+        let mut changes: HashMap<&str, f64> = HashMap::new();
+        let importing: HashSet<&str> = transactions.iter().map(|t| t.username.as_str()).collect();
+        let mut known_merchants = Merchants::known(&tx)?;
+        for transaction in transactions {
+            let exists: bool = tx.query_row(
+                &format!(
+                    "SELECT EXISTS(SELECT 1 FROM transactions WHERE id = ?1)
+                        OR EXISTS(SELECT 1 FROM {} WHERE id = ?1)",
+                    ARCHIVE_TABLE
+                ),
+                params![transaction.id],
+                |row| row.get(0),
+            )?;
+            if exists {
+                continue;
+            }
+            User::create(&tx, &transaction.username)?;
+            let mut merchant = None;
+            if let Some(counterparty) = Self::counterparty(transaction) {
+                if !importing.contains(counterparty) && User::get(&tx, counterparty)?.is_none() {
+                    let name = merchant::normalize(counterparty, &known_merchants);
+                    if !known_merchants.contains(&name) {
+                        known_merchants.push(name.clone());
+                    }
+                    merchant = Some(name);
+                }
+            }
+            Transaction::create(
+                &tx,
+                &Transaction {
+                    merchant,
+                    ..transaction.clone()
+                },
+            )?;
+            *changes.entry(transaction.username.as_str()).or_default() +=
+                transaction.new_balance - transaction.previous_balance;
+            added += 1;
+        }
+        for (username, change) in changes {
+            User::adjust_balance(&tx, username, change, now)?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
+    /// The other side of a transaction: who money went to, or came from.
+    fn counterparty(transaction: &Transaction) -> Option<&str> {
+        let counterparty = if MONEY_IN_TYPES.contains(&transaction.transaction_type.as_str()) {
+            &transaction.sender
+        } else {
+            &transaction.recipient
+        };
+        counterparty.as_deref().filter(|name| !name.trim().is_empty())
+    }
+
+    fn parse_record(line: &str) -> std::result::Result<Transaction, String> {
+        let value: Value =
+            serde_json::from_str(line).map_err(|err| format!("invalid JSON: {}", err))?;
+        let record = value.as_object().ok_or("expected a JSON object")?;
+        match record.get("v").and_then(Value::as_i64) {
+            Some(RECORD_VERSION) => {}
+            Some(version) => return Err(format!("unsupported record version {}", version)),
+            None => return Err("missing field `v`".to_string()),
+        }
+
+        let transaction_type = Self::string(record, "type")?;
+        if !KNOWN_TYPES.contains(&transaction_type.as_str()) {
+            return Err(format!("unknown transaction type `{}`", transaction_type));
+        }
+        let amount = Self::number(record, "amount")?;
+        if amount <= 0.0 {
+            return Err("`amount` must be positive".to_string());
+        }
+        let fx_rate = Self::number(record, "fx_rate")?;
+        if fx_rate <= 0.0 {
+            return Err("`fx_rate` must be positive".to_string());
+        }
+        let currency = Self::string(record, "currency")?;
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("`currency` must be a three-letter code, got `{}`", currency));
+        }
+        let timestamp = Self::string(record, "timestamp")?;
+        let timestamp = NaiveDateTime::parse_from_str(&timestamp, TIMESTAMP_FORMAT).map_err(|_| {
+            format!("`timestamp` must look like 2024-01-31 13:45:00, got `{}`", timestamp)
+        })?;
+
+        let previous_balance = Self::number(record, "previous_balance")?;
+        let new_balance = Self::number(record, "new_balance")?;
+        let expected = if MONEY_IN_TYPES.contains(&transaction_type.as_str()) {
+            previous_balance + amount
+        } else {
+            previous_balance - amount
+        };
+        if (expected - new_balance).abs() >= 0.005 {
+            return Err(format!(
+                "`new_balance` {} does not follow from `previous_balance` {} and `amount` {}",
+                new_balance, previous_balance, amount
+            ));
+        }
+
+        Ok(Transaction {
+            id: Self::string(record, "id")?,
+            username: Self::string(record, "username")?,
+            transaction_type,
+            amount,
+            recipient: Self::optional_string(record, "recipient")?,
+            sender: Self::optional_string(record, "sender")?,
+            previous_balance,
+            new_balance,
+            timestamp,
+            currency,
+            fx_rate,
+            transfer_id: None,
+            merchant: None,
+        })
+    }
+
+    fn string(record: &Map<String, Value>, key: &str) -> std::result::Result<String, String> {
+        match record.get(key) {
+            Some(Value::String(value)) if !value.is_empty() => Ok(value.clone()),
+            Some(Value::String(_)) => Err(format!("`{}` must not be empty", key)),
+            Some(_) => Err(format!("`{}` must be a string", key)),
+            None => Err(format!("missing field `{}`", key)),
+        }
+    }
+
+    fn optional_string(
+        record: &Map<String, Value>,
+        key: &str,
+    ) -> std::result::Result<Option<String>, String> {
+        match record.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(_) => Self::string(record, key).map(Some),
+        }
+    }
+
+    fn number(record: &Map<String, Value>, key: &str) -> std::result::Result<f64, String> {
+        match record.get(key) {
+            Some(value) => value
+                .as_f64()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| format!("`{}` must be a number", key)),
+            None => Err(format!("missing field `{}`", key)),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The balance, committed and vault figures of the user `u`.
+const FIGURES: &str = "u.balance,
+    (SELECT COALESCE(SUM(amount), 0) FROM holds
+        WHERE username = u.username AND status = 'pending')
+    + (SELECT COALESCE(SUM(amount), 0) FROM pending_transfers
+        WHERE sender = u.username AND status = 'pending')
+    + (SELECT COALESCE(SUM(amount), 0) FROM processor_payments
+        WHERE username = u.username AND kind = 'cheque' AND status = 'pending'),
+    u.vault_balance + (SELECT COALESCE(SUM(amount), 0) FROM vault_withdrawals
+        WHERE username = u.username AND status = 'pending')";
+
+/// What a user owns on one day, split by where the money is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetWorth {
+    pub day: NaiveDate,
+    /// The balance less what is committed, which can be spent.
+    pub available: f64,
+    /// Money still in the balance but held for card payments or for
+    /// transfers in their cooling period, or from cheques not cleared yet.
+    pub committed: f64,
+    /// The vault, with withdrawals that are still on their way out.
+    pub vault: f64,
+}
+
+impl NetWorth {
+    pub fn total(&self) -> f64 {
+        self.available + self.committed + self.vault
+    }
+}
+
+/// Daily snapshots of every user's net worth, written by the daemon, so it
+/// can be charted over time. Vault balances and holds are not in the
+/// transaction log, so they cannot be worked out afterwards.
+pub struct NetWorthSnapshots;
+
+impl NetWorthSnapshots {
+    /// Records every user's net worth as it is now under `day`, replacing
+    /// what was recorded earlier that day, so each day keeps its latest
+    /// figures. Returns the number of users recorded.
+    pub fn record_all(conn: &Connection, day: NaiveDate) -> Result<usize> {
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO net_worth_snapshots
+                    (username, day, balance, committed, vault)
+                SELECT u.username, ?1, {} FROM users u",
+                FIGURES
+            ),
+            params![day.format(DATE_FORMAT).to_string()],
+        )
+    }
+
+    /// Works out the user's net worth as it is now, without recording it.
+    pub fn current(conn: &Connection, username: &str, day: NaiveDate) -> Result<NetWorth> {
+        let (balance, committed, vault): (f64, f64, f64) = conn
+            .query_row(
+                &format!("SELECT {} FROM users u WHERE u.username = ?", FIGURES),
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .unwrap_or_default();
+        Ok(NetWorth { day, available: balance - committed, committed, vault })
+    }
+
+    /// Gets the user's snapshots from `since` on, oldest first.
+    pub fn get_since(conn: &Connection, username: &str, since: NaiveDate) -> Result<Vec<NetWorth>> {
+        let mut stmt = conn.prepare(
+            "SELECT day, balance, committed, vault FROM net_worth_snapshots
+            WHERE username = ? AND day >= ?
+            ORDER BY day",
+        )?;
+        let rows = stmt.query_map(
+            params![username, since.format(DATE_FORMAT).to_string()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                ))
+            },
+        )?;
+
+        let mut snapshots = Vec::new();
+        for row in rows {
+            let (day, balance, committed, vault) = row?;
+            if let Ok(day) = NaiveDate::parse_from_str(&day, DATE_FORMAT) {
+                snapshots.push(NetWorth {
+                    day,
+                    available: balance - committed,
+                    committed,
+                    vault,
+                });
+            }
+        }
+        Ok(snapshots)
+    }
+}
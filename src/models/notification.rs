@@ -0,0 +1,138 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// What a notification is about.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum NotificationKind {
+    TransferReceived,
+    RequestExpired,
+    BalanceAlert,
+    LimitExceeded,
+    PaymentSettled,
+    PaymentFailed,
+    WeeklySummary,
+    AccessShared,
+    BalanceSwept,
+    JointApproval,
+    TransferReversed,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::TransferReceived => "transfer_received",
+            NotificationKind::RequestExpired => "request_expired",
+            NotificationKind::BalanceAlert => "balance_alert",
+            NotificationKind::LimitExceeded => "limit_exceeded",
+            NotificationKind::PaymentSettled => "payment_settled",
+            NotificationKind::PaymentFailed => "payment_failed",
+            NotificationKind::WeeklySummary => "weekly_summary",
+            NotificationKind::AccessShared => "access_shared",
+            NotificationKind::BalanceSwept => "balance_swept",
+            NotificationKind::JointApproval => "joint_approval",
+            NotificationKind::TransferReversed => "transfer_reversed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "request_expired" => NotificationKind::RequestExpired,
+            "balance_alert" => NotificationKind::BalanceAlert,
+            "limit_exceeded" => NotificationKind::LimitExceeded,
+            "payment_settled" => NotificationKind::PaymentSettled,
+            "payment_failed" => NotificationKind::PaymentFailed,
+            "weekly_summary" => NotificationKind::WeeklySummary,
+            "access_shared" => NotificationKind::AccessShared,
+            "balance_swept" => NotificationKind::BalanceSwept,
+            "joint_approval" => NotificationKind::JointApproval,
+            "transfer_reversed" => NotificationKind::TransferReversed,
+            _ => NotificationKind::TransferReceived,
+        }
+    }
+}
+
+/// A message kept in a user's inbox until they clear it.
+pub struct Notification {
+    pub id: i64,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+    pub read: bool,
+}
+
+/// Per-user inbox of things that happened, including while the user was
+/// logged out.
+pub struct Notifications;
+
+impl Notifications {
+    pub fn create(
+        conn: &Connection,
+        username: &str,
+        kind: NotificationKind,
+        message: &str,
+        created_at: NaiveDateTime,
+    ) -> Result<()> {
+        conn.execute(
+            "INSERT INTO notifications (username, kind, message, created_at)
+            VALUES (?, ?, ?, ?)",
+            params![
+                username,
+                kind.as_str(),
+                message,
+                created_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the user's notifications, newest first.
+    pub fn get_for_user(conn: &Connection, username: &str) -> Result<Vec<Notification>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, message, created_at, read FROM notifications
+            WHERE username = ? ORDER BY id DESC",
+        )?;
+        let notifications = stmt
+            .query_map(params![username], |row| {
+                Ok(Notification {
+                    id: row.get(0)?,
+                    kind: NotificationKind::parse(&row.get::<_, String>(1)?),
+                    message: row.get(2)?,
+                    created_at: NaiveDateTime::parse_from_str(
+                        &row.get::<_, String>(3)?,
+                        TIMESTAMP_FORMAT,
+                    )
+                    .unwrap_or_default(),
+                    read: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(notifications)
+    }
+
+    pub fn count_unread(conn: &Connection, username: &str) -> Result<usize> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM notifications WHERE username = ? AND read = 0",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn mark_read(conn: &Connection, id: i64) -> Result<()> {
+        conn.execute("UPDATE notifications SET read = 1 WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    pub fn mark_all_read(conn: &Connection, username: &str) -> Result<usize> {
+        conn.execute(
+            "UPDATE notifications SET read = 1 WHERE username = ? AND read = 0",
+            params![username],
+        )
+    }
+
+    /// Deletes all of the user's notifications. Returns how many there were.
+    pub fn clear(conn: &Connection, username: &str) -> Result<usize> {
+        conn.execute("DELETE FROM notifications WHERE username = ?", params![username])
+    }
+}
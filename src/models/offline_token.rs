@@ -0,0 +1,267 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::NaiveDateTime;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::Rng;
+use rusqlite::{params, Connection, Result};
+use serde_json::{json, Value};
+
+use crate::models::key_provider::KeyProvider;
+use crate::models::user_key::SealedKey;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Marks the token format, so a future format can be told apart.
+const TOKEN_PREFIX: &str = "EWT1";
+
+/// Why a pasted token could not be redeemed.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TokenError {
+    Malformed,
+    BadSignature,
+    /// Signed by a wallet this one has not been told to trust.
+    UntrustedKey,
+}
+
+impl TokenError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenError::Malformed => "not a valid transfer token",
+            TokenError::BadSignature => "signature does not match",
+            TokenError::UntrustedKey => "issued by a wallet this one does not trust",
+        }
+    }
+}
+
+/// Which side of a token this wallet was on.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TokenDirection {
+    Issued,
+    Redeemed,
+}
+
+impl TokenDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenDirection::Issued => "issued",
+            TokenDirection::Redeemed => "redeemed",
+        }
+    }
+}
+
+/// A bearer token worth `amount`, signed by the wallet that issued it. The
+/// amount leaves the issuer's balance when the token is made, and the token
+/// can be carried as text to another instance, which needs no connection to
+/// the issuer to check the signature and credit whoever redeems it.
+///
+/// A token is only redeemed if it is signed by a wallet key the redeeming
+/// instance trusts: its own, or one listed in `rules.toml`. The key inside
+/// the token only says which wallet claims to have signed it.
+///
+/// Each instance records the nonces it has redeemed, so a token is only
+/// accepted once per database.
+pub struct OfflineToken {
+    pub nonce: String,
+    pub issuer: String,
+    pub amount: f64,
+    pub issued_at: NaiveDateTime,
+    pub public_key: VerifyingKey,
+}
+
+impl OfflineToken {
+    /// Creates a token with a fresh random nonce, to be signed by `key`.
+    pub fn new(key: &SigningKey, issuer: &str, amount: f64, issued_at: NaiveDateTime) -> Self {
+        OfflineToken {
+            nonce: format!("{:032x}", rand::thread_rng().gen::<u128>()),
+            issuer: issuer.to_string(),
+            amount,
+            issued_at,
+            public_key: key.verifying_key(),
+        }
+    }
+
+    /// Gets this instance's signing key, generating and storing one on first
+    /// use. With a secret from `provider` the key is stored sealed under it,
+    /// and a key stored unsealed is sealed. Without one, a new key is only
+    /// stored unsealed if `provider` allows it. Returns `None` if there is
+    /// no key to use: it is sealed and the secret does not open it, or none
+    /// could be made.
+    pub fn wallet_key(
+        conn: &Connection,
+        provider: &dyn KeyProvider,
+    ) -> Result<Option<SigningKey>> {
+        let secret = provider.secret();
+        if Self::has_wallet_key(conn)? {
+            return Self::stored_wallet_key(conn, secret.as_deref());
+        }
+
+        let key = SigningKey::from_bytes(&rand::thread_rng().gen());
+        let inserted = match secret.as_deref().and_then(|secret| SealedKey::seal(&key, secret)) {
+            Some(sealed) => conn.execute(
+                "INSERT OR IGNORE INTO wallet_key (id, secret_key, salt, nonce)
+                VALUES (1, ?, ?, ?)",
+                params![sealed.encrypted, sealed.salt, sealed.nonce],
+            )?,
+            None if provider.allows_unsealed() => conn.execute(
+                "INSERT OR IGNORE INTO wallet_key (id, secret_key) VALUES (1, ?)",
+                params![key.to_bytes().to_vec()],
+            )?,
+            None => return Ok(None),
+        };
+        if inserted > 0 {
+            return Ok(Some(key));
+        }
+        // Another instance stored its key first; use that one.
+        Self::stored_wallet_key(conn, secret.as_deref())
+    }
+
+    fn has_wallet_key(conn: &Connection) -> Result<bool> {
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM wallet_key WHERE id = 1)", [], |row| {
+            row.get(0)
+        })
+    }
+
+    /// Reads the stored wallet key, sealing it under `secret` if it is
+    /// stored unsealed.
+    fn stored_wallet_key(conn: &Connection, secret: Option<&str>) -> Result<Option<SigningKey>> {
+        let (secret_key, salt, nonce): (Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>) = conn
+            .query_row("SELECT secret_key, salt, nonce FROM wallet_key WHERE id = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        if let (Some(salt), Some(nonce)) = (salt, nonce) {
+            let sealed = SealedKey {
+                salt,
+                nonce,
+                encrypted: secret_key,
+            };
+            return Ok(secret.and_then(|secret| sealed.open(secret)));
+        }
+
+        let key = match <[u8; 32]>::try_from(secret_key) {
+            Ok(bytes) => SigningKey::from_bytes(&bytes),
+            Err(_) => return Ok(None),
+        };
+        if let Some(sealed) = secret.and_then(|secret| SealedKey::seal(&key, secret)) {
+            conn.execute(
+                "UPDATE wallet_key SET secret_key = ?, salt = ?, nonce = ? WHERE id = 1",
+                params![sealed.encrypted, sealed.salt, sealed.nonce],
+            )?;
+        }
+        Ok(Some(key))
+    }
+
+    /// Encodes a wallet's public key as base64url, the form it is listed in
+    /// `rules.toml` to be trusted.
+    pub fn encode_key(key: &VerifyingKey) -> String {
+        URL_SAFE_NO_PAD.encode(key.as_bytes())
+    }
+
+    /// Reads a public key written by `encode_key`.
+    pub fn parse_key(text: &str) -> Option<VerifyingKey> {
+        URL_SAFE_NO_PAD
+            .decode(text.trim())
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+    }
+
+    /// Signs the token and encodes it as `EWT1.<payload>.<signature>`, both
+    /// parts base64url, ready to be shown as text or a QR code.
+    pub fn encode(&self, key: &SigningKey) -> String {
+        let payload = json!({
+            "nonce": self.nonce,
+            "issuer": self.issuer,
+            "amount": self.amount,
+            "issued_at": self.issued_at.format(TIMESTAMP_FORMAT).to_string(),
+            "key": Self::encode_key(&self.public_key),
+        })
+        .to_string();
+        let signature = key.sign(payload.as_bytes());
+        format!(
+            "{}.{}.{}",
+            TOKEN_PREFIX,
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    /// Parses a token and checks it is signed by one of the `trusted` wallet
+    /// keys. Whitespace is ignored, so a token copied across wrapped lines
+    /// still decodes.
+    pub fn decode(text: &str, trusted: &[VerifyingKey]) -> std::result::Result<Self, TokenError> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut parts = text.split('.');
+        let (payload, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(TOKEN_PREFIX), Some(payload), Some(signature), None) => (payload, signature),
+            _ => return Err(TokenError::Malformed),
+        };
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| TokenError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or(TokenError::Malformed)?;
+
+        let fields: Value = serde_json::from_slice(&payload).map_err(|_| TokenError::Malformed)?;
+        let claimed = fields["key"]
+            .as_str()
+            .and_then(Self::parse_key)
+            .ok_or(TokenError::Malformed)?;
+        let public_key = *trusted
+            .iter()
+            .find(|key| **key == claimed)
+            .ok_or(TokenError::UntrustedKey)?;
+        public_key
+            .verify(&payload, &signature)
+            .map_err(|_| TokenError::BadSignature)?;
+
+        let token = OfflineToken {
+            nonce: fields["nonce"].as_str().unwrap_or_default().to_string(),
+            issuer: fields["issuer"].as_str().unwrap_or_default().to_string(),
+            amount: fields["amount"].as_f64().unwrap_or(0.0),
+            issued_at: fields["issued_at"]
+                .as_str()
+                .and_then(|value| NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT).ok())
+                .ok_or(TokenError::Malformed)?,
+            public_key,
+        };
+        if token.nonce.is_empty() || !token.amount.is_finite() || token.amount <= 0.0 {
+            return Err(TokenError::Malformed);
+        }
+        Ok(token)
+    }
+
+    /// Records that `username` issued or redeemed the token. Returns `false`
+    /// if this database has already seen the nonce in that direction.
+    pub fn record(
+        conn: &Connection,
+        token: &OfflineToken,
+        direction: TokenDirection,
+        username: &str,
+        at: NaiveDateTime,
+    ) -> Result<bool> {
+        let counterparty = match direction {
+            TokenDirection::Issued => None,
+            TokenDirection::Redeemed => Some(token.issuer.as_str()),
+        };
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO offline_tokens (nonce, direction, username, amount, counterparty, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                token.nonce,
+                direction.as_str(),
+                username,
+                token.amount,
+                counterparty,
+                at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Short form of the nonce for display.
+    pub fn short_nonce(&self) -> String {
+        self.nonce.chars().take(8).collect()
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{Duration, NaiveDateTime};
+use rusqlite::{params, Connection, Result};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Deliveries tried before a message is given up on. It stays in the outbox
+/// with its last error so it can be looked at.
+pub const MAX_ATTEMPTS: u32 = 15;
+
+/// Longest wait between two attempts, in seconds.
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+/// How long to wait for the receiving server to connect, read or answer.
+const HTTP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// A webhook payload waiting to be delivered.
+pub struct OutboxMessage {
+    pub id: i64,
+    pub username: String,
+    pub url: String,
+    pub payload: String,
+    pub attempts: u32,
+}
+
+/// Outgoing webhooks. A trigger on `transactions` queues one message per
+/// transaction for users with a `webhook_url` setting, in the same SQLite
+/// transaction as the money movement, so a crash can never lose one. The
+/// daemon delivers them and retries failures with exponential backoff.
+pub struct Outbox;
+
+impl Outbox {
+    /// Gets the messages whose next attempt is due, oldest first.
+    pub fn due(conn: &Connection, now: NaiveDateTime) -> Result<Vec<OutboxMessage>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, url, payload, attempts FROM outbox
+            WHERE attempts < ? AND next_attempt_at <= ?
+            ORDER BY id",
+        )?;
+        let messages = stmt
+            .query_map(
+                params![MAX_ATTEMPTS, now.format(TIMESTAMP_FORMAT).to_string()],
+                |row| {
+                    Ok(OutboxMessage {
+                        id: row.get(0)?,
+                        username: row.get(1)?,
+                        url: row.get(2)?,
+                        payload: row.get(3)?,
+                        attempts: row.get(4)?,
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(messages)
+    }
+
+    /// Removes a message the server accepted.
+    pub fn delivered(conn: &Connection, id: i64) -> Result<()> {
+        conn.execute("DELETE FROM outbox WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Records a failed attempt and schedules the next one, waiting twice as
+    /// long after each failure up to an hour.
+    pub fn failed(
+        conn: &Connection,
+        message: &OutboxMessage,
+        error: &str,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let attempts = message.attempts + 1;
+        let backoff = 2_i64.saturating_pow(attempts).min(MAX_BACKOFF_SECS);
+        let next_attempt_at = now + Duration::seconds(backoff);
+        conn.execute(
+            "UPDATE outbox SET attempts = ?, next_attempt_at = ?, last_error = ? WHERE id = ?",
+            params![
+                attempts,
+                next_attempt_at.format(TIMESTAMP_FORMAT).to_string(),
+                error,
+                message.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Counts messages still waiting, and those given up on.
+    pub fn counts(conn: &Connection) -> Result<(i64, i64)> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(attempts < ?1), 0), COALESCE(SUM(attempts >= ?1), 0)
+            FROM outbox",
+            params![MAX_ATTEMPTS],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+}
+
+/// Splits an `http://host[:port][/path]` URL into host, port and path.
+/// Only plain HTTP is supported.
+pub fn parse_url(url: &str) -> Option<(String, u16, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_string(), port, path.to_string()))
+}
+
+/// POSTs `payload` as JSON to `url`. Any 2xx answer counts as delivered;
+/// anything else is returned as an error to retry later.
+pub fn post_json(url: &str, payload: &str) -> std::result::Result<(), String> {
+    let (host, port, path) = parse_url(url).ok_or("not an http:// URL")?;
+    let address = (host.as_str(), port)
+        .to_socket_addrs()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or("host not found")?;
+    let mut stream =
+        TcpStream::connect_timeout(&address, HTTP_TIMEOUT).map_err(|err| err.to_string())?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT)).map_err(|err| err.to_string())?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT)).map_err(|err| err.to_string())?;
+
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        payload.len(),
+        payload
+    );
+    stream.write_all(request.as_bytes()).map_err(|err| err.to_string())?;
+
+    // Only the status line matters
+    let mut response = [0; 64];
+    let read = stream.read(&mut response).map_err(|err| err.to_string())?;
+    let status_line = String::from_utf8_lossy(&response[..read]);
+    let status = status_line.split_whitespace().nth(1).unwrap_or("");
+    if status.starts_with('2') && status.len() == 3 {
+        Ok(())
+    } else if status.is_empty() {
+        Err("no HTTP response".to_string())
+    } else {
+        Err(format!("HTTP {}", status))
+    }
+}
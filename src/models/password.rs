@@ -0,0 +1,211 @@
+use argon2::Argon2;
+use chrono::NaiveDateTime;
+use ed25519_dalek::SigningKey;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+
+use crate::models::user_key::SealedKey;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Shortest password accepted, in characters.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// How many recovery codes are issued at a time.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Characters recovery codes are made of, leaving out ones that are easily
+/// misread, such as `0` and `o`.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Optional account passwords, stored as salted Argon2 hashes. Accounts
+/// without one log in with their username alone.
+pub struct AccountPassword;
+
+impl AccountPassword {
+    pub fn is_valid(password: &str) -> bool {
+        password.chars().count() >= MIN_PASSWORD_LENGTH
+    }
+
+    pub fn exists(conn: &Connection, username: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM account_passwords WHERE username = ?)",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Sets or replaces the user's password.
+    pub fn set(
+        conn: &Connection,
+        username: &str,
+        password: &str,
+        now: NaiveDateTime,
+    ) -> Result<()> {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        conn.execute(
+            "INSERT INTO account_passwords (username, salt, password_hash, changed_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(username) DO UPDATE
+                SET salt = ?2, password_hash = ?3, changed_at = ?4",
+            params![
+                username,
+                salt.to_vec(),
+                Self::derive(password, &salt),
+                now.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Gets when the user's password was last set, if they have one.
+    pub fn changed_at(conn: &Connection, username: &str) -> Result<Option<NaiveDateTime>> {
+        let changed_at: Option<String> = conn
+            .query_row(
+                "SELECT changed_at FROM account_passwords WHERE username = ?",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(changed_at.and_then(|at| NaiveDateTime::parse_from_str(&at, TIMESTAMP_FORMAT).ok()))
+    }
+
+    /// Checks a password. Users without a password never match.
+    pub fn verify(conn: &Connection, username: &str, password: &str) -> Result<bool> {
+        let stored: Option<(Vec<u8>, Vec<u8>)> = conn
+            .query_row(
+                "SELECT salt, password_hash FROM account_passwords WHERE username = ?",
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(stored.is_some_and(|(salt, hash)| Self::derive(password, &salt) == hash))
+    }
+
+    fn derive(password: &str, salt: &[u8]) -> Vec<u8> {
+        let mut derived = [0u8; 32];
+        // Only fails for salts or outputs of invalid length, which these are not.
+        let _ = Argon2::default().hash_password_into(password.as_bytes(), salt, &mut derived);
+        derived.to_vec()
+    }
+}
+
+/// One-time codes that let a user who lost their password set a new one.
+/// Codes are random enough that a SHA-256 hash of each is all that is
+/// stored, like session tokens, and a used code is marked so it cannot be
+/// used again. Each code also holds a copy of the user's signing key sealed
+/// under the code, so a password reset does not lose the key.
+pub struct RecoveryCodes;
+
+impl RecoveryCodes {
+    /// Replaces the user's recovery codes with new ones and returns them.
+    /// They are shown once and cannot be read back.
+    pub fn generate(
+        conn: &Connection,
+        username: &str,
+        key: Option<&SigningKey>,
+    ) -> Result<Vec<String>> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM recovery_codes WHERE username = ?", params![username])?;
+        let mut rng = rand::thread_rng();
+        let mut codes = Vec::new();
+        while codes.len() < RECOVERY_CODE_COUNT {
+            let chars: String = (0..10)
+                .map(|_| *RECOVERY_CODE_ALPHABET.choose(&mut rng).unwrap_or(&b'a') as char)
+                .collect();
+            let code = format!("{}-{}", &chars[..5], &chars[5..]);
+            let sealed = key.and_then(|key| SealedKey::seal(key, &code));
+            let added = tx.execute(
+                "INSERT OR IGNORE INTO recovery_codes
+                    (username, code_hash, key_salt, key_nonce, encrypted_key)
+                VALUES (?, ?, ?, ?, ?)",
+                params![
+                    username,
+                    Self::hash(&code),
+                    sealed.as_ref().map(|sealed| &sealed.salt),
+                    sealed.as_ref().map(|sealed| &sealed.nonce),
+                    sealed.as_ref().map(|sealed| &sealed.encrypted)
+                ],
+            )?;
+            if added > 0 {
+                codes.push(code);
+            }
+        }
+        tx.commit()?;
+        Ok(codes)
+    }
+
+    /// Whether `code` is one of the user's unused codes.
+    pub fn is_valid(conn: &Connection, username: &str, code: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM recovery_codes
+                WHERE username = ? AND code_hash = ? AND used_at IS NULL)",
+            params![username, Self::hash(code)],
+            |row| row.get(0),
+        )
+    }
+
+    /// Gets the signing key sealed under `code`, if it is one of the user's
+    /// unused codes and holds a copy.
+    pub fn signing_key(
+        conn: &Connection,
+        username: &str,
+        code: &str,
+    ) -> Result<Option<SigningKey>> {
+        let sealed = conn
+            .query_row(
+                "SELECT key_salt, key_nonce, encrypted_key FROM recovery_codes
+                WHERE username = ? AND code_hash = ? AND used_at IS NULL
+                    AND encrypted_key IS NOT NULL",
+                params![username, Self::hash(code)],
+                |row| {
+                    Ok(SealedKey {
+                        salt: row.get(0)?,
+                        nonce: row.get(1)?,
+                        encrypted: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(sealed.and_then(|sealed| sealed.open(code)))
+    }
+
+    /// Uses up `code`, and the copy of the signing key it held. Returns
+    /// `false` if it is not one of the user's unused codes.
+    pub fn redeem(
+        conn: &Connection,
+        username: &str,
+        code: &str,
+        now: NaiveDateTime,
+    ) -> Result<bool> {
+        let used = conn.execute(
+            "UPDATE recovery_codes
+            SET used_at = ?3, key_salt = NULL, key_nonce = NULL, encrypted_key = NULL
+            WHERE username = ?1 AND code_hash = ?2 AND used_at IS NULL",
+            params![username, Self::hash(code), now.format(TIMESTAMP_FORMAT).to_string()],
+        )?;
+        Ok(used > 0)
+    }
+
+    /// Counts the user's unused codes.
+    pub fn remaining(conn: &Connection, username: &str) -> Result<usize> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM recovery_codes WHERE username = ? AND used_at IS NULL",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Codes are compared without case, spaces or dashes, so they can be
+    /// typed the way they were written down.
+    fn hash(code: &str) -> String {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        format!("{:x}", Sha256::digest(normalized.as_bytes()))
+    }
+}
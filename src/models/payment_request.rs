@@ -0,0 +1,165 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Lifecycle of a payment request. Requests start out pending and can move
+/// to exactly one final status.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum RequestStatus {
+    Pending,
+    Paid,
+    Declined,
+    Expired,
+}
+
+impl RequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestStatus::Pending => "pending",
+            RequestStatus::Paid => "paid",
+            RequestStatus::Declined => "declined",
+            RequestStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "paid" => RequestStatus::Paid,
+            "declined" => RequestStatus::Declined,
+            "expired" => RequestStatus::Expired,
+            _ => RequestStatus::Pending,
+        }
+    }
+
+    /// Whether a request in this status may move to `next`.
+    pub fn can_become(&self, next: RequestStatus) -> bool {
+        *self == RequestStatus::Pending && next != RequestStatus::Pending
+    }
+}
+
+/// A request from `requester` asking `payer` to send them money.
+pub struct PaymentRequest {
+    pub id: i64,
+    pub requester: String,
+    pub payer: String,
+    pub amount: f64,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+impl PaymentRequest {
+    pub fn create(
+        conn: &Connection,
+        requester: &str,
+        payer: &str,
+        amount: f64,
+        created_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO payment_requests (requester, payer, amount, status, created_at, expires_at)
+            VALUES (?, ?, ?, 'pending', ?, ?)",
+            params![
+                requester,
+                payer,
+                amount,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                expires_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Gets requests the user sent or received, newest first.
+    pub fn get_for_user(conn: &Connection, username: &str) -> Result<Vec<PaymentRequest>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, requester, payer, amount, status, created_at, expires_at
+            FROM payment_requests
+            WHERE requester = ?1 OR payer = ?1
+            ORDER BY created_at DESC, id DESC",
+        )?;
+        let requests = stmt.query_map(params![username], Self::from_row)?;
+        requests.collect()
+    }
+
+    /// Moves a request to a new status if its current status allows it.
+    /// Returns `false` if the request had already been settled.
+    pub fn set_status(conn: &Connection, id: i64, status: RequestStatus) -> Result<bool> {
+        if !RequestStatus::Pending.can_become(status) {
+            return Ok(false);
+        }
+        let updated = conn.execute(
+            "UPDATE payment_requests SET status = ? WHERE id = ? AND status = 'pending'",
+            params![status.as_str(), id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Marks every pending request whose deadline has passed as expired.
+    pub fn expire_due(conn: &Connection, now: NaiveDateTime) -> Result<usize> {
+        conn.execute(
+            "UPDATE payment_requests SET status = 'expired'
+            WHERE status = 'pending' AND expires_at <= ?",
+            params![now.format(TIMESTAMP_FORMAT).to_string()],
+        )
+    }
+
+    /// Counts requests for the user to pay that arrived after `since` and are
+    /// still waiting.
+    pub fn count_received_since(
+        conn: &Connection,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<usize> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM payment_requests
+            WHERE payer = ? AND status = 'pending' AND created_at > ?",
+            params![username, since.format(TIMESTAMP_FORMAT).to_string()],
+            |row| row.get(0),
+        )
+    }
+
+    /// Gets expired requests involving the user that they have not been told
+    /// about yet, and marks them as seen by that party.
+    pub fn take_expiry_notices(conn: &Connection, username: &str) -> Result<Vec<PaymentRequest>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, requester, payer, amount, status, created_at, expires_at
+            FROM payment_requests
+            WHERE status = 'expired'
+              AND ((requester = ?1 AND requester_notified = 0)
+                OR (payer = ?1 AND payer_notified = 0))
+            ORDER BY expires_at",
+        )?;
+        let notices = stmt
+            .query_map(params![username], Self::from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        conn.execute(
+            "UPDATE payment_requests SET requester_notified = 1
+            WHERE status = 'expired' AND requester = ?",
+            params![username],
+        )?;
+        conn.execute(
+            "UPDATE payment_requests SET payer_notified = 1
+            WHERE status = 'expired' AND payer = ?",
+            params![username],
+        )?;
+        Ok(notices)
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<PaymentRequest> {
+        let parse_time = |value: String| {
+            NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT).unwrap_or_default()
+        };
+        Ok(PaymentRequest {
+            id: row.get(0)?,
+            requester: row.get(1)?,
+            payer: row.get(2)?,
+            amount: row.get(3)?,
+            status: RequestStatus::parse(&row.get::<_, String>(4)?),
+            created_at: parse_time(row.get(5)?),
+            expires_at: parse_time(row.get(6)?),
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::models::payment_request::RequestStatus;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A large transfer held back until the sender confirms it again after a
+/// cooling period, or an admin approves it. A transfer from a joint account
+/// over the approval threshold instead waits for a co-owner to approve or
+/// reject it, and expires if none does in time. Uses the same status
+/// lifecycle as payment requests: pending until it is paid, declined
+/// (cancelled or rejected) or expired.
+pub struct PendingTransfer {
+    pub id: i64,
+    pub sender: String,
+    pub recipient: String,
+    pub amount: f64,
+    pub note: Option<String>,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    /// Whether a co-owner of the sending joint account must approve it.
+    pub needs_approval: bool,
+    /// The co-owner who approved or rejected it.
+    pub decided_by: Option<String>,
+}
+
+const COLUMNS: &str =
+    "id, sender, recipient, amount, status, created_at, note, needs_approval, decided_by";
+
+impl PendingTransfer {
+    pub fn create(
+        conn: &Connection,
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+        note: Option<&str>,
+        created_at: NaiveDateTime,
+        needs_approval: bool,
+    ) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO pending_transfers
+                (sender, recipient, amount, note, status, created_at, needs_approval)
+            VALUES (?, ?, ?, ?, 'pending', ?, ?)",
+            params![
+                sender,
+                recipient,
+                amount,
+                note,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                needs_approval
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get(conn: &Connection, id: i64) -> Result<Option<PendingTransfer>> {
+        conn.query_row(
+            &format!("SELECT {} FROM pending_transfers WHERE id = ?", COLUMNS),
+            params![id],
+            Self::from_row,
+        )
+        .optional()
+    }
+
+    /// Gets the user's held transfers that still wait for confirmation, oldest first.
+    pub fn get_pending_for_sender(conn: &Connection, sender: &str) -> Result<Vec<PendingTransfer>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM pending_transfers
+            WHERE sender = ? AND status = 'pending'
+            ORDER BY created_at, id",
+            COLUMNS
+        ))?;
+        let transfers = stmt.query_map(params![sender], Self::from_row)?;
+        transfers.collect()
+    }
+
+    /// Gets the transfers from joint accounts `member` co-owns that wait for
+    /// their approval, oldest first.
+    pub fn get_awaiting_approval(conn: &Connection, member: &str) -> Result<Vec<PendingTransfer>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM pending_transfers
+            WHERE needs_approval = 1 AND status = 'pending'
+                AND sender IN (SELECT account FROM joint_members WHERE member = ?)
+            ORDER BY created_at, id",
+            COLUMNS
+        ))?;
+        let transfers = stmt.query_map(params![member], Self::from_row)?;
+        transfers.collect()
+    }
+
+    /// Gets the transfers still waiting for approval that were made before
+    /// `cutoff`.
+    pub fn get_unapproved_before(
+        conn: &Connection,
+        cutoff: NaiveDateTime,
+    ) -> Result<Vec<PendingTransfer>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM pending_transfers
+            WHERE needs_approval = 1 AND status = 'pending' AND created_at < ?
+            ORDER BY created_at, id",
+            COLUMNS
+        ))?;
+        let transfers = stmt.query_map(
+            params![cutoff.format(TIMESTAMP_FORMAT).to_string()],
+            Self::from_row,
+        )?;
+        transfers.collect()
+    }
+
+    /// Sums the user's held transfers that still wait for confirmation.
+    pub fn total_pending_for_sender(conn: &Connection, sender: &str) -> Result<f64> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM pending_transfers
+            WHERE sender = ? AND status = 'pending'",
+            params![sender],
+            |row| row.get(0),
+        )
+    }
+
+    /// Moves a held transfer to a new status if it is still pending.
+    /// Returns `false` if it had already been settled.
+    pub fn set_status(conn: &Connection, id: i64, status: RequestStatus) -> Result<bool> {
+        if !RequestStatus::Pending.can_become(status) {
+            return Ok(false);
+        }
+        let updated = conn.execute(
+            "UPDATE pending_transfers SET status = ? WHERE id = ? AND status = 'pending'",
+            params![status.as_str(), id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Records which co-owner approved or rejected a transfer.
+    pub fn set_decided_by(conn: &Connection, id: i64, member: &str) -> Result<()> {
+        conn.execute(
+            "UPDATE pending_transfers SET decided_by = ? WHERE id = ?",
+            params![member, id],
+        )?;
+        Ok(())
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<PendingTransfer> {
+        let created_at: String = row.get(5)?;
+        Ok(PendingTransfer {
+            id: row.get(0)?,
+            sender: row.get(1)?,
+            recipient: row.get(2)?,
+            amount: row.get(3)?,
+            note: row.get(6)?,
+            status: RequestStatus::parse(&row.get::<_, String>(4)?),
+            created_at: NaiveDateTime::parse_from_str(&created_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+            needs_approval: row.get(7)?,
+            decided_by: row.get(8)?,
+        })
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, Result};
+
+use crate::models::payment_request::RequestStatus;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Seconds the simulated processor takes to settle a payment.
+const DEFAULT_LATENCY_SECONDS: i64 = 5;
+
+/// Seconds a bank withdrawal takes to settle.
+const DEFAULT_BANK_DELAY_SECONDS: i64 = 60;
+
+/// Seconds a deposited cheque takes to clear.
+const DEFAULT_CHEQUE_CLEARING_SECONDS: i64 = 120;
+
+/// Share of payments the simulated processor fails.
+const DEFAULT_FAILURE_RATE: f64 = 0.1;
+
+/// What a payment sent to the processor does.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PaymentKind {
+    CardTopUp,
+    BankWithdrawal,
+    /// A cheque, credited when it is deposited but not available until it
+    /// clears. A cheque that bounces is taken back out.
+    Cheque,
+}
+
+impl PaymentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentKind::CardTopUp => "card_top_up",
+            PaymentKind::BankWithdrawal => "bank_withdrawal",
+            PaymentKind::Cheque => "cheque",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "bank_withdrawal" => PaymentKind::BankWithdrawal,
+            "cheque" => PaymentKind::Cheque,
+            _ => PaymentKind::CardTopUp,
+        }
+    }
+
+    /// Names the payment in messages, e.g. "Card top-up".
+    pub fn describe(&self) -> &'static str {
+        match self {
+            PaymentKind::CardTopUp => "Card top-up",
+            PaymentKind::BankWithdrawal => "Bank withdrawal",
+            PaymentKind::Cheque => "Cheque",
+        }
+    }
+
+    /// Reasons the simulated processor gives for failing this kind of payment.
+    pub fn failure_reasons(&self) -> &'static [&'static str] {
+        match self {
+            PaymentKind::CardTopUp => &[
+                "card declined",
+                "processor timed out",
+                "refused by the issuing bank",
+            ],
+            PaymentKind::BankWithdrawal => &[
+                "account number not recognised",
+                "rejected by the receiving bank",
+                "processor timed out",
+            ],
+            PaymentKind::Cheque => &[
+                "not enough money in the drawer's account",
+                "signature does not match",
+                "stopped by the drawer",
+            ],
+        }
+    }
+}
+
+/// A payment handed to the simulated external processor. Uses the payment
+/// request lifecycle: pending until it is paid (settled) or declined
+/// (failed, with a reason).
+pub struct ProcessorPayment {
+    pub id: i64,
+    pub username: String,
+    pub kind: PaymentKind,
+    pub amount: f64,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    pub settles_at: NaiveDateTime,
+    pub failure: Option<String>,
+}
+
+impl ProcessorPayment {
+    /// Describes the status in the processor's terms.
+    pub fn status_label(&self) -> &'static str {
+        match (self.kind, self.status) {
+            (PaymentKind::Cheque, RequestStatus::Pending) => "clearing",
+            (PaymentKind::Cheque, RequestStatus::Paid) => "cleared",
+            (PaymentKind::Cheque, _) => "bounced",
+            (_, RequestStatus::Pending) => "pending",
+            (_, RequestStatus::Paid) => "settled",
+            _ => "failed",
+        }
+    }
+}
+
+/// How the simulated processor behaves. Set in `rules.toml` and the
+/// environment so demos can show slow or unreliable processors without
+/// rebuilding.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ProcessorConfig {
+    pub latency_seconds: i64,
+    pub bank_delay_seconds: i64,
+    pub cheque_clearing_seconds: i64,
+    /// Share of payments failed, and of cheques bounced, from 0 to 1.
+    pub failure_rate: f64,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        ProcessorConfig {
+            latency_seconds: DEFAULT_LATENCY_SECONDS,
+            bank_delay_seconds: DEFAULT_BANK_DELAY_SECONDS,
+            cheque_clearing_seconds: DEFAULT_CHEQUE_CLEARING_SECONDS,
+            failure_rate: DEFAULT_FAILURE_RATE,
+        }
+    }
+}
+
+impl ProcessorConfig {
+    /// Overrides the settings with `EWALLET_PROCESSOR_LATENCY` and
+    /// `EWALLET_BANK_SETTLEMENT_DELAY` (seconds) and
+    /// `EWALLET_PROCESSOR_FAILURE_RATE` (0 to 1), ignoring values that are
+    /// missing or out of range.
+    pub fn with_env(self) -> Self {
+        let read = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+        ProcessorConfig {
+            latency_seconds: read("EWALLET_PROCESSOR_LATENCY")
+                .filter(|&seconds| seconds >= 0.0)
+                .map_or(self.latency_seconds, |seconds| seconds as i64),
+            bank_delay_seconds: read("EWALLET_BANK_SETTLEMENT_DELAY")
+                .filter(|&seconds| seconds >= 0.0)
+                .map_or(self.bank_delay_seconds, |seconds| seconds as i64),
+            cheque_clearing_seconds: self.cheque_clearing_seconds,
+            failure_rate: read("EWALLET_PROCESSOR_FAILURE_RATE")
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .unwrap_or(self.failure_rate),
+        }
+    }
+
+    /// Seconds the processor takes to answer this kind of payment.
+    pub fn delay_seconds(&self, kind: PaymentKind) -> i64 {
+        match kind {
+            PaymentKind::CardTopUp => self.latency_seconds,
+            PaymentKind::BankWithdrawal => self.bank_delay_seconds,
+            PaymentKind::Cheque => self.cheque_clearing_seconds,
+        }
+    }
+}
+
+/// Payments waiting on the simulated processor.
+pub struct Processor;
+
+impl Processor {
+    pub fn create(
+        conn: &Connection,
+        username: &str,
+        kind: PaymentKind,
+        amount: f64,
+        created_at: NaiveDateTime,
+        settles_at: NaiveDateTime,
+    ) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO processor_payments
+                (username, kind, amount, status, created_at, settles_at)
+            VALUES (?, ?, ?, 'pending', ?, ?)",
+            params![
+                username,
+                kind.as_str(),
+                amount,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                settles_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Gets the user's most recent payments, newest first.
+    pub fn get_recent(
+        conn: &Connection,
+        username: &str,
+        limit: usize,
+    ) -> Result<Vec<ProcessorPayment>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, kind, amount, status, created_at, settles_at, failure
+            FROM processor_payments
+            WHERE username = ?
+            ORDER BY id DESC
+            LIMIT ?",
+        )?;
+        let payments = stmt.query_map(params![username, limit as i64], Self::from_row)?;
+        payments.collect()
+    }
+
+    /// Gets every user's pending payments that the processor should have
+    /// answered by `now`.
+    pub fn get_due(conn: &Connection, now: NaiveDateTime) -> Result<Vec<ProcessorPayment>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, kind, amount, status, created_at, settles_at, failure
+            FROM processor_payments
+            WHERE status = 'pending' AND settles_at <= ?
+            ORDER BY settles_at, id",
+        )?;
+        let payments = stmt.query_map(
+            params![now.format(TIMESTAMP_FORMAT).to_string()],
+            Self::from_row,
+        )?;
+        payments.collect()
+    }
+
+    pub fn count_pending(conn: &Connection, username: &str) -> Result<usize> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM processor_payments WHERE username = ? AND status = 'pending'",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Sums the user's cheques that have not cleared, which are in the
+    /// balance but cannot be spent yet.
+    pub fn total_uncleared(conn: &Connection, username: &str) -> Result<f64> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM processor_payments
+            WHERE username = ? AND kind = 'cheque' AND status = 'pending'",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Marks a payment settled if it is still pending. Returns `false` if
+    /// it had already been answered.
+    pub fn settle(conn: &Connection, id: i64) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE processor_payments SET status = 'paid' WHERE id = ? AND status = 'pending'",
+            params![id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Marks a payment failed for `reason` if it is still pending. Returns
+    /// `false` if it had already been answered.
+    pub fn fail(conn: &Connection, id: i64, reason: &str) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE processor_payments SET status = 'declined', failure = ?
+            WHERE id = ? AND status = 'pending'",
+            params![reason, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<ProcessorPayment> {
+        let created_at: String = row.get(5)?;
+        let settles_at: String = row.get(6)?;
+        Ok(ProcessorPayment {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            kind: PaymentKind::parse(&row.get::<_, String>(2)?),
+            amount: row.get(3)?,
+            status: RequestStatus::parse(&row.get::<_, String>(4)?),
+            created_at: NaiveDateTime::parse_from_str(&created_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+            settles_at: NaiveDateTime::parse_from_str(&settles_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+            failure: row.get(7)?,
+        })
+    }
+}
@@ -0,0 +1,67 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Directory holding one subdirectory per named profile.
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Name of the profile kept in the working directory itself, where the
+/// wallet lived before profiles existed.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A named wallet with its own database, backups, logs and settings.
+///
+/// Every file the wallet keeps is relative to the working directory, so a
+/// named profile is a directory under `profiles/` the app moves into before
+/// opening anything. The default profile is the working directory itself.
+pub struct Profile;
+
+impl Profile {
+    /// Gets the names of all profiles, the default one first.
+    pub fn list() -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        match fs::read_dir(PROFILES_DIR) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if entry.file_type()?.is_dir() && Self::is_valid_name(&name) {
+                        names.push(name);
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        names.sort();
+        names.insert(0, DEFAULT_PROFILE.to_string());
+        Ok(names)
+    }
+
+    /// Profile names are used as directory names, so they are limited to
+    /// letters, digits, `-` and `_`.
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= 32
+            && name != DEFAULT_PROFILE
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    /// Switches to the profile called `name`, creating it if it does not
+    /// exist yet.
+    pub fn activate(name: &str) -> io::Result<()> {
+        if name == DEFAULT_PROFILE {
+            return Ok(());
+        }
+        if !Self::is_valid_name(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid profile name: {} (use letters, digits, - and _)", name),
+            ));
+        }
+        let dir: PathBuf = [PROFILES_DIR, name].iter().collect();
+        fs::create_dir_all(&dir)?;
+        env::set_current_dir(&dir)
+    }
+}
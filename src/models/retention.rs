@@ -0,0 +1,167 @@
+use chrono::{Datelike, Months, NaiveDateTime};
+use rusqlite::{params, Connection, Result};
+
+use crate::models::transaction::ARCHIVE_TABLE;
+
+/// Transaction types that add to the balance. Every other type takes away.
+const MONEY_IN_TYPES: &str = "'deposit', 'transfer_in', 'token_in', 'vault_out'";
+
+/// Columns of purged rows the rollups are built from.
+const COLUMNS: &str =
+    "rowid AS seq, id, transaction_type, amount, previous_balance, new_balance, timestamp";
+
+/// How long a user's transactions are kept before they are summarized.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Retention {
+    Forever,
+    Months(u32),
+    Years(u32),
+}
+
+impl Retention {
+    /// Parses a stored setting: `forever`, `<n>m` or `<n>y`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value == "forever" {
+            return Some(Retention::Forever);
+        }
+        let (count, unit) = value.split_at(value.len().checked_sub(1)?);
+        let count: u32 = count.parse().ok().filter(|&count| count > 0)?;
+        match unit {
+            "m" => Some(Retention::Months(count)),
+            "y" => Some(Retention::Years(count)),
+            _ => None,
+        }
+    }
+
+    pub fn as_setting(&self) -> String {
+        match self {
+            Retention::Forever => "forever".to_string(),
+            Retention::Months(months) => format!("{}m", months),
+            Retention::Years(years) => format!("{}y", years),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Retention::Forever => "forever".to_string(),
+            Retention::Months(months) => format!("{} month(s)", months),
+            Retention::Years(years) => format!("{} year(s)", years),
+        }
+    }
+
+    /// Gets the start of the oldest month that is kept in full, or `None` if
+    /// everything is kept. Purges only ever remove whole months, so each
+    /// month is summarized in one go.
+    pub fn cutoff(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let months = match self {
+            Retention::Forever => return None,
+            Retention::Months(months) => *months,
+            Retention::Years(years) => years.saturating_mul(12),
+        };
+        now.date()
+            .with_day(1)?
+            .checked_sub_months(Months::new(months))?
+            .and_hms_opt(0, 0, 0)
+    }
+}
+
+/// Summary of one month of a user's purged transactions.
+pub struct Rollup {
+    /// The month, as `YYYY-MM`.
+    pub month: String,
+    pub transactions: i64,
+    pub money_in: f64,
+    pub money_out: f64,
+    pub opening_balance: f64,
+    pub closing_balance: f64,
+}
+
+impl Rollup {
+    /// Summarizes the user's transactions recorded before `cutoff` into
+    /// monthly rollups and deletes them, archived ones included. A month
+    /// that already has a rollup, from transactions imported after it was
+    /// purged, is added to. Returns how many transactions were purged.
+    pub fn purge_before(conn: &Connection, username: &str, cutoff: NaiveDateTime) -> Result<usize> {
+        let cutoff = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+        let purged = format!(
+            "SELECT {0} FROM transactions WHERE username = ?1 AND timestamp < ?2
+            UNION ALL
+            SELECT {0} FROM {1} WHERE username = ?1 AND timestamp < ?2",
+            COLUMNS, ARCHIVE_TABLE
+        );
+        let tx = conn.unchecked_transaction()?;
+        // The opening balance is the balance before the month's first
+        // transaction and the closing balance the one after its last. The
+        // `WHERE true` keeps SQLite from reading ON CONFLICT as a join.
+        tx.execute(
+            &format!(
+                "INSERT INTO transaction_rollups
+                    (username, month, transactions, money_in, money_out,
+                    opening_balance, closing_balance)
+                SELECT ?1, month, COUNT(*),
+                    SUM(CASE WHEN transaction_type IN ({1}) THEN amount ELSE 0 END),
+                    SUM(CASE WHEN transaction_type IN ({1}) THEN 0 ELSE amount END),
+                    MAX(CASE WHEN rank_first = 1 THEN previous_balance END),
+                    MAX(CASE WHEN rank_last = 1 THEN new_balance END)
+                FROM (
+                    SELECT substr(timestamp, 1, 7) AS month, transaction_type, amount,
+                        previous_balance, new_balance,
+                        ROW_NUMBER() OVER (PARTITION BY substr(timestamp, 1, 7)
+                            ORDER BY timestamp, seq) AS rank_first,
+                        ROW_NUMBER() OVER (PARTITION BY substr(timestamp, 1, 7)
+                            ORDER BY timestamp DESC, seq DESC) AS rank_last
+                    FROM ({0})
+                )
+                WHERE true
+                GROUP BY month
+                ON CONFLICT(username, month) DO UPDATE SET
+                    transactions = transactions + excluded.transactions,
+                    money_in = money_in + excluded.money_in,
+                    money_out = money_out + excluded.money_out,
+                    closing_balance = excluded.closing_balance",
+                purged, MONEY_IN_TYPES
+            ),
+            params![username, cutoff],
+        )?;
+        for table in ["transaction_annotations", "round_ups", "sweep_transfers"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE transaction_id IN (SELECT id FROM ({}))",
+                    table, purged
+                ),
+                params![username, cutoff],
+            )?;
+        }
+        let mut count = 0;
+        for table in ["transactions", ARCHIVE_TABLE] {
+            count += tx.execute(
+                &format!("DELETE FROM {} WHERE username = ?1 AND timestamp < ?2", table),
+                params![username, cutoff],
+            )?;
+        }
+        tx.commit()?;
+        Ok(count)
+    }
+
+    /// Gets the user's rollups, newest month first.
+    pub fn get_user_rollups(conn: &Connection, username: &str) -> Result<Vec<Rollup>> {
+        let mut stmt = conn.prepare(
+            "SELECT month, transactions, money_in, money_out, opening_balance, closing_balance
+            FROM transaction_rollups WHERE username = ? ORDER BY month DESC",
+        )?;
+        let rollups = stmt
+            .query_map(params![username], |row| {
+                Ok(Rollup {
+                    month: row.get(0)?,
+                    transactions: row.get(1)?,
+                    money_in: row.get(2)?,
+                    money_out: row.get(3)?,
+                    opening_balance: row.get(4)?,
+                    closing_balance: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<Rollup>>>()?;
+        Ok(rollups)
+    }
+}
@@ -0,0 +1,59 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// Where a user's round-ups go.
+#[derive(PartialEq, Clone, Debug)]
+pub enum RoundUpTarget {
+    Vault,
+    Account(String),
+}
+
+impl RoundUpTarget {
+    /// Parses the stored setting. An empty value means round-ups are off.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "" => None,
+            "vault" => Some(RoundUpTarget::Vault),
+            _ => value
+                .strip_prefix("account:")
+                .map(|username| RoundUpTarget::Account(username.to_string())),
+        }
+    }
+
+    pub fn as_setting(&self) -> String {
+        match self {
+            RoundUpTarget::Vault => "vault".to_string(),
+            RoundUpTarget::Account(username) => format!("account:{}", username),
+        }
+    }
+}
+
+/// Spare change from outgoing payments. Each payment is rounded up to the
+/// next whole unit and the difference recorded as its own transaction,
+/// linked back to the payment it came from.
+pub struct RoundUp;
+
+impl RoundUp {
+    /// Gets what it takes to round `amount` up to the next whole unit, to the cent.
+    pub fn spare_change(amount: f64) -> f64 {
+        let cents = (amount * 100.0).round() as i64;
+        ((100 - cents % 100) % 100) as f64 / 100.0
+    }
+
+    pub fn link(conn: &Connection, transaction_id: &str, payment_id: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO round_ups (transaction_id, payment_id) VALUES (?, ?)",
+            params![transaction_id, payment_id],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the payment a round-up transaction came from, if it is one.
+    pub fn get_payment(conn: &Connection, transaction_id: &str) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT payment_id FROM round_ups WHERE transaction_id = ?",
+            params![transaction_id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+}
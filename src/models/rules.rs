@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use ed25519_dalek::VerifyingKey;
+
+use crate::models::amount::DEFAULT_MAX_AMOUNT;
+use crate::models::money::{Money, RoundingPolicy};
+use crate::models::offline_token::OfflineToken;
+use crate::models::processor::{PaymentKind, ProcessorConfig};
+use crate::models::transaction::BASE_CURRENCY;
+
+/// File the business rules are read from, next to the database.
+pub const RULES_PATH: &str = "rules.toml";
+
+/// Largest transfer sent without a cooling period, for users who have not
+/// picked their own.
+const DEFAULT_LARGE_TRANSFER_CAP: f64 = 10_000.0;
+
+/// Seconds a large transfer is held before it can be confirmed.
+const DEFAULT_COOLING_SECONDS: i64 = 60;
+
+/// Transfers from a joint account above this need a co-owner's approval.
+const DEFAULT_JOINT_APPROVAL_OVER: f64 = 1_000.0;
+
+/// Hours a joint account transfer waits for approval before it expires.
+const DEFAULT_JOINT_APPROVAL_HOURS: i64 = 24;
+
+/// Days an overdrawn user may keep paying before payments are blocked.
+const DEFAULT_OVERDRAFT_GRACE_DAYS: i64 = 30;
+
+/// Days an overdue password keeps working before it must be changed at login.
+const DEFAULT_PASSWORD_GRACE_DAYS: i64 = 7;
+
+/// Fee charged when a deposited cheque bounces.
+const DEFAULT_BOUNCED_CHEQUE_FEE: f64 = 10.0;
+
+/// Notes a cash withdrawal is paid out in, largest first.
+const DEFAULT_CASH_DENOMINATIONS: [f64; 6] = [100.0, 50.0, 20.0, 10.0, 5.0, 1.0];
+
+/// Currencies amounts can be shown in, with the base currency units one
+/// unit of each is worth.
+const DEFAULT_DISPLAY_RATES: [(&str, f64); 3] = [("EUR", 1.08), ("GBP", 1.27), ("JPY", 0.0067)];
+
+/// Limits, fees, interest, confirmation thresholds, the password policy,
+/// the notes cash is paid out in, the rates amounts can be shown at and how
+/// amounts are rounded, which demos may want to change without rebuilding.
+/// Every rule has a default, so `rules.toml` only needs the ones being
+/// changed.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Rules {
+    /// Largest single amount, for users who have not picked their own.
+    pub max_amount: f64,
+    pub overdraft_grace_days: i64,
+    /// Transfers above this are held, for users who have not picked their own.
+    pub large_transfer_cap: f64,
+    pub cooling_seconds: i64,
+    /// Transfers from a joint account above this wait for a co-owner's approval.
+    pub joint_approval_over: f64,
+    pub joint_approval_hours: i64,
+    /// Percentage of a card top-up kept as a fee when it settles.
+    pub card_top_up_fee_percent: f64,
+    /// Flat fee charged when a bank withdrawal settles.
+    pub bank_withdrawal_fee: f64,
+    /// Flat fee charged when a deposited cheque bounces.
+    pub bounced_cheque_fee: f64,
+    /// Yearly interest paid on vault balances, in percent.
+    pub vault_interest_percent: f64,
+    /// Days a password may be used before it is due to be changed; 0 never.
+    pub password_max_age_days: i64,
+    pub password_grace_days: i64,
+    pub processor: ProcessorConfig,
+    /// Notes a cash withdrawal is broken down into, largest first.
+    pub cash_denominations: Vec<f64>,
+    /// Currencies users may see amounts in, by code, with the base currency
+    /// units one unit of each is worth. Listing any replaces the defaults.
+    pub display_rates: BTreeMap<String, f64>,
+    /// How fees and interest are rounded to the cent.
+    pub rounding: RoundingPolicy,
+    /// Keys of other wallets whose offline tokens are accepted, besides
+    /// this wallet's own.
+    pub trusted_wallet_keys: Vec<VerifyingKey>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            max_amount: DEFAULT_MAX_AMOUNT,
+            overdraft_grace_days: DEFAULT_OVERDRAFT_GRACE_DAYS,
+            large_transfer_cap: DEFAULT_LARGE_TRANSFER_CAP,
+            cooling_seconds: DEFAULT_COOLING_SECONDS,
+            joint_approval_over: DEFAULT_JOINT_APPROVAL_OVER,
+            joint_approval_hours: DEFAULT_JOINT_APPROVAL_HOURS,
+            card_top_up_fee_percent: 0.0,
+            bank_withdrawal_fee: 0.0,
+            bounced_cheque_fee: DEFAULT_BOUNCED_CHEQUE_FEE,
+            vault_interest_percent: 0.0,
+            password_max_age_days: 0,
+            password_grace_days: DEFAULT_PASSWORD_GRACE_DAYS,
+            processor: ProcessorConfig::default(),
+            cash_denominations: DEFAULT_CASH_DENOMINATIONS.to_vec(),
+            display_rates: DEFAULT_DISPLAY_RATES
+                .iter()
+                .map(|&(code, rate)| (code.to_string(), rate))
+                .collect(),
+            rounding: RoundingPolicy::default(),
+            trusted_wallet_keys: Vec::new(),
+        }
+    }
+}
+
+impl Rules {
+    /// Parses the rules from TOML. Rules that are not given keep their
+    /// defaults; unknown rules and negative values are errors, so a typo
+    /// does not go unnoticed.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let table: toml::Table = text.parse().map_err(|err: toml::de::Error| {
+            match err.span() {
+                Some(span) => {
+                    let line = text[..span.start].matches('\n').count() + 1;
+                    format!("line {}: {}", line, err.message().replace('\n', ", "))
+                }
+                None => err.message().replace('\n', ", "),
+            }
+        })?;
+        let mut rules = Rules::default();
+        for (section, values) in &table {
+            let values = values
+                .as_table()
+                .ok_or_else(|| format!("'{}' is not a section", section))?;
+            if section == "display_rates" {
+                rules.display_rates.clear();
+            }
+            for (key, value) in values {
+                let name = format!("{}.{}", section, key);
+                if section == "display_rates" {
+                    let rate = Self::parse_display_rate(&name, key, value)?;
+                    rules.display_rates.insert(key.clone(), rate);
+                    continue;
+                }
+                if (section.as_str(), key.as_str()) == ("rounding", "policy") {
+                    rules.rounding = value
+                        .as_str()
+                        .and_then(RoundingPolicy::parse)
+                        .ok_or_else(|| format!("{} must be half_up or half_even", name))?;
+                    continue;
+                }
+                if (section.as_str(), key.as_str()) == ("offline_tokens", "trusted_keys") {
+                    rules.trusted_wallet_keys = Self::parse_wallet_keys(&name, value)?;
+                    continue;
+                }
+                if (section.as_str(), key.as_str()) == ("cash", "denominations") {
+                    rules.cash_denominations = Self::parse_denominations(&name, value)?;
+                    continue;
+                }
+                let number = match value {
+                    toml::Value::Integer(number) => *number as f64,
+                    toml::Value::Float(number) => *number,
+                    _ => return Err(format!("{} is not a number", name)),
+                };
+                if !number.is_finite() || number < 0.0 {
+                    return Err(format!("{} must not be negative", name));
+                }
+                match (section.as_str(), key.as_str()) {
+                    ("limits", "max_amount") if number > 0.0 => rules.max_amount = number,
+                    ("limits", "max_amount") => {
+                        return Err(format!("{} must be above zero", name))
+                    }
+                    ("limits", "overdraft_grace_days") => {
+                        rules.overdraft_grace_days = number as i64
+                    }
+                    ("confirmation", "large_transfer_cap") => rules.large_transfer_cap = number,
+                    ("confirmation", "cooling_seconds") => rules.cooling_seconds = number as i64,
+                    ("confirmation", "joint_approval_over") => rules.joint_approval_over = number,
+                    ("confirmation", "joint_approval_hours") => {
+                        rules.joint_approval_hours = number as i64
+                    }
+                    ("fees", "card_top_up_percent") => rules.card_top_up_fee_percent = number,
+                    ("fees", "bank_withdrawal") => rules.bank_withdrawal_fee = number,
+                    ("fees", "bounced_cheque") => rules.bounced_cheque_fee = number,
+                    ("interest", "vault_percent") => rules.vault_interest_percent = number,
+                    ("passwords", "max_age_days") => rules.password_max_age_days = number as i64,
+                    ("passwords", "grace_days") => rules.password_grace_days = number as i64,
+                    ("processor", "latency_seconds") => {
+                        rules.processor.latency_seconds = number as i64
+                    }
+                    ("processor", "bank_delay_seconds") => {
+                        rules.processor.bank_delay_seconds = number as i64
+                    }
+                    ("processor", "cheque_clearing_seconds") => {
+                        rules.processor.cheque_clearing_seconds = number as i64
+                    }
+                    ("processor", "failure_rate") if number <= 1.0 => {
+                        rules.processor.failure_rate = number
+                    }
+                    ("processor", "failure_rate") => {
+                        return Err(format!("{} must be between 0 and 1", name))
+                    }
+                    _ => return Err(format!("unknown rule {}", name)),
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Reads a list of positive notes, such as `[50, 20, 10]`, and sorts
+    /// it largest first.
+    fn parse_denominations(name: &str, value: &toml::Value) -> Result<Vec<f64>, String> {
+        let values = value
+            .as_array()
+            .filter(|values| !values.is_empty())
+            .ok_or_else(|| format!("{} is not a list of notes", name))?;
+        let mut notes = Vec::new();
+        for value in values {
+            let note = match value {
+                toml::Value::Integer(note) => *note as f64,
+                toml::Value::Float(note) => *note,
+                _ => return Err(format!("{} is not a list of notes", name)),
+            };
+            if !note.is_finite() || note < 0.01 {
+                return Err(format!("{} must all be at least 0.01", name));
+            }
+            notes.push(note);
+        }
+        notes.sort_by(|a, b| b.total_cmp(a));
+        notes.dedup();
+        Ok(notes)
+    }
+
+    /// Reads a list of wallet public keys, as shown on the offline tokens
+    /// screen of the wallet they belong to.
+    fn parse_wallet_keys(name: &str, value: &toml::Value) -> Result<Vec<VerifyingKey>, String> {
+        let values = value
+            .as_array()
+            .ok_or_else(|| format!("{} is not a list of keys", name))?;
+        values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .and_then(OfflineToken::parse_key)
+                    .ok_or_else(|| format!("{} has a key that is not a wallet key", name))
+            })
+            .collect()
+    }
+
+    /// Reads the rate of a display currency, which must be a three-letter
+    /// code other than the base currency worth more than nothing.
+    fn parse_display_rate(name: &str, code: &str, value: &toml::Value) -> Result<f64, String> {
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(format!("{} is not a three-letter currency code", name));
+        }
+        if code == BASE_CURRENCY {
+            return Err(format!("{} is the base currency", name));
+        }
+        let rate = match value {
+            toml::Value::Integer(rate) => *rate as f64,
+            toml::Value::Float(rate) => *rate,
+            _ => return Err(format!("{} is not a number", name)),
+        };
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(format!("{} must be above zero", name));
+        }
+        Ok(rate)
+    }
+
+    /// Gets the fee charged when a processor payment of this kind settles,
+    /// rounded to the cent by the rounding policy. Cheques are free to
+    /// deposit and only charged if they bounce.
+    pub fn fee(&self, kind: PaymentKind, amount: f64) -> f64 {
+        let fee = match kind {
+            PaymentKind::CardTopUp => amount * self.card_top_up_fee_percent / 100.0,
+            PaymentKind::BankWithdrawal => self.bank_withdrawal_fee,
+            PaymentKind::Cheque => 0.0,
+        };
+        Money::round(fee, self.rounding)
+    }
+}
+
+/// The rules file, reread whenever it changes on disk.
+pub struct RulesFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    pub rules: Rules,
+}
+
+impl RulesFile {
+    /// Reads the rules at `path`. A missing file means the defaults; a file
+    /// that cannot be used also leaves the defaults in force, and the reason
+    /// is returned alongside.
+    pub fn open(path: impl Into<PathBuf>) -> (Self, Option<String>) {
+        let mut file = RulesFile {
+            path: path.into(),
+            modified: None,
+            rules: Rules::default(),
+        };
+        let error = file.reload().err();
+        (file, error)
+    }
+
+    /// Rereads the file if its modification time changed since it was last
+    /// read. Returns `None` if it did not change. When the new contents
+    /// cannot be used, the rules read before stay in force.
+    pub fn reload_if_changed(&mut self) -> Option<Result<(), String>> {
+        if self.modified_at() == self.modified {
+            return None;
+        }
+        Some(self.reload())
+    }
+
+    fn reload(&mut self) -> Result<(), String> {
+        self.modified = self.modified_at();
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.to_string()),
+        };
+        self.rules = Rules::parse(&text)?;
+        Ok(())
+    }
+
+    fn modified_at(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
@@ -0,0 +1,197 @@
+use argon2::Argon2;
+use chrono::NaiveDateTime;
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+
+use crate::models::key_provider::KEYCHAIN_SERVICE;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// File listing the accounts remembered on this machine and their session
+/// tokens. It lives next to the database, so each profile has its own.
+pub const REMEMBERED_ACCOUNTS_PATH: &str = "remembered_accounts.json";
+
+/// Shortest and longest PIN accepted, in digits.
+pub const PIN_LENGTH: std::ops::RangeInclusive<usize> = 4..=8;
+
+/// An account remembered on this machine for the account switcher.
+#[derive(Clone)]
+pub struct RememberedAccount {
+    pub username: String,
+    pub token: String,
+    /// Whether the token is kept in the OS keychain rather than in
+    /// `REMEMBERED_ACCOUNTS_PATH`.
+    pub in_keychain: bool,
+}
+
+/// Long-lived sessions that let a remembered account be switched to with
+/// its PIN. Only a SHA-256 hash of each token is stored in the database.
+pub struct Session;
+
+impl Session {
+    /// Starts a session for `username` and returns its token.
+    pub fn issue(conn: &Connection, username: &str, now: NaiveDateTime) -> Result<String> {
+        let token: String = (0..32)
+            .map(|_| format!("{:02x}", rand::thread_rng().gen::<u8>()))
+            .collect();
+        let now = now.format(TIMESTAMP_FORMAT).to_string();
+        conn.execute(
+            "INSERT INTO sessions (token_hash, username, created_at, last_used_at)
+            VALUES (?1, ?2, ?3, ?3)",
+            params![Self::hash(&token), username, now],
+        )?;
+        Ok(token)
+    }
+
+    /// Gets the user a session token belongs to and marks the session as
+    /// used. Returns `None` for tokens that were revoked or never issued.
+    pub fn resume(conn: &Connection, token: &str, now: NaiveDateTime) -> Result<Option<String>> {
+        let now = now.format(TIMESTAMP_FORMAT).to_string();
+        conn.query_row(
+            "UPDATE sessions SET last_used_at = ?1 WHERE token_hash = ?2 RETURNING username",
+            params![now, Self::hash(token)],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    pub fn revoke(conn: &Connection, token: &str) -> Result<()> {
+        conn.execute("DELETE FROM sessions WHERE token_hash = ?", params![Self::hash(token)])?;
+        Ok(())
+    }
+
+    /// Ends every session of `username`, e.g. after their credentials change,
+    /// so each terminal that remembered them must log in again.
+    pub fn revoke_all(conn: &Connection, username: &str) -> Result<usize> {
+        conn.execute("DELETE FROM sessions WHERE username = ?", params![username])
+    }
+
+    fn hash(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+}
+
+/// PINs guarding remembered accounts, stored as salted Argon2 hashes.
+pub struct AccountPin;
+
+impl AccountPin {
+    pub fn is_valid(pin: &str) -> bool {
+        PIN_LENGTH.contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+    }
+
+    pub fn exists(conn: &Connection, username: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM account_pins WHERE username = ?)",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Sets or replaces the user's PIN.
+    pub fn set(conn: &Connection, username: &str, pin: &str) -> Result<()> {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        conn.execute(
+            "INSERT INTO account_pins (username, salt, pin_hash) VALUES (?1, ?2, ?3)
+            ON CONFLICT(username) DO UPDATE SET salt = ?2, pin_hash = ?3",
+            params![username, salt.to_vec(), Self::derive(pin, &salt)],
+        )?;
+        Ok(())
+    }
+
+    /// Checks a PIN. Users without a PIN never match.
+    pub fn verify(conn: &Connection, username: &str, pin: &str) -> Result<bool> {
+        let stored: Option<(Vec<u8>, Vec<u8>)> = conn
+            .query_row(
+                "SELECT salt, pin_hash FROM account_pins WHERE username = ?",
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(stored.is_some_and(|(salt, hash)| Self::derive(pin, &salt) == hash))
+    }
+
+    fn derive(pin: &str, salt: &[u8]) -> Vec<u8> {
+        let mut derived = [0u8; 32];
+        // Only fails for salts or outputs of invalid length, which these are not.
+        let _ = Argon2::default().hash_password_into(pin.as_bytes(), salt, &mut derived);
+        derived.to_vec()
+    }
+}
+
+impl RememberedAccount {
+    /// Reads the accounts remembered on this machine. A missing or unreadable
+    /// file means none are. Tokens kept in the OS keychain are read from it;
+    /// one that cannot be read is left empty, so switching to that account
+    /// finds its session ended.
+    pub fn load() -> Vec<RememberedAccount> {
+        let text = fs::read_to_string(REMEMBERED_ACCOUNTS_PATH).unwrap_or_default();
+        let accounts: Map<String, Value> = serde_json::from_str(&text).unwrap_or_default();
+        accounts
+            .into_iter()
+            .filter_map(|(username, token)| match token {
+                Value::String(token) => Some(RememberedAccount {
+                    username,
+                    token,
+                    in_keychain: false,
+                }),
+                Value::Object(stored) if stored.get("keychain") == Some(&Value::Bool(true)) => {
+                    Some(RememberedAccount {
+                        token: Keychain::load(&username).unwrap_or_default(),
+                        username,
+                        in_keychain: true,
+                    })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Writes the remembered accounts. Tokens kept in the OS keychain are
+    /// listed as `{"keychain": true}` instead of being written out.
+    pub fn save(accounts: &[RememberedAccount]) -> std::io::Result<()> {
+        let map: Map<String, Value> = accounts
+            .iter()
+            .map(|account| {
+                let token = if account.in_keychain {
+                    serde_json::json!({ "keychain": true })
+                } else {
+                    Value::String(account.token.clone())
+                };
+                (account.username.clone(), token)
+            })
+            .collect();
+        fs::write(REMEMBERED_ACCOUNTS_PATH, Value::Object(map).to_string())
+    }
+}
+
+/// Session tokens kept in the OS keychain (Keychain on macOS, Credential
+/// Manager on Windows, the kernel keyring on Linux). Entries are named after
+/// the working directory as well as the user, so each profile has its own.
+pub struct Keychain;
+
+impl Keychain {
+    pub fn store(username: &str, token: &str) -> keyring::Result<()> {
+        Self::entry(username)?.set_password(token)
+    }
+
+    pub fn load(username: &str) -> Option<String> {
+        Self::entry(username).and_then(|entry| entry.get_password()).ok()
+    }
+
+    /// Removes the user's token. A token that is already gone is not an error.
+    pub fn delete(username: &str) -> keyring::Result<()> {
+        match Self::entry(username)?.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            result => result,
+        }
+    }
+
+    fn entry(username: &str) -> keyring::Result<keyring::Entry> {
+        let dir = env::current_dir().unwrap_or_default();
+        keyring::Entry::new(KEYCHAIN_SERVICE, &format!("{}:{}", dir.display(), username))
+    }
+}
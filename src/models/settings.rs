@@ -0,0 +1,48 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+/// Per-user preferences stored as key/value pairs.
+pub struct UserSettings;
+
+impl UserSettings {
+    pub fn get(conn: &Connection, username: &str, key: &str) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT value FROM user_settings WHERE username = ? AND key = ?",
+            params![username, key],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    pub fn set(conn: &Connection, username: &str, key: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO user_settings (username, key, value) VALUES (?1, ?2, ?3)
+            ON CONFLICT(username, key) DO UPDATE SET value = ?3",
+            params![username, key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove(conn: &Connection, username: &str, key: &str) -> Result<()> {
+        conn.execute(
+            "DELETE FROM user_settings WHERE username = ? AND key = ?",
+            params![username, key],
+        )?;
+        Ok(())
+    }
+
+    /// Gets every user that has `key` set, with their value.
+    pub fn get_all(conn: &Connection, key: &str) -> Result<Vec<(String, String)>> {
+        let mut stmt = conn.prepare(
+            "SELECT username, value FROM user_settings WHERE key = ? ORDER BY username",
+        )?;
+        let values = stmt
+            .query_map(params![key], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(String, String)>>>()?;
+        Ok(values)
+    }
+
+    /// Reads a boolean setting, falling back to `default` when it is not set.
+    pub fn get_bool(conn: &Connection, username: &str, key: &str, default: bool) -> Result<bool> {
+        Ok(Self::get(conn, username, key)?.map_or(default, |value| value == "true"))
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{Datelike, Months, NaiveDateTime};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A user's standing rule to move everything above `keep` from their
+/// balance into their vault at the start of each month. The daemon runs
+/// due rules and links each move it makes back to the rule.
+pub struct SweepRule {
+    pub id: i64,
+    pub username: String,
+    pub keep: f64,
+    pub next_sweep_at: NaiveDateTime,
+}
+
+pub struct SweepRules;
+
+impl SweepRules {
+    /// Gets the start of the month after the one `now` is in, when a rule
+    /// made or run at `now` next sweeps.
+    pub fn next_sweep_after(now: NaiveDateTime) -> NaiveDateTime {
+        let first = now.date().with_day(1).unwrap_or(now.date());
+        let next = first.checked_add_months(Months::new(1)).unwrap_or(first);
+        next.and_hms_opt(0, 0, 0).unwrap_or(now)
+    }
+
+    pub fn create(conn: &Connection, username: &str, keep: f64, now: NaiveDateTime) -> Result<()> {
+        conn.execute(
+            "INSERT INTO sweep_rules (username, keep, created_at, next_sweep_at)
+            VALUES (?, ?, ?, ?)",
+            params![
+                username,
+                keep,
+                now.format(TIMESTAMP_FORMAT).to_string(),
+                Self::next_sweep_after(now).format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(conn: &Connection, username: &str, id: i64) -> Result<bool> {
+        let deleted = conn.execute(
+            "DELETE FROM sweep_rules WHERE id = ? AND username = ?",
+            params![id, username],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Gets the user's rules, oldest first.
+    pub fn get_for_user(conn: &Connection, username: &str) -> Result<Vec<SweepRule>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, keep, next_sweep_at FROM sweep_rules
+            WHERE username = ? ORDER BY id",
+        )?;
+        let rules = stmt.query_map(params![username], Self::from_row)?;
+        rules.collect()
+    }
+
+    /// Gets every rule that is due to run.
+    pub fn get_due(conn: &Connection, now: NaiveDateTime) -> Result<Vec<SweepRule>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, keep, next_sweep_at FROM sweep_rules
+            WHERE next_sweep_at <= ? ORDER BY next_sweep_at, id",
+        )?;
+        let rules =
+            stmt.query_map(params![now.format(TIMESTAMP_FORMAT).to_string()], Self::from_row)?;
+        rules.collect()
+    }
+
+    /// Schedules the rule's next sweep, if it is still due at `due_at`.
+    /// Returns `false` if another instance already ran it.
+    pub fn advance(
+        conn: &Connection,
+        id: i64,
+        due_at: NaiveDateTime,
+        next_at: NaiveDateTime,
+    ) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE sweep_rules SET next_sweep_at = ? WHERE id = ? AND next_sweep_at = ?",
+            params![
+                next_at.format(TIMESTAMP_FORMAT).to_string(),
+                id,
+                due_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Records that a transaction was made by a rule.
+    pub fn link(conn: &Connection, transaction_id: &str, rule_id: i64) -> Result<()> {
+        conn.execute(
+            "INSERT INTO sweep_transfers (transaction_id, rule_id) VALUES (?, ?)",
+            params![transaction_id, rule_id],
+        )?;
+        Ok(())
+    }
+
+    /// Gets the rule a transaction was made by, if it is a sweep.
+    pub fn get_rule(conn: &Connection, transaction_id: &str) -> Result<Option<i64>> {
+        conn.query_row(
+            "SELECT rule_id FROM sweep_transfers WHERE transaction_id = ?",
+            params![transaction_id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<SweepRule> {
+        let next_sweep_at: String = row.get(3)?;
+        Ok(SweepRule {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            keep: row.get(2)?,
+            next_sweep_at: NaiveDateTime::parse_from_str(&next_sweep_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+        })
+    }
+}
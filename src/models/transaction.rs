@@ -1,7 +1,100 @@
-use chrono::NaiveDateTime;
-use rusqlite::{params, Connection, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{params, Connection, Result, Row, ToSql};
 use std::collections::HashMap;
 
+use crate::models::category_rule::CategoryRules;
+use crate::models::history_filter::HistoryFilter;
+
+/// Currency balances are kept in. Amounts in other currencies are converted
+/// into it when the transaction is recorded.
+pub const BASE_CURRENCY: &str = "USD";
+
+/// Table old transactions are moved to by `Archive`. Same columns as `transactions`.
+pub const ARCHIVE_TABLE: &str = "transactions_archive";
+
+/// Shown in place of a transfer counterparty that has no account.
+pub const UNKNOWN_USER: &str = "unknown user";
+
+/// Columns shared by `transactions` and `ARCHIVE_TABLE`.
+const COLUMNS: &str = "id, username, transaction_type, amount, recipient, sender, \
+    previous_balance, new_balance, timestamp, currency, fx_rate, transfer_id, merchant";
+
+/// The merchant of an imported payment, the other side of a transfer, or NULL
+/// for anything else.
+const COUNTERPARTY: &str = "COALESCE(t.merchant, CASE t.transaction_type \
+    WHEN 'transfer_out' THEN t.recipient WHEN 'transfer_in' THEN t.sender END)";
+
+/// Order a transaction history is listed in.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum HistorySort {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+    LargestFirst,
+    SmallestFirst,
+    CounterpartyAscending,
+    CounterpartyDescending,
+}
+
+impl HistorySort {
+    /// The sort after this one, wrapping around to `NewestFirst`.
+    pub fn next(self) -> Self {
+        match self {
+            HistorySort::NewestFirst => HistorySort::OldestFirst,
+            HistorySort::OldestFirst => HistorySort::LargestFirst,
+            HistorySort::LargestFirst => HistorySort::SmallestFirst,
+            HistorySort::SmallestFirst => HistorySort::CounterpartyAscending,
+            HistorySort::CounterpartyAscending => HistorySort::CounterpartyDescending,
+            HistorySort::CounterpartyDescending => HistorySort::NewestFirst,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            HistorySort::NewestFirst => "newest first",
+            HistorySort::OldestFirst => "oldest first",
+            HistorySort::LargestFirst => "largest first",
+            HistorySort::SmallestFirst => "smallest first",
+            HistorySort::CounterpartyAscending => "counterparty A-Z",
+            HistorySort::CounterpartyDescending => "counterparty Z-A",
+        }
+    }
+
+    /// The ORDER BY clause for a page query. Archived rows are all older
+    /// than live ones, and ties keep the order they were recorded in.
+    fn order_by(self) -> String {
+        let newest = "t.archived, t.timestamp DESC, t.seq DESC";
+        match self {
+            HistorySort::NewestFirst => newest.to_string(),
+            HistorySort::OldestFirst => "t.archived DESC, t.timestamp, t.seq".to_string(),
+            HistorySort::LargestFirst => format!("t.amount DESC, {}", newest),
+            HistorySort::SmallestFirst => format!("t.amount, {}", newest),
+            // Rows without a counterparty go last either way
+            HistorySort::CounterpartyAscending => format!(
+                "{0} IS NULL, {0} COLLATE NOCASE, {1}",
+                COUNTERPARTY, newest
+            ),
+            HistorySort::CounterpartyDescending => format!(
+                "{0} IS NULL, {0} COLLATE NOCASE DESC, {1}",
+                COUNTERPARTY, newest
+            ),
+        }
+    }
+}
+
+/// Which rows one history query returns, in what order, and how problems
+/// are reported.
+struct PageQuery<'a> {
+    username: &'a str,
+    starred_only: bool,
+    integrity: bool,
+    sort: HistorySort,
+    /// Only the transactions on this day, if set.
+    day: Option<NaiveDate>,
+    filter: &'a HistoryFilter,
+}
+
+#[derive(Clone)]
 pub struct Transaction {
     pub id: String,
     pub username: String,
@@ -12,10 +105,22 @@ pub struct Transaction {
     pub previous_balance: f64,
     pub new_balance: f64,
     pub timestamp: NaiveDateTime,
+    /// Currency the transaction was made in. `amount` and the balances are
+    /// always in `BASE_CURRENCY`; the original amount is `amount / fx_rate`.
+    pub currency: String,
+    /// Base currency units per unit of `currency` at the time of the transaction.
+    pub fx_rate: f64,
+    /// Shared by both legs of a transfer. Its note, if the sender wrote one,
+    /// is stored once in `transfer_notes`.
+    pub transfer_id: Option<String>,
+    /// Canonical name of the merchant of an imported transaction, e.g.
+    /// `AMAZON` for a recipient printed as `AMZN*1A2B3C`.
+    pub merchant: Option<String>,
 }
 
 impl Transaction {
     #[allow(dead_code)] // This suppresses the unused function warning
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
         username: String,
@@ -36,13 +141,17 @@ impl Transaction {
             previous_balance,
             new_balance,
             timestamp: chrono::Local::now().naive_local(),
+            currency: BASE_CURRENCY.to_string(),
+            fx_rate: 1.0,
+            transfer_id: None,
+            merchant: None,
         }
     }
 
     pub fn create(conn: &Connection, transaction: &Transaction) -> Result<()> {
         conn.execute(
-            "INSERT INTO transactions (id, username, transaction_type, amount, recipient, sender, previous_balance, new_balance, timestamp) 
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO transactions (id, username, transaction_type, amount, recipient, sender, previous_balance, new_balance, timestamp, currency, fx_rate, transfer_id, merchant) 
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             params![
                 transaction.id,
                 transaction.username,
@@ -53,47 +162,517 @@ impl Transaction {
                 transaction.previous_balance,
                 transaction.new_balance,
                 transaction.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                transaction.currency,
+                transaction.fx_rate,
+                transaction.transfer_id,
+                transaction.merchant,
             ],
         )?;
+        CategoryRules::apply(conn, &transaction.username, Some(&transaction.id))?;
         Ok(())
     }
 
+    /// Stores the sender's note for a transfer. Both legs show it.
+    pub fn set_transfer_note(conn: &Connection, transfer_id: &str, note: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO transfer_notes (transfer_id, note) VALUES (?, ?)",
+            params![transfer_id, note],
+        )?;
+        Ok(())
+    }
+
+    /// Gets both legs of a transfer, live or archived, the sender's first.
+    /// Legs removed by retention or never recorded are simply missing.
+    pub fn get_transfer_legs(conn: &Connection, transfer_id: &str) -> Result<Vec<Transaction>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {0} FROM transactions WHERE transfer_id = ?1
+            UNION ALL
+            SELECT {0} FROM {1} WHERE transfer_id = ?1
+            ORDER BY transaction_type DESC",
+            COLUMNS, ARCHIVE_TABLE
+        ))?;
+        let legs = stmt.query_map(params![transfer_id], |row| {
+            let timestamp: String = row.get(8)?;
+            Ok(Transaction {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                transaction_type: row.get(2)?,
+                amount: row.get(3)?,
+                recipient: row.get(4)?,
+                sender: row.get(5)?,
+                previous_balance: row.get(6)?,
+                new_balance: row.get(7)?,
+                timestamp: NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%d %H:%M:%S")
+                    .unwrap_or_default(),
+                currency: row.get(9)?,
+                fx_rate: row.get(10)?,
+                transfer_id: row.get(11)?,
+                merchant: row.get(12)?,
+            })
+        })?;
+
+        legs.collect()
+    }
+
     pub fn get_user_transactions(
         conn: &Connection,
         username: &str,
+        starred_only: bool,
+        integrity: bool,
     ) -> Result<Vec<HashMap<String, String>>> {
+        Self::get_user_transactions_page(
+            conn,
+            username,
+            starred_only,
+            integrity,
+            HistorySort::NewestFirst,
+            &HistoryFilter::default(),
+            0,
+            None,
+        )
+    }
+
+    /// Gets one page of a user's transactions, live and archived, that match
+    /// `filter`, in `sort` order. `limit: None` returns everything from
+    /// `offset` on.
+    ///
+    /// Rows that cannot be read are normally left out. With `integrity` set
+    /// they are returned as `unreadable` entries instead, transfers whose
+    /// counterparty is missing show `UNKNOWN_USER` in its place, and both
+    /// carry an `integrity_issue` key describing the problem.
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_user_transactions_page(
+        conn: &Connection,
+        username: &str,
+        starred_only: bool,
+        integrity: bool,
+        sort: HistorySort,
+        filter: &HistoryFilter,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        let query = PageQuery {
+            username,
+            starred_only,
+            integrity,
+            sort,
+            day: None,
+            filter,
+        };
+        Self::get_page(conn, &query, offset, limit)
+    }
+
+    /// Gets the user's transactions on one day, live or archived, oldest first.
+    pub fn get_user_transactions_on(
+        conn: &Connection,
+        username: &str,
+        day: NaiveDate,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        let query = PageQuery {
+            username,
+            starred_only: false,
+            integrity: false,
+            sort: HistorySort::OldestFirst,
+            day: Some(day),
+            filter: &HistoryFilter::default(),
+        };
+        Self::get_page(conn, &query, 0, None)
+    }
+
+    fn get_page(
+        conn: &Connection,
+        query: &PageQuery,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        // Both tables are read as one so the sort applies across the archive.
+        // Transfers are listed through the user's own rows. A 'transfer_in'
+        // whose sender is the user is the other side of a transfer to
+        // themselves, so it is left out.
+        let (filter, filter_values) = query.filter.to_sql(7);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT t.id, t.transaction_type, t.amount, t.recipient, t.sender, t.previous_balance, t.new_balance, t.timestamp,
+                a.category, a.memo, a.note, COALESCE(a.starred, 0), t.currency, t.fx_rate,
+                ?5 AND t.transaction_type IN ('transfer_in', 'transfer_out')
+                    AND NOT EXISTS(SELECT 1 FROM users WHERE username = t.sender)
+                    AND NOT EXISTS(SELECT 1 FROM closed_accounts WHERE username = t.sender),
+                ?5 AND t.transaction_type IN ('transfer_in', 'transfer_out')
+                    AND NOT EXISTS(SELECT 1 FROM users WHERE username = t.recipient)
+                    AND NOT EXISTS(SELECT 1 FROM closed_accounts WHERE username = t.recipient),
+                t.transfer_id, n.note
+            FROM (
+                SELECT {0}, rowid AS seq, 0 AS archived FROM transactions WHERE username = ?1
+                UNION ALL
+                SELECT {0}, rowid AS seq, 1 AS archived FROM {1} WHERE username = ?1
+            ) t
+            LEFT JOIN transaction_annotations a ON a.transaction_id = t.id
+            LEFT JOIN transfer_notes n ON n.transfer_id = t.transfer_id
+            WHERE (?2 = 0 OR a.starred = 1)
+                AND (?6 IS NULL OR substr(t.timestamp, 1, 10) = ?6)
+                AND NOT (t.transaction_type = 'transfer_in' AND t.sender IS ?1){3}
+            ORDER BY {2}
+            LIMIT ?3 OFFSET ?4",
+            COLUMNS,
+            ARCHIVE_TABLE,
+            query.sort.order_by(),
+            filter
+        ))?;
+
+        let limit = limit.map_or(-1, |limit| limit as i64);
+        let offset = offset as i64;
+        let day = query.day.map(|day| day.format("%Y-%m-%d").to_string());
+        let mut params =
+            params![query.username, query.starred_only, limit, offset, query.integrity, day]
+                .to_vec();
+        params.extend(filter_values.iter().map(|value| value as &dyn ToSql));
+        let transactions = stmt.query_map(params.as_slice(), |row| match Self::read_row(row) {
+            Ok(transaction) => Ok(transaction),
+            Err(err) if query.integrity => Ok(Self::unreadable_row(row, &err)),
+            Err(err) => Err(err),
+        })?;
+
+        if query.integrity {
+            transactions.collect()
+        } else {
+            Ok(transactions.filter_map(Result::ok).collect())
+        }
+    }
+
+    fn read_row(row: &Row) -> Result<HashMap<String, String>> {
+        let mut transaction = HashMap::new();
+        transaction.insert("id".to_string(), row.get::<_, String>(0)?);
+        transaction.insert("type".to_string(), row.get::<_, String>(1)?);
+        transaction.insert("amount".to_string(), row.get::<_, f64>(2)?.to_string());
+        transaction.insert("recipient".to_string(), row.get(3).unwrap_or_default());
+        transaction.insert("sender".to_string(), row.get(4).unwrap_or_default());
+        transaction.insert(
+            "previous_balance".to_string(),
+            row.get::<_, f64>(5)?.to_string(),
+        );
+        transaction.insert("new_balance".to_string(), row.get::<_, f64>(6)?.to_string());
+        transaction.insert("timestamp".to_string(), row.get::<_, String>(7)?);
+        transaction.insert("category".to_string(), row.get(8).unwrap_or_default());
+        transaction.insert("memo".to_string(), row.get(9).unwrap_or_default());
+        transaction.insert("note".to_string(), row.get(10).unwrap_or_default());
+        transaction.insert("starred".to_string(), row.get::<_, bool>(11)?.to_string());
+        transaction.insert("currency".to_string(), row.get::<_, String>(12)?);
+        transaction.insert("fx_rate".to_string(), row.get::<_, f64>(13)?.to_string());
+        transaction.insert("transfer_id".to_string(), row.get(16).unwrap_or_default());
+        transaction.insert("transfer_note".to_string(), row.get(17).unwrap_or_default());
+
+        let mut issues = Vec::new();
+        for (column, key) in [(14, "sender"), (15, "recipient")] {
+            if !row.get::<_, bool>(column)? {
+                continue;
+            }
+            let name = transaction.insert(key.to_string(), UNKNOWN_USER.to_string());
+            issues.push(match name.filter(|name| !name.is_empty()) {
+                Some(name) => format!("{} '{}' does not exist", key, name),
+                None => format!("{} is missing", key),
+            });
+        }
+        if !issues.is_empty() {
+            transaction.insert("integrity_issue".to_string(), issues.join(", "));
+        }
+
+        Ok(transaction)
+    }
+
+    /// Builds the entry shown in place of a row that failed to read, keeping
+    /// whatever identifies it.
+    fn unreadable_row(row: &Row, err: &rusqlite::Error) -> HashMap<String, String> {
+        let text = |column: usize| row.get::<_, String>(column).unwrap_or_default();
+        let mut transaction = HashMap::new();
+        transaction.insert("id".to_string(), text(0));
+        transaction.insert("type".to_string(), "unreadable".to_string());
+        transaction.insert("amount".to_string(), "0".to_string());
+        transaction.insert("previous_balance".to_string(), "0".to_string());
+        transaction.insert("new_balance".to_string(), "0".to_string());
+        transaction.insert("timestamp".to_string(), text(7));
+        transaction.insert("starred".to_string(), "false".to_string());
+        transaction.insert("currency".to_string(), BASE_CURRENCY.to_string());
+        transaction.insert("fx_rate".to_string(), "1".to_string());
+        transaction.insert("integrity_issue".to_string(), format!("could not be read: {}", err));
+        transaction
+    }
+
+    /// Counts the transactions `get_user_transactions_page` would return
+    /// for `filter`, including archived ones.
+    pub fn count_user_transactions(
+        conn: &Connection,
+        username: &str,
+        starred_only: bool,
+        filter: &HistoryFilter,
+    ) -> Result<usize> {
+        Ok(Self::count_in(conn, "transactions", username, starred_only, filter, None)?
+            + Self::count_in(conn, ARCHIVE_TABLE, username, starred_only, filter, None)?)
+    }
+
+    /// Counts the transactions `get_user_transactions_page` would return for
+    /// `filter` that were made at or after `since`, including archived ones.
+    pub fn count_user_transactions_since(
+        conn: &Connection,
+        username: &str,
+        starred_only: bool,
+        filter: &HistoryFilter,
+        since: NaiveDateTime,
+    ) -> Result<usize> {
+        let since = since.format("%Y-%m-%d %H:%M:%S").to_string();
+        let count = |table| {
+            Self::count_in(conn, table, username, starred_only, filter, Some(&since))
+        };
+        Ok(count("transactions")? + count(ARCHIVE_TABLE)?)
+    }
+
+    fn count_in(
+        conn: &Connection,
+        table: &str,
+        username: &str,
+        starred_only: bool,
+        filter: &HistoryFilter,
+        since: Option<&str>,
+    ) -> Result<usize> {
+        let (filter, filter_values) = filter.to_sql(if since.is_some() { 4 } else { 3 });
+        // The timestamp bound is a range on the (username, timestamp) index
+        let sql = format!(
+            "SELECT COUNT(*)
+            FROM {} t
+            LEFT JOIN transaction_annotations a ON a.transaction_id = t.id
+            WHERE t.username = ?1 AND (?2 = 0 OR a.starred = 1)
+                AND NOT (t.transaction_type = 'transfer_in' AND t.sender IS ?1){}{}",
+            table,
+            if since.is_some() { " AND t.timestamp >= ?3" } else { "" },
+            filter
+        );
+        let mut params = params![username, starred_only].to_vec();
+        if let Some(since) = &since {
+            params.push(since);
+        }
+        params.extend(filter_values.iter().map(|value| value as &dyn ToSql));
+        conn.query_row(&sql, params.as_slice(), |row| {
+            row.get::<_, i64>(0).map(|count| count as usize)
+        })
+    }
+
+    /// Sums money in (deposits, received transfers, redeemed tokens) and money
+    /// out (withdrawals, sent transfers, issued tokens) for a user since the
+    /// given timestamp.
+    pub fn get_totals_since(
+        conn: &Connection,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<(f64, f64)> {
+        conn.query_row(
+            "SELECT
+                COALESCE(SUM(CASE WHEN transaction_type IN ('deposit', 'transfer_in', 'token_in') THEN amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee') THEN amount ELSE 0 END), 0)
+            FROM transactions
+            WHERE username = ? AND timestamp >= ?",
+            params![username, since.format("%Y-%m-%d %H:%M:%S").to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    /// Like `get_totals_since`, but for each currency other than the base
+    /// currency, in that currency's own units: `(currency, in, out)`.
+    pub fn get_foreign_totals_since(
+        conn: &Connection,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<Vec<(String, f64, f64)>> {
         let mut stmt = conn.prepare(
-            "SELECT id, transaction_type, amount, recipient, sender, previous_balance, new_balance, timestamp
+            "SELECT currency,
+                COALESCE(SUM(CASE WHEN transaction_type IN ('deposit', 'transfer_in', 'token_in') THEN amount / fx_rate ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee') THEN amount / fx_rate ELSE 0 END), 0)
             FROM transactions
-            WHERE username = ? OR sender = ?
-            ORDER BY timestamp DESC"
+            WHERE username = ? AND timestamp >= ? AND currency != ?
+            GROUP BY currency
+            ORDER BY currency",
+        )?;
+        let totals = stmt.query_map(
+            params![
+                username,
+                since.format("%Y-%m-%d %H:%M:%S").to_string(),
+                BASE_CURRENCY
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        totals.collect()
+    }
+
+    /// Sums a user's money out since the given timestamp per category,
+    /// largest first. Uncategorized spending is summed under an empty name.
+    pub fn get_spending_by_category_since(
+        conn: &Connection,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<Vec<(String, f64)>> {
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(a.category, ''), SUM(t.amount)
+            FROM transactions t
+            LEFT JOIN transaction_annotations a ON a.transaction_id = t.id
+            WHERE t.username = ? AND t.timestamp >= ?
+                AND t.transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee')
+            GROUP BY 1
+            ORDER BY 2 DESC, 1",
+        )?;
+        let spending = stmt.query_map(
+            params![username, since.format("%Y-%m-%d %H:%M:%S").to_string()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        spending.collect()
+    }
+
+    /// Sums a user's net spending per day from `from` up to but not
+    /// including `to`, live or archived, oldest first: money out less money
+    /// in, so days with more coming in than going out are negative. Days
+    /// without transactions are left out.
+    pub fn get_daily_net_spend(
+        conn: &Connection,
+        username: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, f64)>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT substr(timestamp, 1, 10),
+                SUM(CASE
+                    WHEN transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee') THEN amount
+                    WHEN transaction_type IN ('deposit', 'transfer_in', 'token_in') THEN -amount
+                    ELSE 0
+                END)
+            FROM (
+                SELECT transaction_type, amount, timestamp FROM transactions
+                WHERE username = ?1 AND timestamp >= ?2 AND timestamp < ?3
+                UNION ALL
+                SELECT transaction_type, amount, timestamp FROM {}
+                WHERE username = ?1 AND timestamp >= ?2 AND timestamp < ?3
+            )
+            GROUP BY 1
+            ORDER BY 1",
+            ARCHIVE_TABLE
+        ))?;
+        let days = stmt.query_map(
+            params![
+                username,
+                from.format("%Y-%m-%d").to_string(),
+                to.format("%Y-%m-%d").to_string()
+            ],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
         )?;
 
-        let transactions = stmt.query_map(params![username, username], |row| {
-            let transaction_type: String = row.get(1)?;
-            let sender: Option<String> = row.get(4)?;
+        let mut spending = Vec::new();
+        for day in days {
+            let (day, net) = day?;
+            if let Ok(day) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") {
+                spending.push((day, net));
+            }
+        }
+        Ok(spending)
+    }
+
+    /// Gets a user's balance over time since the given timestamp, oldest
+    /// first: the balance at `since`, then the balance after each
+    /// transaction. Empty if there were no transactions.
+    pub fn get_balance_history_since(
+        conn: &Connection,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<Vec<(NaiveDateTime, f64)>> {
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, previous_balance, new_balance FROM transactions
+            WHERE username = ? AND timestamp >= ?
+            ORDER BY timestamp, rowid",
+        )?;
+        let rows = stmt.query_map(
+            params![username, since.format("%Y-%m-%d %H:%M:%S").to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get(2)?)),
+        )?;
 
-            // Skip this transaction if it's a 'transfer_in' and the sender is the same as the username
-            if transaction_type == "transfer_in" && sender.as_deref() == Some(username) {
-                return Ok(None);
+        let mut history = Vec::new();
+        for row in rows {
+            let (timestamp, previous_balance, new_balance) = row?;
+            if history.is_empty() {
+                history.push((since, previous_balance));
             }
+            if let Ok(at) = NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%d %H:%M:%S") {
+                history.push((at, new_balance));
+            }
+        }
+        Ok(history)
+    }
 
+    /// Gets the user's latest incoming transactions (deposits, received
+    /// transfers, redeemed tokens), newest first.
+    pub fn get_incoming(
+        conn: &Connection,
+        username: &str,
+        limit: usize,
+    ) -> Result<Vec<HashMap<String, String>>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, transaction_type, amount, sender, recipient, timestamp
+            FROM transactions
+            WHERE username = ?1
+                AND transaction_type IN ('deposit', 'transfer_in', 'token_in')
+                AND NOT (transaction_type = 'transfer_in' AND sender IS ?1)
+            ORDER BY timestamp DESC, rowid DESC
+            LIMIT ?2",
+        )?;
+        let transactions = stmt.query_map(params![username, limit as i64], |row| {
             let mut transaction = HashMap::new();
             transaction.insert("id".to_string(), row.get::<_, String>(0)?);
-            transaction.insert("type".to_string(), transaction_type);
+            transaction.insert("type".to_string(), row.get::<_, String>(1)?);
             transaction.insert("amount".to_string(), row.get::<_, f64>(2)?.to_string());
-            transaction.insert("recipient".to_string(), row.get(3).unwrap_or_default());
-            transaction.insert("sender".to_string(), sender.unwrap_or_default());
-            transaction.insert(
-                "previous_balance".to_string(),
-                row.get::<_, f64>(5)?.to_string(),
-            );
-            transaction.insert("new_balance".to_string(), row.get::<_, f64>(6)?.to_string());
-            transaction.insert("timestamp".to_string(), row.get::<_, String>(7)?);
-
-            Ok(Some(transaction))
+            transaction.insert("sender".to_string(), row.get(3).unwrap_or_default());
+            transaction.insert("recipient".to_string(), row.get(4).unwrap_or_default());
+            transaction.insert("timestamp".to_string(), row.get::<_, String>(5)?);
+            Ok(transaction)
         })?;
 
-        Ok(transactions.filter_map(Result::ok).flatten().collect())
+        transactions.collect()
+    }
+
+    /// Gets the most recent distinct recipients the user has transferred money to.
+    pub fn get_recent_recipients(
+        conn: &Connection,
+        username: &str,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare(
+            "SELECT recipient
+            FROM transactions
+            WHERE username = ? AND transaction_type = 'transfer_out' AND recipient IS NOT NULL
+            GROUP BY recipient
+            ORDER BY MAX(timestamp) DESC
+            LIMIT ?",
+        )?;
+        let recipients = stmt.query_map(params![username, limit as i64], |row| row.get(0))?;
+
+        recipients.collect()
+    }
+
+    /// Checks whether the user already sent the same amount to the same recipient since the given time.
+    pub fn has_similar_transfer_since(
+        conn: &Connection,
+        username: &str,
+        recipient: &str,
+        amount: f64,
+        since: NaiveDateTime,
+    ) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM transactions
+                WHERE username = ? AND transaction_type = 'transfer_out' AND recipient = ?
+                    AND ABS(amount - ?) < 0.005 AND timestamp >= ?
+            )",
+            params![
+                username,
+                recipient,
+                amount,
+                since.format("%Y-%m-%d %H:%M:%S").to_string()
+            ],
+            |row| row.get(0),
+        )
     }
 }
@@ -1,20 +1,37 @@
-use rusqlite::{params, Connection, Result};
+use chrono::NaiveDateTime;
+use rusqlite::{ffi, params, Connection, OptionalExtension, Result};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Times `adjust_balance` re-reads a balance that another writer changed
+/// before it gives up.
+const BALANCE_UPDATE_ATTEMPTS: usize = 5;
 
 #[allow(dead_code)]
 pub struct User {
     pub username: String,
     pub balance: f64,
+    /// Bumped on every balance change, so writers can tell whether the
+    /// balance they read is still current.
+    pub version: i64,
 }
 
 impl User {
     #[allow(dead_code)]
     pub fn new(username: String, balance: f64) -> Self {
-        User { username, balance }
+        User {
+            username,
+            balance,
+            version: 0,
+        }
     }
 
+    /// Creates a user with a zero balance. Returns `false` if the username
+    /// is taken, including by a closed account.
     pub fn create(conn: &Connection, username: &str) -> Result<bool> {
         let user_exists: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)",
+            "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?1)
+                OR EXISTS(SELECT 1 FROM closed_accounts WHERE username = ?1)",
             params![username],
             |row| row.get(0),
         )?;
@@ -31,22 +48,126 @@ impl User {
     }
 
     pub fn get(conn: &Connection, username: &str) -> Result<Option<User>> {
-        let mut stmt = conn.prepare("SELECT username, balance FROM users WHERE username = ?")?;
+        let mut stmt =
+            conn.prepare("SELECT username, balance, version FROM users WHERE username = ?")?;
         let mut user_iter = stmt.query_map(params![username], |row| {
             Ok(User {
                 username: row.get(0)?,
                 balance: row.get(1)?,
+                version: row.get(2)?,
             })
         })?;
 
         user_iter.next().transpose()
     }
 
-    pub fn update_balance(conn: &Connection, username: &str, new_balance: f64) -> Result<()> {
+    pub fn count(conn: &Connection) -> Result<usize> {
+        conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+    }
+
+    /// Gets every username, in order.
+    pub fn get_usernames(conn: &Connection) -> Result<Vec<String>> {
+        let mut stmt = conn.prepare("SELECT username FROM users ORDER BY username")?;
+        let usernames = stmt.query_map([], |row| row.get(0))?;
+        usernames.collect()
+    }
+
+    /// Sets a user's balance if it is still at `version`, the version it was
+    /// read at. Returns `false`, changing nothing, if another writer updated
+    /// it in the meantime. A balance below zero marks the user overdrawn
+    /// since `now`, unless they already were; one at zero or above clears it.
+    pub fn update_balance(
+        conn: &Connection,
+        username: &str,
+        new_balance: f64,
+        version: i64,
+        now: NaiveDateTime,
+    ) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE users SET balance = ?1, version = version + 1,
+                overdrawn_since = CASE WHEN ?1 < -0.005 THEN COALESCE(overdrawn_since, ?4) END
+            WHERE username = ?2 AND version = ?3",
+            params![new_balance, username, version, now.format(TIMESTAMP_FORMAT).to_string()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Adds `amount` to a user's balance, or takes it away if negative, and
+    /// returns the balance before and after. A conflicting write from another
+    /// connection or process is retried against the fresh balance, so no
+    /// update is lost. Inside a transaction SQLite reports such a write as
+    /// busy instead, and the whole transaction fails.
+    pub fn adjust_balance(
+        conn: &Connection,
+        username: &str,
+        amount: f64,
+        now: NaiveDateTime,
+    ) -> Result<(f64, f64)> {
+        for _ in 0..BALANCE_UPDATE_ATTEMPTS {
+            let user = Self::get(conn, username)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            let new_balance = user.balance + amount;
+            if Self::update_balance(conn, username, new_balance, user.version, now)? {
+                return Ok((user.balance, new_balance));
+            }
+        }
+        Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_BUSY),
+            Some(format!("balance of {} kept changing", username)),
+        ))
+    }
+
+    /// Records that the user logged in at `now` and returns when they
+    /// last logged in before that, if ever.
+    pub fn record_login(
+        conn: &Connection,
+        username: &str,
+        now: NaiveDateTime,
+    ) -> Result<Option<NaiveDateTime>> {
+        let previous: Option<String> = conn
+            .query_row(
+                "SELECT last_login_at FROM users WHERE username = ?",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
         conn.execute(
-            "UPDATE users SET balance = ?1 WHERE username = ?2",
-            params![new_balance, username],
+            "UPDATE users SET last_login_at = ? WHERE username = ?",
+            params![now.format(TIMESTAMP_FORMAT).to_string(), username],
         )?;
-        Ok(())
+        Ok(previous.and_then(|value| NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT).ok()))
+    }
+
+    /// Gets when the user's balance last dropped below zero, if it still is.
+    pub fn get_overdrawn_since(conn: &Connection, username: &str) -> Result<Option<NaiveDateTime>> {
+        let since: Option<String> = conn
+            .query_row(
+                "SELECT overdrawn_since FROM users WHERE username = ?",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(since.and_then(|value| NaiveDateTime::parse_from_str(&value, TIMESTAMP_FORMAT).ok()))
+    }
+
+    /// Finds usernames starting with the given prefix, exact match first.
+    pub fn search_by_prefix(conn: &Connection, prefix: &str, limit: usize) -> Result<Vec<String>> {
+        let escaped = prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let mut stmt = conn.prepare(
+            "SELECT username FROM users
+            WHERE username LIKE ? ESCAPE '\\'
+            ORDER BY username = ? DESC, username
+            LIMIT ?",
+        )?;
+        let usernames = stmt.query_map(
+            params![format!("{}%", escaped), prefix, limit as i64],
+            |row| row.get(0),
+        )?;
+
+        usernames.collect()
     }
 }
@@ -0,0 +1,430 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::models::category_rule::CategoryRules;
+use crate::models::delegation::Delegation;
+use crate::models::net_worth::NetWorthSnapshots;
+use crate::models::notification::Notifications;
+use crate::models::retention::Rollup;
+
+/// Export and erasure of everything stored about a single user.
+pub struct UserData;
+
+impl UserData {
+    /// Collects the user's account, ledger, monthly rollups, annotations,
+    /// settings, input history, notifications and who they gave access to
+    /// into one JSON bundle.
+    /// Returns `None` if the user does not exist.
+    pub fn export(conn: &Connection, username: &str, now: NaiveDateTime) -> Result<Option<Value>> {
+        let balances: Option<(f64, f64)> = conn
+            .query_row(
+                "SELECT balance, vault_balance FROM users WHERE username = ?",
+                params![username],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let (balance, vault_balance) = match balances {
+            Some(balances) => balances,
+            None => return Ok(None),
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.transaction_type, t.amount, t.recipient, t.sender,
+                    t.previous_balance, t.new_balance, t.timestamp,
+                    a.category, a.memo, a.note, COALESCE(a.starred, 0), t.currency, t.fx_rate,
+                    t.transfer_id, n.note
+            FROM (SELECT * FROM transactions_archive UNION ALL SELECT * FROM transactions) t
+            LEFT JOIN transaction_annotations a ON a.transaction_id = t.id
+            LEFT JOIN transfer_notes n ON n.transfer_id = t.transfer_id
+            WHERE t.username = ?
+            ORDER BY t.timestamp",
+        )?;
+        let transactions = stmt
+            .query_map(params![username], |row| {
+                Ok(json!({
+                    "id": row.get::<_, String>(0)?,
+                    "type": row.get::<_, String>(1)?,
+                    "amount": row.get::<_, f64>(2)?,
+                    "recipient": row.get::<_, Option<String>>(3)?,
+                    "sender": row.get::<_, Option<String>>(4)?,
+                    "previous_balance": row.get::<_, f64>(5)?,
+                    "new_balance": row.get::<_, f64>(6)?,
+                    "timestamp": row.get::<_, String>(7)?,
+                    "category": row.get::<_, Option<String>>(8)?,
+                    "memo": row.get::<_, Option<String>>(9)?,
+                    "note": row.get::<_, Option<String>>(10)?,
+                    "starred": row.get::<_, bool>(11)?,
+                    "currency": row.get::<_, String>(12)?,
+                    "fx_rate": row.get::<_, f64>(13)?,
+                    "transfer_id": row.get::<_, Option<String>>(14)?,
+                    "transfer_note": row.get::<_, Option<String>>(15)?,
+                }))
+            })?
+            .collect::<Result<Vec<Value>>>()?;
+
+        let rollups: Vec<Value> = Rollup::get_user_rollups(conn, username)?
+            .iter()
+            .map(|rollup| {
+                json!({
+                    "month": rollup.month,
+                    "transactions": rollup.transactions,
+                    "money_in": rollup.money_in,
+                    "money_out": rollup.money_out,
+                    "opening_balance": rollup.opening_balance,
+                    "closing_balance": rollup.closing_balance,
+                })
+            })
+            .collect();
+
+        let mut stmt = conn.prepare("SELECT key, value FROM user_settings WHERE username = ?")?;
+        let settings = stmt
+            .query_map(params![username], |row| {
+                Ok((row.get::<_, String>(0)?, Value::String(row.get(1)?)))
+            })?
+            .collect::<Result<serde_json::Map<String, Value>>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT field, value FROM input_history WHERE username = ? ORDER BY id",
+        )?;
+        let input_history = stmt
+            .query_map(params![username], |row| {
+                Ok(json!({
+                    "field": row.get::<_, String>(0)?,
+                    "value": row.get::<_, String>(1)?,
+                }))
+            })?
+            .collect::<Result<Vec<Value>>>()?;
+
+        let category_rules: Vec<Value> = CategoryRules::get_for_user(conn, username)?
+            .iter()
+            .map(|rule| {
+                json!({
+                    "field": rule.field.as_str(),
+                    "pattern": rule.pattern,
+                    "category": rule.category,
+                })
+            })
+            .collect();
+
+        let notifications: Vec<Value> = Notifications::get_for_user(conn, username)?
+            .iter()
+            .rev()
+            .map(|notification| {
+                json!({
+                    "kind": notification.kind.as_str(),
+                    "message": notification.message,
+                    "created_at": notification.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    "read": notification.read,
+                })
+            })
+            .collect();
+
+        let delegates: Vec<Value> = Delegation::get_delegates(conn, username)?
+            .iter()
+            .map(|delegation| {
+                json!({
+                    "delegate": delegation.delegate,
+                    "granted_at": delegation.granted_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                })
+            })
+            .collect();
+
+        let net_worth: Vec<Value> = NetWorthSnapshots::get_since(conn, username, NaiveDate::MIN)?
+            .iter()
+            .map(|snapshot| {
+                json!({
+                    "day": snapshot.day.format("%Y-%m-%d").to_string(),
+                    "available": snapshot.available,
+                    "committed": snapshot.committed,
+                    "vault": snapshot.vault,
+                })
+            })
+            .collect();
+
+        Ok(Some(json!({
+            "username": username,
+            "balance": balance,
+            "vault_balance": vault_balance,
+            "exported_at": now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            "transactions": transactions,
+            "rollups": rollups,
+            "settings": settings,
+            "input_history": input_history,
+            "category_rules": category_rules,
+            "notifications": notifications,
+            "delegates": delegates,
+            "net_worth": net_worth,
+        })))
+    }
+
+    /// Replaces the username everywhere with a tombstone id and removes the
+    /// user's annotations, settings, category rules, input history, PIN,
+    /// sessions, queued webhooks, notifications, delegated access, net
+    /// worth snapshots and the notes on transfers they sent. Ledger rows and
+    /// the balance are kept, so counterparties' histories still add up, and
+    /// other users' notifications, activity and notes that name the user
+    /// are rewritten to the tombstone id. The file logs are left to
+    /// `record_redaction`. Returns the tombstone id, or `None` if the user does
+    /// not exist.
+    pub fn anonymize(conn: &Connection, username: &str) -> Result<Option<String>> {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)",
+            params![username],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(None);
+        }
+
+        let tombstone = format!("deleted-{:08x}", rand::thread_rng().gen::<u32>());
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM transaction_annotations
+            WHERE transaction_id IN (SELECT id FROM transactions WHERE username = ?1
+                UNION SELECT id FROM transactions_archive WHERE username = ?1)",
+            params![username],
+        )?;
+        tx.execute("DELETE FROM user_settings WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM category_rules WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM input_history WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM user_keys WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM account_pins WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM account_passwords WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM recovery_codes WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM net_worth_snapshots WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM sweep_rules WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM activity_log WHERE username = ?", params![username])?;
+        tx.execute(
+            "DELETE FROM delegations WHERE owner = ?1 OR delegate = ?1",
+            params![username],
+        )?;
+        tx.execute(
+            "DELETE FROM joint_members WHERE account = ?1 OR member = ?1",
+            params![username],
+        )?;
+        tx.execute("DELETE FROM sessions WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM outbox WHERE username = ?", params![username])?;
+        tx.execute("DELETE FROM notifications WHERE username = ?", params![username])?;
+        tx.execute(
+            "DELETE FROM input_history WHERE field = 'recipient' AND value = ?",
+            params![username],
+        )?;
+        tx.execute(
+            "DELETE FROM transfer_notes
+            WHERE transfer_id IN (
+                SELECT transfer_id FROM transactions
+                WHERE username = ?1 AND transaction_type = 'transfer_out'
+                UNION SELECT transfer_id FROM transactions_archive
+                WHERE username = ?1 AND transaction_type = 'transfer_out')",
+            params![username],
+        )?;
+        tx.execute(
+            "UPDATE pending_transfers SET note = NULL WHERE sender = ?",
+            params![username],
+        )?;
+        tx.execute(
+            "UPDATE closed_accounts SET statement = '' WHERE username = ?",
+            params![username],
+        )?;
+        tx.execute(
+            "UPDATE users SET username = ? WHERE username = ?",
+            params![tombstone, username],
+        )?;
+        for table in ["transactions", "transactions_archive"] {
+            for column in ["username", "sender", "recipient"] {
+                tx.execute(
+                    &format!("UPDATE {0} SET {1} = ?1 WHERE {1} = ?2", table, column),
+                    params![tombstone, username],
+                )?;
+            }
+        }
+        for column in ["sender", "recipient", "decided_by"] {
+            tx.execute(
+                &format!("UPDATE pending_transfers SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        for column in ["requester", "payer"] {
+            tx.execute(
+                &format!("UPDATE payment_requests SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        tx.execute(
+            "UPDATE vault_withdrawals SET username = ?1 WHERE username = ?2",
+            params![tombstone, username],
+        )?;
+        tx.execute(
+            "UPDATE processor_payments SET username = ?1 WHERE username = ?2",
+            params![tombstone, username],
+        )?;
+        for column in ["child", "parent"] {
+            tx.execute(
+                &format!("UPDATE child_accounts SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        for column in ["username", "counterparty"] {
+            tx.execute(
+                &format!("UPDATE offline_tokens SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        for column in ["merchant", "paid_by"] {
+            tx.execute(
+                &format!("UPDATE invoices SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        for table in ["transaction_rollups", "balance_snapshots", "holds"] {
+            tx.execute(
+                &format!("UPDATE {} SET username = ?1 WHERE username = ?2", table),
+                params![tombstone, username],
+            )?;
+        }
+        for column in ["username", "nominee"] {
+            tx.execute(
+                &format!("UPDATE closed_accounts SET {0} = ?1 WHERE {0} = ?2", column),
+                params![tombstone, username],
+            )?;
+        }
+        for (table, column) in [
+            ("notifications", "message"),
+            ("activity_log", "description"),
+            ("transfer_notes", "note"),
+            ("pending_transfers", "note"),
+            ("closed_accounts", "statement"),
+        ] {
+            Self::rewrite_mentions(&tx, table, column, username, &tombstone)?;
+        }
+        tx.commit()?;
+        Ok(Some(tombstone))
+    }
+
+    /// Rewrites the user's name to `tombstone` in free text, such as
+    /// `alice sent you 5.00`, wherever it appears in `column`.
+    fn rewrite_mentions(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        username: &str,
+        tombstone: &str,
+    ) -> Result<()> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT rowid, {0} FROM {1} WHERE instr({0}, ?) > 0",
+            column, table
+        ))?;
+        let rows = stmt
+            .query_map(params![username], |row| Ok((row.get::<_, i64>(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, String)>>>()?;
+        for (rowid, text) in rows {
+            let rewritten = Self::replace_name(&text, username, tombstone);
+            if rewritten != text {
+                conn.execute(
+                    &format!("UPDATE {} SET {} = ? WHERE rowid = ?", table, column),
+                    params![rewritten, rowid],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces `username` in `text` where it stands as a whole word, so
+    /// `al` is rewritten in `al sent you 5.00` but not in `balance`.
+    pub fn replace_name(text: &str, username: &str, replacement: &str) -> String {
+        if username.is_empty() {
+            return text.to_string();
+        }
+        let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(username) {
+            let end = start + username.len();
+            let before = rest[..start].chars().next_back();
+            let after = rest[end..].chars().next();
+            result.push_str(&rest[..start]);
+            if before.is_some_and(is_name_char) || after.is_some_and(is_name_char) {
+                result.push_str(username);
+            } else {
+                result.push_str(replacement);
+            }
+            rest = &rest[end..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Appends a line to a plain text log such as `audit.log` naming
+    /// `tombstone` and the numbers of the lines that mention the user, so
+    /// they can be scrubbed from copies. Lines already written are left
+    /// alone, so signed exports of the log still verify. Nothing is written
+    /// if no line mentions the user. Returns the number of lines named.
+    pub fn record_redaction(
+        path: &str,
+        username: &str,
+        tombstone: &str,
+        now: NaiveDateTime,
+    ) -> io::Result<usize> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        let numbers: Vec<String> = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| Self::replace_name(line, username, tombstone) != *line)
+            .map(|(index, _)| (index + 1).to_string())
+            .collect();
+        if numbers.is_empty() {
+            return Ok(0);
+        }
+        let mut file = OpenOptions::new().append(true).open(path)?;
+        if !text.is_empty() && !text.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+        let line = format!(
+            "{} {} was anonymized, redact lines {}\n",
+            now.format("%Y-%m-%d %H:%M:%S"),
+            tombstone,
+            numbers.join(", ")
+        );
+        file.write_all(line.as_bytes())?;
+        Ok(numbers.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::audit_export::AuditExport;
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn redacting_the_audit_log_keeps_signed_exports_valid() {
+        let path = std::env::temp_dir().join(format!("ewallet-audit-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let log = "2026-03-02 09:00:00 alice logged in\n\
+            2026-03-02 09:01:00 bob logged in\n\
+            2026-03-02 09:02:00 bob transfer_out $5.00 to alice, balance $5.00\n";
+        fs::write(path, log).unwrap();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signed_before = AuditExport::sign(log, &key);
+
+        let now = NaiveDate::from_ymd_opt(2026, 3, 3).unwrap().and_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(UserData::record_redaction(path, "alice", "deleted-0000abcd", now).unwrap(), 2);
+        assert_eq!(UserData::record_redaction(path, "dave", "deleted-00001234", now).unwrap(), 0);
+
+        let text = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(text.starts_with(log));
+        assert!(text.ends_with("deleted-0000abcd was anonymized, redact lines 1, 3\n"));
+        assert!(AuditExport::verify(&signed_before, None).is_ok());
+        let (lines, _) = AuditExport::verify(&AuditExport::sign(&text, &key), None).unwrap();
+        assert_eq!(lines.len(), 4);
+    }
+}
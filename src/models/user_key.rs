@@ -0,0 +1,217 @@
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::Rng;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::models::key_provider::KeyProvider;
+
+/// Whether a transfer carries a valid signature from its sender.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SignatureStatus {
+    Unsigned,
+    Verified,
+    Invalid,
+}
+
+impl SignatureStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignatureStatus::Unsigned => "not signed",
+            SignatureStatus::Verified => "verified",
+            SignatureStatus::Invalid => "INVALID",
+        }
+    }
+}
+
+/// A signing key encrypted with ChaCha20-Poly1305 under a key derived from
+/// a credential with Argon2, as it is stored.
+pub struct SealedKey {
+    pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub encrypted: Vec<u8>,
+}
+
+impl SealedKey {
+    /// Encrypts `key` under `credential` with a fresh salt and nonce.
+    pub fn seal(key: &SigningKey, credential: &str) -> Option<Self> {
+        let salt: [u8; 16] = rand::thread_rng().gen();
+        let nonce: [u8; 12] = rand::thread_rng().gen();
+        let encrypted = UserKey::cipher(credential, &salt)?
+            .encrypt(Nonce::from_slice(&nonce), key.to_bytes().as_ref())
+            .ok()?;
+        Some(SealedKey {
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            encrypted,
+        })
+    }
+
+    /// Decrypts the key, or `None` if `credential` is not the one it was
+    /// sealed with.
+    pub fn open(&self, credential: &str) -> Option<SigningKey> {
+        if self.nonce.len() != 12 {
+            return None;
+        }
+        let secret = UserKey::cipher(credential, &self.salt)?
+            .decrypt(Nonce::from_slice(&self.nonce), self.encrypted.as_ref())
+            .ok()?;
+        Some(SigningKey::from_bytes(&<[u8; 32]>::try_from(secret).ok()?))
+    }
+}
+
+/// A user's ed25519 identity. The public key is stored as is; the secret key
+/// is sealed under the user's password, so it can only be used after logging
+/// in with it. Keys made before accounts had passwords were sealed under the
+/// username, and are moved under the password once it is known.
+pub struct UserKey;
+
+impl UserKey {
+    /// Makes a new random signing key.
+    pub fn generate() -> SigningKey {
+        SigningKey::from_bytes(&rand::thread_rng().gen())
+    }
+
+    pub fn exists(conn: &Connection, username: &str) -> Result<bool> {
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM user_keys WHERE username = ?)",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    /// Gets the user's signing key, decrypting it with the secret from
+    /// `provider`. Returns `None` if they have none or it is sealed under
+    /// something else.
+    pub fn unlock(
+        conn: &Connection,
+        username: &str,
+        provider: &dyn KeyProvider,
+    ) -> Result<Option<SigningKey>> {
+        let credential = match provider.secret() {
+            Some(credential) => credential,
+            None => return Ok(None),
+        };
+        let sealed = conn
+            .query_row(
+                "SELECT salt, nonce, encrypted_secret FROM user_keys WHERE username = ?",
+                params![username],
+                |row| {
+                    Ok(SealedKey {
+                        salt: row.get(0)?,
+                        nonce: row.get(1)?,
+                        encrypted: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(sealed.and_then(|sealed| sealed.open(&credential)))
+    }
+
+    /// Stores `key` as the user's signing key, sealed under the secret from
+    /// `provider`, in place of any key they had. Returns `false` if it could
+    /// not be sealed.
+    pub fn store(
+        conn: &Connection,
+        username: &str,
+        key: &SigningKey,
+        provider: &dyn KeyProvider,
+    ) -> Result<bool> {
+        let sealed = match provider.secret().and_then(|secret| SealedKey::seal(key, &secret)) {
+            Some(sealed) => sealed,
+            None => return Ok(false),
+        };
+        conn.execute(
+            "INSERT OR REPLACE INTO user_keys (username, public_key, salt, nonce, encrypted_secret)
+            VALUES (?, ?, ?, ?, ?)",
+            params![
+                username,
+                key.verifying_key().to_bytes().to_vec(),
+                sealed.salt,
+                sealed.nonce,
+                sealed.encrypted
+            ],
+        )?;
+        Ok(true)
+    }
+
+    /// Gets the user's public key, if they have one.
+    pub fn public_key(conn: &Connection, username: &str) -> Result<Option<VerifyingKey>> {
+        let bytes: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT public_key FROM user_keys WHERE username = ?",
+                params![username],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(bytes
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok()))
+    }
+
+    /// Signs a transfer and stores the signature for both of its legs.
+    pub fn sign_transfer(
+        conn: &Connection,
+        key: &SigningKey,
+        transaction_ids: &[&str],
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+        timestamp: &str,
+    ) -> Result<()> {
+        let payload = Self::transfer_payload(sender, recipient, amount, timestamp);
+        let signature = key.sign(payload.as_bytes()).to_bytes().to_vec();
+        for id in transaction_ids {
+            conn.execute(
+                "INSERT OR REPLACE INTO transfer_signatures (transaction_id, signature) VALUES (?, ?)",
+                params![id, signature],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks a transfer's signature against the sender's public key.
+    pub fn verify_transfer(
+        conn: &Connection,
+        transaction_id: &str,
+        sender: &str,
+        recipient: &str,
+        amount: f64,
+        timestamp: &str,
+    ) -> Result<SignatureStatus> {
+        let signature: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT signature FROM transfer_signatures WHERE transaction_id = ?",
+                params![transaction_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let signature = match signature {
+            Some(bytes) => bytes,
+            None => return Ok(SignatureStatus::Unsigned),
+        };
+
+        let payload = Self::transfer_payload(sender, recipient, amount, timestamp);
+        let verified = Self::public_key(conn, sender)?
+            .zip(Signature::from_slice(&signature).ok())
+            .is_some_and(|(key, signature)| key.verify(payload.as_bytes(), &signature).is_ok());
+        Ok(if verified {
+            SignatureStatus::Verified
+        } else {
+            SignatureStatus::Invalid
+        })
+    }
+
+    fn transfer_payload(sender: &str, recipient: &str, amount: f64, timestamp: &str) -> String {
+        format!("transfer\n{}\n{}\n{}\n{}", sender, recipient, amount, timestamp)
+    }
+
+    fn cipher(credential: &str, salt: &[u8]) -> Option<ChaCha20Poly1305> {
+        let mut derived = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(credential.as_bytes(), salt, &mut derived)
+            .ok()?;
+        Some(ChaCha20Poly1305::new(Key::from_slice(&derived)))
+    }
+}
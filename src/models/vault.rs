@@ -0,0 +1,178 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::{params, Connection, Result};
+
+use crate::models::money::{Money, RoundingPolicy};
+use crate::models::payment_request::RequestStatus;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Money on its way from a user's vault back to their spendable balance.
+/// It leaves the vault when requested and is released once `available_at`
+/// has passed. Uses the payment request lifecycle: pending until it is paid
+/// (released) or declined (cancelled, which returns it to the vault).
+#[allow(dead_code)]
+pub struct VaultWithdrawal {
+    pub id: i64,
+    pub username: String,
+    pub amount: f64,
+    pub status: RequestStatus,
+    pub created_at: NaiveDateTime,
+    pub available_at: NaiveDateTime,
+}
+
+/// Savings kept apart from the spendable balance. Moving money in is
+/// instant; taking it out waits for a delay, so a leaked login cannot drain
+/// the vault before the owner notices.
+pub struct Vault;
+
+impl Vault {
+    pub fn get_balance(conn: &Connection, username: &str) -> Result<f64> {
+        conn.query_row(
+            "SELECT COALESCE((SELECT vault_balance FROM users WHERE username = ?), 0)",
+            params![username],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn update_balance(conn: &Connection, username: &str, new_balance: f64) -> Result<()> {
+        conn.execute(
+            "UPDATE users SET vault_balance = ?1 WHERE username = ?2",
+            params![new_balance, username],
+        )?;
+        Ok(())
+    }
+
+    /// Puts `amount` in a user's vault. The vault balance is changed in
+    /// place, so a write from another connection in the meantime is kept.
+    pub fn add(conn: &Connection, username: &str, amount: f64) -> Result<()> {
+        conn.execute(
+            "UPDATE users SET vault_balance = vault_balance + ?1 WHERE username = ?2",
+            params![amount, username],
+        )?;
+        Ok(())
+    }
+
+    /// Takes `amount` out of a user's vault if it holds that much. Returns
+    /// `false`, changing nothing, if it does not.
+    pub fn take(conn: &Connection, username: &str, amount: f64) -> Result<bool> {
+        let updated = conn.execute(
+            "UPDATE users SET vault_balance = vault_balance - ?1
+            WHERE username = ?2 AND vault_balance >= ?1",
+            params![amount, username],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn create_withdrawal(
+        conn: &Connection,
+        username: &str,
+        amount: f64,
+        created_at: NaiveDateTime,
+        available_at: NaiveDateTime,
+    ) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO vault_withdrawals (username, amount, status, created_at, available_at)
+            VALUES (?, ?, 'pending', ?, ?)",
+            params![
+                username,
+                amount,
+                created_at.format(TIMESTAMP_FORMAT).to_string(),
+                available_at.format(TIMESTAMP_FORMAT).to_string()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Gets the user's withdrawals that have not been released yet, soonest first.
+    pub fn get_pending(conn: &Connection, username: &str) -> Result<Vec<VaultWithdrawal>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, amount, status, created_at, available_at
+            FROM vault_withdrawals
+            WHERE username = ? AND status = 'pending'
+            ORDER BY available_at, id",
+        )?;
+        let withdrawals = stmt.query_map(params![username], Self::from_row)?;
+        withdrawals.collect()
+    }
+
+    /// Gets every user's pending withdrawals whose delay has passed.
+    pub fn get_due(conn: &Connection, now: NaiveDateTime) -> Result<Vec<VaultWithdrawal>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, username, amount, status, created_at, available_at
+            FROM vault_withdrawals
+            WHERE status = 'pending' AND available_at <= ?
+            ORDER BY available_at, id",
+        )?;
+        let withdrawals = stmt.query_map(
+            params![now.format(TIMESTAMP_FORMAT).to_string()],
+            Self::from_row,
+        )?;
+        withdrawals.collect()
+    }
+
+    /// Moves a withdrawal to a new status if it is still pending.
+    /// Returns `false` if it had already been settled.
+    pub fn set_status(conn: &Connection, id: i64, status: RequestStatus) -> Result<bool> {
+        if !RequestStatus::Pending.can_become(status) {
+            return Ok(false);
+        }
+        let updated = conn.execute(
+            "UPDATE vault_withdrawals SET status = ? WHERE id = ? AND status = 'pending'",
+            params![status.as_str(), id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Adds simple interest at `yearly_percent` to every vault for the days
+    /// since interest was last paid, up to `today`, each vault's rounded to
+    /// the cent by `rounding`. A vault is paid at most once a day; the first
+    /// call only starts the count. Returns the total interest paid.
+    pub fn pay_interest(
+        conn: &Connection,
+        today: NaiveDate,
+        yearly_percent: f64,
+        rounding: RoundingPolicy,
+    ) -> Result<f64> {
+        let today = today.format("%Y-%m-%d").to_string();
+        let tx = conn.unchecked_transaction()?;
+        let due: Vec<(String, f64, f64)> = {
+            let mut stmt = tx.prepare(
+                "SELECT username, vault_balance,
+                    julianday(?1) - julianday(COALESCE(vault_interest_paid_on, ?1))
+                FROM users
+                WHERE vault_interest_paid_on IS NULL OR vault_interest_paid_on < ?1",
+            )?;
+            let due = stmt.query_map(params![today], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            due.collect::<Result<_>>()?
+        };
+        let mut paid = 0.0;
+        for (username, vault_balance, days) in due {
+            let interest = Money::round(vault_balance * yearly_percent / 36500.0 * days, rounding);
+            tx.execute(
+                "UPDATE users SET vault_balance = vault_balance + ?, vault_interest_paid_on = ?
+                WHERE username = ?",
+                params![interest, today, username],
+            )?;
+            paid += interest;
+        }
+        tx.commit()?;
+        Ok(paid)
+    }
+
+    fn from_row(row: &rusqlite::Row) -> Result<VaultWithdrawal> {
+        let created_at: String = row.get(4)?;
+        let available_at: String = row.get(5)?;
+        Ok(VaultWithdrawal {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            amount: row.get(2)?,
+            status: RequestStatus::parse(&row.get::<_, String>(3)?),
+            created_at: NaiveDateTime::parse_from_str(&created_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+            available_at: NaiveDateTime::parse_from_str(&available_at, TIMESTAMP_FORMAT)
+                .unwrap_or_default(),
+        })
+    }
+}
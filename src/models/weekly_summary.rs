@@ -0,0 +1,73 @@
+use chrono::{Datelike, Duration, NaiveDateTime};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::models::child_account::ChildAccount;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// User setting holding the last ISO week a user was seen in, e.g. `2024-W07`.
+pub const SUMMARY_WEEK_SETTING: &str = "summary_week";
+
+/// What moved through a user's account in one ISO week, sent to their inbox
+/// once the week is over.
+pub struct WeeklySummary {
+    /// The week as `YYYY-Www`.
+    pub week: String,
+    pub money_in: f64,
+    pub money_out: f64,
+    /// The largest single payment out: its amount, type and recipient, or
+    /// merchant for an imported payment.
+    pub biggest_expense: Option<(f64, String, Option<String>)>,
+    /// For a child account with a spending cap: the cap and what was spent.
+    pub spending_cap: Option<(f64, f64)>,
+}
+
+impl WeeklySummary {
+    /// Sums the ISO week starting at `start`, a Monday at midnight.
+    pub fn for_week(conn: &Connection, username: &str, start: NaiveDateTime) -> Result<Self> {
+        let from = start.format(TIMESTAMP_FORMAT).to_string();
+        let to = (start + Duration::days(7)).format(TIMESTAMP_FORMAT).to_string();
+        let (money_in, money_out) = conn.query_row(
+            "SELECT
+                COALESCE(SUM(CASE WHEN transaction_type IN ('deposit', 'transfer_in', 'token_in') THEN amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee') THEN amount ELSE 0 END), 0)
+            FROM transactions
+            WHERE username = ?1 AND timestamp >= ?2 AND timestamp < ?3",
+            params![username, from, to],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let biggest_expense = conn
+            .query_row(
+                "SELECT amount, transaction_type, COALESCE(merchant, recipient) FROM transactions
+                WHERE username = ?1 AND timestamp >= ?2 AND timestamp < ?3
+                    AND transaction_type IN ('withdraw', 'transfer_out', 'token_out', 'fee')
+                ORDER BY amount DESC, timestamp
+                LIMIT 1",
+                params![username, from, to],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let spending_cap = ChildAccount::get(conn, username)?
+            .and_then(|child| child.spending_cap)
+            .map(|cap| (cap, money_out));
+
+        Ok(WeeklySummary {
+            week: week_label(start),
+            money_in,
+            money_out,
+            biggest_expense,
+            spending_cap,
+        })
+    }
+
+    /// Whether any money moved in the week.
+    pub fn is_empty(&self) -> bool {
+        self.money_in == 0.0 && self.money_out == 0.0
+    }
+}
+
+/// Labels the ISO week `at` falls in, e.g. `2024-W07`.
+pub fn week_label(at: NaiveDateTime) -> String {
+    let week = at.date().iso_week();
+    format!("{}-W{:02}", week.year(), week.week())
+}
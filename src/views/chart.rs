@@ -0,0 +1,105 @@
+use chrono::{Duration, NaiveDateTime};
+use plotters::coord::types::RangedCoordf64;
+use plotters::prelude::*;
+use std::error::Error;
+
+use crate::views::format;
+
+/// Size of an exported chart in pixels.
+const SIZE: (u32, u32) = (800, 480);
+
+const FONT: &str = "sans-serif";
+
+/// Draws money out per category as an SVG bar chart, largest first as given.
+/// An empty category is labelled "Uncategorized".
+pub fn spending_breakdown(
+    title: &str,
+    spending: &[(String, f64)],
+) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let top = spending.iter().map(|(_, amount)| *amount).fold(0.0, f64::max);
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, (FONT, 24))
+            .margin(16)
+            .x_label_area_size(40)
+            .y_label_area_size(90)
+            .build_cartesian_2d(
+                (0..spending.len()).into_segmented(),
+                0.0..(top * 1.1).max(1.0),
+            )?;
+        let label = |value: &SegmentValue<usize>| match value {
+            SegmentValue::CenterOf(i) => match spending.get(*i) {
+                Some((category, _)) if category.is_empty() => "Uncategorized".to_string(),
+                Some((category, _)) => category.clone(),
+                None => String::new(),
+            },
+            _ => String::new(),
+        };
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(spending.len())
+            .x_label_formatter(&label)
+            .y_label_formatter(&|amount| format::money(*amount))
+            .draw()?;
+        chart.draw_series(
+            Histogram::vertical(&chart)
+                .style(BLUE.filled())
+                .margin(12)
+                .data(spending.iter().enumerate().map(|(i, (_, amount))| (i, *amount))),
+        )?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+/// Draws the balance after each transaction as an SVG line chart. `history`
+/// is oldest first and must not be empty.
+pub fn balance_history(
+    title: &str,
+    history: &[(NaiveDateTime, f64)],
+) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let first = history.first().map_or_else(NaiveDateTime::default, |(at, _)| *at);
+        let last = history.last().map_or(first, |(at, _)| *at).max(first + Duration::hours(1));
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, (FONT, 24))
+            .margin(16)
+            .x_label_area_size(40)
+            .y_label_area_size(90)
+            .build_cartesian_2d(RangedDateTime::from(first..last), balance_range(history))?;
+        chart
+            .configure_mesh()
+            .x_labels(6)
+            .x_label_formatter(&|at| at.format("%b %d").to_string())
+            .y_label_formatter(&|amount| format::money(*amount))
+            .draw()?;
+        // A balance holds until the next transaction, so it is drawn in steps
+        let mut points = Vec::new();
+        for (i, (at, balance)) in history.iter().enumerate() {
+            if i > 0 {
+                points.push((*at, history[i - 1].1));
+            }
+            points.push((*at, *balance));
+        }
+        chart.draw_series(LineSeries::new(points, BLUE.stroke_width(2)))?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+/// The balances charted, from zero unless the account was overdrawn, with
+/// room above so the line is not drawn on the frame.
+fn balance_range(history: &[(NaiveDateTime, f64)]) -> RangedCoordf64 {
+    let low = history.iter().map(|(_, balance)| *balance).fold(0.0, f64::min);
+    let high = history.iter().map(|(_, balance)| *balance).fold(0.0, f64::max);
+    let padding = ((high - low) * 0.1).max(1.0);
+    let bottom = if low < 0.0 { low - padding } else { 0.0 };
+    (bottom..high + padding).into()
+}
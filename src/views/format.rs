@@ -0,0 +1,95 @@
+use thousands::Separable;
+use unicode_width::UnicodeWidthChar;
+
+use crate::models::transaction::BASE_CURRENCY;
+
+/// Shown in place of amounts while they are hidden for privacy.
+pub const MASKED_AMOUNT: &str = "•••••";
+
+/// Formats an amount of money with thousands separators, e.g. `$1,234.50`.
+pub fn money(amount: f64) -> String {
+    format!("${}", format!("{:.2}", amount).separate_with_commas())
+}
+
+/// Formats an amount of money, or the mask when amounts are hidden.
+pub fn money_or_masked(amount: f64, hidden: bool) -> String {
+    if hidden {
+        MASKED_AMOUNT.to_string()
+    } else {
+        money(amount)
+    }
+}
+
+/// Formats an amount in the given currency. The base currency keeps the `$`
+/// style; others are shown with their code, e.g. `1,234.50 EUR`.
+pub fn money_in(amount: f64, currency: &str) -> String {
+    if currency == BASE_CURRENCY {
+        money(amount)
+    } else {
+        format!("{} {}", format!("{:.2}", amount).separate_with_commas(), currency)
+    }
+}
+
+/// The currency a user sees amounts in. Balances are always kept in the
+/// base currency; showing them in another is only a conversion for display.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayCurrency {
+    pub code: String,
+    /// Base currency units per unit of `code`.
+    pub rate: f64,
+}
+
+impl DisplayCurrency {
+    pub fn base() -> Self {
+        DisplayCurrency {
+            code: BASE_CURRENCY.to_string(),
+            rate: 1.0,
+        }
+    }
+
+    pub fn is_base(&self) -> bool {
+        self.code == BASE_CURRENCY
+    }
+
+    /// Formats an amount in the base currency converted into this one.
+    pub fn money(&self, amount: f64) -> String {
+        if self.is_base() {
+            money(amount)
+        } else {
+            money_in(amount / self.rate, &self.code)
+        }
+    }
+
+    /// Formats a converted amount, or the mask when amounts are hidden.
+    pub fn money_or_masked(&self, amount: f64, hidden: bool) -> String {
+        if hidden {
+            MASKED_AMOUNT.to_string()
+        } else {
+            self.money(amount)
+        }
+    }
+}
+
+/// Fits typed text into `width` columns. Text that is too long keeps its
+/// end, where the user is typing, and starts with `…` to show it was cut.
+pub fn input_tail(text: &str, width: usize) -> String {
+    let text_width: usize = text.chars().map(|c| c.width().unwrap_or(0)).sum();
+    if text_width <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut kept = Vec::new();
+    let mut used = 1;
+    for c in text.chars().rev() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            break;
+        }
+        used += char_width;
+        kept.push(c);
+    }
+    std::iter::once('…').chain(kept.into_iter().rev()).collect()
+}
@@ -0,0 +1,92 @@
+use chrono::{Datelike, NaiveDate};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::Widget,
+};
+
+/// Shades from lightest to darkest, for days up to a quarter, half, three
+/// quarters and all of the month's largest amount.
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Narrowest day cell: the day of the month, a space, one shade and a gap.
+const MIN_CELL_WIDTH: u16 = 5;
+
+/// A month drawn as a calendar grid, weeks starting on Monday, with each day
+/// shaded by its net spending: red where more went out than came in, green
+/// where more came in, and darker the closer the amount is to the month's
+/// largest. Takes a header row and up to six week rows.
+pub struct Heatmap<'a> {
+    days: &'a [(NaiveDate, f64)],
+    selected: NaiveDate,
+}
+
+impl<'a> Heatmap<'a> {
+    /// Draws the month `selected` is in, with `selected` highlighted. `days`
+    /// is the net spending per day, as `Transaction::get_daily_net_spend`
+    /// returns it.
+    pub fn new(days: &'a [(NaiveDate, f64)], selected: NaiveDate) -> Self {
+        Heatmap { days, selected }
+    }
+}
+
+impl Widget for Heatmap<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let cell_width = area.width / 7;
+        if cell_width < MIN_CELL_WIDTH || area.height < 2 {
+            return;
+        }
+        for (column, name) in WEEKDAYS.iter().enumerate() {
+            let x = area.x + column as u16 * cell_width;
+            buf.set_string(x, area.y, name, Style::default().fg(Color::DarkGray));
+        }
+
+        let most_out = self.days.iter().fold(0.0, |most, (_, net)| f64::max(most, *net));
+        let most_in = self.days.iter().fold(0.0, |most, (_, net)| f64::max(most, -*net));
+        let first = self.selected.with_day(1).unwrap_or(self.selected);
+        let offset = first.weekday().num_days_from_monday();
+        let mut day = Some(first);
+        while let Some(date) = day.filter(|date| date.month() == first.month()) {
+            let slot = offset + date.day0();
+            let x = area.x + (slot % 7) as u16 * cell_width;
+            let y = area.y + 1 + (slot / 7) as u16;
+            if y >= area.bottom() {
+                break;
+            }
+            let number_style = if date == self.selected {
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD | Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            buf.set_string(x, y, format!("{:>2}", date.day()), number_style);
+            let net = self
+                .days
+                .iter()
+                .find(|(spent_on, _)| *spent_on == date)
+                .map_or(0.0, |(_, net)| *net);
+            let (shade, style) = shade(net, most_out, most_in);
+            let width = (cell_width - 4) as usize;
+            buf.set_string(x + 3, y, shade.to_string().repeat(width), style);
+            day = date.succ_opt();
+        }
+    }
+}
+
+/// Picks the shade and color for a day's net spending.
+fn shade(net: f64, most_out: f64, most_in: f64) -> (char, Style) {
+    let (most, color) = if net > 0.0 {
+        (most_out, Color::Red)
+    } else {
+        (most_in, Color::Green)
+    };
+    if net.abs() < 0.005 || most <= 0.0 {
+        return ('·', Style::default().fg(Color::DarkGray));
+    }
+    let level = (net.abs() / most * SHADES.len() as f64).ceil() as usize;
+    (SHADES[level.clamp(1, SHADES.len()) - 1], Style::default().fg(color))
+}
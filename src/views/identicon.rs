@@ -0,0 +1,47 @@
+use sha2::{Digest, Sha256};
+use tui::{
+    style::{Color, Style},
+    text::Span,
+};
+
+/// Shapes an identicon is drawn with. All are one column wide.
+const SHAPES: [char; 16] = [
+    '●', '■', '▲', '◆', '★', '♥', '♣', '♠', '▼', '◀', '▶', '◉', '◈', '✚', '✖', '✦',
+];
+
+/// Colors an identicon is drawn in, leaving out the grays and white used for
+/// ordinary text.
+const COLORS: [Color; 12] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+];
+
+/// Two colored shapes derived from a username, always the same for the same
+/// name, so counterparties can be told apart at a glance in long lists.
+pub fn identicon(username: &str) -> Vec<Span<'static>> {
+    let hash = Sha256::digest(username.as_bytes());
+    (0..2)
+        .map(|i| {
+            let shape = SHAPES[hash[2 * i] as usize % SHAPES.len()];
+            let color = COLORS[hash[2 * i + 1] as usize % COLORS.len()];
+            Span::styled(shape.to_string(), Style::default().fg(color))
+        })
+        .collect()
+}
+
+/// A username after its identicon, e.g. for a list row.
+pub fn labelled(username: &str) -> Vec<Span<'static>> {
+    let mut spans = identicon(username);
+    spans.push(Span::raw(format!(" {}", username)));
+    spans
+}
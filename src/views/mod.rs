@@ -1 +1,9 @@
 pub mod ui;
+pub mod format;
+pub mod watch;
+pub mod sql_console;
+pub mod picker;
+pub mod statement;
+pub mod identicon;
+pub mod chart;
+pub mod heatmap;
@@ -0,0 +1,40 @@
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+/// Draws a full-screen list to choose from, such as the profiles shown
+/// before the main menu when there is more than one.
+pub fn draw<B: Backend>(f: &mut Frame<B>, title: &str, choices: &[String], selected: usize) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(f.size());
+
+    let header = Paragraph::new("E-Wallet Demo")
+        .style(Style::default().fg(Color::Cyan))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(header, chunks[0]);
+
+    let items: Vec<ListItem> = choices
+        .iter()
+        .map(|choice| ListItem::new(choice.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
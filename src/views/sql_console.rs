@@ -0,0 +1,100 @@
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Span,
+    widgets::{Block, Borders, Paragraph, Row, Table},
+    Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::controllers::sql_console::{SqlConsole, SQL_CONSOLE_MAX_ROWS};
+
+/// Widest a result column is drawn; longer values are cut off.
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Draws the SQL console: the prompt, then the last query's results as a table.
+pub fn draw<B: Backend>(f: &mut Frame<B>, console: &SqlConsole) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let title = Paragraph::new("SQL Console (read-only)")
+        .style(Style::default().fg(Color::Cyan))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    let input = Paragraph::new(console.input.as_str())
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().title("Query").borders(Borders::ALL));
+    f.render_widget(input, chunks[1]);
+    f.set_cursor(
+        chunks[1].x + console.input.width() as u16 + 1,
+        chunks[1].y + 1,
+    );
+
+    let block = Block::default().title(results_title(console)).borders(Borders::ALL);
+    if let Some(error) = &console.error {
+        let error = Paragraph::new(Span::styled(error.as_str(), Style::default().fg(Color::Red)))
+            .block(block);
+        f.render_widget(error, chunks[2]);
+    } else {
+        let visible = &console.rows[console.scroll.min(console.rows.len())..];
+        let widths: Vec<Constraint> = (0..console.columns.len())
+            .map(|i| {
+                let widest = visible
+                    .iter()
+                    .map(|row| row[i].width())
+                    .chain([console.columns[i].width()])
+                    .max()
+                    .unwrap_or(0);
+                Constraint::Length(widest.min(MAX_COLUMN_WIDTH) as u16)
+            })
+            .collect();
+        let header = Row::new(console.columns.clone())
+            .style(Style::default().add_modifier(Modifier::BOLD).fg(Color::Cyan));
+        let rows = visible.iter().map(|row| Row::new(row.clone()));
+        let table = Table::new(rows)
+            .header(header)
+            .block(block)
+            .widths(&widths)
+            .column_spacing(2);
+        f.render_widget(table, chunks[2]);
+    }
+
+    let footer = Paragraph::new(Span::styled(
+        "Enter: run, Up/Down/PgUp/PgDn: scroll, Esc: quit",
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(footer, chunks[3]);
+}
+
+fn results_title(console: &SqlConsole) -> String {
+    if console.query.is_empty() {
+        return "Results".to_string();
+    }
+    if console.error.is_some() {
+        return format!("Results of {}", console.query);
+    }
+    let shown = console.rows.len();
+    match console.rows_hidden {
+        0 => format!("{} row(s) for {}", shown, console.query),
+        hidden => format!(
+            "First {} of {} rows for {}",
+            SQL_CONSOLE_MAX_ROWS,
+            shown + hidden,
+            console.query
+        ),
+    }
+}
@@ -0,0 +1,109 @@
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::models::retention::Rollup;
+use crate::views::format;
+
+/// Formats the final statement of a closed account as plain text: months
+/// summarized by retention, every transaction oldest first, totals, the fees
+/// among those transactions, and the transfer that emptied the account.
+/// `rollups` and `transactions` are newest first, as the models return them.
+pub fn final_statement(
+    username: &str,
+    nominee: &str,
+    closed_at: NaiveDateTime,
+    rollups: &[Rollup],
+    transactions: &[HashMap<String, String>],
+    closing_amount: f64,
+) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Final statement for {}", username);
+    let _ = writeln!(text, "Closed {}", closed_at.format("%Y-%m-%d %H:%M:%S"));
+    write_activity(&mut text, rollups, transactions);
+    let _ = writeln!(
+        text,
+        "Closing transfer:  {} to {}",
+        format::money(closing_amount),
+        nominee
+    );
+    let _ = writeln!(text, "Closing balance:   {}", format::money(0.0));
+    text
+}
+
+/// Formats a statement of an open account as plain text, laid out like the
+/// final statement but ending with the balance at `generated_at`.
+pub fn statement(
+    username: &str,
+    generated_at: NaiveDateTime,
+    rollups: &[Rollup],
+    transactions: &[HashMap<String, String>],
+    balance: f64,
+) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "Statement for {}", username);
+    let _ = writeln!(text, "Generated {}", generated_at.format("%Y-%m-%d %H:%M:%S"));
+    write_activity(&mut text, rollups, transactions);
+    let _ = writeln!(text, "Balance:           {}", format::money(balance));
+    text
+}
+
+/// Writes the summarized months, the transactions and the start of the
+/// summary: money in, money out and fees.
+fn write_activity(text: &mut String, rollups: &[Rollup], transactions: &[HashMap<String, String>]) {
+    // Folded from 0.0 because summing no floats gives -0.0, shown as $-0.00
+    let mut money_in = rollups.iter().fold(0.0, |sum, rollup| sum + rollup.money_in);
+    let mut money_out = rollups.iter().fold(0.0, |sum, rollup| sum + rollup.money_out);
+    let mut fees = 0.0;
+    if !rollups.is_empty() {
+        let _ = writeln!(text, "\nSummarized months");
+        for rollup in rollups.iter().rev() {
+            let _ = writeln!(
+                text,
+                "{}  {} transaction(s)  in {}  out {}  closing balance {}",
+                rollup.month,
+                rollup.transactions,
+                format::money(rollup.money_in),
+                format::money(rollup.money_out),
+                format::money(rollup.closing_balance)
+            );
+        }
+    }
+
+    let _ = writeln!(text, "\nTransactions");
+    for transaction in transactions.iter().rev() {
+        let field = |key: &str| transaction.get(key).map(String::as_str).unwrap_or_default();
+        let number = |key: &str| field(key).parse::<f64>().unwrap_or(0.0);
+        let change = number("new_balance") - number("previous_balance");
+        if field("type") == "fee" {
+            fees += number("amount");
+        }
+        if change >= 0.0 {
+            money_in += change;
+        } else {
+            money_out -= change;
+        }
+        let counterparty = match (field("type"), field("recipient"), field("sender")) {
+            ("transfer_out" | "token_out", to, _) if !to.is_empty() => format!(" to {}", to),
+            ("transfer_in" | "token_in", _, from) if !from.is_empty() => format!(" from {}", from),
+            _ => String::new(),
+        };
+        let _ = writeln!(
+            text,
+            "{}  {:<12} {:>14}  balance {}{}",
+            field("timestamp"),
+            field("type"),
+            format!("{}{}", if change < 0.0 { "-" } else { "+" }, format::money(change.abs())),
+            format::money(number("new_balance")),
+            counterparty
+        );
+    }
+    if transactions.is_empty() {
+        let _ = writeln!(text, "None");
+    }
+
+    let _ = writeln!(text, "\nSummary");
+    let _ = writeln!(text, "Money in:          {}", format::money(money_in));
+    let _ = writeln!(text, "Money out:         {}", format::money(money_out));
+    let _ = writeln!(text, "Fees charged:      {}", format::money(fees));
+}
@@ -1,45 +1,170 @@
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols,
     text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{
+        Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, ListState, Paragraph,
+        Wrap,
+    },
     Frame,
 };
 
-use crate::controllers::app_controller::{AppController, AppState};
+use crate::controllers::app_controller::{AnnotationField, AppController};
+use crate::controllers::screens::{amount::QUICK_WITHDRAW_AMOUNTS, Screen};
+use crate::models::activity::{Activity, ActivityKind};
+use crate::models::history_filter::HistoryFilter;
+use crate::models::invoice::LineItem;
+use crate::models::leaderboard::{LeaderboardRanking, BALANCE_BUCKETS};
+use crate::models::payment_request::RequestStatus;
+use crate::models::round_up::RoundUpTarget;
+use crate::models::transaction::{HistorySort, BASE_CURRENCY};
+use crate::models::user_key::SignatureStatus;
+use crate::controllers::wallet_service::WalletService;
+use crate::views::format::{self, DisplayCurrency};
+use crate::views::heatmap::Heatmap;
+use crate::views::identicon::{identicon, labelled};
+
+/// Smallest terminal size, in columns and rows, that the screens are laid
+/// out for.
+pub const MIN_TERMINAL_SIZE: (u16, u16) = (60, 20);
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &AppController) {
+    let (min_width, min_height) = MIN_TERMINAL_SIZE;
+    if f.size().width < min_width || f.size().height < min_height {
+        draw_too_small(f);
+        return;
+    }
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(2)
         .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
         .split(f.size());
 
-    let title = Paragraph::new("E-Wallet Demo")
+    let title = if app.view_only && app.get_current_user().is_some() {
+        "E-Wallet Demo (view only)"
+    } else {
+        "E-Wallet Demo"
+    };
+    let title = Paragraph::new(title)
         .style(Style::default().fg(Color::Cyan))
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::ALL));
     f.render_widget(title, chunks[0]);
 
-    match app.current_state {
-        AppState::MainMenu => draw_main_menu(f, chunks[1]),
-        AppState::Login => draw_login(f, app, chunks[1]),
-        AppState::CreateAccount => draw_create_account(f, app, chunks[1]),
-        AppState::LoggedIn => draw_logged_in(f, app, chunks[1]),
-        AppState::Deposit => draw_deposit(f, app, chunks[1]),
-        AppState::Withdraw => draw_withdraw(f, app, chunks[1]),
-        AppState::Transfer => draw_transfer(f, app, chunks[1]),
-        AppState::ViewTransactions => draw_transactions(f, app, chunks[1]),
+    match app.current_screen {
+        Screen::MainMenu => draw_main_menu(f, chunks[1]),
+        Screen::Login => draw_login(f, app, chunks[1]),
+        Screen::EnterPassword => draw_password(
+            f,
+            app,
+            chunks[1],
+            "Enter Password",
+            "Enter: log in, Esc: back",
+        ),
+        Screen::RecoverAccount => draw_recover_account(f, app, chunks[1]),
+        Screen::CreateAccount => draw_create_account(f, app, chunks[1]),
+        Screen::Dashboard => draw_dashboard(f, app, chunks[1]),
+        Screen::Deposit => draw_deposit(f, app, chunks[1]),
+        Screen::QuickWithdraw => draw_quick_withdraw(f, app, chunks[1]),
+        Screen::Withdraw => draw_withdraw(f, app, chunks[1]),
+        Screen::CashBreakdown => draw_cash_breakdown(f, app, chunks[1]),
+        Screen::Transfer => draw_transfer(f, app, chunks[1]),
+        Screen::ConfirmDuplicateTransfer => draw_confirm_duplicate_transfer(f, app, chunks[1]),
+        Screen::BatchTransfer => draw_batch_transfer(f, app, chunks[1]),
+        Screen::PendingTransfers => draw_pending_transfers(f, app, chunks[1]),
+        Screen::ViewTransactions => draw_transactions(f, app, chunks[1]),
+        Screen::Rollups => draw_rollups(f, app, chunks[1]),
+        Screen::Heatmap => draw_heatmap(f, app, chunks[1]),
+        Screen::DayActivity => draw_day_activity(f, app, chunks[1]),
+        Screen::TransactionDetail => draw_transaction_detail(f, app, chunks[1]),
+        Screen::JumpToDate => draw_jump_to_date(f, app, chunks[1]),
+        Screen::FilterHistory => draw_filter_history(f, app, chunks[1]),
+        Screen::EditAnnotation => draw_edit_annotation(f, app, chunks[1]),
+        Screen::Settings => draw_settings(f, app, chunks[1]),
+        Screen::EditBalanceAlert => draw_edit_balance_alert(f, app, chunks[1]),
+        Screen::EditRoundUp => draw_edit_round_up(f, app, chunks[1]),
+        Screen::CategoryRules => draw_category_rules(f, app, chunks[1]),
+        Screen::NewCategoryRule => draw_new_category_rule(f, app, chunks[1]),
+        Screen::Locked => draw_locked(f, app, chunks[1]),
+        Screen::SetPassword | Screen::RotatePassword => draw_set_password(f, app, chunks[1]),
+        Screen::ChangePassword => draw_change_password(f, app, chunks[1]),
+        Screen::RecoveryCodes => draw_recovery_codes(f, app, chunks[1]),
+        Screen::AccountSwitcher => draw_account_switcher(f, app, chunks[1]),
+        Screen::SetPin => draw_pin(f, app, chunks[1], "Choose a PIN for This Account"),
+        Screen::EnterPin => draw_pin(f, app, chunks[1], "Enter PIN"),
+        Screen::PaymentRequests => draw_payment_requests(f, app, chunks[1]),
+        Screen::NewPaymentRequest => draw_new_payment_request(f, app, chunks[1]),
+        Screen::SplitBill => draw_split_bill(f, app, chunks[1]),
+        Screen::NewCheckout => draw_new_checkout(f, app, chunks[1]),
+        Screen::CheckoutCode => draw_checkout_code(f, app, chunks[1]),
+        Screen::PayCheckout => draw_pay_checkout(f, app, chunks[1]),
+        Screen::ReviewCheckout => draw_review_checkout(f, app, chunks[1]),
+        Screen::OfflineTokens => draw_offline_tokens(f, app, chunks[1]),
+        Screen::IssueOfflineToken => draw_issue_offline_token(f, app, chunks[1]),
+        Screen::RedeemOfflineToken => draw_redeem_offline_token(f, app, chunks[1]),
+        Screen::Vault => draw_vault(f, app, chunks[1]),
+        Screen::VaultDeposit => draw_vault_deposit(f, app, chunks[1]),
+        Screen::VaultWithdraw => draw_vault_withdraw(f, app, chunks[1]),
+        Screen::SweepRules => draw_sweep_rules(f, app, chunks[1]),
+        Screen::NewSweepRule => draw_new_sweep_rule(f, app, chunks[1]),
+        Screen::Family => draw_family(f, app, chunks[1]),
+        Screen::NewChild => draw_new_child(f, app, chunks[1]),
+        Screen::EditAllowance => draw_edit_allowance(f, app, chunks[1]),
+        Screen::EditSpendingCap => draw_edit_spending_cap(f, app, chunks[1]),
+        Screen::ChildActivity => draw_child_activity(f, app, chunks[1]),
+        Screen::Delegates => draw_delegates(f, app, chunks[1]),
+        Screen::JointMembers => draw_joint_members(f, app, chunks[1]),
+        Screen::NewJointMember => draw_new_joint_member(f, app, chunks[1]),
+        Screen::Approvals => draw_approvals(f, app, chunks[1]),
+        Screen::NewDelegate => draw_new_delegate(f, app, chunks[1]),
+        Screen::SharedAccounts => draw_shared_accounts(f, app, chunks[1]),
+        Screen::SharedHistory => draw_shared_history(f, app, chunks[1]),
+        Screen::Inbox => draw_inbox(f, app, chunks[1]),
+        Screen::Timeline => draw_timeline(f, app, chunks[1]),
+        Screen::Leaderboard => draw_leaderboard(f, app, chunks[1]),
+        Screen::NetWorth => draw_net_worth(f, app, chunks[1]),
+        Screen::CardTopUp => draw_card_top_up(f, app, chunks[1]),
+        Screen::BankWithdrawal => draw_bank_withdrawal(f, app, chunks[1]),
+        Screen::ChequeDeposit => draw_cheque_deposit(f, app, chunks[1]),
     }
 
     draw_messages(f, app);
 }
 
+/// Asks for a bigger terminal in place of a screen that would not fit.
+fn draw_too_small<B: Backend>(f: &mut Frame<B>) {
+    let size = f.size();
+    let (min_width, min_height) = MIN_TERMINAL_SIZE;
+    let lines = vec![
+        Spans::from(Span::styled(
+            "Please enlarge your terminal",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(format!("to at least {}x{}", min_width, min_height)),
+        Spans::from(Span::styled(
+            format!("(now {}x{})", size.width, size.height),
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+    let top = size.height.saturating_sub(lines.len() as u16) / 2;
+    let area = Rect::new(size.x, size.y + top, size.width, size.height - top);
+    let notice = Paragraph::new(lines)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(notice, area);
+}
+
 fn draw_main_menu<B: Backend>(f: &mut Frame<B>, area: Rect) {
     let items = vec![
         ListItem::new("1. Login"),
         ListItem::new("2. Create Account"),
+        ListItem::new("3. Switch Account"),
+        ListItem::new("4. Recover Account"),
         ListItem::new("q. Quit"),
     ];
 
@@ -53,30 +178,188 @@ fn draw_main_menu<B: Backend>(f: &mut Frame<B>, area: Rect) {
 }
 
 fn draw_login<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let input = Paragraph::new(app.input.as_ref())
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Enter Username"));
-    f.render_widget(input, area);
+    let (title, mode) = if app.view_only {
+        ("Enter Username (view only)", "full login")
+    } else {
+        ("Enter Username", "view only")
+    };
+    draw_input(
+        f,
+        app,
+        area,
+        title,
+        format!("Type your username and press Enter, Tab for {}, Esc to go back", mode),
+    );
 }
 
 fn draw_create_account<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let input = Paragraph::new(app.input.as_ref())
+    draw_input(
+        f,
+        app,
+        area,
+        "Enter New Username",
+        "Choose a unique username and press Enter, Esc to go back".to_string(),
+    );
+}
+
+/// Draws the input box with a hint line underneath and returns the area left below it.
+fn draw_input<B: Backend>(
+    f: &mut Frame<B>,
+    app: &AppController,
+    area: Rect,
+    title: &str,
+    hint: String,
+) -> Rect {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let width = chunks[0].width.saturating_sub(2) as usize;
+    let input = Paragraph::new(format::input_tail(&app.input, width))
         .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Enter New Username"));
-    f.render_widget(input, area);
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(input, chunks[0]);
+
+    let hint = Paragraph::new(Span::styled(hint, Style::default().fg(Color::DarkGray)));
+    f.render_widget(hint, chunks[1]);
+
+    chunks[2]
 }
 
-fn draw_logged_in<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let balance = app.get_balance().unwrap_or(0.0);
-    let account_name = app.get_current_user().unwrap_or("Unknown");
+/// Formats an amount for display in the user's display currency,
+/// respecting the privacy toggle.
+fn money(app: &AppController, amount: f64) -> String {
+    app.display_currency().money_or_masked(amount, app.hide_amounts)
+}
+
+/// Reads a numeric field of a transaction map.
+fn amount_field(t: &HashMap<String, String>, key: &str) -> f64 {
+    t.get(key).and_then(|v| v.parse().ok()).unwrap_or(0.0)
+}
+
+fn draw_dashboard<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let area = match app.get_overdraft_status().unwrap_or(None) {
+        Some(status) => draw_overdraft_reminder(f, app, status, area),
+        None => area,
+    };
+    let area = match app.get_password_rotation_status().unwrap_or(None) {
+        Some((due, days_left)) => draw_password_reminder(f, due, days_left, area),
+        None => area,
+    };
+    let foreign_totals = app.get_monthly_foreign_totals().unwrap_or_default();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(5 + foreign_totals.len() as u16),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(rows[0]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(65), Constraint::Percentage(35)].as_ref())
+        .split(rows[1]);
+
+    draw_overview(f, app, &app.display_currency(), app.hide_amounts, top[0]);
+
+    let (money_in, money_out) = app.get_monthly_totals().unwrap_or((0.0, 0.0));
+    let mut monthly_lines = vec![
+        Spans::from(Span::styled(
+            format!("In:  {}", money(app, money_in)),
+            Style::default().fg(Color::Green),
+        )),
+        Spans::from(Span::styled(
+            format!("Out: {}", money(app, money_out)),
+            Style::default().fg(Color::Red),
+        )),
+        Spans::from(format!("Net: {}", money(app, money_in - money_out))),
+    ];
+    // Totals above are converted; also show what moved in each foreign currency
+    for (currency, native_in, native_out) in &foreign_totals {
+        let native = |amount: f64| {
+            if app.hide_amounts {
+                format::MASKED_AMOUNT.to_string()
+            } else {
+                format::money_in(amount, currency)
+            }
+        };
+        monthly_lines.push(Spans::from(Span::styled(
+            format!("{}: in {}, out {}", currency, native(*native_in), native(*native_out)),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let monthly = Paragraph::new(monthly_lines)
+        .block(Block::default().title("This Month").borders(Borders::ALL));
+    f.render_widget(monthly, top[1]);
+
+    let recent = app.get_recent_transactions(5).unwrap_or_default();
+    let recent_items: Vec<ListItem> = if recent.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No transactions yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        recent
+            .iter()
+            .map(|t| {
+                let timestamp = t.get("timestamp").cloned().unwrap_or_default();
+                ListItem::new(vec![
+                    Spans::from(describe_transaction(app, t)),
+                    Spans::from(Span::styled(
+                        format!("  {}", timestamp),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ])
+            })
+            .collect()
+    };
+    let recent_list = List::new(recent_items)
+        .block(Block::default().title("Last 5 Transactions").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(recent_list, bottom[0]);
+
     let items = vec![
-        ListItem::new(format!("Account: {}", account_name)),
-        ListItem::new(format!("Current Balance: ${:.2}", balance)),
         ListItem::new("1. Deposit"),
         ListItem::new("2. Withdraw"),
         ListItem::new("3. Transfer"),
         ListItem::new("4. View Transactions"),
         ListItem::new("5. Logout"),
+        ListItem::new("6. Settings"),
+        ListItem::new("7. Payment Requests"),
+        ListItem::new("8. Pending Transfers"),
+        ListItem::new("9. Offline Tokens"),
+        ListItem::new("0. Vault"),
+        ListItem::new("m. Transfer to Several People"),
+        ListItem::new("w. Net Worth"),
+        ListItem::new("f. Family"),
+        ListItem::new("a. Shared With Me"),
+        ListItem::new(match app.approvals_waiting().unwrap_or(0) {
+            0 => "j. Joint Approvals".to_string(),
+            waiting => format!("j. Joint Approvals ({} waiting)", waiting),
+        }),
+        ListItem::new(match app.get_unread_count().unwrap_or(0) {
+            0 => "i. Inbox".to_string(),
+            unread => format!("i. Inbox ({} unread)", unread),
+        }),
+        ListItem::new("e. Activity Timeline"),
+        ListItem::new("t. Top Up by Card"),
+        ListItem::new("b. Withdraw to Bank"),
+        ListItem::new("c. Deposit a Cheque"),
+        ListItem::new("l. Leaderboard"),
+        ListItem::new("s. Switch Account"),
+        ListItem::new("u. Undo / r. Redo"),
+        ListItem::new(if app.hide_amounts {
+            "h. Show Amounts"
+        } else {
+            "h. Hide Amounts"
+        }),
     ];
 
     let menu = List::new(items)
@@ -85,59 +368,412 @@ fn draw_logged_in<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect)
         .highlight_style(Style::default().add_modifier(Modifier::BOLD))
         .highlight_symbol("> ");
 
-    f.render_widget(menu, area);
+    f.render_widget(menu, bottom[1]);
+}
+
+/// Draws a one-line reminder to repay an overdraft above the dashboard and
+/// returns the area left below it.
+/// Warns, during the rotation grace period, that the password must be changed.
+fn draw_password_reminder<B: Backend>(
+    f: &mut Frame<B>,
+    due: NaiveDateTime,
+    days_left: i64,
+    area: Rect,
+) -> Rect {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+    let reminder = Paragraph::new(Span::styled(
+        format!(
+            "Password overdue since {}: change it within {} day(s) (p in Settings).",
+            due.format("%Y-%m-%d"),
+            days_left.max(0)
+        ),
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+    f.render_widget(reminder, chunks[0]);
+    chunks[1]
+}
+
+fn draw_overdraft_reminder<B: Backend>(
+    f: &mut Frame<B>,
+    app: &AppController,
+    (owed, days_left): (f64, i64),
+    area: Rect,
+) -> Rect {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+    let (text, color) = if days_left > 0 {
+        (
+            format!(
+                "Overdrawn by {}. Repay within {} day(s) to keep making payments.",
+                money(app, owed),
+                days_left
+            ),
+            Color::Yellow,
+        )
+    } else {
+        (
+            format!("Overdrawn by {}. Payments are blocked until you repay.", money(app, owed)),
+            Color::Red,
+        )
+    };
+    let reminder = Paragraph::new(Span::styled(
+        text,
+        Style::default().fg(color).add_modifier(Modifier::BOLD),
+    ));
+    f.render_widget(reminder, chunks[0]);
+    chunks[1]
+}
+
+/// Draws the account name and balance. Only needs a `WalletService`, so it can be
+/// driven by a mock wallet as well as the SQLite-backed controller.
+fn draw_overview<B: Backend, W: WalletService>(
+    f: &mut Frame<B>,
+    wallet: &W,
+    display: &DisplayCurrency,
+    hide_amounts: bool,
+    area: Rect,
+) {
+    let balance = wallet.balance().unwrap_or(0.0);
+    let available = wallet.available_balance().unwrap_or(balance);
+    let mut account_line = vec![Span::raw("Account: ")];
+    match wallet.current_user() {
+        Some(username) => account_line.extend(labelled(username)),
+        None => account_line.push(Span::raw("Unknown")),
+    }
+    let account = Paragraph::new(vec![
+        Spans::from(account_line),
+        Spans::from(format!(
+            "Ledger Balance:    {}",
+            display.money_or_masked(balance, hide_amounts)
+        )),
+        Spans::from(Span::styled(
+            format!(
+                "Available Balance: {}",
+                display.money_or_masked(available, hide_amounts)
+            ),
+            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        )),
+    ])
+    .block(Block::default().title("Overview").borders(Borders::ALL));
+    f.render_widget(account, area);
 }
 
 fn draw_deposit<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let input = Paragraph::new(app.input.as_ref())
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Enter Deposit Amount"));
-    f.render_widget(input, area);
+    draw_input(
+        f,
+        app,
+        area,
+        "Enter Deposit Amount",
+        "Enter amount, Up/Down for history, Esc to cancel".to_string(),
+    );
+}
+
+fn draw_quick_withdraw<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let mut items: Vec<ListItem> = QUICK_WITHDRAW_AMOUNTS
+        .iter()
+        .enumerate()
+        .map(|(i, amount)| ListItem::new(format!("{}. {}", i + 1, format::money(*amount))))
+        .collect();
+    items.push(ListItem::new(format!("{}. Other amount", QUICK_WITHDRAW_AMOUNTS.len() + 1)));
+    items.push(ListItem::new(""));
+    items.push(ListItem::new(Span::styled(
+        format!(
+            "Press a number, Esc to cancel, {} available",
+            money(app, app.get_balance().unwrap_or(0.0))
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let list = List::new(items)
+        .block(Block::default().title("Withdraw").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
 }
 
 fn draw_withdraw<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let input = Paragraph::new(app.input.as_ref())
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title("Enter Withdrawal Amount"));
-    f.render_widget(input, area);
+    let balance = app.get_balance().unwrap_or(0.0);
+    draw_input(
+        f,
+        app,
+        area,
+        "Enter Withdrawal Amount",
+        format!(
+            "Enter amount, Up/Down for history, Esc to cancel, {} available",
+            money(app, balance)
+        ),
+    );
+}
+
+fn draw_cash_breakdown<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let breakdown = match app.get_cash_breakdown() {
+        Some(breakdown) => breakdown,
+        None => return,
+    };
+    let mut lines = Vec::new();
+    if app.hide_amounts {
+        lines.push(Spans::from("Amounts are hidden. Press h to show them."));
+    } else {
+        for (note, count) in &breakdown.notes {
+            lines.push(Spans::from(format!(
+                "{:>4} x {:>10}  = {}",
+                count,
+                format::money(*note),
+                format::money(*note * *count as f64)
+            )));
+        }
+        lines.push(Spans::from(""));
+        lines.push(Spans::from(Span::styled(
+            format!("{} note(s)", breakdown.note_count()),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        if breakdown.remainder > 0.0 {
+            lines.push(Spans::from(Span::styled(
+                format!("{} in coins, too little for a note", format::money(breakdown.remainder)),
+                Style::default().fg(Color::Yellow),
+            )));
+        }
+    }
+    let title = format!(
+        "Cash Out: {} (Enter: done, h: hide amounts)",
+        money(app, app.last_cash_out.unwrap_or(0.0))
+    );
+    let paragraph =
+        Paragraph::new(lines).block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(paragraph, area);
 }
 
 fn draw_transfer<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let title = if app.transfer_recipient.is_none() {
-        "Enter Recipient Username"
+    let recipient = app.wizard_values.first();
+    let amount = app.wizard_values.get(1).and_then(|amount| amount.parse::<f64>().ok());
+    let (title, hint) = match (recipient, amount) {
+        (None, _) => (
+            "Enter Recipient Username",
+            "Type a username, Up/Down to pick a match or recall history, \
+            1-5 for a recent recipient, Esc to cancel"
+                .to_string(),
+        ),
+        (Some(recipient), Some(amount)) => (
+            "Add a Note (optional)",
+            format!(
+                "Sending {} to {}. {} will see the note. Enter to send, Esc to go back",
+                money(app, amount),
+                recipient,
+                recipient
+            ),
+        ),
+        (Some(recipient), None) => (
+            "Enter Transfer Amount",
+            format!(
+                "Sending to {}. Enter amount, Up/Down for history, Esc to go back, {} available",
+                recipient,
+                money(app, app.get_balance().unwrap_or(0.0))
+            ),
+        ),
+    };
+    let below = draw_input(f, app, area, title, hint);
+
+    if recipient.is_none() && !app.recipient_suggestions.is_empty() {
+        let items: Vec<ListItem> = app
+            .recipient_suggestions
+            .iter()
+            .map(|username| ListItem::new(Spans::from(labelled(username))))
+            .collect();
+        let suggestions = List::new(items)
+            .block(Block::default().title("Matching Users").borders(Borders::ALL))
+            .style(Style::default().fg(Color::White))
+            .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        state.select(Some(app.selected_suggestion));
+        let height = (app.recipient_suggestions.len() as u16 + 2).min(below.height);
+        let dropdown = Rect::new(below.x, below.y, below.width, height);
+        f.render_stateful_widget(suggestions, dropdown, &mut state);
+    } else if recipient.is_none()
+        && app.input.is_empty()
+        && !app.recent_recipients.is_empty()
+    {
+        let items: Vec<ListItem> = app
+            .recent_recipients
+            .iter()
+            .enumerate()
+            .map(|(i, username)| {
+                let mut spans = vec![Span::raw(format!("{}. ", i + 1))];
+                spans.extend(labelled(username));
+                ListItem::new(Spans::from(spans))
+            })
+            .collect();
+        let recent = List::new(items)
+            .block(Block::default().title("Recent Recipients").borders(Borders::ALL))
+            .style(Style::default().fg(Color::White));
+        let height = (app.recent_recipients.len() as u16 + 2).min(below.height);
+        let quick_picks = Rect::new(below.x, below.y, below.width, height);
+        f.render_widget(recent, quick_picks);
+    }
+}
+
+fn draw_batch_transfer<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let rest = draw_input(
+        f,
+        app,
+        area,
+        "Recipient and Amount (username amount)",
+        "Enter: add, Enter on empty line: send all, Del: remove last, Esc: cancel".to_string(),
+    );
+    let mut rows: Vec<ListItem> = app
+        .batch_transfers
+        .iter()
+        .map(|(recipient, amount)| {
+            ListItem::new(format!("{:<24} {:>12}", recipient, money(app, *amount)))
+        })
+        .collect();
+    let total: f64 = app.batch_transfers.iter().map(|(_, amount)| amount).sum();
+    rows.push(ListItem::new(Span::styled(
+        format!("{:>37}", format!("Total: {}", money(app, total))),
+        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+    )));
+    let list = List::new(rows)
+        .block(Block::default().title("Transfers").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, rest);
+}
+
+fn draw_confirm_duplicate_transfer<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let (recipient, amount, _) = app.pending_transfer.clone().unwrap_or_default();
+    let lines = vec![
+        Spans::from(Span::styled(
+            "Possible duplicate transfer",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(""),
+        Spans::from(format!(
+            "You already sent {} to {} in the last 2 minutes.",
+            money(app, amount),
+            recipient
+        )),
+        Spans::from("Send it again?"),
+        Spans::from(""),
+        Spans::from(Span::styled(
+            "y: send anyway, n/Esc: cancel",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+
+    let warning = Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().title("Confirm Transfer").borders(Borders::ALL));
+    f.render_widget(warning, area);
+}
+
+fn draw_pending_transfers<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.pending_transfers.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No transfers waiting for confirmation or approval",
+            Style::default().fg(Color::DarkGray),
+        ))]
     } else {
-        "Enter Transfer Amount"
+        app.pending_transfers
+            .iter()
+            .map(|pending| {
+                let seconds_left = app.cooling_seconds_left(pending);
+                let status = if pending.needs_approval {
+                    Span::styled(
+                        format!(
+                            "  waiting for a co-owner's approval until {}",
+                            app.approval_expires_at(pending).format("%Y-%m-%d %H:%M")
+                        ),
+                        Style::default().fg(Color::Yellow),
+                    )
+                } else if seconds_left > 0 {
+                    Span::styled(
+                        format!("  ready in {}s", seconds_left),
+                        Style::default().fg(Color::DarkGray),
+                    )
+                } else {
+                    Span::styled("  ready to confirm", Style::default().fg(Color::Green))
+                };
+                ListItem::new(vec![
+                    Spans::from(format!(
+                        "#{} Transfer: {} to {}",
+                        pending.id,
+                        money(app, pending.amount),
+                        pending.recipient
+                    )),
+                    Spans::from(status),
+                ])
+            })
+            .collect()
     };
-    let input = Paragraph::new(app.input.as_ref())
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL).title(title));
-    f.render_widget(input, area);
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Pending Transfers (Enter: confirm, x: cancel, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.pending_transfers.is_empty() {
+        state.select(Some(app.selected_pending_transfer));
+    }
+    f.render_stateful_widget(list, area, &mut state);
 }
 
 fn draw_transactions<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
-    let transactions = app.get_transactions().unwrap_or_default();
+    // Each row takes one line when compact and three otherwise; only the
+    // rows that fit are fetched and built
+    let row_height = if app.compact_history { 1 } else { 3 };
+    let visible_rows = (area.height.saturating_sub(2) / row_height).max(1) as usize;
+    let start = app.history_window(visible_rows);
+    let transactions = app.get_transaction_page(start, visible_rows).unwrap_or_default();
     let items: Vec<ListItem> = transactions
         .iter()
         .map(|t| {
-            let amount = t.get("amount").unwrap_or(&String::from("0")).clone();
-            let transaction_type = t.get("type").unwrap_or(&String::from("Unknown")).clone();
-            let recipient = t.get("recipient").unwrap_or(&String::from("")).clone();
-            let sender = t.get("sender").unwrap_or(&String::from("")).clone();
-            let previous_balance = t.get("previous_balance").unwrap_or(&String::from("0")).clone();
-            let new_balance = t.get("new_balance").unwrap_or(&String::from("0")).clone();
+            let previous_balance = money(app, amount_field(t, "previous_balance"));
+            let new_balance = money(app, amount_field(t, "new_balance"));
             let timestamp = t.get("timestamp").unwrap_or(&String::from("")).clone();
-            
-            let description = match transaction_type.as_str() {
-                "deposit" => format!("Deposit: ${}", amount),
-                "withdraw" => format!("Withdrawal: ${}", amount),
-                "transfer_out" => format!("Transfer: ${} to {}", amount, recipient),
-                "transfer_in" => format!("Received: ${} from {}", amount, sender),
-                _ => format!("Unknown transaction: ${}", amount),
+            let category = t.get("category").cloned().unwrap_or_default();
+
+            let mut description = match counterparty(t) {
+                Some(username) => {
+                    let mut spans = identicon(username);
+                    spans.push(Span::raw(" "));
+                    spans
+                }
+                None => Vec::new(),
             };
-            
+            if t.get("starred").map(String::as_str) == Some("true") {
+                description.push(Span::styled("★ ", Style::default().fg(Color::Yellow)));
+            }
+            description.push(Span::raw(describe_transaction(app, t)));
+            if !category.is_empty() {
+                description.push(Span::styled(
+                    format!(" [{}]", category),
+                    Style::default().fg(Color::Magenta),
+                ));
+            }
+
+            if app.compact_history {
+                description.push(Span::styled(
+                    format!("  {}  {}", new_balance, timestamp),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                return ListItem::new(Spans::from(description));
+            }
             ListItem::new(vec![
                 Spans::from(description),
-                Spans::from(format!("  Previous Balance: ${} | New Balance: ${}", previous_balance, new_balance)),
+                Spans::from(format!(
+                    "  Previous Balance: {} | New Balance: {}",
+                    previous_balance, new_balance
+                )),
                 Spans::from(Span::styled(
                     format!("  {}", timestamp),
                     Style::default().fg(Color::DarkGray),
@@ -146,11 +782,1935 @@ fn draw_transactions<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Re
         })
         .collect();
 
+    let density = if app.compact_history { "detailed" } else { "compact" };
+    let heading = if !app.history_filter.is_empty() {
+        format!("Transactions matching '{}'", app.history_filter.text)
+    } else if app.starred_only {
+        "Starred Transactions".to_string()
+    } else if app.history_sort == HistorySort::NewestFirst {
+        "Recent Transactions".to_string()
+    } else {
+        "Transactions".to_string()
+    };
+    let filter = if app.starred_only { "f: show all" } else { "f: starred only, o: older months" };
+    let title = format!(
+        "{}, {} (Enter: details, s: star, {}, /: filter, t: sort, g: date, c: calendar, v: {}, \
+        Esc: back)",
+        heading,
+        app.history_sort.describe(),
+        filter,
+        density
+    );
     let transactions_list = List::new(items)
-        .block(Block::default().title("Recent Transactions").borders(Borders::ALL))
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !transactions.is_empty() {
+        state.select(Some(app.selected_transaction - start));
+    }
+    f.render_stateful_widget(transactions_list, area, &mut state);
+}
+
+fn draw_rollups<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let rollups = app.get_rollups().unwrap_or_default();
+    let items: Vec<ListItem> = if rollups.is_empty() {
+        vec![ListItem::new("No transactions have been summarized.")]
+    } else {
+        rollups
+            .iter()
+            .map(|rollup| {
+                ListItem::new(vec![
+                    Spans::from(format!(
+                        "{}: {} transaction(s), in {}, out {}",
+                        rollup.month,
+                        rollup.transactions,
+                        money(app, rollup.money_in),
+                        money(app, rollup.money_out)
+                    )),
+                    Spans::from(Span::styled(
+                        format!(
+                            "  Opening Balance: {} | Closing Balance: {}",
+                            money(app, rollup.opening_balance),
+                            money(app, rollup.closing_balance)
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Monthly Totals (history kept {}; h: hide amounts, Esc: back)",
+                    app.retention.describe()
+                ))
+                .borders(Borders::ALL),
+        )
         .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+fn draw_heatmap<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(9), Constraint::Min(0)].as_ref())
+        .split(area);
+    let days = app.get_heatmap().unwrap_or_default();
+    let block = Block::default()
+        .title(format!(
+            "Spending, {} (arrows: day, [ ]: month, Enter: transactions, Esc: back)",
+            app.heatmap_day.format("%B %Y")
+        ))
+        .borders(Borders::ALL);
+    let grid = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
+    f.render_widget(Heatmap::new(&days, app.heatmap_day), grid);
+
+    let net = days
+        .iter()
+        .find(|(day, _)| *day == app.heatmap_day)
+        .map_or(0.0, |(_, net)| *net);
+    let summary = if net > 0.005 {
+        format!("spent {} more than came in", money(app, net))
+    } else if net < -0.005 {
+        format!("{} more came in than was spent", money(app, -net))
+    } else {
+        "nothing spent".to_string()
+    };
+    let lines = vec![
+        Spans::from(format!("{}: {}", app.heatmap_day.format("%a %Y-%m-%d"), summary)),
+        Spans::from(vec![
+            Span::styled("░▒▓█", Style::default().fg(Color::Red)),
+            Span::raw(" spent   "),
+            Span::styled("░▒▓█", Style::default().fg(Color::Green)),
+            Span::raw(" came in, darker for more"),
+        ]),
+    ];
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+}
 
-    f.render_widget(transactions_list, area);
+fn draw_day_activity<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let transactions = app.get_heatmap_day_transactions().unwrap_or_default();
+    let mut items = Vec::new();
+    if transactions.is_empty() {
+        items.push(ListItem::new(Span::styled(
+            "No transactions on this day",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for t in &transactions {
+        items.push(ListItem::new(vec![
+            Spans::from(describe_transaction(app, t)),
+            Spans::from(Span::styled(
+                format!("  {}", t.get("timestamp").cloned().unwrap_or_default()),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ]));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "{} (h: hide amounts, Esc: back)",
+                    app.heatmap_day.format("%A, %B %-d %Y")
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+fn draw_transaction_detail<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let transaction = app.get_selected_transaction().ok().flatten().unwrap_or_default();
+    let field = |key: &str| transaction.get(key).cloned().unwrap_or_default();
+    let or_none = |value: String| if value.is_empty() { "-".to_string() } else { value };
+
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            describe_transaction(app, &transaction),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(""),
+        Spans::from(format!("ID:               {}", field("id"))),
+        Spans::from(format!("Date:             {}", field("timestamp"))),
+        Spans::from(match original_amount(app, &transaction) {
+            Some(original) => format!(
+                "Original Amount:  {} at {} {} each",
+                original,
+                field("fx_rate"),
+                BASE_CURRENCY
+            ),
+            None => format!("Currency:         {}", BASE_CURRENCY),
+        }),
+    ];
+    let display = app.display_currency();
+    if !display.is_base() {
+        lines.push(Spans::from(format!(
+            "Recorded Amount:  {}, shown in {} at {} each",
+            format::money_or_masked(amount_field(&transaction, "amount"), app.hide_amounts),
+            display.code,
+            format::money(display.rate)
+        )));
+    }
+    lines.extend([
+        Spans::from(format!(
+            "Previous Balance: {}",
+            money(app, amount_field(&transaction, "previous_balance"))
+        )),
+        Spans::from(format!(
+            "New Balance:      {}",
+            money(app, amount_field(&transaction, "new_balance"))
+        )),
+        Spans::from(format!("Category:         {}", or_none(field("category")))),
+        Spans::from(format!("Memo:             {}", or_none(field("memo")))),
+        Spans::from(format!(
+            "Starred:          {}",
+            if field("starred") == "true" { "yes" } else { "no" }
+        )),
+        Spans::from(format!("Note:             {}", or_none(field("note")))),
+    ]);
+    if matches!(field("type").as_str(), "transfer_out" | "transfer_in") {
+        let status = app
+            .get_signature_status(&transaction)
+            .unwrap_or(SignatureStatus::Unsigned);
+        let color = match status {
+            SignatureStatus::Verified => Color::Green,
+            SignatureStatus::Invalid => Color::Red,
+            SignatureStatus::Unsigned => Color::DarkGray,
+        };
+        lines.push(Spans::from(format!("Transfer ID:      {}", or_none(field("transfer_id")))));
+        lines.push(Spans::from(vec![
+            Span::raw("Signature:        "),
+            Span::styled(status.as_str(), Style::default().fg(color)),
+        ]));
+    }
+    if let Some(issue) = transaction.get("integrity_issue") {
+        lines.push(Spans::from(Span::styled(
+            format!("Integrity:        {}", issue),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    if let Ok(Some(payment_id)) = app.get_round_up_payment(&field("id")) {
+        lines.push(Spans::from(format!("Round-up of:      {}", payment_id)));
+    }
+    if let Ok(Some(rule_id)) = app.get_sweep_rule(&field("id")) {
+        lines.push(Spans::from(format!("Automatic:        sweep rule #{}", rule_id)));
+    }
+    lines.extend([
+        Spans::from(""),
+        Spans::from(Span::styled(
+            "c: edit category, m: edit memo, n: edit note, s: star/unstar, y/j: copy as TSV/JSON, u/r: undo/redo, h: hide amounts, Esc: back",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ]);
+
+    let detail = Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().title("Transaction Details").borders(Borders::ALL));
+    f.render_widget(detail, area);
+}
+
+fn draw_jump_to_date<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Jump to Date",
+        "Enter a date as YYYY-MM-DD to select the first transaction on or after it, Esc to cancel"
+            .to_string(),
+    );
+}
+
+fn draw_filter_history<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let rest = draw_input(
+        f,
+        app,
+        area,
+        "Filter History",
+        "Enter to apply (leave empty to show everything), Esc to cancel".to_string(),
+    );
+    let mut lines = match HistoryFilter::parse(&app.input) {
+        Err(err) => vec![Spans::from(Span::styled(
+            format!("Cannot filter by {}", err),
+            Style::default().fg(Color::Red),
+        ))],
+        Ok(_) => vec![Spans::from("")],
+    };
+    lines.push(Spans::from(""));
+    lines.extend(
+        [
+            "Every term must match, e.g. type:transfer amount>100 to:bob since:2024-01",
+            "  type:     transfer, deposit, withdraw, token, vault or fee",
+            "  amount:   amount>N, amount>=N, amount<N, amount<=N or amount=N",
+            "  to:       recipient or merchant    from: sender    category: category",
+            "  since:    from YYYY, YYYY-MM or YYYY-MM-DD    until: to the end of one",
+        ]
+        .iter()
+        .map(|line| Spans::from(Span::styled(*line, Style::default().fg(Color::DarkGray)))),
+    );
+    f.render_widget(Paragraph::new(lines), rest);
+}
+
+fn draw_edit_annotation<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let title = match app.editing_field {
+        AnnotationField::Category => "Edit Category",
+        AnnotationField::Memo => "Edit Memo",
+        AnnotationField::Note => "Edit Note",
+    };
+    draw_input(
+        f,
+        app,
+        area,
+        title,
+        "Enter to save (leave empty to clear), Esc to cancel".to_string(),
+    );
+}
+
+fn draw_settings<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let idle_lock = if app.idle_lock_minutes == 0 {
+        "Off".to_string()
+    } else {
+        format!("after {} minute(s)", app.idle_lock_minutes)
+    };
+    let balance_alert = match app.balance_alert {
+        Some(threshold) => format!("below {}", format::money(threshold)),
+        None => "Off".to_string(),
+    };
+    let display = app.display_currency();
+    let display_currency = if display.is_base() {
+        BASE_CURRENCY.to_string()
+    } else {
+        format!("{} ({} to the {})", display.code, format::money(display.rate), BASE_CURRENCY)
+    };
+    let round_up = match &app.round_up_to {
+        Some(RoundUpTarget::Vault) => "into your vault".to_string(),
+        Some(RoundUpTarget::Account(account)) => format!("change sent to {}", account),
+        None => "Off".to_string(),
+    };
+    let items = vec![
+        ListItem::new(format!("1. Idle lock: {}", idle_lock)),
+        ListItem::new(format!(
+            "2. Hide amounts: {}",
+            if app.hide_amounts { "On" } else { "Off" }
+        )),
+        ListItem::new("3. Export my data"),
+        ListItem::new(format!(
+            "4. Payment requests expire after: {} hour(s)",
+            app.request_ttl_hours
+        )),
+        ListItem::new(format!("5. Low balance alert: {}", balance_alert)),
+        ListItem::new(format!(
+            "6. Hold transfers over: {}",
+            format::money(app.large_transfer_cap)
+        )),
+        ListItem::new(format!(
+            "7. Sign transfers: {}",
+            if app.sign_transfers { "On" } else { "Off" }
+        )),
+        ListItem::new(format!(
+            "8. Vault withdrawals wait: {} hour(s)",
+            app.vault_delay_hours
+        )),
+        ListItem::new(format!("9. Round up payments: {}", round_up)),
+        ListItem::new(format!(
+            "0. Integrity mode: {}",
+            if app.integrity_mode { "On" } else { "Off" }
+        )),
+        ListItem::new(format!("r. Keep history: {}", app.retention.describe())),
+        ListItem::new(format!(
+            "m. Largest amount allowed: {}",
+            format::money(app.max_amount)
+        )),
+        ListItem::new(format!(
+            "o. Overdraft: {}",
+            if app.overdraft_limit > 0.0 {
+                format!("up to {}", format::money(app.overdraft_limit))
+            } else {
+                "Off".to_string()
+            }
+        )),
+        ListItem::new(format!("u. Show amounts in: {}", display_currency)),
+        ListItem::new(format!(
+            "n. Show the notes after a cash withdrawal: {}",
+            if app.cash_breakdown { "On" } else { "Off" }
+        )),
+        ListItem::new(format!(
+            "l. Show me on the leaderboard: {}",
+            if app.on_leaderboard { "On" } else { "Off" }
+        )),
+        ListItem::new(format!(
+            "k. Keep remembered session in the OS keychain: {}",
+            if app.keychain_sessions { "On" } else { "Off" }
+        )),
+        ListItem::new(if app.has_password {
+            "p. Change password"
+        } else {
+            "p. Password: none (set one, with recovery codes)"
+        }),
+        ListItem::new("c. Export this month's charts (SVG)"),
+        ListItem::new("a. Categorization rules"),
+        ListItem::new("d. Read-only access to my history"),
+        ListItem::new("j. Joint account co-owners"),
+        ListItem::new(""),
+        ListItem::new(Span::styled(
+            "Press a number, r, m, o, u, n, l, k, p, c, a, d or j to choose, Esc to go back",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+
+    let settings = List::new(items)
+        .block(Block::default().title("Settings").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(settings, area);
+}
+
+fn draw_edit_balance_alert<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Warn Me When My Balance Drops Below",
+        "Enter: save (leave empty to turn off), Esc: cancel".to_string(),
+    );
+}
+
+fn draw_category_rules<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.category_rules.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No rules yet. Press n to add one, e.g. recipient contains 'pizza' -> Food",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.category_rules
+            .iter()
+            .map(|rule| {
+                ListItem::new(format!(
+                    "{} contains '{}'  ->  {}",
+                    rule.field.as_str(),
+                    rule.pattern,
+                    rule.category
+                ))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(
+                    "Categorization Rules, first match wins (n: new, d: delete, \
+                    r: apply to uncategorized history, Esc: back)",
+                )
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.category_rules.is_empty() {
+        state.select(Some(app.selected_category_rule));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_new_category_rule<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let (title, hint) = match app.wizard_values.as_slice() {
+        [] => (
+            "Match On (recipient, sender or merchant)".to_string(),
+            "Enter: next, Esc: back".to_string(),
+        ),
+        [field] => (
+            format!("Text the {} Contains", field),
+            "Case is ignored. Enter: next, Esc: change field".to_string(),
+        ),
+        [field, pattern, ..] => (
+            format!("Category for {} Containing '{}'", field, pattern),
+            "Enter: save rule, Esc: change text".to_string(),
+        ),
+    };
+    draw_input(f, app, area, &title, hint);
+}
+
+fn draw_edit_round_up<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Round Up Payments Into (vault or a username)",
+        "Enter: save (leave empty to turn off), Esc: cancel".to_string(),
+    );
+}
+
+fn draw_payment_requests<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let username = app.get_current_user().unwrap_or_default();
+    let items: Vec<ListItem> = if app.payment_requests.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No payment requests yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.payment_requests
+            .iter()
+            .map(|r| {
+                let description = if r.requester == username {
+                    format!("You asked {} for {}", r.payer, money(app, r.amount))
+                } else {
+                    format!("{} asks you for {}", r.requester, money(app, r.amount))
+                };
+                let status_color = match r.status {
+                    RequestStatus::Pending => Color::Yellow,
+                    RequestStatus::Paid => Color::Green,
+                    RequestStatus::Declined | RequestStatus::Expired => Color::DarkGray,
+                };
+                let detail = if r.status == RequestStatus::Pending {
+                    format!("  expires {}", r.expires_at.format("%Y-%m-%d %H:%M"))
+                } else {
+                    format!("  requested {}", r.created_at.format("%Y-%m-%d %H:%M"))
+                };
+                ListItem::new(vec![
+                    Spans::from(vec![
+                        Span::raw(description),
+                        Span::styled(
+                            format!(" [{}]", r.status.as_str()),
+                            Style::default().fg(status_color),
+                        ),
+                    ]),
+                    Spans::from(Span::styled(detail, Style::default().fg(Color::DarkGray))),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(
+                    "Payment Requests (n: new, s: split a bill, p: pay, d: decline, \
+                    m: merchant checkout, c: pay a code, Esc: back)",
+                )
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.payment_requests.is_empty() {
+        state.select(Some(app.selected_request));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Describes a line of the activity timeline.
+fn describe_activity(app: &AppController, activity: &Activity) -> String {
+    let amount = money(app, activity.amount.unwrap_or(0.0));
+    let counterparty = activity.counterparty.as_deref().unwrap_or("");
+    let status = activity.status.map_or("", |status| status.as_str());
+    match (activity.kind, activity.detail.as_str()) {
+        (ActivityKind::Transaction, "deposit") => format!("Deposited {}", amount),
+        (ActivityKind::Transaction, "withdraw") => format!("Withdrew {}", amount),
+        (ActivityKind::Transaction, "transfer_out") => {
+            format!("Sent {} to {}", amount, counterparty)
+        }
+        (ActivityKind::Transaction, "transfer_in") => {
+            format!("Received {} from {}", amount, counterparty)
+        }
+        (ActivityKind::Transaction, "token_out") => {
+            format!("Issued an offline token of {}", amount)
+        }
+        (ActivityKind::Transaction, "token_in") => {
+            format!("Redeemed an offline token of {}", amount)
+        }
+        (ActivityKind::Transaction, "vault_in") => format!("Moved {} to the vault", amount),
+        (ActivityKind::Transaction, "vault_out") => format!("{} released from the vault", amount),
+        (ActivityKind::Transaction, "fee") => format!("Paid a fee of {}", amount),
+        (ActivityKind::Transaction, other) => format!("{} of {}", other, amount),
+        (ActivityKind::Request, "sent") => {
+            format!("Requested {} from {} ({})", amount, counterparty, status)
+        }
+        (ActivityKind::Request, _) => {
+            format!("{} requested {} ({})", counterparty, amount, status)
+        }
+        (_, detail) if activity.amount.is_some() => format!("{} {}", detail, amount),
+        (_, detail) => detail.to_string(),
+    }
+}
+
+fn draw_timeline<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.timeline.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nothing has happened yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.timeline
+            .iter()
+            .map(|activity| {
+                let color = match activity.kind {
+                    ActivityKind::Transaction => Color::Green,
+                    ActivityKind::Login => Color::Blue,
+                    ActivityKind::Limit => Color::Yellow,
+                    ActivityKind::Request => Color::Magenta,
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::styled(
+                        format!("{}  ", activity.at.format("%Y-%m-%d %H:%M")),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        format!("{:<12}", activity.kind.as_str()),
+                        Style::default().fg(color),
+                    ),
+                    Span::raw(describe_activity(app, activity)),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Activity Timeline, newest first (h: hide amounts, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.timeline.is_empty() {
+        state.select(Some(app.selected_activity));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_inbox<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.notifications.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No notifications",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.notifications
+            .iter()
+            .map(|n| {
+                let style = if n.read {
+                    Style::default().fg(Color::DarkGray)
+                } else {
+                    Style::default().add_modifier(Modifier::BOLD)
+                };
+                ListItem::new(vec![
+                    Spans::from(Span::styled(
+                        format!("{} {}", if n.read { " " } else { "•" }, n.message),
+                        style,
+                    )),
+                    Spans::from(Span::styled(
+                        format!("  {}", n.created_at.format("%Y-%m-%d %H:%M")),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let unread = app.notifications.iter().filter(|n| !n.read).count();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Inbox, {} unread (Enter: mark read, a: mark all read, \
+                    c: clear all, Esc: back)",
+                    unread
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.notifications.is_empty() {
+        state.select(Some(app.selected_notification));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_leaderboard<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let mut items: Vec<ListItem> = if app.leaderboard.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nobody has joined the leaderboard yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.leaderboard
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let value = match app.leaderboard_ranking {
+                    LeaderboardRanking::Balance => balance_range(entry.value),
+                    LeaderboardRanking::MonthlySavings => money(app, entry.value),
+                };
+                let style = if app.get_current_user() == Some(entry.username.as_str()) {
+                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let mut spans = vec![Span::styled(format!("{:>2}. ", i + 1), style)];
+                spans.extend(identicon(&entry.username));
+                spans.push(Span::styled(format!(" {:<20} {}", entry.username, value), style));
+                ListItem::new(Spans::from(spans))
+            })
+            .collect()
+    };
+    items.push(ListItem::new(""));
+    items.push(ListItem::new(Span::styled(
+        if app.on_leaderboard {
+            "You are on the leaderboard. Press j to leave it."
+        } else {
+            "You are not on the leaderboard. Press j to join."
+        },
+        Style::default().fg(Color::DarkGray),
+    )));
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Leaderboard: {} (Tab: switch ranking, Esc: back)",
+                    app.leaderboard_ranking.as_str()
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+/// Describes the balance range starting at `floor`, e.g. `$1,000 - $10,000`.
+fn balance_range(floor: f64) -> String {
+    match BALANCE_BUCKETS.iter().find(|&&bound| bound > floor) {
+        Some(&upper) if floor <= BALANCE_BUCKETS[0] => format!("under {}", format::money(upper)),
+        Some(&upper) => format!("{} - {}", format::money(floor), format::money(upper)),
+        None => format!("{} and up", format::money(floor)),
+    }
+}
+
+fn draw_card_top_up<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let area = draw_input(
+        f,
+        app,
+        area,
+        "Top Up by Card",
+        "Enter amount, Up/Down for history, Esc to go back".to_string(),
+    );
+    draw_processor_payments(f, app, area);
+}
+
+fn draw_bank_withdrawal<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let available = app.get_available_balance().unwrap_or(0.0);
+    let area = draw_input(
+        f,
+        app,
+        area,
+        "Withdraw to Bank",
+        format!(
+            "Enter amount, Up/Down for history, Esc to go back, {} available",
+            money(app, available)
+        ),
+    );
+    draw_processor_payments(f, app, area);
+}
+
+fn draw_cheque_deposit<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let area = draw_input(
+        f,
+        app,
+        area,
+        "Deposit a Cheque",
+        format!(
+            "Enter amount, Up/Down for history, Esc to go back; clears in {}s, a bounce costs {}",
+            app.rules().processor.cheque_clearing_seconds,
+            format::money(app.rules().bounced_cheque_fee)
+        ),
+    );
+    draw_processor_payments(f, app, area);
+}
+
+/// Lists the current user's recent payments through the simulated processor.
+fn draw_processor_payments<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.processor_payments.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nothing sent to the processor yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.processor_payments
+            .iter()
+            .map(|payment| {
+                let color = match payment.status {
+                    RequestStatus::Pending => Color::Yellow,
+                    RequestStatus::Paid => Color::Green,
+                    _ => Color::Red,
+                };
+                let mut line = format!(
+                    "{}  {}  {:>12}  {}",
+                    payment.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    payment.kind.describe(),
+                    money(app, payment.amount),
+                    payment.status_label()
+                );
+                if let Some(failure) = &payment.failure {
+                    line.push_str(&format!(": {}", failure));
+                }
+                ListItem::new(Span::styled(line, Style::default().fg(color)))
+            })
+            .collect()
+    };
+    let list = List::new(items)
+        .block(Block::default().title("Recent Payments").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+fn draw_new_payment_request<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    match app.wizard_values.first() {
+        None => {
+            draw_input(
+                f,
+                app,
+                area,
+                "Request From (username)",
+                "Enter: next, Esc: back".to_string(),
+            );
+        }
+        Some(payer) => {
+            draw_input(
+                f,
+                app,
+                area,
+                &format!("Amount to Request from {}", payer),
+                format!(
+                    "Expires after {} hour(s). Enter: send request, Esc: change user",
+                    app.request_ttl_hours
+                ),
+            );
+        }
+    }
+}
+
+fn draw_split_bill<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let people = match app.wizard_values.first() {
+        None => {
+            draw_input(
+                f,
+                app,
+                area,
+                "Split a Bill With (usernames, separated by commas)",
+                "Enter: next, Esc: back".to_string(),
+            );
+            return;
+        }
+        Some(people) => people,
+    };
+    let split = match (&app.bill_split, app.wizard_values.len()) {
+        (Some(split), 2) => split,
+        _ => {
+            draw_input(
+                f,
+                app,
+                area,
+                &format!("Amount to Split with {}", people),
+                "Enter: preview shares, Esc: change people".to_string(),
+            );
+            return;
+        }
+    };
+
+    let username = app.get_current_user().unwrap_or_default();
+    let even = split.shares.iter().map(|share| share.amount).fold(f64::INFINITY, f64::min);
+    let mut lines = vec![Spans::from(Span::styled(
+        format!(
+            "{} split {} ways: {} each",
+            money(app, split.total),
+            split.shares.len(),
+            money(app, even)
+        ),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    let extra: Vec<&str> = split
+        .shares
+        .iter()
+        .filter(|share| share.extra_cent)
+        .map(|share| share.username.as_str())
+        .collect();
+    lines.push(Spans::from(Span::styled(
+        if extra.is_empty() {
+            "It divides evenly.".to_string()
+        } else {
+            format!(
+                "{} cent(s) left over, paid one each by {} (alphabetical order).",
+                split.extra_cents(),
+                extra.join(", ")
+            )
+        },
+        Style::default().fg(Color::DarkGray),
+    )));
+    lines.push(Spans::from(""));
+    for share in &split.shares {
+        let mut spans = vec![Span::raw(format!(
+            "{:<20} {:>12}",
+            share.username,
+            money(app, share.amount)
+        ))];
+        if share.extra_cent {
+            spans.push(Span::styled("  +1¢ left over", Style::default().fg(Color::Yellow)));
+        }
+        if share.username == username {
+            spans.push(Span::styled("  (you)", Style::default().fg(Color::DarkGray)));
+        }
+        lines.push(Spans::from(spans));
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        format!(
+            "Everyone else is sent a request that expires after {} hour(s).",
+            app.request_ttl_hours
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+    let preview = Paragraph::new(lines).block(
+        Block::default()
+            .title("Split Preview (Enter: send requests, Esc: change amount)")
+            .borders(Borders::ALL),
+    );
+    f.render_widget(preview, area);
+}
+
+/// Lists a checkout's line items followed by the total.
+fn line_item_rows(app: &AppController, items: &[LineItem]) -> Vec<ListItem<'static>> {
+    let mut rows: Vec<ListItem> = items
+        .iter()
+        .map(|item| {
+            ListItem::new(format!(
+                "{:>3} x {:<24} {:>12}  {:>12}",
+                item.quantity,
+                item.description,
+                money(app, item.unit_price),
+                money(app, item.total())
+            ))
+        })
+        .collect();
+    let total: f64 = items.iter().map(LineItem::total).sum();
+    rows.push(ListItem::new(Span::styled(
+        format!("{:>58}", format!("Total: {}", money(app, total))),
+        Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+    )));
+    rows
+}
+
+fn draw_new_checkout<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let rest = draw_input(
+        f,
+        app,
+        area,
+        "Item (description, price[, quantity])",
+        "Enter: add item, Enter on empty line: show code, Del: remove last, Esc: cancel"
+            .to_string(),
+    );
+    let items = List::new(line_item_rows(app, &app.checkout_items))
+        .block(Block::default().title("Checkout").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(items, rest);
+}
+
+fn draw_checkout_code<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let invoice = match &app.checkout {
+        Some(invoice) => invoice,
+        None => return,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let (status, color) = match invoice.status {
+        RequestStatus::Pending => ("waiting for payment".to_string(), Color::Yellow),
+        RequestStatus::Paid => (
+            format!("paid by {}", invoice.paid_by.as_deref().unwrap_or("?")),
+            Color::Green,
+        ),
+        _ => (invoice.status.as_str().to_string(), Color::DarkGray),
+    };
+    let summary = Paragraph::new(vec![
+        Spans::from(vec![
+            Span::raw("Code: "),
+            Span::styled(
+                invoice.code.clone(),
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("   Total: {}", money(app, invoice.total()))),
+        ]),
+        Spans::from(vec![
+            Span::raw("Status: "),
+            Span::styled(status, Style::default().fg(color)),
+        ]),
+        Spans::from(Span::styled(
+            format!(
+                "Expires {}. Enter: done",
+                invoice.expires_at.format("%Y-%m-%d %H:%M")
+            ),
+            Style::default().fg(Color::DarkGray),
+        )),
+    ])
+    .block(Block::default().borders(Borders::BOTTOM));
+    f.render_widget(summary, chunks[0]);
+
+    if invoice.status == RequestStatus::Pending {
+        draw_qr(f, &invoice.payment_uri(), chunks[1]);
+    } else {
+        let items = List::new(line_item_rows(app, &invoice.items))
+            .style(Style::default().fg(Color::White));
+        f.render_widget(items, chunks[1]);
+    }
+}
+
+fn draw_pay_checkout<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Checkout Code",
+        "Type the merchant's code or paste the scanned text. Enter: review, Esc: back"
+            .to_string(),
+    );
+}
+
+fn draw_review_checkout<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let invoice = match &app.checkout {
+        Some(invoice) => invoice,
+        None => return,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+        .split(area);
+
+    let title = format!("Pay {} (code {})", invoice.merchant, invoice.code);
+    let items = List::new(line_item_rows(app, &invoice.items))
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(items, chunks[0]);
+
+    let hint = Paragraph::new(Span::styled(
+        format!("Pay {}? y/Enter: pay, n/Esc: cancel", money(app, invoice.total())),
+        Style::default().fg(Color::Yellow),
+    ));
+    f.render_widget(hint, chunks[1]);
+}
+
+fn draw_offline_tokens<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items = vec![
+        ListItem::new("1. Issue a token"),
+        ListItem::new("2. Redeem a token"),
+        ListItem::new(""),
+        ListItem::new(Span::styled(
+            "Tokens carry money between wallets that are not connected.",
+            Style::default().fg(Color::DarkGray),
+        )),
+        ListItem::new(Span::styled(
+            "To accept this wallet's tokens, another wallet lists its key under",
+            Style::default().fg(Color::DarkGray),
+        )),
+        ListItem::new(Span::styled(
+            format!("[offline_tokens] trusted_keys in rules.toml: {}", app.wallet_public_key),
+            Style::default().fg(Color::DarkGray),
+        )),
+        ListItem::new(Span::styled(
+            "Press a number to choose, Esc to go back",
+            Style::default().fg(Color::DarkGray),
+        )),
+    ];
+
+    let menu = List::new(items)
+        .block(Block::default().title("Offline Tokens").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(menu, area);
+}
+
+fn draw_issue_offline_token<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let token = match &app.issued_token {
+        Some(token) => token,
+        None => {
+            draw_input(
+                f,
+                app,
+                area,
+                "Token Amount",
+                "Enter: issue token, Esc: back".to_string(),
+            );
+            return;
+        }
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(6), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let hint = Paragraph::new(Span::styled(
+        "Copy the token or scan the code, then press Enter. It is shown only once.",
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(hint, chunks[0]);
+
+    // No border, so copying the wrapped text picks up only the token
+    let text = Paragraph::new(token.as_str())
+        .style(Style::default().fg(Color::Yellow))
+        .wrap(Wrap { trim: false });
+    f.render_widget(text, chunks[1]);
+
+    draw_qr(f, token, chunks[2]);
+}
+
+/// Draws `text` as a QR code, centred in `area`.
+fn draw_qr<B: Backend>(f: &mut Frame<B>, text: &str, area: Rect) {
+    if let Ok(code) = qrcode::QrCode::new(text.as_bytes()) {
+        let rendered = code
+            .render::<qrcode::render::unicode::Dense1x2>()
+            .dark_color(qrcode::render::unicode::Dense1x2::Light)
+            .light_color(qrcode::render::unicode::Dense1x2::Dark)
+            .build();
+        let qr = Paragraph::new(rendered).alignment(Alignment::Center);
+        f.render_widget(qr, area);
+    }
+}
+
+fn draw_redeem_offline_token<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(8), Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    // Show as much of the end of the token as fits in the wrapped box.
+    let inner = chunks[0].width.saturating_sub(2) * chunks[0].height.saturating_sub(2);
+    let input = Paragraph::new(format::input_tail(&app.input, inner as usize))
+        .style(Style::default().fg(Color::Yellow))
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Paste Token"));
+    f.render_widget(input, chunks[0]);
+
+    let hint = Paragraph::new(Span::styled(
+        "Enter: redeem, Esc: back",
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(hint, chunks[1]);
+}
+
+fn draw_vault<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let on_the_way: f64 = app.vault_withdrawals.iter().map(|w| w.amount).sum();
+    let summary = Paragraph::new(vec![
+        Spans::from(Span::styled(
+            format!("In the vault: {}", money(app, app.get_vault_balance().unwrap_or(0.0))),
+            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        )),
+        Spans::from(format!(
+            "On the way out: {} (withdrawals wait {} hour(s))",
+            money(app, on_the_way),
+            app.vault_delay_hours
+        )),
+    ])
+    .block(Block::default().title("Vault").borders(Borders::ALL));
+    f.render_widget(summary, chunks[0]);
+
+    let now = app.now();
+    let items: Vec<ListItem> = if app.vault_withdrawals.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No withdrawals waiting",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.vault_withdrawals
+            .iter()
+            .map(|w| {
+                let minutes_left = (w.available_at - now).num_minutes().max(0);
+                ListItem::new(vec![
+                    Spans::from(format!(
+                        "{} released at {}",
+                        money(app, w.amount),
+                        w.available_at.format("%Y-%m-%d %H:%M")
+                    )),
+                    Spans::from(Span::styled(
+                        format!(
+                            "  requested {}, {}h {}m left",
+                            w.created_at.format("%Y-%m-%d %H:%M"),
+                            minutes_left / 60,
+                            minutes_left % 60
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(
+                    "Withdrawals (d: move in, w: withdraw, x: cancel, s: sweep rules, Esc: back)",
+                )
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.vault_withdrawals.is_empty() {
+        state.select(Some(app.selected_vault_withdrawal));
+    }
+    f.render_stateful_widget(list, chunks[1], &mut state);
+}
+
+fn draw_net_worth<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(7), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let history = app.get_net_worth_history().unwrap_or_default();
+    let now = match history.last() {
+        Some(now) => now,
+        None => return,
+    };
+    let summary = Paragraph::new(vec![
+        Spans::from(format!("Available: {}", money(app, now.available))),
+        Spans::from(format!(
+            "Committed: {} (card holds, transfers waiting to go out, uncleared cheques)",
+            money(app, now.committed)
+        )),
+        Spans::from(format!("Vault: {}", money(app, now.vault))),
+        Spans::from(Span::styled(
+            format!("Net worth: {}", money(app, now.total())),
+            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        )),
+    ])
+    .block(
+        Block::default()
+            .title("Net Worth (h: hide amounts, Esc: back)")
+            .borders(Borders::ALL),
+    );
+    f.render_widget(summary, chunks[0]);
+
+    let first_day = history[0].day;
+    let points: Vec<(f64, f64)> = history
+        .iter()
+        .map(|n| ((n.day - first_day).num_days() as f64, n.total()))
+        .collect();
+    let last_x = points.last().map_or(0.0, |(x, _)| *x).max(1.0);
+    let (low, high) = points.iter().fold((f64::MAX, f64::MIN), |(low, high), (_, y)| {
+        (low.min(*y), high.max(*y))
+    });
+    // A flat line is drawn in the middle rather than on the chart's edge
+    let margin = ((high - low) * 0.1).max(1.0);
+    let (low, high) = (low - margin, high + margin);
+
+    let datasets = vec![Dataset::default()
+        .marker(symbols::Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Cyan))
+        .data(&points)];
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(format!("Over {} day(s)", (now.day - first_day).num_days() + 1))
+                .borders(Borders::ALL),
+        )
+        .x_axis(
+            Axis::default()
+                .bounds([0.0, last_x])
+                .labels(vec![
+                    Span::raw(first_day.format("%Y-%m-%d").to_string()),
+                    Span::raw(now.day.format("%Y-%m-%d").to_string()),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .bounds([low, high])
+                .labels(vec![Span::raw(money(app, low)), Span::raw(money(app, high))]),
+        );
+    f.render_widget(chart, chunks[1]);
+}
+
+fn draw_vault_deposit<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Amount to Move into the Vault",
+        "Enter: move now, Esc: back".to_string(),
+    );
+}
+
+fn draw_sweep_rules<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.sweep_rules.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No rules yet. Press n to move everything above an amount into the vault each month",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.sweep_rules
+            .iter()
+            .map(|rule| {
+                ListItem::new(format!(
+                    "#{}  keep {}, sweep the rest on {}",
+                    rule.id,
+                    money(app, rule.keep),
+                    rule.next_sweep_at.format("%Y-%m-%d")
+                ))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Monthly Sweeps into the Vault (n: new, d: delete, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.sweep_rules.is_empty() {
+        state.select(Some(app.selected_sweep_rule));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_new_sweep_rule<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Amount to Keep in Your Balance",
+        "Whatever is available above it moves to the vault at the start of each month. \
+        Enter: save, Esc: back"
+            .to_string(),
+    );
+}
+
+fn draw_vault_withdraw<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Amount to Withdraw from the Vault",
+        format!(
+            "Released to your balance after {} hour(s). Enter: withdraw, Esc: back",
+            app.vault_delay_hours
+        ),
+    );
+}
+
+fn draw_family<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.children.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No child accounts yet. Press n to create one.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.children
+            .iter()
+            .map(|c| {
+                let allowance = match c.next_allowance_at {
+                    Some(next) if c.weekly_allowance > 0.0 => format!(
+                        "{} a week, next on {}",
+                        money(app, c.weekly_allowance),
+                        next.format("%Y-%m-%d %H:%M")
+                    ),
+                    _ => "no allowance".to_string(),
+                };
+                let spent = money(app, app.get_weekly_spending(&c.child).unwrap_or(0.0));
+                let spending = match c.spending_cap {
+                    Some(cap) => format!("spent {} of {} this week", spent, money(app, cap)),
+                    None => format!("spent {} this week, no cap", spent),
+                };
+                ListItem::new(vec![
+                    Spans::from(format!("{}: {}", c.child, allowance)),
+                    Spans::from(Span::styled(
+                        format!("  {}", spending),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ])
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Family (n: new child, a: allowance, c: cap, Enter: activity, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.children.is_empty() {
+        state.select(Some(app.selected_child));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_new_child<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Child's Username",
+        "Enter: create account, Esc: back".to_string(),
+    );
+}
+
+fn draw_edit_allowance<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let child = app.get_selected_child().map_or("", |c| c.child.as_str());
+    draw_input(
+        f,
+        app,
+        area,
+        &format!("Weekly Allowance for {}", child),
+        "Sent from your balance every week. Leave empty to stop. Enter: save, Esc: back"
+            .to_string(),
+    );
+}
+
+fn draw_edit_spending_cap<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let child = app.get_selected_child().map_or("", |c| c.child.as_str());
+    draw_input(
+        f,
+        app,
+        area,
+        &format!("Weekly Spending Cap for {}", child),
+        "Leave empty for no cap. Enter: save, Esc: back".to_string(),
+    );
+}
+
+fn draw_child_activity<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let child = match app.get_selected_child() {
+        Some(child) => child,
+        None => return,
+    };
+    let transactions = app.get_child_activity(20).unwrap_or_default();
+    let mut items = vec![
+        ListItem::new(Span::styled(
+            format!(
+                "Balance: {}   Spent this week: {}",
+                money(app, app.get_child_balance().unwrap_or(0.0)),
+                money(app, app.get_weekly_spending(&child.child).unwrap_or(0.0))
+            ),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        ListItem::new(" "),
+    ];
+    if transactions.is_empty() {
+        items.push(ListItem::new(Span::styled(
+            "No activity yet",
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    for t in &transactions {
+        items.push(ListItem::new(vec![
+            Spans::from(describe_transaction(app, t)),
+            Spans::from(Span::styled(
+                format!("  {}", t.get("timestamp").cloned().unwrap_or_default()),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ]));
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!("{}'s Activity (h: hide amounts, Esc: back)", child.child))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+fn draw_delegates<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.delegates.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nobody else can see your history. Press n to give someone read-only access.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.delegates
+            .iter()
+            .map(|d| {
+                ListItem::new(format!(
+                    "{}: since {}",
+                    d.delegate,
+                    d.granted_at.format("%Y-%m-%d %H:%M")
+                ))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Read-Only Access (n: give access, d: take back, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.delegates.is_empty() {
+        state.select(Some(app.selected_delegate));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_new_delegate<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Give Read-Only Access To",
+        "They can view your history and statements, not move money. Enter: give, Esc: back"
+            .to_string(),
+    );
+}
+
+fn draw_joint_members<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.joint_members.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "This account has no co-owners. Press n to make it a joint account.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.joint_members
+            .iter()
+            .map(|m| {
+                ListItem::new(format!(
+                    "{}: co-owner since {}",
+                    m.member,
+                    m.added_at.format("%Y-%m-%d %H:%M")
+                ))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Co-Owners, approve transfers over {} (n: add, d: remove, Esc: back)",
+                    money(app, app.rules().joint_approval_over)
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.joint_members.is_empty() {
+        state.select(Some(app.selected_joint_member));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_new_joint_member<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    draw_input(
+        f,
+        app,
+        area,
+        "Make a Co-Owner",
+        format!(
+            "They approve or reject transfers over {} from this account. Enter: add, Esc: back",
+            money(app, app.rules().joint_approval_over)
+        ),
+    );
+}
+
+fn draw_approvals<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.approvals.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No joint account transfers waiting for your approval",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.approvals
+            .iter()
+            .map(|pending| {
+                let mut lines = vec![
+                    Spans::from(format!(
+                        "#{} {} to {}: {}",
+                        pending.id,
+                        pending.sender,
+                        pending.recipient,
+                        money(app, pending.amount)
+                    )),
+                    Spans::from(Span::styled(
+                        format!(
+                            "  expires {}",
+                            app.approval_expires_at(pending).format("%Y-%m-%d %H:%M")
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ];
+                if let Some(note) = &pending.note {
+                    lines.insert(1, Spans::from(format!("  \"{}\"", note)));
+                }
+                ListItem::new(lines)
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Joint Approvals (a: approve and send, x: reject, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.approvals.is_empty() {
+        state.select(Some(app.selected_approval));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_shared_accounts<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.shared_accounts.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nobody has shared their history with you.",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        app.shared_accounts
+            .iter()
+            .map(|d| {
+                ListItem::new(format!(
+                    "{}: shared since {}",
+                    d.owner,
+                    d.granted_at.format("%Y-%m-%d %H:%M")
+                ))
+            })
+            .collect()
+    };
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Shared With Me (Enter: history, e: write statement, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.shared_accounts.is_empty() {
+        state.select(Some(app.selected_shared_account));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_shared_history<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let owner = match app.get_selected_shared_account() {
+        Some(account) => account.owner.as_str(),
+        None => return,
+    };
+    let mut items = Vec::new();
+    match app.get_shared_history(20).unwrap_or_default() {
+        None => items.push(ListItem::new(Span::styled(
+            format!("{} no longer shares their history with you.", owner),
+            Style::default().fg(Color::Red),
+        ))),
+        Some(transactions) if transactions.is_empty() => items.push(ListItem::new(Span::styled(
+            "No activity yet",
+            Style::default().fg(Color::DarkGray),
+        ))),
+        Some(transactions) => {
+            for t in &transactions {
+                items.push(ListItem::new(vec![
+                    Spans::from(describe_transaction(app, t)),
+                    Spans::from(Span::styled(
+                        format!("  {}", t.get("timestamp").cloned().unwrap_or_default()),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ]));
+            }
+        }
+    }
+
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title(format!(
+                    "{}'s History, read-only (e: write statement, h: hide amounts, Esc: back)",
+                    owner
+                ))
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White));
+    f.render_widget(list, area);
+}
+
+fn draw_locked<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let notice = Paragraph::new("Session locked due to inactivity")
+        .style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("Locked"));
+    f.render_widget(notice, chunks[0]);
+
+    let masked = "*".repeat(app.input.chars().count());
+    let width = chunks[1].width.saturating_sub(2) as usize;
+    let input = Paragraph::new(format::input_tail(&masked, width))
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::ALL).title(if app.has_password {
+            "Enter Password to Unlock"
+        } else {
+            "Enter Username to Unlock"
+        }));
+    f.render_widget(input, chunks[1]);
+}
+
+fn draw_account_switcher<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let items: Vec<ListItem> = if app.remembered_accounts.is_empty() {
+        vec![ListItem::new("No accounts are remembered on this terminal yet.")]
+    } else {
+        app.remembered_accounts
+            .iter()
+            .map(|account| ListItem::new(Spans::from(labelled(&account.username))))
+            .collect()
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .title("Switch Account (Enter: switch, a: remember me, d: forget, Esc: back)")
+                .borders(Borders::ALL),
+        )
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default();
+    if !app.remembered_accounts.is_empty() {
+        state.select(Some(app.selected_account));
+    }
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_pin<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect, title: &str) {
+    let masked: String = "*".repeat(app.input.chars().count());
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let input = Paragraph::new(masked)
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(input, chunks[0]);
+
+    let hint = Paragraph::new(Span::styled(
+        "4 to 8 digits. Enter: confirm, Esc: cancel",
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(hint, chunks[1]);
+}
+
+/// Draws a text field whose typed characters are shown as `*`.
+fn draw_password<B: Backend>(
+    f: &mut Frame<B>,
+    app: &AppController,
+    area: Rect,
+    title: &str,
+    hint: &str,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let masked = "*".repeat(app.input.chars().count());
+    let width = chunks[0].width.saturating_sub(2) as usize;
+    let input = Paragraph::new(format::input_tail(&masked, width))
+        .style(Style::default().fg(Color::Yellow))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(input, chunks[0]);
+
+    let hint = Paragraph::new(Span::styled(hint, Style::default().fg(Color::DarkGray)));
+    f.render_widget(hint, chunks[1]);
+}
+
+fn draw_set_password<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    match app.wizard_values.len() {
+        0 if app.current_screen == Screen::RotatePassword => draw_password(
+            f,
+            app,
+            area,
+            "Your Password Expired: Choose a New One",
+            "At least 8 characters. Enter: next, Esc: cancel",
+        ),
+        0 => draw_password(
+            f,
+            app,
+            area,
+            "Choose a Password",
+            "At least 8 characters. Enter: next, Esc: cancel",
+        ),
+        _ => draw_password(
+            f,
+            app,
+            area,
+            "Type the Password Again",
+            "Enter: set password, Esc: back",
+        ),
+    }
+}
+
+fn draw_change_password<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let (title, hint) = match app.wizard_values.len() {
+        0 => ("Current Password", "Enter: next, Esc: cancel"),
+        1 => ("New Password", "At least 8 characters. Enter: next, Esc: back"),
+        _ => ("Type the New Password Again", "Enter: change password, Esc: back"),
+    };
+    draw_password(f, app, area, title, hint);
+}
+
+fn draw_recovery_codes<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let mut lines = vec![
+        Spans::from(Span::styled(
+            "Write these down and keep them somewhere safe. Each one can be used once",
+            Style::default().fg(Color::Yellow),
+        )),
+        Spans::from(Span::styled(
+            "to set a new password (4 on the main menu). They will not be shown again.",
+            Style::default().fg(Color::Yellow),
+        )),
+        Spans::from(""),
+    ];
+    lines.extend(app.recovery_codes.iter().map(|code| {
+        Spans::from(Span::styled(
+            format!("  {}", code),
+            Style::default().add_modifier(Modifier::BOLD),
+        ))
+    }));
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(Span::styled(
+        "Enter: done",
+        Style::default().fg(Color::DarkGray),
+    )));
+    let codes = Paragraph::new(lines)
+        .block(Block::default().title("Recovery Codes").borders(Borders::ALL));
+    f.render_widget(codes, area);
+}
+
+fn draw_recover_account<B: Backend>(f: &mut Frame<B>, app: &AppController, area: Rect) {
+    let (title, hint) = match app.wizard_values.as_slice() {
+        [] => ("Recover Account: Username".to_string(), "Enter: next, Esc: back"),
+        [username] => (
+            format!("A Recovery Code for {}", username),
+            "Each code works once. Enter: next, Esc: back",
+        ),
+        [_, _] => (
+            "Choose a New Password".to_string(),
+            "At least 8 characters. Enter: next, Esc: back",
+        ),
+        _ => (
+            "Type the New Password Again".to_string(),
+            "Enter: reset password and log in, Esc: back",
+        ),
+    };
+    if app.wizard_values.len() < 2 {
+        draw_input(f, app, area, &title, hint.to_string());
+    } else {
+        draw_password(f, app, area, &title, hint);
+    }
+}
+
+/// The other user in a transfer, whose identicon is shown beside it.
+fn counterparty(t: &HashMap<String, String>) -> Option<&str> {
+    let key = match t.get("type").map(String::as_str) {
+        Some("transfer_out") => "recipient",
+        Some("transfer_in") => "sender",
+        _ => return None,
+    };
+    t.get(key).map(String::as_str).filter(|username| !username.is_empty())
+}
+
+fn describe_transaction(app: &AppController, t: &HashMap<String, String>) -> String {
+    let amount = money(app, amount_field(t, "amount"));
+    let transaction_type = t.get("type").unwrap_or(&String::from("Unknown")).clone();
+    let recipient = t.get("recipient").unwrap_or(&String::from("")).clone();
+    let sender = t.get("sender").unwrap_or(&String::from("")).clone();
+    let transfer_note = match t.get("transfer_note") {
+        Some(note) if !note.is_empty() => format!(": {}", note),
+        _ => String::new(),
+    };
+
+    let description = match transaction_type.as_str() {
+        "deposit" => format!("Deposit: {}", amount),
+        "withdraw" => format!("Withdrawal: {}", amount),
+        "transfer_out" => format!("Transfer: {} to {}{}", amount, recipient, transfer_note),
+        "transfer_in" => format!("Received: {} from {}{}", amount, sender, transfer_note),
+        "token_out" => format!("Issued {}: {}", recipient, amount),
+        "token_in" => format!("Redeemed {}: {} from {}", recipient, amount, sender),
+        "vault_in" => format!("Moved to vault: {}", amount),
+        "vault_out" => format!("Released from vault: {}", amount),
+        "fee" => format!("Fee: {}", amount),
+        "unreadable" => "Unreadable transaction".to_string(),
+        _ => format!("Unknown transaction: {}", amount),
+    };
+    match original_amount(app, t) {
+        Some(original) => format!("{} ({})", description, original),
+        None => description,
+    }
+}
+
+/// Formats the amount in the currency a transaction was made in, if that is
+/// not the base currency.
+fn original_amount(app: &AppController, t: &HashMap<String, String>) -> Option<String> {
+    let currency = t.get("currency").map(String::as_str).unwrap_or(BASE_CURRENCY);
+    if currency == BASE_CURRENCY {
+        return None;
+    }
+    let fx_rate = amount_field(t, "fx_rate");
+    if app.hide_amounts || fx_rate <= 0.0 {
+        return Some(format!("{} {}", format::MASKED_AMOUNT, currency));
+    }
+    Some(format::money_in(amount_field(t, "amount") / fx_rate, currency))
 }
 
 fn draw_messages<B: Backend>(f: &mut Frame<B>, app: &AppController) {
@@ -0,0 +1,91 @@
+use tui::{
+    backend::Backend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+use crate::controllers::watch_controller::WatchController;
+use crate::views::format;
+
+/// Draws the read-only watch screen: the balance and the latest money coming in.
+pub fn draw<B: Backend>(f: &mut Frame<B>, watch: &WatchController) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(5),
+                Constraint::Min(0),
+                Constraint::Length(1),
+            ]
+            .as_ref(),
+        )
+        .split(f.size());
+
+    let title = Paragraph::new(format!("Watching {} (read-only)", watch.username))
+        .style(Style::default().fg(Color::Cyan))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+    f.render_widget(title, chunks[0]);
+
+    let balance = Paragraph::new(vec![
+        Spans::from(""),
+        Spans::from(Span::styled(
+            format::money(watch.balance),
+            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+        )),
+    ])
+    .alignment(Alignment::Center)
+    .block(Block::default().title("Balance").borders(Borders::ALL));
+    f.render_widget(balance, chunks[1]);
+
+    let items: Vec<ListItem> = if watch.incoming.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "Nothing received yet",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        watch
+            .incoming
+            .iter()
+            .map(|t| {
+                let field = |key: &str| t.get(key).cloned().unwrap_or_default();
+                let amount = format::money(field("amount").parse().unwrap_or(0.0));
+                let description = match field("type").as_str() {
+                    "deposit" => format!("Deposit: {}", amount),
+                    "token_in" => format!("Redeemed token: {} from {}", amount, field("sender")),
+                    _ => format!("Received: {} from {}", amount, field("sender")),
+                };
+                let style = if watch.new_ids.contains(&field("id")) {
+                    Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(Spans::from(vec![
+                    Span::styled(
+                        format!("{}  ", field("timestamp")),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(description, style),
+                ]))
+            })
+            .collect()
+    };
+    let incoming = List::new(items)
+        .block(Block::default().title("Incoming").borders(Borders::ALL))
+        .style(Style::default().fg(Color::White));
+    f.render_widget(incoming, chunks[2]);
+
+    let footer = Paragraph::new(Span::styled(
+        format!(
+            "Updated {}. New arrivals are highlighted. q: quit",
+            watch.refreshed_at.format("%H:%M:%S")
+        ),
+        Style::default().fg(Color::DarkGray),
+    ));
+    f.render_widget(footer, chunks[3]);
+}